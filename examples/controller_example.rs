@@ -4,7 +4,7 @@ use anyhow::Result;
 
 use streampager::action::{Action, ActionSender};
 use streampager::bindings::{Binding, Category, KeyCode, Keymap, Modifiers};
-use streampager::control::{Change, Controller};
+use streampager::control::{Change, Controller, Section};
 use streampager::file::FileIndex;
 use streampager::pager::Pager;
 
@@ -49,13 +49,25 @@ fn make_add_text(
 fn main() -> Result<()> {
     let controller = Controller::new("Example");
 
-    controller.apply_changes(vec![Change::AppendLines {
-        contents: vec![
-            b"Hello!".to_vec(),
-            b"".to_vec(),
-            b"This is an example controlled file.".to_vec(),
-        ],
-    }])?;
+    controller.apply_changes(vec![
+        Change::AppendLines {
+            contents: vec![
+                b"Hello!".to_vec(),
+                b"".to_vec(),
+                b"This is an example controlled file.".to_vec(),
+                b"Details (press Enter to expand)".to_vec(),
+                b"  These lines are in a collapsible section.".to_vec(),
+                b"  They are hidden until it is expanded.".to_vec(),
+            ],
+        },
+        Change::SetSection {
+            index: 3,
+            section: Some(Section {
+                lines: 2,
+                collapsed: true,
+            }),
+        },
+    ])?;
 
     let mut pager = Pager::new_using_system_terminal()?;
     let file_index = pager.add_controlled_file(&controller)?;
@@ -1,9 +1,18 @@
 //! Actions.
 
-use std::sync::{Arc, Mutex};
+use std::fmt;
+use std::ops::Range;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{mpsc, Arc, Mutex};
 
 use crate::error::Error;
 use crate::event::{Event, EventSender};
+use crate::file::{File, FileIndex, FileInfo};
+use crate::line::strip_escapes;
+
+/// The maximum number of bytes of lines returned by
+/// [`ActionSender::get_lines`].
+pub const MAX_LINES_BYTES: usize = 16 * 1024 * 1024;
 
 /// Actions that can be performed on the pager.
 #[derive(Clone, Debug, Hash, PartialEq, Eq)]
@@ -14,12 +23,79 @@ pub enum Action {
     /// Refresh the screen.
     Refresh,
 
+    /// Re-read the configuration file and apply any changes.
+    ReloadConfig,
+
     /// Show the help screen.
     Help,
 
+    /// Show all of the errors for the current file.
+    ShowErrorLog,
+
+    /// Collapse the error file below the ruler to its latest line, or
+    /// expand it again.
+    ToggleErrorFile,
+
+    /// Switch to the error file of the current file, to see all of it.
+    OpenErrorFile,
+
+    /// Show statistics about the line caches of the current file: how many
+    /// lines they hold, the memory those use, and how many lookups hit,
+    /// missed, evicted a line or found a line too large to cache.
+    ShowCacheStats,
+
+    /// Show the earlier pages of the current file's progress indicator,
+    /// oldest first, followed by the current page.
+    ShowProgressHistory,
+
+    /// Show the internal state of the pager, for diagnosing performance
+    /// problems.  This isn't listed in the help screen or offered by the
+    /// command palette.
+    ShowDiagnostics,
+
+    /// Quit, printing the lines that are visible on the screen to the
+    /// terminal.
+    QuitAndPrintScreen,
+
+    /// Quit the pager, closing all of the files, without asking for
+    /// confirmation while they are still loading.
+    QuitAll,
+
+    /// Close the current file, quitting if it is the last one.
+    QuitCurrentFile,
+
+    /// Kill the commands that are still running for any of the files, then
+    /// quit.
+    QuitAndKillChild,
+
+    /// Interrupt the command the current file is the output of, if it is
+    /// still running, otherwise quit.
+    Interrupt,
+
     /// Cancel the current action.
     Cancel,
 
+    /// Prompt the user for an action to perform, chosen from a list of all
+    /// available actions.
+    ShowCommandPalette,
+
+    /// Stop reading from the file's input stream until loading is resumed.
+    PauseLoading,
+
+    /// Resume reading from the file's input stream after it was paused.
+    ResumeLoading,
+
+    /// Copy what has been loaded of the current file into a new file that
+    /// doesn't change, and switch to it.
+    SnapshotFile,
+
+    /// Show how the file has changed since it was snapshotted.
+    DiffSnapshot,
+
+    /// Temporarily release the mouse so that text can be selected with it,
+    /// or capture it again.
+    ToggleMouseSelection,
+
     /// Switch to the previous file.
     PreviousFile,
 
@@ -41,12 +117,36 @@ pub enum Action {
     /// Scroll down 1/*n* of the screen height.
     ScrollDownScreenFraction(usize),
 
+    /// Scroll up half the screen height.  If a repeat count has been
+    /// entered, it is used as the number of lines for this and subsequent
+    /// half screen scrolls.
+    ScrollUpHalfScreen,
+
+    /// Scroll down half the screen height.  If a repeat count has been
+    /// entered, it is used as the number of lines for this and subsequent
+    /// half screen scrolls.
+    ScrollDownHalfScreen,
+
+    /// Scroll up one window, which is the screen height unless it has been
+    /// changed.  If a repeat count has been entered, it is used as the new
+    /// window size.
+    ScrollUpWindow,
+
+    /// Scroll down one window, which is the screen height unless it has been
+    /// changed.  If a repeat count has been entered, it is used as the new
+    /// window size.
+    ScrollDownWindow,
+
     /// Scroll to the top of the file.
     ScrollToTop,
 
     /// Scroll to the bottom of the file, and start following it.
     ScrollToBottom,
 
+    /// Scroll to *n*% of the way through the file.  If a repeat count has
+    /// been entered, it is used as the percentage instead.
+    ScrollToPercent(usize),
+
     /// Scroll left *n* columns.
     ScrollLeftColumns(usize),
 
@@ -65,9 +165,34 @@ pub enum Action {
     /// Toggle line wrapping mode.
     ToggleLineWrapping,
 
+    /// Toggle between showing the raw text of markdown and rendering it.
+    ToggleMarkdown,
+
+    /// Toggle between the raw file and a view of the file with its JSON
+    /// content pretty-printed.
+    ToggleJsonView,
+
+    /// Toggle showing the type of each line's ending.
+    ToggleLineEndings,
+
+    /// Toggle highlighting of trailing whitespace and mixed indentation, and
+    /// markers for tabs.
+    ToggleWhitespace,
+
     /// Prompt the user for a line to move to.
     PromptGoToLine,
 
+    /// Open the entry on the current line of a directory listing, or
+    /// collapse or expand the section the current line is the header of in
+    /// a controlled file.  The current line is the line of the current
+    /// search match, if it is on the screen, otherwise the line at the top
+    /// of the screen.  Otherwise, scroll down one line.
+    OpenListedFile,
+
+    /// Prompt the user for a shell command, and run it with the terminal
+    /// temporarily restored.
+    PromptShellCommand,
+
     /// Prompt the user for a search term.  The search will start at the beginning of the file.
     PromptSearchFromStart,
 
@@ -102,19 +227,162 @@ pub enum Action {
     /// Move to the last match.
     LastMatch,
 
+    /// Toggle whether searches only look at the visible columns of each line
+    /// when lines are not wrapped.
+    ToggleSearchVisibleColumns,
+
+    /// Search the current file for the last pattern searched for, including
+    /// searches in other files and in previous sessions.
+    RepeatLastSearch,
+
+    /// Search forwards from the top of the screen for a pattern.
+    Search(String),
+
+    /// Run a shell command with the terminal temporarily restored, as if it
+    /// had been entered at the shell command prompt.
+    RunCommand(String),
+
+    /// Run the command the current file is the output of again.
+    RerunCommand(RerunMode),
+
     /// Append a digit to the "repeat count".
     /// The count defines how many times to do the next operation.
     AppendDigitToRepeatCount(usize),
+
+    /// Prompt the user for a value on behalf of the application using the
+    /// pager.  See [`PromptRequest`].
+    Prompt(PromptRequest),
+
+    /// Show a message at the bottom of the screen.  Messages are shown one
+    /// at a time, in the order they are sent, and each disappears after the
+    /// configured message timeout.
+    ShowMessage(String, Severity),
+}
+
+/// What happens to the output of a command when it is run again by
+/// [`Action::RerunCommand`].
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq)]
+pub enum RerunMode {
+    /// The output of the new run replaces the old output.
+    Replace,
+
+    /// The output of the new run is added after the old output, below a
+    /// separator.
+    Append,
+}
+
+/// The severity of a message shown by [`Action::ShowMessage`].
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq)]
+pub enum Severity {
+    /// Information about something that has happened.
+    Info,
+
+    /// A warning about something the user may need to act on.
+    Warning,
+
+    /// An error.
+    Error,
+}
+
+impl Action {
+    /// Every action that doesn't need to be told what to act on, with the
+    /// parameters used when it is chosen from the command palette.
+    pub(crate) const ALL: &'static [Action] = &[
+        Action::Quit,
+        Action::Refresh,
+        Action::ReloadConfig,
+        Action::Help,
+        Action::ShowErrorLog,
+        Action::ToggleErrorFile,
+        Action::OpenErrorFile,
+        Action::ShowCacheStats,
+        Action::ShowProgressHistory,
+        Action::ShowDiagnostics,
+        Action::QuitAndPrintScreen,
+        Action::QuitAll,
+        Action::QuitCurrentFile,
+        Action::QuitAndKillChild,
+        Action::Interrupt,
+        Action::Cancel,
+        Action::ShowCommandPalette,
+        Action::PauseLoading,
+        Action::ResumeLoading,
+        Action::SnapshotFile,
+        Action::DiffSnapshot,
+        Action::ToggleMouseSelection,
+        Action::PreviousFile,
+        Action::NextFile,
+        Action::ToggleRuler,
+        Action::ScrollUpLines(1),
+        Action::ScrollDownLines(1),
+        Action::ScrollUpScreenFraction(1),
+        Action::ScrollDownScreenFraction(1),
+        Action::ScrollUpHalfScreen,
+        Action::ScrollDownHalfScreen,
+        Action::ScrollUpWindow,
+        Action::ScrollDownWindow,
+        Action::ScrollToTop,
+        Action::ScrollToBottom,
+        Action::ScrollToPercent(50),
+        Action::ScrollLeftColumns(1),
+        Action::ScrollRightColumns(1),
+        Action::ScrollLeftScreenFraction(1),
+        Action::ScrollRightScreenFraction(1),
+        Action::ToggleLineNumbers,
+        Action::ToggleLineWrapping,
+        Action::ToggleMarkdown,
+        Action::ToggleJsonView,
+        Action::ToggleLineEndings,
+        Action::ToggleWhitespace,
+        Action::PromptGoToLine,
+        Action::OpenListedFile,
+        Action::PromptShellCommand,
+        Action::PromptSearchFromStart,
+        Action::PromptSearchForwards,
+        Action::PromptSearchBackwards,
+        Action::PreviousMatch,
+        Action::NextMatch,
+        Action::PreviousMatchLine,
+        Action::NextMatchLine,
+        Action::PreviousMatchScreen,
+        Action::NextMatchScreen,
+        Action::FirstMatch,
+        Action::LastMatch,
+        Action::ToggleSearchVisibleColumns,
+        Action::RepeatLastSearch,
+        Action::RerunCommand(RerunMode::Replace),
+        Action::RerunCommand(RerunMode::Append),
+    ];
 }
 
 impl std::fmt::Display for Action {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         use Action::*;
         match *self {
+            Prompt(ref request) => write!(f, "Prompt for {}", request.label),
+            ShowMessage(ref message, _) => write!(f, "Show message: {}", message),
             Quit => write!(f, "Quit"),
+            QuitAndPrintScreen => write!(f, "Quit and print the screen to the terminal"),
+            QuitAll => write!(f, "Quit without confirmation"),
+            QuitCurrentFile => write!(f, "Close the current file, quitting after the last"),
+            QuitAndKillChild => write!(f, "Kill any running commands and quit"),
+            Interrupt => write!(f, "Interrupt the running command, or quit"),
             Refresh => write!(f, "Refresh the screen"),
+            ReloadConfig => write!(f, "Reload the configuration file"),
             Help => write!(f, "Show this help"),
+            ShowErrorLog => write!(f, "Show all errors for this file"),
+            ToggleErrorFile => write!(f, "Collapse or expand the error stream"),
+            OpenErrorFile => write!(f, "Switch to the error stream"),
+            ShowCacheStats => write!(f, "Show line cache statistics"),
+            ShowProgressHistory => write!(f, "Show the progress indicator history"),
+            ShowDiagnostics => write!(f, "Show diagnostics"),
             Cancel => write!(f, "Close help or any open prompt"),
+            ShowCommandPalette => write!(f, "Choose an action to perform"),
+            PauseLoading => write!(f, "Pause loading the file"),
+            ResumeLoading => write!(f, "Resume loading the file"),
+            SnapshotFile => write!(f, "Snapshot the file as it is now"),
+            DiffSnapshot => write!(f, "Show changes since the snapshot"),
+            ToggleMouseSelection => write!(f, "Toggle selecting text with the mouse"),
             PreviousFile => write!(f, "Switch to the previous file"),
             NextFile => write!(f, "Switch to the next file"),
             ToggleRuler => write!(f, "Toggle the ruler"),
@@ -126,8 +394,13 @@ impl std::fmt::Display for Action {
             ScrollUpScreenFraction(n) => write!(f, "Scroll up 1/{} screen", n),
             ScrollDownScreenFraction(1) => write!(f, "Scroll down one screen"),
             ScrollDownScreenFraction(n) => write!(f, "Scroll down 1/{} screen", n),
+            ScrollUpHalfScreen => write!(f, "Scroll up half a screen"),
+            ScrollDownHalfScreen => write!(f, "Scroll down half a screen"),
+            ScrollUpWindow => write!(f, "Scroll up one window"),
+            ScrollDownWindow => write!(f, "Scroll down one window"),
             ScrollToTop => write!(f, "Move to the start of the file"),
             ScrollToBottom => write!(f, "Move to and follow the end of the file"),
+            ScrollToPercent(n) => write!(f, "Move to {}% of the way through the file", n),
             ScrollLeftColumns(1) => write!(f, "Scroll left"),
             ScrollLeftColumns(n) => write!(f, "Scroll left {} columns", n),
             ScrollRightColumns(1) => write!(f, "Scroll right"),
@@ -138,7 +411,16 @@ impl std::fmt::Display for Action {
             ScrollRightScreenFraction(n) => write!(f, "Scroll right 1/{} screen", n),
             ToggleLineNumbers => write!(f, "Toggle line numbers"),
             ToggleLineWrapping => write!(f, "Cycle through line wrapping modes"),
+            ToggleMarkdown => write!(f, "Toggle rendering of markdown"),
+            ToggleJsonView => write!(f, "Toggle pretty-printing of JSON"),
+            ToggleLineEndings => write!(f, "Toggle showing line endings"),
+            ToggleWhitespace => write!(f, "Toggle showing whitespace"),
             PromptGoToLine => write!(f, "Go to position in file"),
+            OpenListedFile => write!(
+                f,
+                "Open the listed file or toggle the section on the current line"
+            ),
+            PromptShellCommand => write!(f, "Run a shell command"),
             PromptSearchFromStart => write!(f, "Search from the start of the file"),
             PromptSearchForwards => write!(f, "Search forwards"),
             PromptSearchBackwards => write!(f, "Search backwards"),
@@ -150,6 +432,14 @@ impl std::fmt::Display for Action {
             NextMatchScreen => write!(f, "Move to the next match following the screen"),
             FirstMatch => write!(f, "Move to the first match"),
             LastMatch => write!(f, "Move to the last match"),
+            RepeatLastSearch => write!(f, "Repeat the last search in this file"),
+            ToggleSearchVisibleColumns => write!(f, "Toggle searching only the visible columns"),
+            Search(ref pattern) => write!(f, "Search for {}", pattern),
+            RunCommand(ref command) => write!(f, "Run {}", command),
+            RerunCommand(RerunMode::Replace) => write!(f, "Run the command again"),
+            RerunCommand(RerunMode::Append) => {
+                write!(f, "Run the command again, keeping the old output")
+            }
             AppendDigitToRepeatCount(n) => write!(f, "Append digit {} to repeat count", n),
         }
     }
@@ -171,4 +461,337 @@ impl ActionSender {
         sender.send(Event::Action(action))?;
         Ok(())
     }
+
+    /// Get the contents of lines of a file from the pager.
+    ///
+    /// Returns the contents of the lines in the range `lines` of the file
+    /// with index `file_index`, including their line endings.  If
+    /// `strip_escapes` is set, escape sequences (e.g. colors) are removed.
+    /// Lines that haven't been loaded yet are omitted, and no more lines are
+    /// returned once their total size reaches [`MAX_LINES_BYTES`].  Returns
+    /// `None` if there is no file with that index.
+    ///
+    /// This waits for the pager to respond, so it must not be called from
+    /// the thread running the pager.
+    pub fn get_lines(
+        &self,
+        file_index: FileIndex,
+        lines: Range<usize>,
+        strip_escapes: bool,
+    ) -> Result<Option<Vec<Vec<u8>>>, Error> {
+        let (reply, response) = mpsc::channel();
+        let request = LinesRequest {
+            file_index,
+            lines,
+            strip_escapes,
+            reply,
+        };
+        self.0.lock().unwrap().send(Event::LinesRequest(request))?;
+        Ok(response.recv()?)
+    }
+}
+
+static PROMPT_REQUEST_ID: AtomicUsize = AtomicUsize::new(0);
+
+/// A request from the application using the pager to prompt the user for a
+/// value.
+///
+/// Send it to the pager with [`Action::Prompt`].  The prompt is shown at the
+/// bottom of the current screen, with the same line editing and history as
+/// the pager's own prompts.  When the user presses Return, the value they
+/// entered is sent to the receiver returned by [`PromptRequest::new`].  If
+/// they cancel the prompt instead, `None` is sent.
+#[derive(Clone)]
+pub struct PromptRequest {
+    /// The id of this request.  This is unique for each request.
+    id: usize,
+
+    /// The label shown before the value.
+    label: String,
+
+    /// Where to send the value.
+    reply: Arc<Mutex<mpsc::Sender<Option<String>>>>,
+}
+
+impl PromptRequest {
+    /// Create a new prompt request with the given label, and the receiver
+    /// for the values the user enters.
+    ///
+    /// The same request can be sent more than once, in which case each
+    /// prompt sends its value to the same receiver.
+    pub fn new(label: impl Into<String>) -> (PromptRequest, mpsc::Receiver<Option<String>>) {
+        let (reply, values) = mpsc::channel();
+        let request = PromptRequest {
+            id: PROMPT_REQUEST_ID.fetch_add(1, Ordering::SeqCst),
+            label: label.into(),
+            reply: Arc::new(Mutex::new(reply)),
+        };
+        (request, values)
+    }
+
+    /// The label shown before the value.
+    pub fn label(&self) -> &str {
+        &self.label
+    }
+
+    /// Create the reply for a prompt shown for this request.
+    pub(crate) fn reply(&self) -> PromptReply {
+        PromptReply(Some(self.reply.clone()))
+    }
+}
+
+impl PartialEq for PromptRequest {
+    fn eq(&self, other: &Self) -> bool {
+        self.id == other.id
+    }
+}
+
+impl Eq for PromptRequest {}
+
+impl std::hash::Hash for PromptRequest {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.id.hash(state);
+    }
+}
+
+impl fmt::Debug for PromptRequest {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("PromptRequest")
+            .field(&self.id)
+            .field(&self.label)
+            .finish()
+    }
+}
+
+/// The reply to a prompt shown for a [`PromptRequest`].
+///
+/// If the prompt is dropped without a value being sent, e.g. because the
+/// user cancelled it, `None` is sent instead.
+pub(crate) struct PromptReply(Option<Arc<Mutex<mpsc::Sender<Option<String>>>>>);
+
+impl PromptReply {
+    /// Send the value the user entered.
+    pub(crate) fn send(mut self, value: &str) {
+        if let Some(reply) = self.0.take() {
+            // The requester may have gone away, in which case there is no
+            // one to reply to.
+            let _ = reply.lock().unwrap().send(Some(value.to_string()));
+        }
+    }
+}
+
+impl Drop for PromptReply {
+    fn drop(&mut self) {
+        if let Some(reply) = self.0.take() {
+            let _ = reply.lock().unwrap().send(None);
+        }
+    }
+}
+
+/// A request for the contents of lines of a file, made by
+/// [`ActionSender::get_lines`].
+pub(crate) struct LinesRequest {
+    /// The file to get lines from.
+    file_index: FileIndex,
+
+    /// The lines to get.
+    lines: Range<usize>,
+
+    /// Whether to remove escape sequences from the lines.
+    strip_escapes: bool,
+
+    /// Where to send the lines.
+    reply: mpsc::Sender<Option<Vec<Vec<u8>>>>,
+}
+
+impl LinesRequest {
+    /// The index of the file to get lines from.
+    pub(crate) fn file_index(&self) -> FileIndex {
+        self.file_index
+    }
+
+    /// Respond to the request with lines from the file, if it exists.
+    pub(crate) fn respond(self, file: Option<&File>) {
+        let lines = file.map(|file| {
+            let mut lines = Vec::new();
+            let mut size = 0;
+            for index in self.lines.clone() {
+                if size >= MAX_LINES_BYTES {
+                    break;
+                }
+                let line = file.with_line(index, |line| {
+                    if self.strip_escapes {
+                        strip_escapes(&line)
+                    } else {
+                        line.into_owned()
+                    }
+                });
+                match line {
+                    Some(line) => {
+                        size += line.len();
+                        lines.push(line);
+                    }
+                    None => break,
+                }
+            }
+            lines
+        });
+        // The requester may have gone away, in which case there is no one
+        // to respond to.
+        let _ = self.reply.send(lines);
+    }
+}
+
+impl fmt::Debug for LinesRequest {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("LinesRequest")
+            .field("file_index", &self.file_index)
+            .field("lines", &self.lines)
+            .field("strip_escapes", &self.strip_escapes)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashSet;
+    use std::thread;
+    use std::time::Duration;
+
+    use super::*;
+    use crate::bindings::Binding;
+    use crate::event::EventStream;
+    use crate::file::LoadedFile;
+
+    #[test]
+    fn test_get_lines() -> Result<(), Error> {
+        let events = EventStream::new(None);
+        let file: File = LoadedFile::new_static(
+            0,
+            "test",
+            &b"one\n\x1B[1mtwo\x1B[m\nthree\n"[..],
+            events.sender(),
+        )
+        .into();
+        while !file.loaded() {
+            thread::sleep(Duration::from_millis(1));
+        }
+        let action_sender = events.action_sender();
+        let requester = thread::spawn(move || {
+            (
+                action_sender.get_lines(0, 1..5, true),
+                action_sender.get_lines(1, 0..1, false),
+            )
+        });
+        let mut responded = 0;
+        while responded < 2 {
+            match events.try_recv()? {
+                Some(Event::LinesRequest(request)) => {
+                    let file = Some(&file).filter(|_| request.file_index() == 0);
+                    request.respond(file);
+                    responded += 1;
+                }
+                _ => thread::sleep(Duration::from_millis(1)),
+            }
+        }
+        let (lines, missing) = requester.join().unwrap();
+        assert_eq!(lines?, Some(vec![b"two\n".to_vec(), b"three\n".to_vec()]));
+        assert_eq!(missing?, None);
+        Ok(())
+    }
+
+    #[test]
+    fn test_all_actions() {
+        // This match is exhaustive so that new actions have to be added here,
+        // and to `Action::ALL` unless they need to be told what to act on.
+        let listed = |action: &Action| {
+            use Action::*;
+            match action {
+                Quit
+                | Refresh
+                | ReloadConfig
+                | Help
+                | ShowErrorLog
+                | ToggleErrorFile
+                | OpenErrorFile
+                | ShowCacheStats
+                | ShowProgressHistory
+                | ShowDiagnostics
+                | QuitAndPrintScreen
+                | QuitAll
+                | QuitCurrentFile
+                | QuitAndKillChild
+                | Interrupt
+                | Cancel
+                | ShowCommandPalette
+                | PauseLoading
+                | ResumeLoading
+                | SnapshotFile
+                | DiffSnapshot
+                | ToggleMouseSelection
+                | PreviousFile
+                | NextFile
+                | ToggleRuler
+                | ScrollUpLines(_)
+                | ScrollDownLines(_)
+                | ScrollUpScreenFraction(_)
+                | ScrollDownScreenFraction(_)
+                | ScrollUpHalfScreen
+                | ScrollDownHalfScreen
+                | ScrollUpWindow
+                | ScrollDownWindow
+                | ScrollToTop
+                | ScrollToBottom
+                | ScrollToPercent(_)
+                | ScrollLeftColumns(_)
+                | ScrollRightColumns(_)
+                | ScrollLeftScreenFraction(_)
+                | ScrollRightScreenFraction(_)
+                | ToggleLineNumbers
+                | ToggleLineWrapping
+                | ToggleMarkdown
+                | ToggleJsonView
+                | ToggleLineEndings
+                | ToggleWhitespace
+                | PromptGoToLine
+                | OpenListedFile
+                | PromptShellCommand
+                | PromptSearchFromStart
+                | PromptSearchForwards
+                | PromptSearchBackwards
+                | PreviousMatch
+                | NextMatch
+                | PreviousMatchLine
+                | NextMatchLine
+                | PreviousMatchScreen
+                | NextMatchScreen
+                | FirstMatch
+                | LastMatch
+                | ToggleSearchVisibleColumns
+                | RepeatLastSearch
+                | RerunCommand(_) => true,
+                Search(_)
+                | RunCommand(_)
+                | AppendDigitToRepeatCount(_)
+                | Prompt(_)
+                | ShowMessage(..) => false,
+            }
+        };
+        assert!(Action::ALL.iter().all(listed));
+        // Each action can be bound by its name, and is described differently
+        // from the others.
+        let mut descriptions = HashSet::new();
+        for action in Action::ALL {
+            let debug = format!("{:?}", action);
+            let (name, params) = match debug.split_once('(') {
+                Some((name, param)) => (name, vec![param.trim_end_matches(')').to_lowercase()]),
+                None => (debug.as_str(), Vec::new()),
+            };
+            assert_eq!(
+                Binding::parse(name.to_string(), params).unwrap(),
+                Binding::Action(action.clone()),
+            );
+            assert!(descriptions.insert(action.to_string()), "{:?}", action);
+        }
+    }
 }
@@ -0,0 +1,86 @@
+//! Async Streams
+//!
+//! Streams that implement `tokio::io::AsyncRead` are loaded like any other
+//! stream, by the file's loader thread, which blocks on each read.
+
+use std::io::{Read, Result};
+use std::pin::Pin;
+
+use tokio::io::{AsyncRead, AsyncReadExt};
+use tokio::runtime::{Builder, Handle, Runtime};
+
+/// The runtime that reads from an async stream are run on.
+enum Executor {
+    /// The runtime the stream was added from, which drives its I/O.
+    Handle(Handle),
+
+    /// A runtime of the stream's own, for streams added from outside of a
+    /// runtime.
+    Runtime(Runtime),
+}
+
+/// An async stream, read by blocking on each read.
+pub(crate) struct AsyncStream<R> {
+    /// The stream being read.
+    stream: Pin<Box<R>>,
+
+    /// The runtime reads are run on.
+    executor: Executor,
+}
+
+impl<R: AsyncRead> AsyncStream<R> {
+    /// Wrap `stream`.  If this is called from within a runtime, reads are
+    /// run on that runtime.  Otherwise a runtime is created for the stream.
+    pub(crate) fn new(stream: R) -> Result<AsyncStream<R>> {
+        let executor = match Handle::try_current() {
+            Ok(handle) => Executor::Handle(handle),
+            Err(_) => Executor::Runtime(Builder::new_current_thread().enable_all().build()?),
+        };
+        Ok(AsyncStream {
+            stream: Box::pin(stream),
+            executor,
+        })
+    }
+}
+
+impl<R: AsyncRead> Read for AsyncStream<R> {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        let read = self.stream.read(buf);
+        match self.executor {
+            Executor::Handle(ref handle) => handle.block_on(read),
+            Executor::Runtime(ref runtime) => runtime.block_on(read),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_read_outside_runtime() {
+        let mut stream = AsyncStream::new(&b"line 1\nline 2\n"[..]).unwrap();
+        let mut data = Vec::new();
+        stream.read_to_end(&mut data).unwrap();
+        assert_eq!(data, b"line 1\nline 2\n");
+    }
+
+    #[test]
+    fn test_read_from_runtime() {
+        let runtime = Builder::new_current_thread().build().unwrap();
+        let (mut writer, reader) = tokio::io::duplex(64);
+        let mut stream = runtime
+            .block_on(async { AsyncStream::new(reader) })
+            .unwrap();
+        let reader = std::thread::spawn(move || {
+            let mut data = Vec::new();
+            stream.read_to_end(&mut data).map(|_| data)
+        });
+        runtime.block_on(async {
+            use tokio::io::AsyncWriteExt;
+            writer.write_all(b"some data\n").await.unwrap();
+        });
+        drop(writer);
+        assert_eq!(reader.join().unwrap().unwrap(), b"some data\n");
+    }
+}
@@ -1,6 +1,5 @@
 //! A horizontal bar on the screen.
 
-use std::cmp::min;
 use std::sync::Arc;
 
 use termwiz::cell::CellAttributes;
@@ -12,8 +11,10 @@ use unicode_width::UnicodeWidthStr;
 use crate::util;
 
 /// A horizontal bar on the screen, e.g. the ruler or search bar.
+#[derive(Clone)]
 pub(crate) struct Bar {
     left_items: Vec<Arc<dyn BarItem>>,
+    center_items: Vec<Arc<dyn BarItem>>,
     right_items: Vec<Arc<dyn BarItem>>,
     style: BarStyle,
 }
@@ -22,8 +23,40 @@ pub(crate) struct Bar {
 pub(crate) trait BarItem {
     fn width(&self) -> usize;
     fn render(&self, changes: &mut Vec<Change>, width: usize);
+
+    /// How important the item is when the bar is too narrow for all of its
+    /// items.
+    fn priority(&self) -> BarPriority {
+        BarPriority::Normal
+    }
+
+    /// The narrowest the item can be truncated to.  If there isn't room for
+    /// this much of the item, it is hidden.
+    fn min_width(&self) -> usize {
+        self.width().min(MIN_TRUNCATED_WIDTH)
+    }
 }
 
+/// How important it is to show a bar item.  When a bar is too narrow for all
+/// of its items, items with lower priorities are truncated, and then hidden,
+/// before items with higher priorities.  Items with the same priority give
+/// up space from the center of the bar first, then from the left, then from
+/// the right, and from the last item on each side first.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub(crate) enum BarPriority {
+    /// Items that are nice to have, e.g. titles and hints.
+    Low,
+
+    /// Most items.
+    Normal,
+
+    /// Items that should be shown for as long as possible, e.g. positions.
+    High,
+}
+
+/// The smallest width items are truncated to by default.
+const MIN_TRUNCATED_WIDTH: usize = 4;
+
 /// The style of the bar.  This mostly affects the default background color.
 #[allow(unused)]
 #[derive(Clone, Copy, Debug)]
@@ -54,23 +87,73 @@ impl BarStyle {
 
 impl Bar {
     pub(crate) fn new(style: BarStyle) -> Self {
-        let left_items = Vec::new();
-        let right_items = Vec::new();
         Bar {
-            left_items,
-            right_items,
+            left_items: Vec::new(),
+            center_items: Vec::new(),
+            right_items: Vec::new(),
             style,
         }
     }
 
+    pub(crate) fn set_style(&mut self, style: BarStyle) {
+        self.style = style;
+    }
+
     pub(crate) fn add_left_item(&mut self, item: Arc<dyn BarItem>) {
         self.left_items.push(item);
     }
 
+    pub(crate) fn add_center_item(&mut self, item: Arc<dyn BarItem>) {
+        self.center_items.push(item);
+    }
+
     pub(crate) fn add_right_item(&mut self, item: Arc<dyn BarItem>) {
         self.right_items.push(item);
     }
 
+    /// Works out the widths to render the items of each side of the bar at,
+    /// so that they fit in `width` columns.  Each item is followed by two
+    /// columns of padding, and the bar starts with two columns of padding.
+    fn layout(&self, width: usize) -> [Vec<usize>; 3] {
+        let sides = [&self.left_items, &self.center_items, &self.right_items];
+        let mut widths =
+            sides.map(|items| items.iter().map(|item| item.width()).collect::<Vec<_>>());
+        let padded_width = |widths: &[Vec<usize>; 3]| -> usize {
+            2 + widths
+                .iter()
+                .flatten()
+                .map(|&w| if w == 0 { 0 } else { w + 2 })
+                .sum::<usize>()
+        };
+
+        // The order in which items give up space, among items of the same
+        // priority.
+        let mut order: Vec<(usize, usize)> = Vec::new();
+        for side in [1, 0, 2] {
+            order.extend((0..sides[side].len()).rev().map(|index| (side, index)));
+        }
+        order.sort_by_key(|&(side, index)| sides[side][index].priority());
+
+        for (side, index) in order {
+            let needed = padded_width(&widths);
+            if needed <= width {
+                break;
+            }
+            let item_width = &mut widths[side][index];
+            if *item_width == 0 {
+                continue;
+            }
+            let excess = needed - width;
+            let min_width = sides[side][index].min_width().max(1);
+            *item_width = if *item_width >= excess + min_width {
+                *item_width - excess
+            } else {
+                0
+            };
+        }
+        widths
+    }
+
     /// Render the bar to the given row on screen.
     pub(crate) fn render(&self, changes: &mut Vec<Change>, row: usize, width: usize) {
         changes.push(Change::CursorPosition {
@@ -91,83 +174,131 @@ impl Bar {
             return;
         }
 
-        let padded_item_width = |item: &Arc<dyn BarItem>| match item.width() {
-            0 => 0,
-            w => w + 2,
+        let [left_widths, center_widths, right_widths] = self.layout(width);
+        let side_width = |widths: &[usize]| -> usize {
+            widths.iter().map(|&w| if w == 0 { 0 } else { w + 2 }).sum()
         };
-        let mut left_items_width = self.left_items.iter().map(padded_item_width).sum();
-        let mut right_items_width = self.right_items.iter().map(padded_item_width).sum();
-
-        // The right-hand side is shown only if it can fit.
-        if right_items_width + 2 > width {
-            // Show only left items.
-            right_items_width = 0;
-            left_items_width = min(left_items_width, width.saturating_sub(2));
-        } else {
-            // Show both items, truncating or padding the left items to the remaining width.
-            left_items_width = width.saturating_sub(right_items_width + 2);
-        }
+        let left_end = 2 + side_width(&left_widths);
+        let right_start = width - side_width(&right_widths);
 
-        changes.push(Change::AllAttributes(bar_attribs.clone()));
-        changes.push(Change::Text(String::from("  ")));
-        let rendered_left_width = self.render_items(
-            changes,
-            self.left_items.as_slice(),
-            left_items_width.saturating_sub(2),
-        );
-        if right_items_width > 0 {
-            changes.push(Change::AllAttributes(bar_attribs));
-            let gap = left_items_width.saturating_sub(rendered_left_width);
-            changes.push(Change::Text(" ".repeat(gap)));
-            self.render_items(changes, self.right_items.as_slice(), right_items_width);
-        }
-        changes.push(Change::ClearToEndOfLine(
-            self.style.background_color().into(),
-        ));
-    }
+        // Center items are centered in the bar if they can be, otherwise
+        // they are placed as close to the center as the other items allow.
+        let center_width = side_width(&center_widths);
+        let center_start = (width.saturating_sub(center_width) / 2)
+            .max(left_end)
+            .min(right_start.saturating_sub(center_width));
 
-    fn render_items(
-        &self,
-        changes: &mut Vec<Change>,
-        items: &[Arc<dyn BarItem>],
-        width: usize,
-    ) -> usize {
-        let mut rendered_width = 0;
-        for item in items.iter() {
-            let item_width = item.width().min(width.saturating_sub(rendered_width));
-            if item_width > 0 {
-                item.render(changes, item_width);
-                rendered_width += item_width;
-                let pad = min(width - rendered_width, 2);
-                changes.push(Change::Text(" ".repeat(pad)));
-                rendered_width += pad;
-                if rendered_width >= width {
-                    break;
+        let mut column = 0;
+        let mut render_side = |items: &[Arc<dyn BarItem>], widths: &[usize], start: usize| {
+            changes.push(Change::AllAttributes(bar_attribs.clone()));
+            changes.push(Change::Text(" ".repeat(start.saturating_sub(column))));
+            column = column.max(start);
+            for (item, &item_width) in items.iter().zip(widths) {
+                if item_width > 0 {
+                    item.render(changes, item_width);
+                    changes.push(Change::AllAttributes(bar_attribs.clone()));
+                    changes.push(Change::Text(String::from("  ")));
+                    column += item_width + 2;
                 }
             }
-        }
-        rendered_width
+        };
+        render_side(&self.left_items, &left_widths, 2);
+        render_side(&self.center_items, &center_widths, center_start);
+        render_side(&self.right_items, &right_widths, right_start);
+        changes.push(Change::ClearToEndOfLine(
+            self.style.background_color().into(),
+        ));
     }
 }
 
-pub(crate) struct BarString(String);
+/// A bar item showing a fixed string.
+pub(crate) struct BarString {
+    text: String,
+    priority: BarPriority,
+    whole: bool,
+}
 
 impl BarString {
     pub(crate) fn new(s: impl Into<String>) -> Self {
-        BarString(s.into())
+        BarString {
+            text: s.into(),
+            priority: BarPriority::Normal,
+            whole: false,
+        }
+    }
+
+    /// Sets the priority of the string when the bar is too narrow.
+    pub(crate) fn with_priority(mut self, priority: BarPriority) -> Self {
+        self.priority = priority;
+        self
+    }
+
+    /// Hides the string, rather than truncating it, when the bar is too
+    /// narrow.
+    pub(crate) fn whole(mut self) -> Self {
+        self.whole = true;
+        self
     }
 }
 
 impl BarItem for BarString {
     fn width(&self) -> usize {
-        self.0.as_str().width()
+        self.text.as_str().width()
     }
 
     fn render(&self, changes: &mut Vec<Change>, width: usize) {
         changes.push(Change::Text(util::truncate_string(
-            self.0.as_str(),
+            self.text.as_str(),
             0,
             width,
         )));
     }
+
+    fn priority(&self) -> BarPriority {
+        self.priority
+    }
+
+    fn min_width(&self) -> usize {
+        if self.whole {
+            self.width()
+        } else {
+            self.width().min(MIN_TRUNCATED_WIDTH)
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn item(text: &str, priority: BarPriority) -> Arc<dyn BarItem> {
+        Arc::new(BarString::new(text).with_priority(priority))
+    }
+
+    #[test]
+    fn test_layout() {
+        let mut bar = Bar::new(BarStyle::Normal);
+        bar.add_left_item(item("a long title", BarPriority::Low));
+        bar.add_center_item(Arc::new(
+            BarString::new("hint")
+                .with_priority(BarPriority::Low)
+                .whole(),
+        ));
+        bar.add_right_item(item("info", BarPriority::Normal));
+        bar.add_right_item(item("position", BarPriority::High));
+
+        // Everything fits.
+        assert_eq!(bar.layout(40), [vec![12], vec![4], vec![4, 8]]);
+
+        // The center items give way first, then the title is truncated.
+        assert_eq!(bar.layout(33), [vec![12], vec![0], vec![4, 8]]);
+        assert_eq!(bar.layout(31), [vec![11], vec![0], vec![4, 8]]);
+        assert_eq!(bar.layout(24), [vec![4], vec![0], vec![4, 8]]);
+
+        // The title is hidden rather than truncated any further, and then
+        // the info gives way to the position.
+        assert_eq!(bar.layout(23), [vec![0], vec![0], vec![4, 8]]);
+        assert_eq!(bar.layout(12), [vec![0], vec![0], vec![0, 8]]);
+        assert_eq!(bar.layout(10), [vec![0], vec![0], vec![0, 6]]);
+    }
 }
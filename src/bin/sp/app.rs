@@ -19,11 +19,25 @@ pub(crate) fn app() -> App<'static, 'static> {
                 .help("Runs the command in a subshell and displays its output and error streams")
                 .multiple(true),
         )
+        .arg(
+            Arg::with_name("then")
+                .long("then")
+                .help("Runs the next --command after the previous one has finished, rather than at the same time")
+                .multiple(true),
+        )
+        .arg(
+            Arg::with_name("listen")
+                .long("listen")
+                .short("l")
+                .value_name("ADDRESS")
+                .help("Listens on a Unix socket (a path containing '/') or TCP address (HOST:PORT), and displays the data written to each connection")
+                .multiple(true),
+        )
         .arg(
             Arg::with_name("fullscreen")
                 .long("fullscreen")
                 .short("F")
-                .overrides_with_all(&["delayed", "no_alternate"])
+                .overrides_with_all(&["delayed", "no_alternate", "fullscreen_no_alternate"])
                 .help("Enter full screen immediately")
         )
         .arg(
@@ -31,15 +45,74 @@ pub(crate) fn app() -> App<'static, 'static> {
                 .long("delayed")
                 .short("D")
                 .value_name("SEC")
-                .overrides_with_all(&["fullscreen", "no_alternate"])
+                .overrides_with_all(&["fullscreen", "no_alternate", "fullscreen_no_alternate"])
                 .help("Enter full screen after SEC seconds without waiting for content to fill one screen."),
         )
         .arg(
             Arg::with_name("no_alternate")
                 .long("no-alternate")
                 .short("X")
-                .overrides_with_all(&["fullscreen", "delayed"])
+                .overrides_with_all(&["fullscreen", "delayed", "fullscreen_no_alternate"])
                 .help("Disables using the alternate screen. Enables streaming output before full screen."),
+        )
+        .arg(
+            Arg::with_name("fullscreen_no_alternate")
+                .long("fullscreen-no-alternate")
+                .overrides_with_all(&["fullscreen", "delayed", "no_alternate"])
+                .help("Enter full screen immediately, without using the alternate screen. The terminal's scrollback is preserved."),
+        )
+        .arg(
+            Arg::with_name("keep_screen")
+                .long("keep-screen")
+                .requires("fullscreen_no_alternate")
+                .help("Leaves the contents of the screen in the terminal on exit (with --fullscreen-no-alternate)"),
+        )
+        .arg(
+            Arg::with_name("encoding")
+                .long("encoding")
+                .value_name("ENCODING")
+                .possible_values(&["utf-8", "latin1", "utf-16le", "utf-16be", "auto"])
+                .help("Sets the character encoding of the files, or detects it with 'auto'"),
+        )
+        .arg(
+            Arg::with_name("assume_ansi")
+                .long("assume-ansi")
+                .help("Assumes a basic ANSI terminal if the terminfo database can't be found"),
+        )
+        .arg(
+            Arg::with_name("redact")
+                .long("redact")
+                .help("Masks text that looks like a secret, such as access keys and bearer tokens"),
+        )
+        .arg(
+            Arg::with_name("retry")
+                .long("retry")
+                .help("Waits for files that don't exist yet to be created, rather than failing"),
+        )
+        .arg(
+            Arg::with_name("resume")
+                .long("resume")
+                .help("Reopens the files from the last session, where they were left"),
+        )
+        .arg(
+            Arg::with_name("cmd")
+                .long("cmd")
+                .value_name("COMMANDS")
+                .help("Runs these commands once the first screen is shown, e.g. 'search ERROR; scroll-to-end'")
+                .multiple(true)
+                .number_of_values(1),
+        )
+        .arg(
+            Arg::with_name("write_output")
+                .long("write-output")
+                .value_name("FILE")
+                .help("Writes the first stream to FILE as it is read, like tee"),
+        )
+        .arg(
+            Arg::with_name("strip_escapes")
+                .long("strip-escapes")
+                .requires("write_output")
+                .help("Removes escape sequences (e.g. colors) from the output written by --write-output"),
         );
     if cfg!(unix) {
         app.arg(
@@ -60,7 +133,41 @@ pub(crate) fn app() -> App<'static, 'static> {
             Arg::with_name("progress_fd")
                 .long("progress-fd")
                 .value_name("FD")
-                .help("Displays pages from this file descriptor as progress indicators"),
+                .help("Displays pages from this file descriptor as progress indicators for the previous file or file descriptor, or for all files if none comes before it")
+                .multiple(true),
+        )
+        .arg(
+            Arg::with_name("send")
+                .long("send")
+                .conflicts_with_all(&["command", "listen", "fd", "error_fd", "resume"])
+                .help("Sends the files (or stdin) to the running sp started with --send as new files, or starts it if there isn't one"),
+        )
+        .arg(
+            Arg::with_name("reload_on_hangup")
+                .long("reload-on-hangup")
+                .help("Reloads the configuration file when sp receives SIGHUP, rather than exiting"),
+        )
+    } else if cfg!(windows) {
+        app.arg(
+            Arg::with_name("pipe")
+                .long("pipe")
+                .value_name("PIPE[=TITLE]")
+                .help("Displays the contents of this named pipe (e.g. \\\\.\\pipe\\name)")
+                .multiple(true),
+        )
+        .arg(
+            Arg::with_name("error_pipe")
+                .long("error-pipe")
+                .value_name("PIPE[=TITLE]")
+                .help("Displays the contents of this named pipe as the error stream of the previous file or pipe")
+                .multiple(true),
+        )
+        .arg(
+            Arg::with_name("progress_pipe")
+                .long("progress-pipe")
+                .value_name("PIPE")
+                .help("Displays pages from this named pipe as progress indicators for the previous file or pipe, or for all files if none comes before it")
+                .multiple(true),
         )
     } else {
         app
@@ -17,10 +17,16 @@ use clap::ArgMatches;
 use termwiz::istty::IsTty;
 use vec_map::VecMap;
 
-use streampager::{config::InterfaceMode, config::WrappingMode, Pager};
+use streampager::{
+    config::Config, config::Encoding, config::InterfaceMode, config::WrappingMode, Pager,
+};
 
 mod app;
 
+/// How many times to try sending to another `sp --send` that is starting.
+#[cfg(unix)]
+const SEND_ATTEMPTS: usize = 10;
+
 /// Main.
 fn main() {
     let args = app::app().get_matches();
@@ -47,16 +53,76 @@ enum FileSpec {
     Fd(RawFd, String),
     #[cfg(unix)]
     ErrorFd(RawFd, String),
+    #[cfg(unix)]
+    ProgressFd(RawFd),
+    #[cfg(windows)]
+    Pipe(String, String),
+    #[cfg(windows)]
+    ErrorPipe(String, String),
+    #[cfg(windows)]
+    ProgressPipe(String),
     Command(OsString),
+    Then,
+}
+
+impl FileSpec {
+    /// True if this is a progress stream rather than a file.
+    fn is_progress(&self) -> bool {
+        match self {
+            #[cfg(unix)]
+            FileSpec::ProgressFd(_) => true,
+            #[cfg(windows)]
+            FileSpec::ProgressPipe(_) => true,
+            _ => false,
+        }
+    }
 }
 
 /// Run the pager, opening files or file descriptors (including stdin).
 fn open_files(args: ArgMatches) -> Result<(), Error> {
-    let mut pager = Pager::new_using_system_terminal()?;
+    #[cfg(unix)]
+    {
+        if args.is_present("send") && send_files(&args)? {
+            return Ok(());
+        }
+    }
+
+    let mut config = Config::from_config_file().with_env();
+    if args.is_present("assume_ansi") {
+        config.assume_ansi = true;
+    }
+    if args.is_present("redact") {
+        config.redact_secrets = true;
+    }
+    let mut pager = Pager::new_using_system_terminal_with_config(config)?;
+    #[cfg(unix)]
+    {
+        // Listen before adding any files, so that they can still be sent
+        // to another pager that started listening first.
+        if args.is_present("send") && !pager.add_send_listener()? {
+            drop(pager);
+            // The other pager may not have created its socket yet.
+            for _ in 0..SEND_ATTEMPTS {
+                if send_files(&args)? {
+                    return Ok(());
+                }
+                std::thread::sleep(Duration::from_millis(100));
+            }
+            bail!("another sp --send is running but can't be connected to");
+        }
+    }
+    #[cfg(unix)]
+    {
+        if args.is_present("reload_on_hangup") {
+            pager.set_reload_config_on_hangup(true);
+        }
+    }
     if args.is_present("no_alternate") {
         pager.set_interface_mode(InterfaceMode::Hybrid);
     } else if args.is_present("fullscreen") {
         pager.set_interface_mode(InterfaceMode::FullScreen);
+    } else if args.is_present("fullscreen_no_alternate") {
+        pager.set_interface_mode(InterfaceMode::FullScreenNoAlternate);
     } else if let Some(delay) = args.value_of("delayed") {
         let delay = delay.parse::<u64>()?;
         if delay == 0 {
@@ -70,6 +136,28 @@ fn open_files(args: ArgMatches) -> Result<(), Error> {
         pager.set_wrapping_mode(WrappingMode::GraphemeBoundary);
     }
 
+    if args.is_present("keep_screen") {
+        pager.set_keep_screen_on_quit(true);
+    }
+
+    if let Some(encoding) = args.value_of("encoding").and_then(Encoding::from_name) {
+        pager.set_encoding(encoding);
+    }
+
+    for commands in args.values_of("cmd").into_iter().flatten() {
+        pager.add_startup_commands(commands);
+    }
+
+    if let Some(path) = args.value_of_os("write_output") {
+        pager.set_write_output(path, args.is_present("strip_escapes"));
+    }
+
+    // Reopen the last session's files before any others that are given.
+    let resumed = args.is_present("resume");
+    if resumed {
+        pager.resume_session()?;
+    }
+
     let mut specs = VecMap::new();
 
     // Collect file specifications from arguments.
@@ -101,6 +189,57 @@ fn open_files(args: ArgMatches) -> Result<(), Error> {
                 specs.insert(index, FileSpec::ErrorFd(fd, title.to_string()));
             }
         }
+
+        // Collect progress streams from --progress-fd arguments.
+        if let (Some(fds), Some(indices)) = (
+            args.values_of_lossy("progress_fd"),
+            args.indices_of("progress_fd"),
+        ) {
+            for (fd_spec, index) in fds.iter().zip(indices) {
+                if let Ok(fd) = fd_spec.parse::<RawFd>() {
+                    specs.insert(index, FileSpec::ProgressFd(fd));
+                }
+            }
+        }
+    }
+
+    #[cfg(windows)]
+    {
+        // Collect file specifications from --pipe arguments.
+        if let (Some(pipes), Some(indices)) =
+            (args.values_of_lossy("pipe"), args.indices_of("pipe"))
+        {
+            for (pipe_spec, index) in pipes.iter().zip(indices) {
+                let (pipe, title) = parse_pipe_title(&pipe_spec);
+                let title = title.unwrap_or(pipe);
+                specs.insert(index, FileSpec::Pipe(pipe.to_string(), title.to_string()));
+            }
+        }
+
+        // Collect file specifications from --error-pipe arguments.
+        if let (Some(pipes), Some(indices)) = (
+            args.values_of_lossy("error_pipe"),
+            args.indices_of("error_pipe"),
+        ) {
+            for (pipe_spec, index) in pipes.iter().zip(indices) {
+                let (pipe, title) = parse_pipe_title(&pipe_spec);
+                let title = title.unwrap_or(pipe);
+                specs.insert(
+                    index,
+                    FileSpec::ErrorPipe(pipe.to_string(), title.to_string()),
+                );
+            }
+        }
+
+        // Collect progress streams from --progress-pipe arguments.
+        if let (Some(pipes), Some(indices)) = (
+            args.values_of_lossy("progress_pipe"),
+            args.indices_of("progress_pipe"),
+        ) {
+            for (pipe, index) in pipes.into_iter().zip(indices) {
+                specs.insert(index, FileSpec::ProgressPipe(pipe));
+            }
+        }
     }
 
     // Collect file specifications from --command arguments.
@@ -112,20 +251,46 @@ fn open_files(args: ArgMatches) -> Result<(), Error> {
         }
     }
 
-    if specs.is_empty() {
+    // Collect the positions of --then arguments, which chain the commands
+    // either side of them.
+    if let Some(indices) = args.indices_of("then") {
+        for index in indices {
+            specs.insert(index, FileSpec::Then);
+        }
+    }
+
+    let addresses: Vec<&str> = args
+        .values_of("listen")
+        .map_or_else(Vec::new, Iterator::collect);
+    for address in addresses.iter() {
+        pager.add_socket_listener(address)?;
+    }
+
+    // Progress streams aren't files of their own.
+    let no_files = specs.values().all(FileSpec::is_progress);
+    if no_files && !addresses.is_empty() {
+        // Show something until the first connection arrives.
+        let text = format!(
+            "Listening on {}\nEach connection is displayed as a new file.\n",
+            addresses.join(", ")
+        );
+        pager.add_stream(std::io::Cursor::new(text), "sp --listen")?;
+    } else if no_files && !resumed {
         if std::io::stdin().is_tty() {
             bail!("expected filename or piped input");
         }
 
         // Nothing specified on the command line - page standard streams.
-        specs.insert(0, FileSpec::Stdin);
+        // They come after any progress streams, which are for all files.
+        let index = specs.keys().max().map_or(0, |index| index + 1);
+        specs.insert(index, FileSpec::Stdin);
 
         #[cfg(unix)]
         {
             if let Ok(fd_spec) = env::var("PAGER_ERROR_FD") {
                 if let Ok((fd, title)) = parse_fd_title(&fd_spec) {
                     let title = title.unwrap_or("STDERR");
-                    specs.insert(1, FileSpec::ErrorFd(fd, title.to_string()));
+                    specs.insert(index + 1, FileSpec::ErrorFd(fd, title.to_string()));
                 }
             }
         }
@@ -133,12 +298,7 @@ fn open_files(args: ArgMatches) -> Result<(), Error> {
 
     #[cfg(unix)]
     {
-        if let Some(fd_spec) = env::var("PAGER_PROGRESS_FD")
-            .ok()
-            .as_ref()
-            .map(String::as_ref)
-            .or_else(|| args.value_of("progress_fd"))
-        {
+        if let Ok(fd_spec) = env::var("PAGER_PROGRESS_FD") {
             if let Ok(fd) = fd_spec.parse::<RawFd>() {
                 let file = unsafe { std::fs::File::from_raw_fd(fd) };
                 pager.set_progress_stream(file);
@@ -146,44 +306,144 @@ fn open_files(args: ArgMatches) -> Result<(), Error> {
         }
     }
 
+    // Progress streams belong to the output file that comes before them.
+    // Chained commands don't have files until they start, so they can't
+    // have progress streams.
+    let mut last_output = None;
+    let mut after_chained = false;
+    // Commands after --then are chained after the first command of the chain.
+    let mut last_command = None;
+    let mut then = false;
     for (_index, spec) in specs.iter() {
         match spec {
             FileSpec::Stdin => {
                 let title = env::var("PAGER_TITLE").ok();
                 let title = title.as_ref().map(String::as_ref).unwrap_or("");
-                pager.add_stream(std::io::stdin(), title)?;
+                last_output = Some(pager.add_stream(std::io::stdin(), title)?);
+                after_chained = false;
             }
             FileSpec::Named(filename) => {
-                pager.add_file(filename)?;
+                last_output = Some(if args.is_present("retry") {
+                    pager.add_file_or_wait(filename)?
+                } else {
+                    pager.add_file(filename)?
+                });
+                after_chained = false;
             }
             #[cfg(unix)]
             FileSpec::Fd(fd, title) => {
                 let stream = unsafe { std::fs::File::from_raw_fd(*fd) };
-                pager.add_stream(stream, title)?;
+                last_output = Some(pager.add_stream(stream, title)?);
+                after_chained = false;
             }
             #[cfg(unix)]
             FileSpec::ErrorFd(fd, title) => {
                 let stream = unsafe { std::fs::File::from_raw_fd(*fd) };
                 pager.add_error_stream(stream, title)?;
             }
+            #[cfg(unix)]
+            FileSpec::ProgressFd(fd) => {
+                if after_chained {
+                    bail!("--progress-fd can't follow a command after --then");
+                }
+                let stream = unsafe { std::fs::File::from_raw_fd(*fd) };
+                match last_output {
+                    Some(index) => pager.add_progress_stream_for(index, stream)?,
+                    None => pager.set_progress_stream(stream),
+                }
+            }
+            #[cfg(windows)]
+            FileSpec::Pipe(pipe, title) => {
+                last_output = Some(pager.add_stream(open_pipe(pipe)?, title)?);
+                after_chained = false;
+            }
+            #[cfg(windows)]
+            FileSpec::ErrorPipe(pipe, title) => {
+                pager.add_error_stream(open_pipe(pipe)?, title)?;
+            }
+            #[cfg(windows)]
+            FileSpec::ProgressPipe(pipe) => {
+                if after_chained {
+                    bail!("--progress-pipe can't follow a command after --then");
+                }
+                let stream = open_pipe(pipe)?;
+                match last_output {
+                    Some(index) => pager.add_progress_stream_for(index, stream)?,
+                    None => pager.set_progress_stream(stream),
+                }
+            }
             FileSpec::Command(command) => {
                 let (shell, flag) = if cfg!(windows) {
                     ("cmd.exe", "/C")
                 } else {
                     ("/bin/sh", "-c")
                 };
-                pager.add_subprocess(
-                    OsStr::new(shell),
-                    &[OsStr::new(flag), command],
-                    &command.to_string_lossy(),
-                )?;
+                let args = [OsStr::new(flag), command];
+                let title = command.to_string_lossy();
+                match last_command {
+                    Some(index) if then => {
+                        pager.add_subprocess_then(index, OsStr::new(shell), &args, &title)?;
+                        then = false;
+                        after_chained = true;
+                    }
+                    _ => {
+                        let (index, _error_index) =
+                            pager.add_subprocess(OsStr::new(shell), &args, &title)?;
+                        last_output = Some(index);
+                        last_command = Some(index);
+                        after_chained = false;
+                    }
+                }
+            }
+            FileSpec::Then => {
+                if last_command.is_none() || then {
+                    bail!("--then must come between two commands");
+                }
+                then = true;
             }
         }
     }
+    if then {
+        bail!("--then must come between two commands");
+    }
     pager.run()?;
     Ok(())
 }
 
+#[cfg(unix)]
+/// Send the files, or stdin, to the pager started by an earlier `sp --send`.
+///
+/// Returns `false` without sending anything if there is no such pager.
+fn send_files(args: &ArgMatches) -> Result<bool, Error> {
+    use streampager::send;
+
+    let filenames: Vec<&OsStr> = args
+        .values_of_os("FILE")
+        .map_or_else(Vec::new, Iterator::collect);
+    if filenames.is_empty() {
+        let connection = match send::connect()? {
+            Some(connection) => connection,
+            None => return Ok(false),
+        };
+        if std::io::stdin().is_tty() {
+            bail!("expected filename or piped input");
+        }
+        let title = env::var("PAGER_TITLE").unwrap_or_else(|_| String::from("sp --send"));
+        send::send(connection, &title, std::io::stdin())?;
+        return Ok(true);
+    }
+    for (index, filename) in filenames.iter().enumerate() {
+        let connection = match send::connect()? {
+            Some(connection) => connection,
+            None if index == 0 => return Ok(false),
+            None => bail!("the running sp went away"),
+        };
+        let file = std::fs::File::open(filename)?;
+        send::send(connection, &filename.to_string_lossy(), file)?;
+    }
+    Ok(true)
+}
+
 #[cfg(unix)]
 /// Parse a file description and title specification.
 ///
@@ -195,3 +455,75 @@ fn parse_fd_title(fd_spec: &str) -> Result<(RawFd, Option<&str>), <RawFd as From
         Ok((fd_spec.parse::<RawFd>()?, None))
     }
 }
+
+#[cfg(any(windows, test))]
+/// Parse a pipe and title specification.
+///
+/// Parses `PIPE[=TITLE]` and returns the pipe and the optional title.
+fn parse_pipe_title(pipe_spec: &str) -> (&str, Option<&str>) {
+    match pipe_spec.find('=') {
+        Some(eq) => (&pipe_spec[..eq], Some(&pipe_spec[eq + 1..])),
+        None => (pipe_spec, None),
+    }
+}
+
+#[cfg(any(windows, test))]
+/// The prefix of the names of named pipes.
+const PIPE_PREFIX: &str = r"\\.\pipe\";
+
+#[cfg(any(windows, test))]
+/// True if `name` is the name of a named pipe, like `\\.\pipe\name`.
+fn is_pipe_name(name: &str) -> bool {
+    name.len() > PIPE_PREFIX.len()
+        && matches!(name.get(..PIPE_PREFIX.len()), Some(prefix) if prefix.eq_ignore_ascii_case(PIPE_PREFIX))
+}
+
+#[cfg(windows)]
+/// Open a named pipe given on the command line, like `\\.\pipe\name`.
+///
+/// Other paths are refused, so that files aren't mistaken for pipes.
+fn open_pipe(pipe: &str) -> Result<std::fs::File, Error> {
+    if !is_pipe_name(pipe) {
+        bail!(
+            "{} is not a named pipe (expected {}NAME)",
+            pipe,
+            PIPE_PREFIX
+        );
+    }
+    Ok(std::fs::File::open(pipe)?)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse_pipe_title() {
+        assert_eq!(
+            parse_pipe_title(r"\\.\pipe\build=Build log"),
+            (r"\\.\pipe\build", Some("Build log"))
+        );
+        assert_eq!(
+            parse_pipe_title(r"\\.\pipe\build"),
+            (r"\\.\pipe\build", None)
+        );
+        assert!(is_pipe_name(r"\\.\pipe\build"));
+        assert!(is_pipe_name(r"\\.\PIPE\build"));
+        assert!(!is_pipe_name(r"\\.\pipe\"));
+        assert!(!is_pipe_name(r"C:\build.log"));
+        assert!(!is_pipe_name("build.log"));
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn test_pipe_args() {
+        let args = app::app().get_matches_from(vec!["sp", "--pipe", r"\\.\pipe\build=Build"]);
+        let pipes = args.values_of_lossy("pipe").unwrap();
+        assert_eq!(
+            parse_pipe_title(&pipes[0]),
+            (r"\\.\pipe\build", Some("Build"))
+        );
+        // Paths that aren't named pipes are refused.
+        assert!(open_pipe(r"C:\Windows\win.ini").is_err());
+    }
+}
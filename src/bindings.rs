@@ -7,7 +7,7 @@ use std::sync::Arc;
 use indexmap::IndexMap;
 use thiserror::Error;
 
-use crate::action::Action;
+use crate::action::{Action, RerunMode};
 use crate::file::FileIndex;
 
 /// Key codes for key bindings.
@@ -145,21 +145,36 @@ impl Binding {
             Binding::Action(action) => {
                 use Action::*;
                 match action {
-                    Quit | Refresh | Help | Cancel => Category::General,
+                    Quit | QuitAndPrintScreen | QuitAll | QuitCurrentFile | QuitAndKillChild
+                    | Interrupt | Refresh | ReloadConfig | Help | ShowErrorLog | ShowCacheStats
+                    | ShowProgressHistory | Cancel | ShowCommandPalette | PauseLoading
+                    | ResumeLoading | SnapshotFile | DiffSnapshot | ToggleMouseSelection
+                    | PromptShellCommand | RunCommand(_) | RerunCommand(_) | Prompt(_)
+                    | ShowMessage(..) => Category::General,
                     PreviousFile
                     | NextFile
+                    | OpenErrorFile
                     | ScrollUpLines(_)
                     | ScrollDownLines(_)
                     | ScrollUpScreenFraction(_)
                     | ScrollDownScreenFraction(_)
+                    | ScrollUpHalfScreen
+                    | ScrollDownHalfScreen
+                    | ScrollUpWindow
+                    | ScrollDownWindow
                     | ScrollToTop
                     | ScrollToBottom
+                    | ScrollToPercent(_)
                     | ScrollLeftColumns(_)
                     | ScrollRightColumns(_)
                     | ScrollLeftScreenFraction(_)
                     | ScrollRightScreenFraction(_)
-                    | PromptGoToLine => Category::Navigation,
-                    ToggleRuler | ToggleLineNumbers | ToggleLineWrapping => Category::Presentation,
+                    | PromptGoToLine
+                    | OpenListedFile => Category::Navigation,
+                    ToggleRuler | ToggleErrorFile | ToggleLineNumbers | ToggleLineWrapping
+                    | ToggleMarkdown | ToggleJsonView | ToggleLineEndings | ToggleWhitespace => {
+                        Category::Presentation
+                    }
                     PromptSearchFromStart
                     | PromptSearchForwards
                     | PromptSearchBackwards
@@ -170,8 +185,11 @@ impl Binding {
                     | PreviousMatchScreen
                     | NextMatchScreen
                     | FirstMatch
-                    | LastMatch => Category::Searching,
-                    AppendDigitToRepeatCount(_) => Category::Hidden,
+                    | LastMatch
+                    | RepeatLastSearch
+                    | ToggleSearchVisibleColumns
+                    | Search(_) => Category::Searching,
+                    AppendDigitToRepeatCount(_) | ShowDiagnostics => Category::Hidden,
                 }
             }
             Binding::Custom(binding) => binding.category,
@@ -193,11 +211,36 @@ impl Binding {
             Ok(value)
         };
 
+        let param_string = |index| -> Result<String> {
+            let value: &String = params
+                .get(index)
+                .ok_or_else(|| BindingError::MissingParameter(ident.clone(), index))?;
+            Ok(value.clone())
+        };
+
         let action = match ident.as_str() {
             "Quit" => Quit,
+            "QuitAndPrintScreen" => QuitAndPrintScreen,
+            "QuitAll" => QuitAll,
+            "QuitCurrentFile" => QuitCurrentFile,
+            "QuitAndKillChild" => QuitAndKillChild,
+            "Interrupt" => Interrupt,
             "Refresh" => Refresh,
             "Help" => Help,
+            "ReloadConfig" => ReloadConfig,
+            "ShowErrorLog" => ShowErrorLog,
+            "ToggleErrorFile" => ToggleErrorFile,
+            "OpenErrorFile" => OpenErrorFile,
+            "ShowCacheStats" => ShowCacheStats,
+            "ShowProgressHistory" => ShowProgressHistory,
+            "ShowDiagnostics" => ShowDiagnostics,
             "Cancel" => Cancel,
+            "ShowCommandPalette" => ShowCommandPalette,
+            "PauseLoading" => PauseLoading,
+            "ResumeLoading" => ResumeLoading,
+            "SnapshotFile" => SnapshotFile,
+            "DiffSnapshot" => DiffSnapshot,
+            "ToggleMouseSelection" => ToggleMouseSelection,
             "PreviousFile" => PreviousFile,
             "NextFile" => NextFile,
             "ToggleRuler" => ToggleRuler,
@@ -205,15 +248,26 @@ impl Binding {
             "ScrollDownLines" => ScrollDownLines(param_usize(0)?),
             "ScrollUpScreenFraction" => ScrollUpScreenFraction(param_usize(0)?),
             "ScrollDownScreenFraction" => ScrollDownScreenFraction(param_usize(0)?),
+            "ScrollUpHalfScreen" => ScrollUpHalfScreen,
+            "ScrollDownHalfScreen" => ScrollDownHalfScreen,
+            "ScrollUpWindow" => ScrollUpWindow,
+            "ScrollDownWindow" => ScrollDownWindow,
             "ScrollToTop" => ScrollToTop,
             "ScrollToBottom" => ScrollToBottom,
+            "ScrollToPercent" | "GoToPercent" => ScrollToPercent(param_usize(0)?),
             "ScrollLeftColumns" => ScrollLeftColumns(param_usize(0)?),
             "ScrollRightColumns" => ScrollRightColumns(param_usize(0)?),
             "ScrollLeftScreenFraction" => ScrollLeftScreenFraction(param_usize(0)?),
             "ScrollRightScreenFraction" => ScrollRightScreenFraction(param_usize(0)?),
             "ToggleLineNumbers" => ToggleLineNumbers,
             "ToggleLineWrapping" => ToggleLineWrapping,
+            "ToggleMarkdown" => ToggleMarkdown,
+            "ToggleJsonView" => ToggleJsonView,
+            "ToggleLineEndings" => ToggleLineEndings,
+            "ToggleWhitespace" => ToggleWhitespace,
             "PromptGoToLine" => PromptGoToLine,
+            "OpenListedFile" => OpenListedFile,
+            "PromptShellCommand" => PromptShellCommand,
             "PromptSearchFromStart" => PromptSearchFromStart,
             "PromptSearchForwards" => PromptSearchForwards,
             "PromptSearchBackwards" => PromptSearchBackwards,
@@ -221,8 +275,21 @@ impl Binding {
             "NextMatch" => NextMatch,
             "PreviousMatchLine" => PreviousMatchLine,
             "NextMatchLine" => NextMatchLine,
+            "PreviousMatchScreen" => PreviousMatchScreen,
+            "NextMatchScreen" => NextMatchScreen,
             "FirstMatch" => FirstMatch,
             "LastMatch" => LastMatch,
+            "RepeatLastSearch" => RepeatLastSearch,
+            "ToggleSearchVisibleColumns" => ToggleSearchVisibleColumns,
+            "Search" => Search(param_string(0)?),
+            "RunCommand" => RunCommand(param_string(0)?),
+            "RerunCommand" => RerunCommand(match params.first().map(String::as_str) {
+                None | Some("replace") => RerunMode::Replace,
+                Some("append") => RerunMode::Append,
+                Some(mode) => {
+                    return Err(BindingError::Invalid(mode.to_string()).for_parameter(ident, 0))
+                }
+            }),
             _ => return Ok(Binding::Unrecognized(ident)),
         };
 
@@ -416,6 +483,41 @@ impl Keymap {
         self
     }
 
+    /// Returns a copy of this keymap with the bindings in `overrides` added,
+    /// replacing any existing bindings for the same keys.
+    pub(crate) fn merge(&self, overrides: &Keymap) -> Keymap {
+        let mut keymap = Keymap {
+            bindings: self.bindings.clone(),
+            keys: self.keys.clone(),
+        };
+        for (&(modifiers, keycode), binding) in overrides.bindings.iter() {
+            keymap.bind_impl(modifiers, keycode, Some(binding.clone()), false);
+        }
+        for (binding, keys) in overrides.keys.iter() {
+            for &(modifiers, keycode) in keys.iter() {
+                keymap.bind_impl(modifiers, keycode, Some(binding.clone()), true);
+            }
+        }
+        keymap
+    }
+
+    /// Returns the keys that this keymap and `other` bind differently,
+    /// along with both bindings.
+    #[cfg(feature = "keymap-file")]
+    pub(crate) fn conflicts<'a>(
+        &'a self,
+        other: &'a Keymap,
+    ) -> impl Iterator<Item = ((Modifiers, KeyCode), &'a Binding, &'a Binding)> {
+        self.bindings
+            .iter()
+            .filter_map(move |(key, binding)| match other.bindings.get(key) {
+                Some(other_binding) if other_binding != binding => {
+                    Some((*key, binding, other_binding))
+                }
+                _ => None,
+            })
+    }
+
     pub(crate) fn iter_keys(&self) -> impl Iterator<Item = (&Binding, &Vec<(Modifiers, KeyCode)>)> {
         self.keys.iter()
     }
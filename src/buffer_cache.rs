@@ -28,6 +28,11 @@ impl BufferCache {
         }
     }
 
+    /// The number of blocks in the cache.
+    pub(crate) fn len(&self) -> usize {
+        self.cache.len()
+    }
+
     pub(crate) fn clear(&mut self) {
         self.cache.clear();
         self.file = None;
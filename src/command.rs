@@ -2,19 +2,56 @@
 //!
 //! Commands the user can invoke.
 
+use std::cmp::Reverse;
+
+use crate::action::{Action, PromptRequest};
+use crate::bindings::{Binding, Category, Keymap};
 use crate::display::DisplayAction;
 use crate::error::Error;
 use crate::event::EventSender;
 use crate::file::FileInfo;
 use crate::prompt::Prompt;
 use crate::screen::Screen;
-use crate::search::{MatchMotion, Search, SearchKind};
+use crate::search::{MatchMotion, SearchKind};
+use crate::util;
+
+/// Confirm quitting while the file is still loading (Shortcut: 'q')
+///
+/// Asks the user whether to quit anyway.  If the file is the output of a
+/// command that is still running, the user can also choose to kill the
+/// command before quitting.
+pub(crate) fn confirm_quit(running_command: bool) -> Prompt {
+    let prompt = if running_command {
+        "Command still running \u{2014} quit anyway? (y/n/k=kill)"
+    } else {
+        "Still loading \u{2014} quit anyway? (y/n)"
+    };
+    Prompt::new(
+        "confirm_quit",
+        prompt,
+        Box::new(
+            move |screen: &mut Screen, value: &str| -> Result<DisplayAction, Error> {
+                match value {
+                    "y" | "Y" => Ok(DisplayAction::Quit),
+                    "k" | "K" if running_command => {
+                        screen.file.kill_command();
+                        Ok(DisplayAction::Quit)
+                    }
+                    _ => Ok(DisplayAction::Render),
+                }
+            },
+        ),
+    )
+    .with_single_key()
+}
 
 /// Go to a line (Shortcut: ':')
 ///
 /// Prompts the user for a line number or percentage within the file and jumps
-/// to that position.  Negative numbers can be used to refer to locations
-/// relative to the end of the file.
+/// to that position.  Negative percentages can be used to refer to locations
+/// relative to the end of the file.  Numbers prefixed with `+` or `-` move
+/// forwards or backwards that many lines, and numbers prefixed with `@` move
+/// to the line containing that byte offset.
 pub(crate) fn goto() -> Prompt {
     Prompt::new(
         "goto",
@@ -27,8 +64,24 @@ pub(crate) fn goto() -> Prompt {
                     "" => return Ok(DisplayAction::Render),
                     _ => {}
                 }
-                let lines = screen.file.lines() as isize;
-                if let Some(value_percent) = value.strip_suffix('%') {
+                let lines = screen.file.lines();
+                if let Some(value_offset) = value.strip_prefix('@') {
+                    // Byte offset
+                    match str::parse::<usize>(value_offset) {
+                        Ok(offset) => match screen.file.line_at_offset(offset) {
+                            Some(line) => screen.scroll_to(line),
+                            None => {
+                                screen.set_error(format!(
+                                    "offset {} is past the end of the file",
+                                    offset
+                                ));
+                            }
+                        },
+                        Err(e) => {
+                            screen.set_error(e.to_string());
+                        }
+                    }
+                } else if let Some(value_percent) = value.strip_suffix('%') {
                     // Percentage
                     match str::parse::<isize>(value_percent) {
                         Ok(mut value_percent) => {
@@ -36,30 +89,34 @@ pub(crate) fn goto() -> Prompt {
                             if value_percent < 0 {
                                 value_percent += 100;
                             }
-                            let value = value_percent * (lines - 1) / 100;
-                            screen.scroll_to(value as usize);
+                            screen.scroll_to_percent(value_percent as usize);
+                        }
+                        Err(e) => {
+                            screen.set_error(e.to_string());
+                        }
+                    }
+                } else if let Some(value_lines) = value.strip_prefix('+') {
+                    // Relative forwards
+                    match str::parse::<usize>(value_lines) {
+                        Ok(value_lines) => screen.scroll_down(value_lines),
+                        Err(e) => {
+                            screen.set_error(e.to_string());
                         }
+                    }
+                } else if let Some(value_lines) = value.strip_prefix('-') {
+                    // Relative backwards
+                    match str::parse::<usize>(value_lines) {
+                        Ok(value_lines) => screen.scroll_up(value_lines),
                         Err(e) => {
-                            screen.error = Some(e.to_string());
+                            screen.set_error(e.to_string());
                         }
                     }
                 } else {
                     // Absolute
-                    match str::parse::<isize>(value) {
-                        Ok(value) => {
-                            let value = if value < -lines || value == 0 {
-                                0
-                            } else if value > lines {
-                                lines - 1
-                            } else if value < 0 {
-                                lines + value - 1
-                            } else {
-                                value - 1
-                            };
-                            screen.scroll_to(value as usize);
-                        }
+                    match str::parse::<usize>(value) {
+                        Ok(value) => screen.scroll_to(value.min(lines).saturating_sub(1)),
                         Err(e) => {
-                            screen.error = Some(e.to_string());
+                            screen.set_error(e.to_string());
                         }
                     }
                 }
@@ -87,12 +144,125 @@ pub(crate) fn search(kind: SearchKind, event_sender: EventSender) -> Prompt {
                         SearchKind::FirstBefore(_) => screen.move_match(MatchMotion::PreviousLine),
                     }
                 } else {
-                    screen.set_search(
-                        Search::new(&screen.file, value, kind, event_sender.clone()).ok(),
-                    );
+                    screen.search_for(value, kind, event_sender.clone());
                 }
                 Ok(DisplayAction::Render)
             },
         ),
     )
 }
+
+/// Run a shell command (Shortcut: '!')
+///
+/// Prompts the user for a command to run.  The command is run by the shell
+/// with the terminal restored to its normal state, so interactive commands
+/// can be used.  The pager returns once the user presses a key.
+pub(crate) fn shell_command() -> Prompt {
+    Prompt::new(
+        "shell",
+        "!",
+        Box::new(
+            |_screen: &mut Screen, value: &str| -> Result<DisplayAction, Error> {
+                if value.is_empty() {
+                    return Ok(DisplayAction::Render);
+                }
+                Ok(DisplayAction::RunShellCommand(value.to_string()))
+            },
+        ),
+    )
+}
+
+/// Prompt for the application using the pager
+///
+/// Prompts the user for a value with the request's label, and sends the
+/// value back to the application that made the request.
+pub(crate) fn request(request: &PromptRequest) -> Prompt {
+    let mut reply = Some(request.reply());
+    Prompt::new(
+        "request",
+        request.label(),
+        Box::new(
+            move |_screen: &mut Screen, value: &str| -> Result<DisplayAction, Error> {
+                if let Some(reply) = reply.take() {
+                    reply.send(value);
+                }
+                Ok(DisplayAction::Render)
+            },
+        ),
+    )
+}
+
+/// Choose an action (Shortcut: 'Alt-x')
+///
+/// Prompts the user for an action to perform.  All of the bindings in the
+/// keymap, as well as actions that aren't bound to any key, are offered and
+/// fuzzy-matched against what the user types.  The best match is performed.
+pub(crate) fn palette(keymap: &Keymap, event_sender: EventSender) -> Prompt {
+    let mut bindings: Vec<Binding> = keymap
+        .iter_keys()
+        .map(|(binding, _keys)| binding.clone())
+        .filter(|binding| match binding {
+            Binding::Action(Action::ShowCommandPalette) | Binding::Unrecognized(_) => false,
+            binding => binding.category() != Category::Hidden,
+        })
+        .collect();
+    for action in Action::ALL.iter() {
+        let binding = Binding::Action(action.clone());
+        if binding.category() != Category::Hidden
+            && binding != Binding::Action(Action::ShowCommandPalette)
+            && !bindings.contains(&binding)
+        {
+            bindings.push(binding);
+        }
+    }
+    let completion_bindings = bindings.clone();
+    Prompt::new(
+        "palette",
+        "Action:",
+        Box::new(
+            move |screen: &mut Screen, value: &str| -> Result<DisplayAction, Error> {
+                if value.is_empty() {
+                    return Ok(DisplayAction::Render);
+                }
+                match palette_matches(&bindings, value).first() {
+                    Some(Binding::Action(action)) => {
+                        Ok(screen.dispatch_action(action.clone(), &event_sender))
+                    }
+                    Some(Binding::Custom(binding)) => {
+                        binding.run(screen.file.index());
+                        Ok(DisplayAction::Render)
+                    }
+                    _ => {
+                        screen.set_error(format!("No action matches: {}", value));
+                        Ok(DisplayAction::Render)
+                    }
+                }
+            },
+        ),
+    )
+    .with_completion(Box::new(move |value: &str| {
+        palette_matches(&completion_bindings, value)
+            .into_iter()
+            .map(|binding| binding.to_string())
+            .collect()
+    }))
+}
+
+/// Returns the bindings whose descriptions match `value`, best match first.
+fn palette_matches<'a>(bindings: &'a [Binding], value: &str) -> Vec<&'a Binding> {
+    let mut matches: Vec<_> = bindings
+        .iter()
+        .filter_map(|binding| {
+            let description = binding.to_string();
+            util::fuzzy_match(value, &description)
+                .map(|score| ((description.eq_ignore_ascii_case(value), score), binding))
+        })
+        .collect();
+    // Exact matches come first.  Sorting is stable, so equal matches remain
+    // in keymap order.
+    matches.sort_by_key(|(key, _binding)| Reverse(*key));
+    matches
+        .into_iter()
+        .map(|(_score, binding)| binding)
+        .collect()
+}
@@ -1,5 +1,7 @@
 //! Configuration that affects Pager behaviors.
 
+use std::collections::HashMap;
+use std::path::PathBuf;
 use std::sync::Arc;
 use std::time::Duration;
 
@@ -7,6 +9,8 @@ use serde::Deserialize;
 
 use crate::bindings::Keymap;
 use crate::error::Result;
+use crate::file::FileIndex;
+use crate::transform::LineTransforms;
 
 /// Specify what interface to use.
 #[derive(Copy, Clone, Debug, PartialEq, Eq, Deserialize)]
@@ -22,6 +26,16 @@ pub enum InterfaceMode {
     /// Similar to external command `less` without flags. This is the default.
     FullScreen,
 
+    /// The full screen terminal interface, without the alternate screen.
+    ///
+    /// The contents of the terminal are pushed into the scrollback before
+    /// the pager starts, so that they are preserved.  At exit, the pager's
+    /// screen is cleared, unless `keep_screen_on_quit` is set, in which case
+    /// the current screen contents are left in the terminal.
+    ///
+    /// Similar to external command `less -X`.
+    FullScreenNoAlternate,
+
     /// The minimal interface. Output goes to the terminal directly.
     ///
     /// Does not support text search or other fancy operations.
@@ -71,6 +85,7 @@ impl From<&str> for InterfaceMode {
     fn from(value: &str) -> InterfaceMode {
         match value.to_lowercase().as_ref() {
             "full" | "fullscreen" | "" => InterfaceMode::FullScreen,
+            "full-no-alternate" | "fullscreen-no-alternate" => InterfaceMode::FullScreenNoAlternate,
             "direct" => InterfaceMode::Direct,
             "hybrid" => InterfaceMode::Hybrid,
             s if s.starts_with("delayed") => {
@@ -101,6 +116,10 @@ pub enum WrappingMode {
     /// Lines are wrapped on word boundaries.
     #[serde(rename = "word")]
     WordBoundary,
+    /// Lines are wrapped on word boundaries at the wrap column, or the
+    /// screen width if that is narrower.
+    #[serde(rename = "column")]
+    Column,
 }
 
 impl WrappingMode {
@@ -108,7 +127,9 @@ impl WrappingMode {
         match self {
             WrappingMode::Unwrapped => WrappingMode::GraphemeBoundary,
             WrappingMode::GraphemeBoundary => WrappingMode::WordBoundary,
-            WrappingMode::WordBoundary => WrappingMode::Unwrapped,
+            // Column wrapping is only chosen in the configuration, so
+            // cycling leaves it for no wrapping.
+            WrappingMode::WordBoundary | WrappingMode::Column => WrappingMode::Unwrapped,
         }
     }
 }
@@ -119,6 +140,543 @@ impl Default for WrappingMode {
     }
 }
 
+/// Specify the character encoding of files.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Deserialize)]
+pub enum Encoding {
+    /// Files are UTF-8.  Invalid bytes are shown as `<XX>`.
+    #[default]
+    #[serde(rename = "utf-8", alias = "utf8")]
+    Utf8,
+    /// Files are ISO-8859-1.
+    #[serde(rename = "latin1", alias = "latin-1", alias = "iso-8859-1")]
+    Latin1,
+    /// Files are little-endian UTF-16.
+    #[serde(rename = "utf-16le", alias = "utf16le")]
+    Utf16Le,
+    /// Files are big-endian UTF-16.
+    #[serde(rename = "utf-16be", alias = "utf16be")]
+    Utf16Be,
+    /// Detect the encoding of each file from its byte order mark or the
+    /// start of its content.
+    #[serde(rename = "auto")]
+    Auto,
+}
+
+impl Encoding {
+    /// Returns the encoding with the given name, which is any of the names
+    /// accepted in the configuration, ignoring case.
+    pub fn from_name(name: &str) -> Option<Encoding> {
+        use serde::de::value::{Error, StrDeserializer};
+        let name = name.to_lowercase();
+        Encoding::deserialize(StrDeserializer::<Error>::new(&name)).ok()
+    }
+
+    /// Returns the name of the encoding, as it is written in the
+    /// configuration.
+    pub fn name(self) -> &'static str {
+        match self {
+            Encoding::Utf8 => "utf-8",
+            Encoding::Latin1 => "latin1",
+            Encoding::Utf16Le => "utf-16le",
+            Encoding::Utf16Be => "utf-16be",
+            Encoding::Auto => "auto",
+        }
+    }
+}
+
+/// Specify whether searches ignore differences in how equivalent text is
+/// written.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Deserialize)]
+pub enum SearchNormalization {
+    /// Text only matches if it is written the same way as the pattern.
+    #[default]
+    #[serde(rename = "none")]
+    None,
+    /// Text and patterns are compared in Unicode normalization form C, and
+    /// ignoring case, so that precomposed characters match the same
+    /// characters written with combining marks.
+    #[serde(rename = "nfc")]
+    Nfc,
+    /// Text and patterns are compared in Unicode normalization form KC, and
+    /// ignoring case, so that compatibility characters, like full-width
+    /// letters, also match the characters they are variants of.
+    #[serde(rename = "nfkc")]
+    Nfkc,
+}
+
+impl SearchNormalization {
+    /// Returns the search normalization with the given name.
+    pub fn from_name(name: &str) -> Option<SearchNormalization> {
+        match name.to_lowercase().as_ref() {
+            "none" => Some(SearchNormalization::None),
+            "nfc" => Some(SearchNormalization::Nfc),
+            "nfkc" => Some(SearchNormalization::Nfkc),
+            _ => None,
+        }
+    }
+}
+
+/// Specify how far ahead of the screen to read from streams.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize)]
+pub enum ReadAheadProfile {
+    /// Only read as much of the stream as is needed to fill the screen.
+    ///
+    /// Suitable for commands that are expensive to run, for example ones
+    /// that fetch data from a remote server.
+    #[serde(rename = "conservative")]
+    Conservative,
+    /// Read a moderate number of lines ahead of the screen.
+    #[serde(rename = "normal")]
+    Normal,
+    /// Read the whole stream as quickly as possible.
+    ///
+    /// Suitable for fast local commands.
+    #[serde(rename = "aggressive")]
+    Aggressive,
+}
+
+impl ReadAheadProfile {
+    /// Returns the profile with the given name.
+    pub fn from_name(name: &str) -> Option<ReadAheadProfile> {
+        match name.to_lowercase().as_ref() {
+            "conservative" => Some(ReadAheadProfile::Conservative),
+            "normal" => Some(ReadAheadProfile::Normal),
+            "aggressive" => Some(ReadAheadProfile::Aggressive),
+            _ => None,
+        }
+    }
+
+    /// Returns the number of lines to read ahead of the screen.
+    pub fn read_ahead_lines(self) -> usize {
+        match self {
+            ReadAheadProfile::Conservative => 0,
+            ReadAheadProfile::Normal => crate::file::DEFAULT_NEEDED_LINES,
+            ReadAheadProfile::Aggressive => usize::MAX,
+        }
+    }
+}
+
+/// Specify when reading from a stream stops once enough of it has been read
+/// ahead of the screen.
+///
+/// While reading is stopped, the pipe the stream comes from fills up, and
+/// the process writing to it blocks until more is read.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Deserialize)]
+pub enum Backpressure {
+    /// Always stop reading once enough has been read ahead.
+    #[default]
+    #[serde(rename = "always")]
+    Always,
+    /// Stop reading once enough has been read ahead, except while following
+    /// the end of the file.
+    #[serde(rename = "unless-following")]
+    UnlessFollowing,
+    /// Never stop reading.  The whole stream is read into memory as it is
+    /// written, so the writing process never blocks.
+    #[serde(rename = "never")]
+    Never,
+}
+
+impl Backpressure {
+    /// Returns the backpressure setting with the given name.
+    pub fn from_name(name: &str) -> Option<Backpressure> {
+        match name.to_lowercase().as_ref() {
+            "always" => Some(Backpressure::Always),
+            "unless-following" => Some(Backpressure::UnlessFollowing),
+            "never" => Some(Backpressure::Never),
+            _ => None,
+        }
+    }
+
+    /// Returns whether reading should stop once enough has been read ahead,
+    /// given whether the end of the file is being followed.
+    pub(crate) fn applies(self, following_end: bool) -> bool {
+        match self {
+            Backpressure::Always => true,
+            Backpressure::UnlessFollowing => !following_end,
+            Backpressure::Never => false,
+        }
+    }
+}
+
+/// Specify how often the screen is updated for animations, such as the
+/// loading indicator.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Deserialize)]
+pub enum Animation {
+    /// Don't animate.  The screen is still updated when more of a file is
+    /// loaded, but the loading indicator doesn't wake the pager.
+    #[serde(rename = "off")]
+    Off,
+    /// Animate slowly, reducing the number of redraws.
+    #[serde(rename = "slow")]
+    Slow,
+    /// Animate normally.
+    #[default]
+    #[serde(rename = "normal")]
+    Normal,
+}
+
+impl Animation {
+    /// Returns the animation setting with the given name.
+    pub fn from_name(name: &str) -> Option<Animation> {
+        match name.to_lowercase().as_ref() {
+            "off" => Some(Animation::Off),
+            "slow" => Some(Animation::Slow),
+            "normal" => Some(Animation::Normal),
+            _ => None,
+        }
+    }
+
+    /// Returns how long to wait between updates to the screen while
+    /// something is changing.
+    pub(crate) fn interval(self) -> Duration {
+        match self {
+            Animation::Off => Duration::from_millis(1000),
+            Animation::Slow => Duration::from_millis(500),
+            Animation::Normal => Duration::from_millis(100),
+        }
+    }
+
+    /// Returns how long each frame of the loading indicator is shown for.
+    pub(crate) fn frame_duration(self) -> Option<Duration> {
+        match self {
+            Animation::Off => None,
+            Animation::Slow => Some(Duration::from_millis(1000)),
+            Animation::Normal => Some(Duration::from_millis(200)),
+        }
+    }
+}
+
+/// Specify when to use color.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Deserialize)]
+pub enum ColorMode {
+    /// Use color unless the `NO_COLOR` environment variable is set.
+    #[default]
+    #[serde(rename = "auto")]
+    Auto,
+    /// Always use color.
+    #[serde(rename = "always")]
+    Always,
+    /// Never use color.
+    #[serde(rename = "never")]
+    Never,
+}
+
+impl ColorMode {
+    /// Returns the color mode with the given name.
+    pub fn from_name(name: &str) -> Option<ColorMode> {
+        match name.to_lowercase().as_ref() {
+            "auto" => Some(ColorMode::Auto),
+            "always" => Some(ColorMode::Always),
+            "never" => Some(ColorMode::Never),
+            _ => None,
+        }
+    }
+}
+
+/// Specify how many colors the terminal can show.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Deserialize)]
+pub enum ColorDepth {
+    /// Send colors to the terminal as they are.
+    #[default]
+    #[serde(rename = "auto")]
+    Auto,
+    /// The terminal supports 24-bit RGB colors.
+    #[serde(rename = "truecolor")]
+    TrueColor,
+    /// The terminal supports the 256-color palette.  RGB colors are
+    /// converted to the nearest palette color.
+    #[serde(rename = "256")]
+    Colors256,
+    /// The terminal supports the 16 standard colors.  Other colors are
+    /// converted to the nearest standard color.
+    #[serde(rename = "16")]
+    Colors16,
+}
+
+impl ColorDepth {
+    /// Returns the color depth with the given name.
+    pub fn from_name(name: &str) -> Option<ColorDepth> {
+        match name.to_lowercase().as_ref() {
+            "auto" => Some(ColorDepth::Auto),
+            "truecolor" | "24bit" => Some(ColorDepth::TrueColor),
+            "256" => Some(ColorDepth::Colors256),
+            "16" => Some(ColorDepth::Colors16),
+            _ => None,
+        }
+    }
+}
+
+/// Specify how the screen is drawn.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Deserialize)]
+pub enum Renderer {
+    /// Only redraw the rows that are known to have changed, and move text
+    /// with the terminal's scrolling regions where possible.
+    #[default]
+    #[serde(rename = "incremental")]
+    Incremental,
+    /// Compose the whole screen each time, and only send the cells that
+    /// differ from the previous screen.
+    #[serde(rename = "surface")]
+    Surface,
+}
+
+impl Renderer {
+    /// Returns the renderer with the given name.
+    pub fn from_name(name: &str) -> Option<Renderer> {
+        match name.to_lowercase().as_ref() {
+            "incremental" => Some(Renderer::Incremental),
+            "surface" => Some(Renderer::Surface),
+            _ => None,
+        }
+    }
+}
+
+/// Specify how the user is alerted when a watch pattern matches.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Deserialize)]
+pub enum WatchAlert {
+    /// Ring the terminal bell.
+    #[serde(rename = "bell")]
+    Bell,
+    /// Briefly flash the ruler.
+    #[serde(rename = "flash")]
+    Flash,
+    /// Show a message with the line that matched.
+    #[default]
+    #[serde(rename = "message")]
+    Message,
+}
+
+impl WatchAlert {
+    /// Returns the watch alert with the given name.
+    pub fn from_name(name: &str) -> Option<WatchAlert> {
+        match name.to_lowercase().as_ref() {
+            "bell" => Some(WatchAlert::Bell),
+            "flash" => Some(WatchAlert::Flash),
+            "message" => Some(WatchAlert::Message),
+            _ => None,
+        }
+    }
+}
+
+/// A rule for highlighting text that matches a regular expression.
+///
+/// Colors are either ANSI color names, like `red` or `bright-blue`, or
+/// RGB colors, like `#ff8000`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(default)]
+pub struct HighlightRule {
+    /// The regular expression to highlight.
+    pub pattern: String,
+
+    /// The foreground color of highlighted text.
+    pub foreground: Option<String>,
+
+    /// The background color of highlighted text.
+    pub background: Option<String>,
+
+    /// Whether highlighted text is bold.
+    pub bold: bool,
+
+    /// Whether highlighted text is underlined.
+    pub underline: bool,
+}
+
+/// The style of text that isn't matched by a pattern, such as the lines
+/// of error files.
+///
+/// Colors are named as in [`HighlightRule`].  A style with no colors or
+/// attributes leaves the text unchanged.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(default)]
+pub struct TextStyle {
+    /// The foreground color of the text.
+    pub foreground: Option<String>,
+
+    /// The background color of the text.
+    pub background: Option<String>,
+
+    /// Whether the text is bold.
+    pub bold: bool,
+
+    /// Whether the text is underlined.
+    pub underline: bool,
+}
+
+/// Settings that apply to particular kinds of file.
+///
+/// A profile applies to files whose title has one of the profile's
+/// extensions, or matches its title pattern.  If more than one profile
+/// applies, the first one is used.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(default)]
+pub struct Profile {
+    /// File extensions, without the leading `.`, that the profile applies to.
+    pub extensions: Vec<String>,
+
+    /// A regular expression for the titles of files the profile applies to.
+    pub title_pattern: Option<String>,
+
+    /// The wrapping mode for these files, overriding `wrapping_mode`.
+    pub wrapping_mode: Option<WrappingMode>,
+
+    /// The number of columns between tab stops, overriding `tab_width`.
+    pub tab_width: Option<usize>,
+
+    /// When to stop reading these files, overriding `backpressure`.
+    pub backpressure: Option<Backpressure>,
+
+    /// Rules for highlighting text in these files.  These take precedence
+    /// over the general highlight rules.
+    pub highlight_rules: Vec<HighlightRule>,
+}
+
+impl Profile {
+    /// Returns true if the profile applies to a file with the given title.
+    fn applies_to(&self, title: &str) -> Result<bool> {
+        let extension = std::path::Path::new(title)
+            .extension()
+            .and_then(|extension| extension.to_str());
+        if let Some(extension) = extension {
+            if self
+                .extensions
+                .iter()
+                .any(|e| e.eq_ignore_ascii_case(extension))
+            {
+                return Ok(true);
+            }
+        }
+        match self.title_pattern.as_ref() {
+            Some(pattern) => Ok(regex::Regex::new(pattern)?.is_match(title)),
+            None => Ok(false),
+        }
+    }
+}
+
+/// An item that can be shown in the ruler.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RulerItem {
+    /// The title of the file (`{title}`).
+    Title,
+
+    /// Additional information about the file (`{info}`).
+    Info,
+
+    /// The current position within the file (`{position}`).
+    Position,
+
+    /// Whether the file is still loading (`{loading}`).
+    Loading,
+
+    /// The repeat count that is being entered (`{count}`).
+    RepeatCount,
+
+    /// How far through the file the bottom of the screen is, as a
+    /// percentage (`{percent}`).
+    Percent,
+
+    /// The byte offset of the top line on the screen (`{offset}`).
+    ByteOffset,
+
+    /// Whether mouse capture is paused so that text can be selected with
+    /// the mouse (`{mouse}`).
+    MouseSelection,
+
+    /// The size of the file on disk (`{size}`).
+    Size,
+
+    /// How long ago the file on disk was last modified (`{modified}`).
+    Modified,
+
+    /// The character encoding of the file (`{encoding}`).
+    Encoding,
+
+    /// Literal text.
+    Text(String),
+}
+
+impl RulerItem {
+    fn from_name(name: &str) -> Option<RulerItem> {
+        match name {
+            "title" => Some(RulerItem::Title),
+            "info" => Some(RulerItem::Info),
+            "position" => Some(RulerItem::Position),
+            "loading" => Some(RulerItem::Loading),
+            "count" => Some(RulerItem::RepeatCount),
+            "percent" => Some(RulerItem::Percent),
+            "offset" => Some(RulerItem::ByteOffset),
+            "mouse" => Some(RulerItem::MouseSelection),
+            "size" => Some(RulerItem::Size),
+            "modified" => Some(RulerItem::Modified),
+            "encoding" => Some(RulerItem::Encoding),
+            _ => None,
+        }
+    }
+}
+
+/// Specify the items shown in the ruler, and their order.
+///
+/// This is parsed from a format string like `"{title} | {position} {loading}"`.
+/// Items before the `|` are shown on the left of the ruler, and items after it
+/// are shown on the right.  Any other text is shown as-is.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(from = "&str")]
+pub struct RulerFormat {
+    /// Items shown on the left of the ruler.
+    pub left: Vec<RulerItem>,
+
+    /// Items shown on the right of the ruler.
+    pub right: Vec<RulerItem>,
+}
+
+impl Default for RulerFormat {
+    fn default() -> Self {
+        RulerFormat::from("{title} | {count} {mouse} {info} {position} {loading}")
+    }
+}
+
+impl From<&str> for RulerFormat {
+    fn from(value: &str) -> RulerFormat {
+        fn push_text(items: &mut Vec<RulerItem>, text: &mut String) {
+            let trimmed = text.trim();
+            if !trimmed.is_empty() {
+                items.push(RulerItem::Text(trimmed.to_string()));
+            }
+            text.clear();
+        }
+
+        // Items for the left and right sides of the ruler.
+        let mut sides = [Vec::new(), Vec::new()];
+        let mut side = 0;
+        let mut text = String::new();
+        let mut rest = value;
+        while let Some(c) = rest.chars().next() {
+            rest = &rest[c.len_utf8()..];
+            match c {
+                '|' if side == 0 => {
+                    push_text(&mut sides[side], &mut text);
+                    side = 1;
+                }
+                '{' => match rest
+                    .find('}')
+                    .and_then(|end| Some((end, RulerItem::from_name(&rest[..end])?)))
+                {
+                    Some((end, item)) => {
+                        push_text(&mut sides[side], &mut text);
+                        sides[side].push(item);
+                        rest = &rest[end + 1..];
+                    }
+                    None => text.push(c),
+                },
+                c => text.push(c),
+            }
+        }
+        push_text(&mut sides[side], &mut text);
+        let [left, right] = sides;
+        RulerFormat { left, right }
+    }
+}
+
 /// Keymap Configuration
 #[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
 #[serde(from = "&str")]
@@ -164,6 +722,14 @@ pub struct Config {
     /// Specify how many lines to read ahead.
     pub read_ahead_lines: usize,
 
+    /// Specify a read-ahead profile.  If set, this overrides
+    /// `read_ahead_lines`.
+    pub read_ahead_profile: Option<ReadAheadProfile>,
+
+    /// Specify when reading from streams stops once enough has been read
+    /// ahead of the screen.
+    pub backpressure: Backpressure,
+
     /// Specify whether to poll input during start-up (delayed or direct mode).
     pub startup_poll_input: bool,
 
@@ -178,6 +744,217 @@ pub struct Config {
 
     /// Specify the name of the default key map.
     pub keymap: KeymapConfig,
+
+    /// Specify additional key bindings for particular files, which override
+    /// the key map when that file's screen is current.
+    #[serde(skip)]
+    pub file_keymaps: HashMap<FileIndex, Arc<Keymap>>,
+
+    /// Specify commands to run once the first screen is shown, after the
+    /// commands in the user's init script.
+    #[serde(skip)]
+    pub startup_commands: Vec<String>,
+
+    /// Specify transforms applied to the lines of files before they are
+    /// parsed.
+    #[serde(skip)]
+    pub line_transforms: LineTransforms,
+
+    /// Specify the items shown in the ruler.
+    pub ruler_format: RulerFormat,
+
+    /// Specify whether to ask for confirmation before quitting while the
+    /// current file is still loading.
+    pub confirm_quit_while_loading: bool,
+
+    /// Specify whether text pasted to start a search is searched for
+    /// literally, rather than as a regular expression.
+    pub literal_paste_search: bool,
+
+    /// Specify whether searches only look at the columns of each line that
+    /// are visible when lines are not wrapped.
+    pub search_visible_columns: bool,
+
+    /// Specify whether the pager captures the mouse, so that the mouse wheel
+    /// scrolls the file.
+    pub mouse: bool,
+
+    /// Specify whether to render in a way that is easier for screen readers
+    /// to follow: no scrolling regions or partial repaints, no animations,
+    /// and the position always shown at the start of the ruler.
+    pub accessibility_mode: bool,
+
+    /// Specify how often the screen is updated for animations.
+    pub animation: Animation,
+
+    /// Specify whether to leave the contents of the screen in the terminal
+    /// when quitting from the full screen interface without the alternate
+    /// screen.
+    pub keep_screen_on_quit: bool,
+
+    /// Specify whether to print the lines of the file that are visible on
+    /// the screen to the terminal after quitting.
+    pub print_screen_on_quit: bool,
+
+    /// Specify whether to set the terminal's window title to the title of
+    /// the current file, restoring the previous title when quitting.
+    pub set_terminal_title: bool,
+
+    /// Specify whether to switch to the output of each command chained
+    /// after another when it starts running.
+    pub switch_to_chained_commands: bool,
+
+    /// Specify whether to turn references to lines in files, like
+    /// `src/main.rs:12`, into hyperlinks to those files, if the terminal
+    /// supports hyperlinks.
+    pub linkify_file_paths: bool,
+
+    /// Specify rules for highlighting text in every line.  Where rules
+    /// overlap, earlier rules take precedence.
+    pub highlight_rules: Vec<HighlightRule>,
+
+    /// Specify the number of columns between tab stops.
+    pub tab_width: usize,
+
+    /// Specify settings for particular kinds of file.
+    pub profiles: Vec<Profile>,
+
+    /// Specify whether to render simple markdown constructs, like headings
+    /// and emphasis, in markdown files.
+    pub render_markdown: bool,
+
+    /// Specify whether to color the keys of objects when pretty-printing
+    /// JSON.
+    pub color_json_keys: bool,
+
+    /// Specify whether scrolling right in unwrapped mode snaps to the starts
+    /// of fields, like the columns of a table, in the visible lines.
+    pub snap_to_columns: bool,
+
+    /// Specify the column to wrap lines at in the `column` wrapping mode.
+    /// If 0, lines are wrapped at the edge of the screen.
+    pub wrap_column: usize,
+
+    /// Specify whether to show a scrollbar in the rightmost column, which
+    /// also marks where search matches are.
+    pub show_scrollbar: bool,
+
+    /// Specify the character encoding of files.  Text in other encodings is
+    /// converted to UTF-8 for display.
+    pub encoding: Encoding,
+
+    /// Specify the longest line, in bytes, that is shown in full.  Longer
+    /// lines are truncated, with a marker showing how much was left out.
+    /// If 0, lines are never truncated.
+    pub max_line_length: usize,
+
+    /// Specify whether to show the type of each line's ending (LF or CRLF)
+    /// at the end of the line.
+    pub show_line_endings: bool,
+
+    /// Specify whether to hide carriage returns at the end of lines, rather
+    /// than showing them as `<0D>`.
+    pub hide_trailing_cr: bool,
+
+    /// Specify whether to highlight trailing whitespace and indentation that
+    /// mixes tabs and spaces, and show tabs with a marker.
+    pub show_whitespace: bool,
+
+    /// Specify the background color of trailing whitespace, when whitespace
+    /// is shown.
+    pub trailing_whitespace_color: String,
+
+    /// Specify the background color of indentation that mixes tabs and
+    /// spaces, when whitespace is shown.
+    pub mixed_indent_color: String,
+
+    /// Specify the color of the markers shown for tabs, when whitespace is
+    /// shown.
+    pub tab_marker_color: String,
+
+    /// Specify how long messages are shown for, in milliseconds.
+    pub message_timeout_ms: u64,
+
+    /// Specify the style of line numbers.
+    pub line_number_style: TextStyle,
+
+    /// Specify text to draw between line numbers and lines, such as `│`.
+    pub line_number_separator: String,
+
+    /// Specify whether the line number gutter is dimmed on the rows that
+    /// continue a wrapped line, rather than drawn in the line number style.
+    pub dim_line_number_continuation: bool,
+
+    /// Specify how often line numbers are shown.  If more than 1, only the
+    /// numbers of lines that are a multiple of it are shown.
+    pub line_number_interval: usize,
+
+    /// Specify the most rows the error file may use below the ruler.  If 0,
+    /// the error file may use the whole screen.
+    pub error_file_max_height: usize,
+
+    /// Specify the style of the lines of error files, so that they stand
+    /// out from the file's own lines.  Colors in the lines themselves take
+    /// precedence.
+    pub error_file_style: TextStyle,
+
+    /// Specify the most rows the progress indicator may use.  If a progress
+    /// page is longer, only its first rows are shown.  If 0, the whole page
+    /// is shown.
+    pub progress_max_height: usize,
+
+    /// Specify when to use color.  Without color, the file's colors are
+    /// dropped and the interface is drawn in inverse video.
+    pub color: ColorMode,
+
+    /// Specify whether attributes like bold and underline are kept when
+    /// color is not used.
+    pub monochrome_attributes: bool,
+
+    /// Specify how many colors the terminal can show, overriding what the
+    /// terminal reports.  Colors are converted to the nearest color
+    /// available.
+    pub color_depth: ColorDepth,
+
+    /// Specify the most memory, in bytes, that each cache of rendered lines
+    /// may use.
+    pub line_cache_bytes: usize,
+
+    /// Specify how many threads each search uses.  If 0, one thread is used
+    /// for each CPU.
+    pub search_threads: usize,
+
+    /// Specify whether searches ignore differences in case and in how
+    /// equivalent characters are written.
+    pub search_normalization: SearchNormalization,
+
+    /// Specify whether to mask text that looks like a secret, such as access
+    /// keys and bearer tokens.
+    pub redact_secrets: bool,
+
+    /// Specify regular expressions for additional text to mask when secrets
+    /// are redacted.  If a pattern has a capture group, only the text it
+    /// captures is masked.
+    pub redact_patterns: Vec<String>,
+
+    /// Specify regular expressions to watch for while following the end of
+    /// a file.  When a line that arrives matches one of them, the user is
+    /// alerted.
+    pub watch_patterns: Vec<String>,
+
+    /// Specify how the user is alerted when a watch pattern matches.
+    pub watch_alert: WatchAlert,
+
+    /// Specify whether to assume a basic ANSI terminal if the terminfo
+    /// database for the terminal can't be found, rather than failing.
+    pub assume_ansi: bool,
+
+    /// Specify how the screen is drawn.
+    pub renderer: Renderer,
+
+    /// Specify whether to save the files opened by name, and where they
+    /// were scrolled to, when the pager quits, so that they can be resumed.
+    pub save_session: bool,
 }
 
 impl Default for Config {
@@ -186,12 +963,70 @@ impl Default for Config {
             interface_mode: Default::default(),
             scroll_past_eof: true,
             read_ahead_lines: crate::file::DEFAULT_NEEDED_LINES,
+            read_ahead_profile: None,
+            backpressure: Default::default(),
             startup_poll_input: true,
             show_ruler: true,
             // See issue #52. With cursor hidden, scrolling is flaky in VSCode terminal.
             show_cursor: std::env::var("TERM_PROGRAM").ok().as_deref() == Some("vscode"),
             wrapping_mode: Default::default(),
             keymap: Default::default(),
+            file_keymaps: HashMap::new(),
+            startup_commands: Vec::new(),
+            line_transforms: LineTransforms::default(),
+            ruler_format: Default::default(),
+            confirm_quit_while_loading: false,
+            literal_paste_search: false,
+            search_visible_columns: false,
+            mouse: false,
+            accessibility_mode: false,
+            animation: Default::default(),
+            keep_screen_on_quit: false,
+            print_screen_on_quit: false,
+            set_terminal_title: false,
+            switch_to_chained_commands: true,
+            linkify_file_paths: false,
+            highlight_rules: Vec::new(),
+            tab_width: crate::line::DEFAULT_TAB_WIDTH,
+            profiles: Vec::new(),
+            render_markdown: false,
+            color_json_keys: true,
+            snap_to_columns: false,
+            wrap_column: 80,
+            show_scrollbar: false,
+            encoding: Default::default(),
+            max_line_length: 0,
+            show_line_endings: false,
+            hide_trailing_cr: false,
+            show_whitespace: false,
+            trailing_whitespace_color: String::from("red"),
+            mixed_indent_color: String::from("yellow"),
+            tab_marker_color: String::from("bright-black"),
+            message_timeout_ms: 3000,
+            line_number_style: TextStyle {
+                foreground: Some(String::from("black")),
+                background: Some(String::from("white")),
+                ..Default::default()
+            },
+            line_number_separator: String::new(),
+            dim_line_number_continuation: false,
+            line_number_interval: 1,
+            error_file_max_height: 8,
+            error_file_style: TextStyle::default(),
+            progress_max_height: 0,
+            color: Default::default(),
+            monochrome_attributes: true,
+            color_depth: Default::default(),
+            line_cache_bytes: 4 * 1024 * 1024,
+            search_threads: 0,
+            search_normalization: Default::default(),
+            redact_secrets: false,
+            redact_patterns: Vec::new(),
+            watch_patterns: Vec::new(),
+            watch_alert: Default::default(),
+            assume_ansi: false,
+            renderer: Default::default(),
+            save_session: false,
         }
     }
 }
@@ -199,20 +1034,67 @@ impl Default for Config {
 impl Config {
     /// Create [`Config`] from the user's default config file.
     pub fn from_config_file() -> Self {
+        match Self::read_config_file() {
+            Ok(config) => config,
+            Err((path, e)) => {
+                eprintln!(
+                    "streampager: failed to parse config at {:?}, using defaults: {}",
+                    path, e
+                );
+                Self::default()
+            }
+        }
+    }
+
+    /// Read the user's default config file.  If there is no config file,
+    /// the default config is returned.  If the file can't be parsed, its
+    /// path and the parse error are returned.
+    fn read_config_file() -> std::result::Result<Self, (PathBuf, toml::de::Error)> {
         if let Some(mut path) = dirs::config_dir() {
             path.push("streampager");
             path.push("streampager.toml");
             if let Ok(config) = std::fs::read_to_string(&path) {
-                match toml::from_str(&config) {
-                    Ok(config) => return config,
-                    Err(e) => eprintln!(
-                        "streampager: failed to parse config at {:?}, using defaults: {}",
-                        path, e
-                    ),
-                }
+                return toml::from_str(&config).map_err(|e| (path, e));
             }
         }
-        Self::default()
+        Ok(Self::default())
+    }
+
+    /// Returns a copy of this config with the settings that can change
+    /// while the pager is running re-read from the user's default config
+    /// file and environment.
+    ///
+    /// Settings that affect how the terminal is set up or how files are
+    /// loaded, or that the user can toggle while paging, keep their current
+    /// values.  A keymap given directly (rather than by name) is also kept.
+    pub(crate) fn reloaded(&self) -> std::result::Result<Config, String> {
+        let new = Self::read_config_file()
+            .map_err(|(path, e)| format!("Failed to parse config at {:?}: {}", path, e))?
+            .with_env();
+        let mut config = new;
+        config.interface_mode = self.interface_mode;
+        config.startup_poll_input = self.startup_poll_input;
+        config.show_ruler = self.show_ruler;
+        config.show_cursor = self.show_cursor;
+        config.wrapping_mode = self.wrapping_mode;
+        if !matches!(self.keymap, KeymapConfig::Name(_)) {
+            config.keymap = self.keymap.clone();
+        }
+        config.file_keymaps = self.file_keymaps.clone();
+        config.startup_commands = self.startup_commands.clone();
+        config.line_transforms = self.line_transforms.clone();
+        config.search_visible_columns = self.search_visible_columns;
+        config.mouse = self.mouse;
+        config.accessibility_mode = self.accessibility_mode;
+        config.keep_screen_on_quit = self.keep_screen_on_quit;
+        config.print_screen_on_quit = self.print_screen_on_quit;
+        config.set_terminal_title = self.set_terminal_title;
+        config.encoding = self.encoding;
+        config.max_line_length = self.max_line_length;
+        config.redact_secrets = self.redact_secrets;
+        config.redact_patterns = self.redact_patterns.clone();
+        config.assume_ansi = self.assume_ansi;
+        Ok(config)
     }
 
     /// Modify [`Config`] using environment variables.
@@ -231,8 +1113,290 @@ impl Config {
                 self.read_ahead_lines = n;
             }
         }
+        if let Ok(s) = var("SP_READ_AHEAD_PROFILE") {
+            if let Some(profile) = ReadAheadProfile::from_name(&s) {
+                self.read_ahead_profile = Some(profile);
+            }
+        }
+        if let Ok(s) = var("SP_BACKPRESSURE") {
+            if let Some(backpressure) = Backpressure::from_name(&s) {
+                self.backpressure = backpressure;
+            }
+        }
+        if let Ok(s) = var("SP_RULER_FORMAT") {
+            self.ruler_format = RulerFormat::from(s.as_ref());
+        }
+        if let Ok(s) = var("SP_CONFIRM_QUIT_WHILE_LOADING") {
+            if let Some(b) = parse_bool(&s) {
+                self.confirm_quit_while_loading = b;
+            }
+        }
+        if let Ok(s) = var("SP_LITERAL_PASTE_SEARCH") {
+            if let Some(b) = parse_bool(&s) {
+                self.literal_paste_search = b;
+            }
+        }
+        if let Ok(s) = var("SP_SEARCH_VISIBLE_COLUMNS") {
+            if let Some(b) = parse_bool(&s) {
+                self.search_visible_columns = b;
+            }
+        }
+        if let Ok(s) = var("SP_MOUSE") {
+            if let Some(b) = parse_bool(&s) {
+                self.mouse = b;
+            }
+        }
+        if let Ok(s) = var("SP_ACCESSIBILITY_MODE") {
+            if let Some(b) = parse_bool(&s) {
+                self.accessibility_mode = b;
+            }
+        }
+        if let Ok(s) = var("SP_ANIMATION") {
+            if let Some(animation) = Animation::from_name(&s) {
+                self.animation = animation;
+            }
+        }
+        if let Ok(s) = var("SP_KEEP_SCREEN_ON_QUIT") {
+            if let Some(b) = parse_bool(&s) {
+                self.keep_screen_on_quit = b;
+            }
+        }
+        if let Ok(s) = var("SP_PRINT_SCREEN_ON_QUIT") {
+            if let Some(b) = parse_bool(&s) {
+                self.print_screen_on_quit = b;
+            }
+        }
+        if let Ok(s) = var("SP_SET_TERMINAL_TITLE") {
+            if let Some(b) = parse_bool(&s) {
+                self.set_terminal_title = b;
+            }
+        }
+        if let Ok(s) = var("SP_SWITCH_TO_CHAINED_COMMANDS") {
+            if let Some(b) = parse_bool(&s) {
+                self.switch_to_chained_commands = b;
+            }
+        }
+        if let Ok(s) = var("SP_TAB_WIDTH") {
+            if let Ok(n) = s.parse::<usize>() {
+                self.tab_width = n;
+            }
+        }
+        if let Ok(s) = var("SP_LINKIFY_FILE_PATHS") {
+            if let Some(b) = parse_bool(&s) {
+                self.linkify_file_paths = b;
+            }
+        }
+        if let Ok(s) = var("SP_RENDER_MARKDOWN") {
+            if let Some(b) = parse_bool(&s) {
+                self.render_markdown = b;
+            }
+        }
+        if let Ok(s) = var("SP_COLOR_JSON_KEYS") {
+            if let Some(b) = parse_bool(&s) {
+                self.color_json_keys = b;
+            }
+        }
+        if let Ok(s) = var("SP_SHOW_SCROLLBAR") {
+            if let Some(b) = parse_bool(&s) {
+                self.show_scrollbar = b;
+            }
+        }
+        if let Ok(s) = var("SP_MESSAGE_TIMEOUT_MS") {
+            if let Ok(n) = s.parse::<u64>() {
+                self.message_timeout_ms = n;
+            }
+        }
+        if let Ok(s) = var("SP_WRAP_COLUMN") {
+            if let Ok(n) = s.parse::<usize>() {
+                self.wrap_column = n;
+            }
+        }
+        if let Ok(s) = var("SP_SNAP_TO_COLUMNS") {
+            if let Some(b) = parse_bool(&s) {
+                self.snap_to_columns = b;
+            }
+        }
+        if let Ok(s) = var("SP_ENCODING") {
+            if let Some(encoding) = Encoding::from_name(&s) {
+                self.encoding = encoding;
+            }
+        }
+        if let Ok(s) = var("SP_SHOW_LINE_ENDINGS") {
+            if let Some(b) = parse_bool(&s) {
+                self.show_line_endings = b;
+            }
+        }
+        if let Ok(s) = var("SP_HIDE_TRAILING_CR") {
+            if let Some(b) = parse_bool(&s) {
+                self.hide_trailing_cr = b;
+            }
+        }
+        if let Ok(s) = var("SP_SHOW_WHITESPACE") {
+            if let Some(b) = parse_bool(&s) {
+                self.show_whitespace = b;
+            }
+        }
+        if let Ok(s) = var("SP_TRAILING_WHITESPACE_COLOR") {
+            self.trailing_whitespace_color = s;
+        }
+        if let Ok(s) = var("SP_MIXED_INDENT_COLOR") {
+            self.mixed_indent_color = s;
+        }
+        if let Ok(s) = var("SP_TAB_MARKER_COLOR") {
+            self.tab_marker_color = s;
+        }
+        if let Ok(s) = var("SP_COLOR") {
+            if let Some(color) = ColorMode::from_name(&s) {
+                self.color = color;
+            }
+        }
+        if let Ok(s) = var("SP_MONOCHROME_ATTRIBUTES") {
+            if let Some(b) = parse_bool(&s) {
+                self.monochrome_attributes = b;
+            }
+        }
+        if let Ok(s) = var("SP_PROGRESS_MAX_HEIGHT") {
+            if let Ok(n) = s.parse::<usize>() {
+                self.progress_max_height = n;
+            }
+        }
+        if let Ok(s) = var("SP_LINE_NUMBER_SEPARATOR") {
+            self.line_number_separator = s;
+        }
+        if let Ok(s) = var("SP_DIM_LINE_NUMBER_CONTINUATION") {
+            if let Some(b) = parse_bool(&s) {
+                self.dim_line_number_continuation = b;
+            }
+        }
+        if let Ok(s) = var("SP_LINE_NUMBER_INTERVAL") {
+            if let Ok(n) = s.parse::<usize>() {
+                self.line_number_interval = n;
+            }
+        }
+        if let Ok(s) = var("SP_ERROR_FILE_MAX_HEIGHT") {
+            if let Ok(n) = s.parse::<usize>() {
+                self.error_file_max_height = n;
+            }
+        }
+        if let Ok(s) = var("SP_LINE_CACHE_BYTES") {
+            if let Ok(n) = s.parse::<usize>() {
+                self.line_cache_bytes = n;
+            }
+        }
+        if let Ok(s) = var("SP_SEARCH_THREADS") {
+            if let Ok(n) = s.parse::<usize>() {
+                self.search_threads = n;
+            }
+        }
+        if let Ok(s) = var("SP_MAX_LINE_LENGTH") {
+            if let Ok(n) = s.parse::<usize>() {
+                self.max_line_length = n;
+            }
+        }
+        if let Ok(s) = var("SP_REDACT_SECRETS") {
+            if let Some(b) = parse_bool(&s) {
+                self.redact_secrets = b;
+            }
+        }
+        if let Ok(s) = var("SP_SEARCH_NORMALIZATION") {
+            if let Some(normalization) = SearchNormalization::from_name(&s) {
+                self.search_normalization = normalization;
+            }
+        }
+        if let Ok(s) = var("SP_WATCH_ALERT") {
+            if let Some(alert) = WatchAlert::from_name(&s) {
+                self.watch_alert = alert;
+            }
+        }
+        if let Ok(s) = var("SP_COLOR_DEPTH") {
+            if let Some(depth) = ColorDepth::from_name(&s) {
+                self.color_depth = depth;
+            }
+        }
+        if let Ok(s) = var("SP_ASSUME_ANSI") {
+            if let Some(b) = parse_bool(&s) {
+                self.assume_ansi = b;
+            }
+        }
+        if let Ok(s) = var("SP_RENDERER") {
+            if let Some(renderer) = Renderer::from_name(&s) {
+                self.renderer = renderer;
+            }
+        }
+        if let Ok(s) = var("SP_SAVE_SESSION") {
+            if let Some(b) = parse_bool(&s) {
+                self.save_session = b;
+            }
+        }
         self
     }
+
+    /// Returns true if markdown should be rendered for a file with the
+    /// given title.
+    pub(crate) fn render_markdown_for(&self, title: &str) -> bool {
+        let extension = std::path::Path::new(title)
+            .extension()
+            .and_then(|extension| extension.to_str());
+        self.render_markdown
+            && matches!(extension, Some(e) if e.eq_ignore_ascii_case("md") || e.eq_ignore_ascii_case("markdown"))
+    }
+
+    /// Returns the profile for a file with the given title, if any.
+    pub(crate) fn profile(&self, title: &str) -> Result<Option<&Profile>> {
+        for profile in self.profiles.iter() {
+            if profile.applies_to(title)? {
+                return Ok(Some(profile));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Returns the effective animation setting.  Accessibility mode turns
+    /// animations off.
+    pub(crate) fn animation(&self) -> Animation {
+        if self.accessibility_mode {
+            Animation::Off
+        } else {
+            self.animation
+        }
+    }
+
+    /// Returns true if color should be used.  In `auto` mode, color is
+    /// used unless the `NO_COLOR` environment variable is set to a
+    /// non-empty value.
+    pub(crate) fn use_color(&self) -> bool {
+        match self.color {
+            ColorMode::Auto => std::env::var_os("NO_COLOR")
+                .filter(|v| !v.is_empty())
+                .is_none(),
+            ColorMode::Always => true,
+            ColorMode::Never => false,
+        }
+    }
+
+    /// Returns how long messages are shown for.
+    pub(crate) fn message_timeout(&self) -> Duration {
+        Duration::from_millis(self.message_timeout_ms)
+    }
+
+    /// Returns the number of threads each search uses.
+    pub(crate) fn search_threads(&self) -> usize {
+        match self.search_threads {
+            0 => std::thread::available_parallelism()
+                .map(|threads| threads.get())
+                .unwrap_or(1),
+            threads => threads,
+        }
+    }
+
+    /// Returns the number of lines to read ahead of the screen, taking the
+    /// read-ahead profile into account.
+    pub fn read_ahead(&self) -> usize {
+        match self.read_ahead_profile {
+            Some(profile) => profile.read_ahead_lines(),
+            None => self.read_ahead_lines,
+        }
+    }
 }
 
 fn parse_bool(value: &str) -> Option<bool> {
@@ -242,3 +1406,193 @@ fn parse_bool(value: &str) -> Option<bool> {
         _ => None,
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_ruler_format() {
+        use RulerItem::*;
+        let format = RulerFormat::from("{title} {info} | {position} {loading}");
+        assert_eq!(format.left, vec![Title, Info]);
+        assert_eq!(format.right, vec![Position, Loading]);
+
+        let format = RulerFormat::from("[{title}] {bogus} | line: {position} | {count");
+        assert_eq!(
+            format.left,
+            vec![Text("[".into()), Title, Text("] {bogus}".into())]
+        );
+        assert_eq!(
+            format.right,
+            vec![Text("line:".into()), Position, Text("| {count".into())]
+        );
+
+        let format = RulerFormat::from("{position}");
+        assert_eq!(format.left, vec![Position]);
+        assert!(format.right.is_empty());
+    }
+
+    #[test]
+    fn test_read_ahead_profile() {
+        let config = Config::default();
+        assert_eq!(config.read_ahead(), config.read_ahead_lines);
+
+        let config: Config = toml::from_str("read_ahead_profile = \"aggressive\"").unwrap();
+        assert_eq!(
+            config.read_ahead_profile,
+            Some(ReadAheadProfile::Aggressive)
+        );
+        assert_eq!(config.read_ahead(), usize::MAX);
+
+        assert_eq!(
+            ReadAheadProfile::from_name("Conservative"),
+            Some(ReadAheadProfile::Conservative)
+        );
+        assert_eq!(ReadAheadProfile::from_name("bogus"), None);
+    }
+
+    #[test]
+    fn test_backpressure() {
+        let config: Config = toml::from_str(
+            r#"
+            backpressure = "unless-following"
+
+            [[profiles]]
+            title_pattern = "^tail "
+            backpressure = "never"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config.backpressure, Backpressure::UnlessFollowing);
+        let profile = config.profile("tail -f log").unwrap().unwrap();
+        assert_eq!(profile.backpressure, Some(Backpressure::Never));
+
+        assert!(Backpressure::Always.applies(true));
+        assert!(!Backpressure::UnlessFollowing.applies(true));
+        assert!(Backpressure::UnlessFollowing.applies(false));
+        assert!(!Backpressure::Never.applies(false));
+        assert_eq!(Backpressure::from_name("NEVER"), Some(Backpressure::Never));
+        assert_eq!(Backpressure::from_name("sometimes"), None);
+    }
+
+    #[test]
+    fn test_profiles() {
+        let config: Config = toml::from_str(
+            r#"
+            [[profiles]]
+            extensions = ["log"]
+            wrapping_mode = "none"
+
+            [[profiles]]
+            title_pattern = "^README"
+            wrapping_mode = "word"
+            tab_width = 4
+            "#,
+        )
+        .unwrap();
+        let profile = config.profile("/var/log/messages.LOG").unwrap().unwrap();
+        assert_eq!(profile.wrapping_mode, Some(WrappingMode::Unwrapped));
+        let profile = config.profile("README.md").unwrap().unwrap();
+        assert_eq!(profile.tab_width, Some(4));
+        assert_eq!(config.profile("main.rs").unwrap(), None);
+    }
+
+    #[test]
+    fn test_animation() {
+        let mut config: Config = toml::from_str("animation = \"slow\"").unwrap();
+        assert_eq!(config.animation(), Animation::Slow);
+        config.accessibility_mode = true;
+        assert_eq!(config.animation(), Animation::Off);
+        assert_eq!(Animation::from_name("OFF"), Some(Animation::Off));
+        assert_eq!(Animation::from_name("fast"), None);
+    }
+
+    #[test]
+    fn test_color_mode() {
+        let mut config: Config = toml::from_str("color = \"never\"").unwrap();
+        assert!(!config.use_color());
+        config.color = ColorMode::Always;
+        assert!(config.use_color());
+        assert_eq!(ColorMode::from_name("Auto"), Some(ColorMode::Auto));
+        assert_eq!(ColorMode::from_name("sometimes"), None);
+    }
+
+    #[test]
+    fn test_color_depth() {
+        let config: Config = toml::from_str("color_depth = \"256\"").unwrap();
+        assert_eq!(config.color_depth, ColorDepth::Colors256);
+        assert_eq!(
+            ColorDepth::from_name("TrueColor"),
+            Some(ColorDepth::TrueColor)
+        );
+        assert_eq!(ColorDepth::from_name("8"), None);
+    }
+
+    #[test]
+    fn test_assume_ansi() {
+        assert!(!Config::default().assume_ansi);
+        let config: Config = toml::from_str("assume_ansi = true").unwrap();
+        assert!(config.assume_ansi);
+    }
+
+    #[test]
+    fn test_renderer() {
+        let config: Config = toml::from_str("renderer = \"surface\"").unwrap();
+        assert_eq!(config.renderer, Renderer::Surface);
+        assert_eq!(
+            Renderer::from_name("Incremental"),
+            Some(Renderer::Incremental)
+        );
+        assert_eq!(Renderer::from_name("diff"), None);
+    }
+
+    #[test]
+    fn test_encoding_names() {
+        for encoding in [
+            Encoding::Utf8,
+            Encoding::Latin1,
+            Encoding::Utf16Le,
+            Encoding::Utf16Be,
+            Encoding::Auto,
+        ] {
+            assert_eq!(Encoding::from_name(encoding.name()), Some(encoding));
+        }
+        assert_eq!(Encoding::from_name("UTF8"), Some(Encoding::Utf8));
+        assert_eq!(Encoding::from_name("ISO-8859-1"), Some(Encoding::Latin1));
+        assert_eq!(Encoding::from_name("utf-32"), None);
+        let config: Config = toml::from_str("encoding = \"utf16le\"").unwrap();
+        assert_eq!(config.encoding, Encoding::Utf16Le);
+    }
+
+    #[test]
+    fn test_watch_alert() {
+        assert_eq!(Config::default().watch_alert, WatchAlert::Message);
+        let config: Config =
+            toml::from_str("watch_patterns = [\"ERROR\"]\nwatch_alert = \"bell\"").unwrap();
+        assert_eq!(config.watch_patterns, vec![String::from("ERROR")]);
+        assert_eq!(config.watch_alert, WatchAlert::Bell);
+        assert_eq!(WatchAlert::from_name("Flash"), Some(WatchAlert::Flash));
+        assert_eq!(WatchAlert::from_name("beep"), None);
+    }
+
+    #[test]
+    fn test_error_file_style() {
+        assert_eq!(Config::default().error_file_style, TextStyle::default());
+        let config: Config =
+            toml::from_str("error_file_style = { background = \"#400000\", bold = true }").unwrap();
+        assert_eq!(config.error_file_style.foreground, None);
+        assert_eq!(
+            config.error_file_style.background.as_deref(),
+            Some("#400000")
+        );
+        assert!(config.error_file_style.bold);
+    }
+
+    #[test]
+    fn test_save_session() {
+        assert!(!Config::default().save_session);
+        let config: Config = toml::from_str("save_session = true").unwrap();
+        assert!(config.save_session);
+    }
+}
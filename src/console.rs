@@ -0,0 +1,67 @@
+//! Windows Console
+//!
+//! Windows consoles interpret terminal sequences once virtual terminal
+//! processing is turned on.  Older consoles refuse it, and must be driven
+//! through the console API instead, which termwiz does when there is no
+//! terminfo database.  The console API can't set scrolling regions, so
+//! without a terminfo database the screen is redrawn rather than scrolled.
+//!
+//! Resizes are reported by the console as window buffer size events, which
+//! termwiz turns into resize events.
+//!
+//! This is enabled by the `windows-console` feature.
+
+use termwiz::caps::{Capabilities, ProbeHints};
+use termwiz::terminal::SystemTerminal;
+
+use crate::error::{Error, Result};
+
+/// Turn on virtual terminal processing for the console `term`, which was
+/// opened with `caps`.
+///
+/// If the console refuses, it is reopened with `reopen`, using capabilities
+/// from `hints` without a terminfo database.  Returns the terminal and the
+/// capabilities it was opened with.
+pub(crate) fn enable_virtual_terminal(
+    mut term: SystemTerminal,
+    caps: Capabilities,
+    hints: ProbeHints,
+    reopen: impl FnOnce(Capabilities) -> Result<SystemTerminal>,
+) -> Result<(SystemTerminal, Capabilities)> {
+    if term.enable_virtual_terminal_processing().is_ok() {
+        return Ok((term, caps));
+    }
+    // Close the console before reopening it, so that its original mode is
+    // restored first.
+    drop(term);
+    let caps = Capabilities::new_with_hints(hints.terminfo_db(None)).map_err(Error::Termwiz)?;
+    let term = reopen(caps.clone())?;
+    Ok((term, caps))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_enable_virtual_terminal() {
+        // Tests are often run without a console, in which case there is
+        // nothing to check.
+        let caps = match Capabilities::new_with_hints(ProbeHints::new_from_env()) {
+            Ok(caps) => caps,
+            Err(_) => return,
+        };
+        let term = match SystemTerminal::new(caps.clone()) {
+            Ok(term) => term,
+            Err(_) => return,
+        };
+        let (mut term, caps) =
+            enable_virtual_terminal(term, caps, ProbeHints::new_from_env(), |caps| {
+                SystemTerminal::new(caps).map_err(Error::Termwiz)
+            })
+            .unwrap();
+        // Either the console now interprets terminal sequences, or it is
+        // driven through the console API without a terminfo database.
+        assert!(term.enable_virtual_terminal_processing().is_ok() || caps.terminfo_db().is_none());
+    }
+}
@@ -1,15 +1,30 @@
 //! Controlled files.
 //!
 //! Files where data is provided by a controller.
+//!
+//! Controllers can also hide lines, and make lines the headers of sections
+//! that can be collapsed and expanded.  Only the lines that aren't hidden
+//! or in a collapsed section are shown, and the line numbers used by the
+//! pager (e.g. for searching) count only those lines.
+//!
+//! A controller can also give the file a cursor: a highlighted line that the
+//! user moves with the keys that would otherwise scroll by lines, and
+//! selects by pressing Enter.  The controller is told about each of these
+//! with a `CursorEvent`, which makes the pager usable as a picker.
 
 use std::borrow::Cow;
+use std::ffi::OsString;
 use std::ops::Range;
+use std::path::PathBuf;
 use std::sync::{Arc, Mutex, RwLock};
 
 use thiserror::Error;
 
+use crate::config::Encoding;
 use crate::event::{Event, EventSender};
-use crate::file::{FileIndex, FileInfo};
+use crate::file::{ChainedCommand, FileDiagnostics, FileIndex, FileInfo};
+use crate::tee::Tee;
+use crate::transform::LineTransforms;
 
 /// Errors that may occur during controlled file operations.
 #[derive(Debug, Error)]
@@ -40,6 +55,27 @@ pub type Result<T> = std::result::Result<T, ControlledFileError>;
 pub struct Controller {
     data: Arc<RwLock<FileData>>,
     notify: Arc<Mutex<Vec<(EventSender, FileIndex)>>>,
+    cursor_handler: Arc<Mutex<Option<CursorHandler>>>,
+}
+
+/// Function called with the user's changes to the cursor.
+type CursorHandler = Arc<dyn Fn(CursorEvent) + Send + Sync>;
+
+/// Something the user has done with the cursor of a controlled file.  Line
+/// indexes are indexes in the controlled file, including hidden lines.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CursorEvent {
+    /// The cursor has been moved to another line.
+    Moved {
+        /// Index of the line the cursor is now on.
+        index: usize,
+    },
+
+    /// The user pressed Enter on the cursor line.
+    Selected {
+        /// Index of the line the cursor is on.
+        index: usize,
+    },
 }
 
 impl Controller {
@@ -48,6 +84,7 @@ impl Controller {
         Controller {
             data: Arc::new(RwLock::new(FileData::new(title))),
             notify: Arc::new(Mutex::new(Vec::new())),
+            cursor_handler: Arc::new(Mutex::new(None)),
         }
     }
 
@@ -63,20 +100,63 @@ impl Controller {
         data.info.clone()
     }
 
+    /// Returns the section that line `index` is the header of, if any.  The
+    /// user can collapse or expand sections, so this is how the section
+    /// currently is.
+    pub fn section(&self, index: usize) -> Option<Section> {
+        let data = self.data.read().unwrap();
+        data.lines.get(index)?.section
+    }
+
+    /// Returns the index of the line the cursor is on, if the file has a
+    /// cursor.
+    pub fn cursor(&self) -> Option<usize> {
+        self.data.read().unwrap().cursor
+    }
+
+    /// Set the function that is called when the user moves the cursor or
+    /// selects the cursor line.  It is called on the pager's display
+    /// thread, so it should return quickly.
+    pub fn set_cursor_handler(&self, handler: impl Fn(CursorEvent) + Send + Sync + 'static) {
+        *self.cursor_handler.lock().unwrap() = Some(Arc::new(handler));
+    }
+
     /// Apply a sequence of changes to the controlled file.
     pub fn apply_changes(&self, changes: impl IntoIterator<Item = Change>) -> Result<()> {
         let mut data = self.data.write().unwrap();
-        for change in changes {
-            data.apply_change(change)?;
-        }
-        // TODO(markbt): more fine-grained notifications.
-        // For now, just reload the file.
-        let notify = self.notify.lock().unwrap();
-        for (event_sender, index) in notify.iter() {
-            event_sender.send(Event::Reloading(*index))?;
-        }
-        Ok(())
+        let result = changes
+            .into_iter()
+            .try_for_each(|change| data.apply_change(change));
+        data.update_visible();
+        result?;
+        notify_reloading(&self.notify)
+    }
+}
+
+/// Tells each screen showing the file to reload it.
+fn notify_reloading(notify: &Mutex<Vec<(EventSender, FileIndex)>>) -> Result<()> {
+    // TODO(markbt): more fine-grained notifications.
+    // For now, just reload the file.
+    let notify = notify.lock().unwrap();
+    for (event_sender, index) in notify.iter() {
+        event_sender.send(Event::Reloading(*index))?;
     }
+    Ok(())
+}
+
+/// A section of a controlled file: a header line, and the lines after it,
+/// which are hidden while the section is collapsed.
+///
+/// Sections can be nested.  The section's lines are not adjusted when lines
+/// are inserted or deleted, so sections should be set again when lines in
+/// them change.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Section {
+    /// The number of lines after the header that are in the section.
+    pub lines: usize,
+
+    /// Whether the section is collapsed.
+    pub collapsed: bool,
 }
 
 /// A change to apply to a controlled file.
@@ -155,6 +235,31 @@ pub enum Change {
         /// The new contents of the file.
         contents: Vec<Vec<u8>>,
     },
+
+    /// Hide or show a range of lines.  Lines in a collapsed section stay
+    /// hidden until the section is expanded.
+    SetHidden {
+        /// The range of lines in the file to hide or show.
+        range: Range<usize>,
+        /// Whether the lines are hidden.
+        hidden: bool,
+    },
+
+    /// Make a line the header of a section, or stop it being one.
+    SetSection {
+        /// Index of the header line in the file.
+        index: usize,
+        /// The section, or `None` to remove it.
+        section: Option<Section>,
+    },
+
+    /// Move the cursor to a line, or remove it.  The cursor doesn't move
+    /// when lines are inserted or deleted, so it should be set again if the
+    /// lines before it change.
+    SetCursor {
+        /// Index of the line the cursor is on, or `None` for no cursor.
+        index: Option<usize>,
+    },
 }
 
 /// A file whose contents is controlled by a `Controller`.
@@ -162,6 +267,8 @@ pub enum Change {
 pub struct ControlledFile {
     index: FileIndex,
     data: Arc<RwLock<FileData>>,
+    notify: Arc<Mutex<Vec<(EventSender, FileIndex)>>>,
+    cursor_handler: Arc<Mutex<Option<CursorHandler>>>,
 }
 
 impl ControlledFile {
@@ -175,6 +282,17 @@ impl ControlledFile {
         ControlledFile {
             index,
             data: controller.data.clone(),
+            notify: controller.notify.clone(),
+            cursor_handler: controller.cursor_handler.clone(),
+        }
+    }
+
+    /// Tells the controller about something the user did with the cursor.
+    /// No locks are held while the handler runs, so it can apply changes.
+    fn cursor_event(&self, event: CursorEvent) {
+        let handler = self.cursor_handler.lock().unwrap().clone();
+        if let Some(handler) = handler {
+            handler(event);
         }
     }
 }
@@ -197,31 +315,172 @@ impl FileInfo for ControlledFile {
         Cow::Owned(data.info.clone())
     }
 
+    /// The errors encountered loading the file, starting from error `start`.
+    /// Controlled files are never loaded, so there are none.
+    fn errors(&self, _start: usize) -> Vec<String> {
+        Vec::new()
+    }
+
     /// True once the file is loaded and all newlines have been parsed.
     fn loaded(&self) -> bool {
         true
     }
 
-    /// Returns the number of lines in the file.
+    /// Returns the number of lines in the file that are shown.
     fn lines(&self) -> usize {
-        self.data.read().unwrap().lines.len()
+        self.data.read().unwrap().visible.len()
+    }
+
+    /// Returns the number of bytes in the file, counting a newline at the
+    /// end of each line, as `line_offset` does.
+    fn length(&self) -> usize {
+        let data = self.data.read().unwrap();
+        data.line_ends.last().copied().unwrap_or(0)
+    }
+
+    /// Returns the byte offset of the start of line `index`, if the line has
+    /// been loaded.
+    ///
+    /// Controlled files don't have an underlying byte representation, so
+    /// this is the offset the line would have if each line was terminated
+    /// by a newline.
+    fn line_offset(&self, index: usize) -> Option<usize> {
+        let data = self.data.read().unwrap();
+        match index.checked_sub(1) {
+            None => Some(0),
+            Some(previous) => data.line_ends.get(previous).copied(),
+        }
+    }
+
+    /// Returns the index of the line containing byte `offset`, if that part
+    /// of the file has been loaded.
+    ///
+    /// As with `line_offset`, each line is treated as if it was terminated
+    /// by a newline.
+    fn line_at_offset(&self, offset: usize) -> Option<usize> {
+        let data = self.data.read().unwrap();
+        let index = data.line_ends.partition_point(|&end| end <= offset);
+        Some(index).filter(|&index| index < data.line_ends.len())
     }
 
     /// Runs the `call` function, passing it the contents of line `index`.
     /// Tries to avoid copying the data if possible, however the borrowed
     /// line only lasts as long as the function call.
+    ///
+    /// Section headers start with a marker showing whether the section is
+    /// collapsed.
     fn with_line<T, F>(&self, index: usize, mut call: F) -> Option<T>
     where
         F: FnMut(Cow<'_, [u8]>) -> T,
     {
         let data = self.data.read().unwrap();
-        if let Some(line) = data.lines.get(index) {
-            Some(call(Cow::Borrowed(line.content.as_slice())))
-        } else {
-            None
+        let line = &data.lines[*data.visible.get(index)?];
+        match line.marker() {
+            Some(marker) => Some(call(Cow::Owned([marker, &line.content].concat()))),
+            None => Some(call(Cow::Borrowed(line.content.as_slice()))),
         }
     }
 
+    /// The lines of controlled files are stored separately, so are never
+    /// available as a single slice.
+    fn with_lines<T, F>(&self, _lines: Range<usize>, _call: F) -> Option<T>
+    where
+        F: FnMut(&[u8], &[usize]) -> T,
+    {
+        None
+    }
+
+    /// Controlled files are always UTF-8.
+    fn encoding(&self) -> Encoding {
+        Encoding::Utf8
+    }
+
+    /// Set the character encoding of the file.  Controlled files are always
+    /// UTF-8.
+    fn set_encoding(&self, _encoding: Encoding) {}
+
+    /// Controlled files are not truncated, as the controller provides their
+    /// lines as they should be shown.
+    fn set_max_line_length(&self, _length: usize) {}
+
+    /// Controlled files are not transformed, as the controller provides
+    /// their lines as they should be shown.
+    fn set_line_transforms(&self, _transforms: LineTransforms) {}
+
+    /// Controlled files are never directory listings.
+    fn listed_path(&self, _index: usize) -> Option<PathBuf> {
+        None
+    }
+
+    /// The controller can give a controlled file a cursor or sections at
+    /// any time, which Enter selects or toggles.
+    fn opens_lines(&self) -> bool {
+        true
+    }
+
+    /// Collapse or expand the section with its header on line `index`.
+    fn toggle_section(&self, index: usize) -> bool {
+        {
+            let mut data = self.data.write().unwrap();
+            let line_index = match data.visible.get(index) {
+                Some(&line_index) => line_index,
+                None => return false,
+            };
+            match data.lines[line_index].section.as_mut() {
+                Some(section) => section.collapsed = !section.collapsed,
+                None => return false,
+            }
+            data.changed(line_index);
+            data.update_visible();
+        }
+        let _ = notify_reloading(&self.notify);
+        true
+    }
+
+    /// The line the cursor is on.  If the cursor line isn't shown, this is
+    /// the nearest shown line before it.
+    fn cursor(&self) -> Option<usize> {
+        let data = self.data.read().unwrap();
+        match data.visible.binary_search(&data.cursor?) {
+            Ok(index) => Some(index),
+            Err(index) => Some(index.saturating_sub(1)),
+        }
+    }
+
+    /// Move the cursor to line `index`.
+    fn move_cursor(&self, index: usize) {
+        let line_index = {
+            let mut data = self.data.write().unwrap();
+            let line_index = match data.visible.get(index) {
+                Some(&line_index) => line_index,
+                None => return,
+            };
+            if data.cursor.is_none() || data.cursor == Some(line_index) {
+                return;
+            }
+            data.cursor = Some(line_index);
+            line_index
+        };
+        self.cursor_event(CursorEvent::Moved { index: line_index });
+    }
+
+    /// Select the cursor line.
+    fn select_cursor(&self) -> bool {
+        let cursor = self.data.read().unwrap().cursor;
+        match cursor {
+            Some(index) => {
+                self.cursor_event(CursorEvent::Selected { index });
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// The path the file was opened from.  Controlled files don't have one.
+    fn path(&self) -> Option<PathBuf> {
+        None
+    }
+
     /// Set how many lines are needed.
     ///
     /// If `self.lines()` exceeds that number, pause loading until
@@ -229,16 +488,113 @@ impl FileInfo for ControlledFile {
     /// This is only effective for "streamed" input.
     fn set_needed_lines(&self, _lines: usize) {}
 
+    /// Controlled files are never loaded.
+    fn set_backpressure(&self, _backpressure: bool) {}
+
     /// True if the loading thread has been paused.
     fn paused(&self) -> bool {
         false
     }
+
+    /// Suspend or resume loading.
+    fn set_loading_suspended(&self, _suspended: bool) {}
+
+    /// True if loading has been suspended.
+    fn loading_suspended(&self) -> bool {
+        false
+    }
+
+    /// True if the loading thread is waiting for more input.
+    fn waiting_for_input(&self) -> bool {
+        false
+    }
+
+    /// True if the file is the output of a command that is still running.
+    fn running_command(&self) -> bool {
+        false
+    }
+
+    /// Kill the command the file is the output of, if it is still running.
+    fn kill_command(&self) {}
+
+    /// Interrupt the command the file is the output of, if it is still
+    /// running.
+    fn interrupt_command(&self) -> bool {
+        false
+    }
+
+    /// Returns the command the file is the output of, and its arguments.
+    fn command(&self) -> Option<(OsString, Vec<OsString>)> {
+        None
+    }
+
+    /// Controlled files are never the output of a command.
+    fn command_output(&self) -> Option<Vec<u8>> {
+        None
+    }
+
+    /// Controlled files aren't loaded from data, so nothing is written.
+    fn set_tee(&self, _tee: Tee) -> crate::error::Result<()> {
+        Ok(())
+    }
+
+    /// Controlled files never have a tee.
+    fn finish_tee(&self) -> crate::error::Result<()> {
+        Ok(())
+    }
+
+    /// Run a command after the command the file is the output of.
+    fn chain_command(&self, _chained: ChainedCommand) -> bool {
+        false
+    }
+
+    /// Take the commands chained after the command the file is the output
+    /// of.
+    fn take_chained_commands(&self) -> Vec<ChainedCommand> {
+        Vec::new()
+    }
+
+    /// Returns information about how the file is stored.  Each line of a
+    /// controlled file is stored separately.
+    fn diagnostics(&self) -> FileDiagnostics {
+        let data = self.data.read().unwrap();
+        let bytes = data.lines.iter().map(|line| line.content.len()).sum();
+        let memory = data
+            .lines
+            .iter()
+            .map(|line| line.content.capacity() + std::mem::size_of::<LineData>())
+            .sum();
+        FileDiagnostics {
+            storage: "controlled",
+            bytes,
+            buffers: data.lines.len(),
+            memory,
+        }
+    }
 }
 
 struct FileData {
     title: String,
     info: String,
     lines: Vec<LineData>,
+
+    /// The indexes in `lines` of the lines that are shown.
+    visible: Vec<usize>,
+
+    /// The offset of the end of each line that is shown, counting a
+    /// newline at the end of each line.
+    line_ends: Vec<usize>,
+
+    /// For each line in `lines` that has been checked for whether it is
+    /// shown, the end of the last collapsed section that started before it.
+    collapsed_ends: Vec<usize>,
+
+    /// The index in `lines` of the first line that has changed since which
+    /// lines are shown was last worked out.
+    changed_from: Option<usize>,
+
+    /// The index in `lines` of the line the cursor is on.
+    cursor: Option<usize>,
 }
 
 impl FileData {
@@ -247,6 +603,65 @@ impl FileData {
             title: title.into(),
             info: String::new(),
             lines: Vec::new(),
+            visible: Vec::new(),
+            line_ends: Vec::new(),
+            collapsed_ends: Vec::new(),
+            changed_from: None,
+            cursor: None,
+        }
+    }
+
+    fn lines_mut(&mut self, range: Range<usize>) -> Result<&mut [LineData]> {
+        let length = self.lines.len();
+        if range.end > length {
+            return Err(ControlledFileError::LineOutOfRange {
+                index: range.end,
+                length,
+            });
+        }
+        Ok(&mut self.lines[range])
+    }
+
+    /// Records that line `index`, and possibly the lines after it, have
+    /// changed, so whether they are shown must be worked out again.
+    fn changed(&mut self, index: usize) {
+        self.changed_from = Some(self.changed_from.map_or(index, |from| from.min(index)));
+    }
+
+    /// Works out which lines are shown, skipping hidden lines and the lines
+    /// of collapsed sections, and the offset each shown line ends at.  Only
+    /// the lines from the first line that changed onwards are checked again,
+    /// so the ruler can look up offsets without going through every line.
+    fn update_visible(&mut self) {
+        let start = match self.changed_from.take() {
+            Some(start) => start.min(self.collapsed_ends.len()).min(self.lines.len()),
+            None => return,
+        };
+        self.collapsed_ends.truncate(start);
+        let shown = self.visible.partition_point(|&index| index < start);
+        self.visible.truncate(shown);
+        self.line_ends.truncate(shown);
+        let mut line_end = self.line_ends.last().copied().unwrap_or(0);
+        let mut collapsed_end = match start.checked_sub(1) {
+            Some(previous) => self.lines[previous]
+                .section
+                .filter(|section| section.collapsed)
+                .map_or(0, |section| previous + 1 + section.lines)
+                .max(self.collapsed_ends[previous]),
+            None => 0,
+        };
+        for (index, line) in self.lines.iter().enumerate().skip(start) {
+            self.collapsed_ends.push(collapsed_end);
+            if index >= collapsed_end && !line.hidden {
+                line_end += line.shown_len() + 1;
+                self.visible.push(index);
+                self.line_ends.push(line_end);
+            }
+            if let Some(section) = line.section {
+                if section.collapsed {
+                    collapsed_end = collapsed_end.max(index + 1 + section.lines);
+                }
+            }
         }
     }
 
@@ -259,6 +674,22 @@ impl FileData {
     }
 
     fn apply_change(&mut self, change: Change) -> Result<()> {
+        match &change {
+            Change::SetTitle { .. } | Change::SetInfo { .. } | Change::SetCursor { .. } => {}
+            Change::AppendLine { .. } | Change::AppendLines { .. } => {
+                self.changed(self.lines.len())
+            }
+            Change::InsertLine { before_index, .. } | Change::InsertLines { before_index, .. } => {
+                self.changed(*before_index)
+            }
+            Change::ReplaceLine { index, .. }
+            | Change::DeleteLine { index }
+            | Change::SetSection { index, .. } => self.changed(*index),
+            Change::ReplaceLines { range, .. }
+            | Change::DeleteLines { range }
+            | Change::SetHidden { range, .. } => self.changed(range.start),
+            Change::ReplaceAll { .. } => self.changed(0),
+        }
         match change {
             Change::SetTitle { title } => {
                 self.title = title;
@@ -304,6 +735,20 @@ impl FileData {
                 let new_lines = contents.into_iter().map(LineData::with_content);
                 self.lines = new_lines.collect();
             }
+            Change::SetHidden { range, hidden } => {
+                for line in self.lines_mut(range)? {
+                    line.hidden = hidden;
+                }
+            }
+            Change::SetSection { index, section } => {
+                self.line_mut(index)?.section = section;
+            }
+            Change::SetCursor { index } => {
+                if let Some(index) = index {
+                    self.line_mut(index)?;
+                }
+                self.cursor = index;
+            }
         }
         Ok(())
     }
@@ -311,10 +756,221 @@ impl FileData {
 
 struct LineData {
     content: Vec<u8>,
+    hidden: bool,
+    section: Option<Section>,
 }
 
 impl LineData {
     fn with_content(content: Vec<u8>) -> LineData {
-        LineData { content }
+        LineData {
+            content,
+            hidden: false,
+            section: None,
+        }
+    }
+
+    /// The marker shown before the line if it is a section header, showing
+    /// whether the section is collapsed.
+    fn marker(&self) -> Option<&'static [u8]> {
+        self.section.map(|section| {
+            if section.collapsed {
+                "\u{25B8} ".as_bytes()
+            } else {
+                "\u{25BE} ".as_bytes()
+            }
+        })
+    }
+
+    /// The length of the line as it is shown, including any marker.
+    fn shown_len(&self) -> usize {
+        self.marker().map_or(0, <[u8]>::len) + self.content.len()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::event::EventStream;
+
+    fn visible_contents(file: &ControlledFile) -> Vec<String> {
+        (0..file.lines())
+            .map(|index| {
+                file.with_line(index, |line| String::from_utf8_lossy(&line).into_owned())
+                    .unwrap()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_sections() -> Result<()> {
+        let controller = Controller::new("test");
+        let events = EventStream::new(None);
+        let file = ControlledFile::new(&controller, 0, events.sender());
+        let contents = ["build", "step 1", "step 2", "test", "case 1", "case 2"];
+        controller.apply_changes(vec![
+            Change::AppendLines {
+                contents: contents
+                    .iter()
+                    .map(|line| line.as_bytes().to_vec())
+                    .collect(),
+            },
+            Change::SetSection {
+                index: 0,
+                section: Some(Section {
+                    lines: 2,
+                    collapsed: true,
+                }),
+            },
+            Change::SetSection {
+                index: 3,
+                section: Some(Section {
+                    lines: 2,
+                    collapsed: false,
+                }),
+            },
+            Change::SetHidden {
+                range: 4..5,
+                hidden: true,
+            },
+        ])?;
+        assert_eq!(
+            visible_contents(&file),
+            ["\u{25B8} build", "\u{25BE} test", "case 2"]
+        );
+        // Offsets include the markers, as the lines are shown with them.
+        assert_eq!(file.line_offset(2), Some(19));
+        assert_eq!(file.length(), 26);
+        assert_eq!(file.line_at_offset(18), Some(1));
+        assert_eq!(file.line_at_offset(19), Some(2));
+
+        assert!(file.toggle_section(0));
+        assert!(!file.toggle_section(1));
+        assert!(file.toggle_section(3));
+        assert_eq!(
+            visible_contents(&file),
+            ["\u{25BE} build", "step 1", "step 2", "\u{25B8} test"]
+        );
+        assert_eq!(
+            controller.section(3),
+            Some(Section {
+                lines: 2,
+                collapsed: true
+            })
+        );
+
+        assert!(matches!(
+            controller.apply_changes(vec![Change::SetHidden {
+                range: 5..7,
+                hidden: true,
+            }]),
+            Err(ControlledFileError::LineOutOfRange {
+                index: 7,
+                length: 6
+            })
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_update_visible() -> Result<()> {
+        let section = |lines, collapsed| Some(Section { lines, collapsed });
+        let contents = |count: usize| (0..count).map(|n| n.to_string().into_bytes()).collect();
+        let changes = vec![
+            Change::AppendLines {
+                contents: contents(10),
+            },
+            Change::SetSection {
+                index: 1,
+                section: section(5, true),
+            },
+            Change::SetSection {
+                index: 2,
+                section: section(1, true),
+            },
+            Change::AppendLines {
+                contents: contents(3),
+            },
+            Change::SetHidden {
+                range: 8..11,
+                hidden: true,
+            },
+            Change::SetSection {
+                index: 1,
+                section: section(5, false),
+            },
+            Change::InsertLines {
+                before_index: 3,
+                contents: contents(2),
+            },
+            Change::DeleteLines { range: 0..2 },
+            Change::SetSection {
+                index: 0,
+                section: section(20, true),
+            },
+            Change::ReplaceLine {
+                index: 5,
+                content: b"five".to_vec(),
+            },
+            Change::SetSection {
+                index: 0,
+                section: None,
+            },
+            Change::DeleteLine { index: 12 },
+        ];
+        let mut data = FileData::new("test");
+        for change in changes {
+            data.apply_change(change)?;
+            data.update_visible();
+            let visible = data.visible.clone();
+            let line_ends = data.line_ends.clone();
+            // Working out which lines are shown from scratch gives the same
+            // lines.
+            data.changed(0);
+            data.update_visible();
+            assert_eq!(visible, data.visible);
+            assert_eq!(line_ends, data.line_ends);
+            assert_eq!(data.collapsed_ends.len(), data.lines.len());
+        }
+        assert_eq!(data.visible, [0, 1, 2, 3, 4, 5, 6, 7, 11]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_cursor() -> Result<()> {
+        let controller = Controller::new("test");
+        let events = EventStream::new(None);
+        let file = ControlledFile::new(&controller, 0, events.sender());
+        let received = Arc::new(Mutex::new(Vec::new()));
+        controller.set_cursor_handler({
+            let received = received.clone();
+            move |event| received.lock().unwrap().push(event)
+        });
+        controller.apply_changes(vec![
+            Change::AppendLines {
+                contents: vec![b"a".to_vec(), b"b".to_vec(), b"c".to_vec()],
+            },
+            Change::SetHidden {
+                range: 1..2,
+                hidden: true,
+            },
+        ])?;
+        assert_eq!(file.cursor(), None);
+        file.move_cursor(1);
+        assert!(!file.select_cursor());
+        assert!(received.lock().unwrap().is_empty());
+
+        controller.apply_changes(vec![Change::SetCursor { index: Some(1) }])?;
+        assert_eq!(file.cursor(), Some(0));
+        file.move_cursor(1);
+        assert_eq!(controller.cursor(), Some(2));
+        assert!(file.select_cursor());
+        assert_eq!(
+            *received.lock().unwrap(),
+            [
+                CursorEvent::Moved { index: 2 },
+                CursorEvent::Selected { index: 2 }
+            ]
+        );
+        Ok(())
     }
 }
@@ -0,0 +1,207 @@
+//! Line Diffs
+//!
+//! Compares two versions of a file line by line, for showing how a file has
+//! changed since a snapshot of it was taken.
+
+use std::fmt::Write;
+
+use crate::file::{File, FileInfo};
+
+/// The largest number of line pairs compared when working out the smallest
+/// diff.  If the changed parts of the files are bigger than this, they are
+/// shown as entirely removed and added.
+const MAX_DIFF_CELLS: usize = 4 * 1024 * 1024;
+
+/// The number of unchanged lines shown either side of each change.
+pub(crate) const DIFF_CONTEXT: usize = 3;
+
+/// How a line differs between the two versions.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Edit {
+    /// The line is in both versions.
+    Same,
+
+    /// The line is only in the old version.
+    Removed,
+
+    /// The line is only in the new version.
+    Added,
+}
+
+/// Works out the edits that turn `old` into `new`.
+fn edits(old: &[Vec<u8>], new: &[Vec<u8>]) -> Vec<Edit> {
+    let prefix = old.iter().zip(new).take_while(|(o, n)| o == n).count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(o, n)| o == n)
+        .count();
+    let old_middle = &old[prefix..old.len() - suffix];
+    let new_middle = &new[prefix..new.len() - suffix];
+
+    let mut edits = vec![Edit::Same; prefix];
+    if old_middle.len().saturating_mul(new_middle.len()) > MAX_DIFF_CELLS {
+        edits.extend(old_middle.iter().map(|_| Edit::Removed));
+        edits.extend(new_middle.iter().map(|_| Edit::Added));
+    } else {
+        // lengths[i][j] is the length of the longest common subsequence of
+        // old_middle[i..] and new_middle[j..].
+        let width = new_middle.len() + 1;
+        let mut lengths = vec![0usize; (old_middle.len() + 1) * width];
+        for i in (0..old_middle.len()).rev() {
+            for j in (0..new_middle.len()).rev() {
+                lengths[i * width + j] = if old_middle[i] == new_middle[j] {
+                    lengths[(i + 1) * width + j + 1] + 1
+                } else {
+                    lengths[(i + 1) * width + j].max(lengths[i * width + j + 1])
+                };
+            }
+        }
+        let (mut i, mut j) = (0, 0);
+        while i < old_middle.len() && j < new_middle.len() {
+            if old_middle[i] == new_middle[j] {
+                edits.push(Edit::Same);
+                i += 1;
+                j += 1;
+            } else if lengths[(i + 1) * width + j] >= lengths[i * width + j + 1] {
+                edits.push(Edit::Removed);
+                i += 1;
+            } else {
+                edits.push(Edit::Added);
+                j += 1;
+            }
+        }
+        edits.extend((i..old_middle.len()).map(|_| Edit::Removed));
+        edits.extend((j..new_middle.len()).map(|_| Edit::Added));
+    }
+    edits.resize(edits.len() + suffix, Edit::Same);
+    edits
+}
+
+/// Returns the lines of `file` that have been loaded so far, without their
+/// line endings.
+pub(crate) fn file_lines(file: &File) -> Vec<Vec<u8>> {
+    (0..file.lines())
+        .filter_map(|index| {
+            file.with_line(index, |line| {
+                let line = line.strip_suffix(b"\n").unwrap_or(&line);
+                line.strip_suffix(b"\r").unwrap_or(line).to_vec()
+            })
+        })
+        .collect()
+}
+
+/// Returns a unified diff of the lines of `old` and `new`, colored with
+/// escape sequences, or `None` if they are the same.  The lines should not
+/// include their line endings.
+pub(crate) fn unified_diff(old: &[Vec<u8>], new: &[Vec<u8>], context: usize) -> Option<Vec<u8>> {
+    let edits = edits(old, new);
+
+    // Find the ranges of edits to show, merging changes whose context
+    // overlaps.
+    let mut hunks: Vec<(usize, usize)> = Vec::new();
+    for (index, edit) in edits.iter().enumerate() {
+        if *edit != Edit::Same {
+            let start = index.saturating_sub(context);
+            let end = (index + context + 1).min(edits.len());
+            match hunks.last_mut() {
+                Some((_, last_end)) if start <= *last_end => *last_end = end,
+                _ => hunks.push((start, end)),
+            }
+        }
+    }
+    if hunks.is_empty() {
+        return None;
+    }
+
+    // The line numbers in each version at the start of each edit.
+    let mut positions = Vec::with_capacity(edits.len() + 1);
+    let (mut old_line, mut new_line) = (0, 0);
+    for edit in edits.iter() {
+        positions.push((old_line, new_line));
+        match edit {
+            Edit::Same => {
+                old_line += 1;
+                new_line += 1;
+            }
+            Edit::Removed => old_line += 1,
+            Edit::Added => new_line += 1,
+        }
+    }
+    positions.push((old_line, new_line));
+
+    let mut diff = Vec::new();
+    for (start, end) in hunks {
+        let (old_start, new_start) = positions[start];
+        let (old_end, new_end) = positions[end];
+        let mut header = String::new();
+        let _ = writeln!(
+            header,
+            "\x1B[36m@@ -{},{} +{},{} @@\x1B[m",
+            old_start + 1,
+            old_end - old_start,
+            new_start + 1,
+            new_end - new_start,
+        );
+        diff.extend_from_slice(header.as_bytes());
+        for (edit, (old_line, new_line)) in edits[start..end].iter().zip(&positions[start..end]) {
+            let (prefix, line, suffix): (&[u8], _, &[u8]) = match edit {
+                Edit::Same => (b" ", &old[*old_line], b""),
+                Edit::Removed => (b"\x1B[31m-", &old[*old_line], b"\x1B[m"),
+                Edit::Added => (b"\x1B[32m+", &new[*new_line], b"\x1B[m"),
+            };
+            diff.extend_from_slice(prefix);
+            diff.extend_from_slice(line);
+            diff.extend_from_slice(suffix);
+            diff.push(b'\n');
+        }
+    }
+    Some(diff)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn lines(text: &str) -> Vec<Vec<u8>> {
+        text.lines().map(|line| line.as_bytes().to_vec()).collect()
+    }
+
+    fn plain_diff(old: &str, new: &str, context: usize) -> Option<String> {
+        unified_diff(&lines(old), &lines(new), context).map(|diff| {
+            String::from_utf8(diff)
+                .unwrap()
+                .replace("\x1B[36m", "")
+                .replace("\x1B[31m", "")
+                .replace("\x1B[32m", "")
+                .replace("\x1B[m", "")
+        })
+    }
+
+    #[test]
+    fn test_same() {
+        assert_eq!(plain_diff("a\nb\n", "a\nb\n", 3), None);
+        assert_eq!(plain_diff("", "", 3), None);
+    }
+
+    #[test]
+    fn test_appended() {
+        assert_eq!(
+            plain_diff("1\n2\n3\n4\n5\n", "1\n2\n3\n4\n5\n6\n7\n", 2).as_deref(),
+            Some("@@ -4,2 +4,4 @@\n 4\n 5\n+6\n+7\n")
+        );
+    }
+
+    #[test]
+    fn test_changed() {
+        assert_eq!(
+            plain_diff("a\nb\nc\nd\ne\nf\ng\nh\n", "a\nB\nc\nd\ne\nf\ng\n", 1).as_deref(),
+            Some("@@ -1,3 +1,3 @@\n a\n-b\n+B\n c\n@@ -7,2 +7,1 @@\n g\n-h\n")
+        );
+        assert_eq!(
+            plain_diff("a\nb\nc\n", "a\nx\nc\n", 3).as_deref(),
+            Some("@@ -1,3 +1,3 @@\n a\n-b\n+x\n c\n")
+        );
+    }
+}
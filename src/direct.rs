@@ -14,7 +14,9 @@ use crate::error::{Error, Result};
 use crate::event::{Event, EventStream};
 use crate::file::{File, FileInfo};
 use crate::line::Line;
+use crate::pager::{DirectFormat, DirectStream};
 use crate::progress::Progress;
+use crate::search::trim_trailing_newline;
 
 /// Return value of `direct`.
 #[derive(Debug)]
@@ -43,30 +45,41 @@ pub(crate) enum Outcome {
 /// - Error (append-only)
 /// - Progress (mutable)
 ///
+/// If `format` is given, each line is formatted by it before it is rendered.
+///
 /// Return `Outcome::Interrupted` if `q` or `Ctrl+C` is pressed.
+///
+/// In `Hybrid` and `Delayed` modes, pressing any other key enters full screen
+/// straight away, as `f` and `Space` do, and the key is passed on to the full
+/// screen pager, so that keys like `G` or `/` aren't lost.
 /// Otherwise, return values and conditions are as follows:
 ///
-/// | Interface  | Fits Screen | Streams Ended | Return           |
-/// |------------|-------------|---------------|------------------|
-/// | FullScreen | (any)       | (any)         | RenderNothing    |
-/// | Direct     | (any)       | no            | -                |
-/// | Direct     | (any)       | yes           | RenderComplete   |
-/// | Hybrid     | yes         | no            | -                |
-/// | Hybrid     | yes         | yes           | RenderComplete   |
-/// | Hybrid     | no          | (any)         | RenderIncomplete |
-/// | Delayed    | (any)       | no (time out) | RenderNothing    |
-/// | Delayed    | yes         | yes           | RenderComplete   |
-/// | Delayed    | no          | yes           | RenderNothing    |
-pub(crate) fn direct<T: Terminal>(
-    term: &mut T,
+/// | Interface             | Fits Screen | Streams Ended | Return           |
+/// |-----------------------|-------------|---------------|------------------|
+/// | FullScreen            | (any)       | (any)         | RenderNothing    |
+/// | FullScreenNoAlternate | (any)       | (any)         | RenderNothing    |
+/// | Direct                | (any)       | no            | -                |
+/// | Direct                | (any)       | yes           | RenderComplete   |
+/// | Hybrid                | yes         | no            | -                |
+/// | Hybrid                | yes         | yes           | RenderComplete   |
+/// | Hybrid                | no          | (any)         | RenderIncomplete |
+/// | Delayed               | (any)       | no (time out) | RenderNothing    |
+/// | Delayed               | yes         | yes           | RenderComplete   |
+/// | Delayed               | no          | yes           | RenderNothing    |
+pub(crate) fn direct(
+    term: &mut dyn Terminal,
     output_files: &[File],
     error_files: &[File],
     progress: Option<&Progress>,
     events: &mut EventStream,
     mode: InterfaceMode,
     poll_input: bool,
+    format: Option<&DirectFormat>,
 ) -> Result<Outcome> {
-    if mode == InterfaceMode::FullScreen {
+    if matches!(
+        mode,
+        InterfaceMode::FullScreen | InterfaceMode::FullScreenNoAlternate
+    ) {
         return Ok(Outcome::RenderNothing);
     }
     let delayed_deadline = match mode {
@@ -117,11 +130,16 @@ pub(crate) fn direct<T: Terminal>(
     let mut state = StreamingLines::default();
     let delayed = delayed_deadline.is_some();
     let has_one_screen_limit = !matches!(mode, InterfaceMode::Direct);
-    let mut render = |term: &mut T, h: usize, w: usize| -> Result<Option<Outcome>> {
+    let mut render = |term: &mut dyn Terminal, h: usize, w: usize| -> Result<Option<Outcome>> {
         let append_output_lines = collect_unread(output_files, h + 2);
         let append_error_lines = collect_unread(error_files, h + 2);
         let progress_lines = read_progress_lines();
-        state.add_lines(append_output_lines, append_error_lines, progress_lines);
+        state.add_lines(
+            append_output_lines,
+            append_error_lines,
+            progress_lines,
+            format,
+        );
         if delayed {
             if has_one_screen_limit && state.height(w) >= h {
                 return Ok(Some(Outcome::RenderNothing));
@@ -161,22 +179,39 @@ pub(crate) fn direct<T: Terminal>(
             }
             Some(Event::Input(InputEvent::Key(key))) => {
                 use termwiz::input::{KeyCode::Char, Modifiers};
-                match (key.modifiers, key.key) {
+                let enter_full_screen = match (key.modifiers, &key.key) {
                     (Modifiers::NONE, Char('q')) | (Modifiers::CTRL, Char('C')) => {
                         term.render(&state.abort()).map_err(Error::Termwiz)?;
                         return Ok(Outcome::Interrupted);
                     }
-                    (Modifiers::NONE, Char('f')) | (Modifiers::NONE, Char(' ')) => {
-                        let outcome = if delayed {
-                            Outcome::RenderNothing
-                        } else {
-                            Outcome::RenderIncomplete(state.rendered_row_count())
-                        };
-                        return Ok(outcome);
+                    (Modifiers::NONE, Char('f')) | (Modifiers::NONE, Char(' ')) => true,
+                    _ if has_one_screen_limit => {
+                        // Pass the key on to the full screen pager.
+                        events.sender().send(Event::Input(InputEvent::Key(key)))?;
+                        true
                     }
-                    _ => (),
+                    _ => false,
+                };
+                if enter_full_screen {
+                    let outcome = if delayed {
+                        Outcome::RenderNothing
+                    } else {
+                        Outcome::RenderIncomplete(state.rendered_row_count())
+                    };
+                    return Ok(outcome);
                 }
             }
+            Some(Event::InputFailed(error)) if has_one_screen_limit => {
+                // Without keyboard input there is no way to ask for the full
+                // screen pager, so switch to it now.  It reports the failure.
+                events.sender().send(Event::InputFailed(error))?;
+                let outcome = if delayed {
+                    Outcome::RenderNothing
+                } else {
+                    Outcome::RenderIncomplete(state.rendered_row_count())
+                };
+                return Ok(outcome);
+            }
             _ => (),
         }
         if let Some(deadline) = delayed_deadline {
@@ -214,6 +249,8 @@ struct StreamingLines {
     new_output_lines: Vec<Vec<u8>>,
     error_lines: Vec<Vec<u8>>,
     progress_lines: Vec<Vec<u8>>,
+    /// The progress lines before they were formatted, to detect changes.
+    unformatted_progress_lines: Vec<Vec<u8>>,
     erase_row_count: usize,
     pending_changes: bool,
     cursor_hidden: bool,
@@ -225,16 +262,30 @@ impl StreamingLines {
         mut append_output_lines: Vec<Vec<u8>>,
         mut append_error_lines: Vec<Vec<u8>>,
         replace_progress_lines: Vec<Vec<u8>>,
+        format: Option<&DirectFormat>,
     ) {
         if append_output_lines.is_empty()
             && append_error_lines.is_empty()
-            && replace_progress_lines == self.progress_lines
+            && replace_progress_lines == self.unformatted_progress_lines
         {
             return;
         }
+        let format_lines = |stream, lines: &mut Vec<Vec<u8>>| {
+            if let Some(format) = format {
+                for line in lines.iter_mut() {
+                    let len = trim_trailing_newline(&line[..]);
+                    *line = format(stream, &line[..len]);
+                }
+            }
+        };
+        format_lines(DirectStream::Output, &mut append_output_lines);
+        format_lines(DirectStream::Error, &mut append_error_lines);
+        let mut progress_lines = replace_progress_lines.clone();
+        format_lines(DirectStream::Progress, &mut progress_lines);
         self.new_output_lines.append(&mut append_output_lines);
         self.error_lines.append(&mut append_error_lines);
-        self.progress_lines = replace_progress_lines;
+        self.progress_lines = progress_lines;
+        self.unformatted_progress_lines = replace_progress_lines;
         self.pending_changes = true;
     }
 
@@ -266,7 +317,7 @@ impl StreamingLines {
             for line in lines {
                 let line = Line::new(0, line);
                 let height = line.height(terminal_width, WrappingMode::GraphemeBoundary);
-                line.render(&mut changes, 0, terminal_width * height, None);
+                line.render(&mut changes, 0, terminal_width * height, None, false);
                 changes.push(Change::CursorPosition {
                     x: Position::Absolute(0),
                     y: Position::Relative(1),
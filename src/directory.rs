@@ -0,0 +1,115 @@
+//! Directory Listings
+//!
+//! When a directory is paged, a listing of its contents is shown instead.
+//! Each line of the listing shows the modification time, size and name of
+//! one entry, and the entries can be opened from the listing.
+
+use std::fs;
+use std::io::Result;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// A listing of the contents of a directory.
+pub(crate) struct Listing {
+    /// The text of the listing, one line per entry.
+    pub(crate) data: Vec<u8>,
+
+    /// The path of the entry on each line of the listing.
+    pub(crate) paths: Vec<PathBuf>,
+}
+
+/// Format a time as a UTC date and time, e.g. `2020-01-31 12:34`.
+fn format_time(time: SystemTime) -> String {
+    let secs = match time.duration_since(UNIX_EPOCH) {
+        Ok(duration) => duration.as_secs(),
+        Err(_) => return String::from("-"),
+    };
+    let (days, secs) = (secs / 86400, secs % 86400);
+
+    // Convert days since the epoch to a civil date.  See
+    // http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+    let z = days + 719_468;
+    let era = z / 146_097;
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    format!(
+        "{:04}-{:02}-{:02} {:02}:{:02}",
+        year,
+        month,
+        day,
+        secs / 3600,
+        secs / 60 % 60
+    )
+}
+
+/// Format the line for one entry of a listing.
+fn format_entry(name: &str, metadata: Option<&fs::Metadata>) -> String {
+    let (time, size, suffix) = match metadata {
+        Some(metadata) if metadata.is_dir() => (metadata.modified().ok(), None, "/"),
+        Some(metadata) => (metadata.modified().ok(), Some(metadata.len()), ""),
+        None => (None, None, ""),
+    };
+    let time = time.map_or_else(|| String::from("-"), format_time);
+    let size = size.map_or_else(|| String::from("-"), |size| size.to_string());
+    format!("{:<16}  {:>12}  {}{}\n", time, size, name, suffix)
+}
+
+impl Listing {
+    /// List the contents of the directory at `path`.  The parent directory
+    /// is listed first, followed by the entries sorted by name.
+    pub(crate) fn new(path: &Path) -> Result<Listing> {
+        let mut entries = fs::read_dir(path)?
+            .map(|entry| entry.map(|entry| entry.path()))
+            .collect::<Result<Vec<_>>>()?;
+        entries.sort();
+        let mut data = Vec::new();
+        let mut paths = Vec::new();
+        let parent = path.join("..");
+        data.extend_from_slice(format_entry("..", fs::metadata(&parent).ok().as_ref()).as_bytes());
+        paths.push(parent);
+        for entry in entries {
+            let name = entry
+                .file_name()
+                .map(|name| name.to_string_lossy().into_owned())
+                .unwrap_or_default();
+            let metadata = fs::metadata(&entry).ok();
+            data.extend_from_slice(format_entry(&name, metadata.as_ref()).as_bytes());
+            paths.push(entry);
+        }
+        Ok(Listing { data, paths })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_format_time() {
+        assert_eq!(format_time(UNIX_EPOCH), "1970-01-01 00:00");
+        let time = UNIX_EPOCH + std::time::Duration::from_secs(951_827_696);
+        assert_eq!(format_time(time), "2000-02-29 12:34");
+    }
+
+    #[test]
+    fn test_listing() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        fs::write(dir.path().join("b.log"), b"hello\n")?;
+        fs::create_dir(dir.path().join("a"))?;
+        let listing = Listing::new(dir.path())?;
+        let text = String::from_utf8(listing.data).unwrap();
+        let lines = text.lines().collect::<Vec<_>>();
+        assert_eq!(lines.len(), 3);
+        assert!(lines[0].ends_with("  ../"));
+        assert!(lines[1].ends_with("             -  a/"));
+        assert!(lines[2].ends_with("             6  b.log"));
+        assert_eq!(listing.paths[2], dir.path().join("b.log"));
+        Ok(())
+    }
+}
@@ -1,41 +1,72 @@
 //! Manage the Display.
 
+use std::borrow::Cow;
+use std::env;
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
 use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Arc;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
 use scopeguard::guard;
-use termwiz::caps::Capabilities as TermCapabilities;
+use termwiz::caps::{Capabilities as TermCapabilities, ColorLevel};
 use termwiz::cell::CellAttributes;
 use termwiz::color::ColorAttribute;
-use termwiz::input::InputEvent;
+use termwiz::escape::csi::{DecPrivateMode, DecPrivateModeCode, Mode, Window, CSI};
+use termwiz::input::{InputEvent, MouseButtons};
 use termwiz::surface::change::Change;
 use termwiz::surface::{CursorVisibility, Position};
 use termwiz::terminal::Terminal;
 use vec_map::VecMap;
 
+use crate::action::{Action, RerunMode, Severity};
 use crate::command;
-use crate::config::Config;
+use crate::config::{Config, InterfaceMode, KeymapConfig, Renderer, WrappingMode};
+use crate::diff::{self, DIFF_CONTEXT};
 use crate::direct;
 use crate::error::Error;
-use crate::event::{Event, EventStream, UniqueInstance};
+use crate::event::{Event, EventSender, EventStream, UniqueInstance};
 use crate::file::{File, FileIndex, FileInfo, LoadedFile};
+use crate::handle::PagerHandle;
 use crate::help::help_text;
-use crate::progress::Progress;
+use crate::keymaps;
+use crate::line::Line;
+use crate::pager::DirectFormat;
+use crate::progress::{Progress, ProgressStreams};
+use crate::prompt;
+use crate::prompt_history::LastValues;
+use crate::ruler::Ruler;
 use crate::screen::Screen;
+use crate::script;
 use crate::search::SearchKind;
+use crate::session::{Resume, Session};
+use crate::trace::{trace_event, trace_span};
 
 /// Capabilities of the terminal that we care about.
 #[derive(Default)]
 pub(crate) struct Capabilities {
     pub(crate) scroll_up: bool,
     pub(crate) scroll_down: bool,
+    /// Whether the terminal supports hyperlinks.
+    pub(crate) hyperlinks: bool,
+    /// Whether the terminal has a terminfo entry.
+    pub(crate) terminfo: bool,
+    /// The number of colors the terminal reports supporting.
+    pub(crate) color_level: Option<ColorLevel>,
 }
 
 impl Capabilities {
     fn new(term_caps: TermCapabilities) -> Capabilities {
         use terminfo::capability as cap;
-        let mut caps = Capabilities::default();
+        let mut caps = Capabilities {
+            hyperlinks: term_caps.hyperlinks(),
+            terminfo: term_caps.terminfo_db().is_some(),
+            color_level: Some(term_caps.color_level()),
+            ..Default::default()
+        };
+        // Scrolling regions are only sent when the terminfo database
+        // describes how.  Without it (e.g. when assuming a basic ANSI
+        // terminal) the screen is redrawn instead.
         if let Some(db) = term_caps.terminfo_db() {
             if db.get::<cap::ChangeScrollRegion>().is_some() {
                 caps.scroll_up = db.get::<cap::ParmIndex>().is_some()
@@ -50,6 +81,394 @@ impl Capabilities {
     }
 }
 
+/// The number of changes to send to the terminal at once.  Between batches
+/// of this size, the terminal is checked for input.
+const RENDER_CHUNK_SIZE: usize = 256;
+
+/// Renders the screen, sending the changes to the terminal in chunks.
+///
+/// On slow terminals a large render can take a long time to complete.  If
+/// input arrives between chunks, the rest of the render is abandoned and the
+/// input is stored in `interrupted` so that it can be handled first.  The
+/// whole screen is rendered again next time.
+fn render_screen(
+    term: &mut dyn Terminal,
+    screen: &mut Screen,
+    caps: &Capabilities,
+    interrupted: &mut Option<InputEvent>,
+) -> Result<(), Error> {
+    let _span = trace_span!("render", file = screen.file.index());
+    let start = Instant::now();
+    let changes = screen.render(caps);
+    for (index, chunk) in changes.chunks(RENDER_CHUNK_SIZE).enumerate() {
+        if index > 0 {
+            // Failures to read input are reported by the event loop.
+            match term.poll_input(Some(Duration::from_millis(0))) {
+                Err(_) | Ok(None) | Ok(Some(InputEvent::Wake)) => {}
+                Ok(Some(input)) => {
+                    *interrupted = Some(input);
+                    screen.refresh();
+                    return Ok(());
+                }
+            }
+        }
+        term.render(chunk).map_err(Error::Termwiz)?;
+    }
+    screen.record_render(start.elapsed(), changes.len());
+    Ok(())
+}
+
+/// The shortest time between renders that are asked for by events, about 30
+/// renders a second.  A fast producer can otherwise keep the display busy
+/// rendering every update to the file.
+const MIN_RENDER_INTERVAL: Duration = Duration::from_millis(33);
+
+/// Limits how often the screen is rendered.  Renders that are asked for too
+/// soon after the last one are deferred, and all the renders deferred within
+/// one interval are coalesced into a single render at the end of it.
+struct RenderLimiter {
+    /// The shortest time between renders.
+    interval: Duration,
+
+    /// When the last render started.
+    last: Option<Instant>,
+
+    /// Whether a render has been deferred.
+    deferred: bool,
+}
+
+impl RenderLimiter {
+    fn new(interval: Duration) -> RenderLimiter {
+        RenderLimiter {
+            interval,
+            last: None,
+            deferred: false,
+        }
+    }
+
+    /// Ask to render at `now`.  Returns `true` if the render should happen
+    /// now, otherwise it is deferred.
+    fn request(&mut self, now: Instant) -> bool {
+        match self.last {
+            Some(last) if now.saturating_duration_since(last) < self.interval => {
+                self.deferred = true;
+                false
+            }
+            _ => true,
+        }
+    }
+
+    /// Record that a render started at `now`.
+    fn rendered(&mut self, now: Instant) {
+        self.last = Some(now);
+        self.deferred = false;
+    }
+
+    /// How long to wait until a deferred render is due.
+    fn wait(&self, now: Instant) -> Option<Duration> {
+        match self.last {
+            Some(last) if self.deferred => {
+                Some((last + self.interval).saturating_duration_since(now))
+            }
+            _ => None,
+        }
+    }
+
+    /// Whether a deferred render is due at `now`.
+    fn due(&self, now: Instant) -> bool {
+        self.wait(now) == Some(Duration::ZERO)
+    }
+}
+
+/// Renders the screen if `limiter` allows it, otherwise defers the render.
+fn render_screen_limited(
+    term: &mut dyn Terminal,
+    screen: &mut Screen,
+    caps: &Capabilities,
+    interrupted: &mut Option<InputEvent>,
+    limiter: &mut RenderLimiter,
+) -> Result<(), Error> {
+    let now = Instant::now();
+    if limiter.request(now) {
+        limiter.rendered(now);
+        render_screen(term, screen, caps, interrupted)?;
+    }
+    Ok(())
+}
+
+/// Parses the user's init script and the configured startup commands into
+/// the actions to run once the first screen is shown.  Scripts with errors
+/// are skipped, and the error is shown on the screen.
+fn startup_actions(screens: &mut Screens, config: &Config) -> Vec<Action> {
+    let mut actions = Vec::new();
+    if let Some((path, script)) = script::read_init_script() {
+        match script::parse(&script) {
+            Ok(script_actions) => actions.extend(script_actions),
+            Err((line, e)) => screens.current().set_error(format!(
+                "Error on line {} of {}: {}",
+                line,
+                path.display(),
+                e
+            )),
+        }
+    }
+    for commands in config.startup_commands.iter() {
+        match script::parse(commands) {
+            Ok(script_actions) => actions.extend(script_actions),
+            Err((_, e)) => screens
+                .current()
+                .set_error(format!("Error in startup commands: {}", e)),
+        }
+    }
+    actions
+}
+
+/// Checks that the configured keymap loads.  If a keymap file has errors,
+/// switches to the default keymap and returns a report of the errors.
+fn check_keymap(config: &mut Config) -> Option<String> {
+    match config.keymap.load() {
+        Err(Error::Keymap(error)) => {
+            let report = keymaps::error_report(&error)?;
+            config.keymap = KeymapConfig::default();
+            Some(report)
+        }
+        _ => None,
+    }
+}
+
+/// Shows `data` in an overlay with the given title, e.g. the help screen,
+/// or an error report so that a keymap file can be fixed while paging.
+fn show_overlay(
+    term: &mut dyn Terminal,
+    screens: &mut Screens,
+    title: &str,
+    data: impl Into<Cow<'static, [u8]>>,
+    event_sender: &EventSender,
+    config: &Arc<Config>,
+    caps: &Capabilities,
+    interrupted: &mut Option<InputEvent>,
+) -> Result<(), Error> {
+    let overlay_index = screens.next_index(1);
+    let mut screen = screens.new_screen(
+        LoadedFile::new_static(overlay_index, title, data, event_sender.clone()).into(),
+        config.clone(),
+    )?;
+    screen.set_context_ruler(screens.context_ruler());
+    let size = term.get_screen_size().map_err(Error::Termwiz)?;
+    screen.resize(size.cols, size.rows);
+    screen.refresh();
+    render_screen(term, &mut screen, caps, interrupted)?;
+    screens.overlay = Some(screen);
+    screens.overlay_index = overlay_index;
+    screens.json_view = false;
+    Ok(())
+}
+
+/// Explains that the pager can't be controlled once keyboard input fails.
+fn input_failed_report(error: &str) -> String {
+    format!(
+        "Keyboard input can't be read from the terminal: {}\n\n\
+         The pager can't be controlled without keyboard input.  To stop it,\n\
+         send it a signal, e.g.:\n\n    kill {}\n",
+        error,
+        std::process::id()
+    )
+}
+
+/// Describes the internal state of the pager, for diagnosing performance
+/// problems.
+fn diagnostics(
+    screens: &Screens,
+    caps: &Capabilities,
+    events: &EventStream,
+    config: &Config,
+) -> String {
+    let mut text = String::from("Files:\n");
+    let files = screens
+        .screens
+        .iter()
+        .flat_map(|screen| std::iter::once(&screen.file).chain(screen.error_file()));
+    for file in files {
+        let info = file.diagnostics();
+        let state = if file.loaded() {
+            "loaded"
+        } else if file.paused() {
+            "paused"
+        } else {
+            "loading"
+        };
+        text.push_str(&format!(
+            "  {}: {}\n    {}, {} lines, {} bytes\n    storage: {}, {} buffers, {} bytes of memory\n",
+            file.index(),
+            file.title(),
+            state,
+            file.lines(),
+            info.bytes,
+            info.storage,
+            info.buffers,
+            info.memory,
+        ));
+    }
+
+    let screen = &screens.screens[screens.current_index];
+    text.push_str(&format!("\nCurrent file: {}\n\n", screen.file.title()));
+    text.push_str(&screen.cache_stats());
+    let timings = screen.render_timings();
+    let average = match timings.count {
+        0 => Duration::default(),
+        count => timings.total / count,
+    };
+    let renderer = match config.renderer {
+        Renderer::Incremental => "incremental",
+        Renderer::Surface => "surface",
+    };
+    text.push_str(&format!(
+        "Renders:\n  renderer:   {}\n  count:      {}\n  last:       {:?}\n  changes:    {}\n  average:    {:?}\n  slowest:    {:?}\n\n",
+        renderer, timings.count, timings.last, timings.last_changes, average, timings.slowest,
+    ));
+
+    text.push_str(&format!("Events:\n  queued:     {}\n\n", events.queued()));
+
+    let yes_no = |value| if value { "yes" } else { "no" };
+    text.push_str(&format!(
+        "Terminal:\n  TERM:        {}\n  terminfo:    {}\n  colors:      {}\n  hyperlinks:  {}\n  scroll up:   {}\n  scroll down: {}\n  color:       {:?} ({})\n  color depth: {:?}\n",
+        env::var("TERM").unwrap_or_default(),
+        yes_no(caps.terminfo),
+        caps.color_level
+            .map_or_else(|| String::from("unknown"), |level| format!("{:?}", level)),
+        yes_no(caps.hyperlinks),
+        yes_no(caps.scroll_up),
+        yes_no(caps.scroll_down),
+        config.color,
+        if config.use_color() { "in use" } else { "not used" },
+        config.color_depth,
+    ));
+    text
+}
+
+/// Prints lines to the terminal outside of the full screen interface.
+pub(crate) fn print_lines(term: &mut dyn Terminal, lines: &[Vec<u8>]) -> Result<(), Error> {
+    term.exit_alternate_screen().map_err(Error::Termwiz)?;
+    let width = term.get_screen_size().map_err(Error::Termwiz)?.cols;
+    let mut changes = Vec::new();
+    for line in lines {
+        let line = Line::new(0, line);
+        let height = line.height(width, WrappingMode::GraphemeBoundary);
+        line.render(&mut changes, 0, width * height, None, false);
+        changes.push(Change::AllAttributes(CellAttributes::default()));
+        changes.push(Change::CursorPosition {
+            x: Position::Absolute(0),
+            y: Position::Relative(1),
+        });
+    }
+    term.render(&changes).map_err(Error::Termwiz)?;
+    Ok(())
+}
+
+/// Returns the change that enables or disables mouse reporting.
+pub(crate) fn mouse_reporting(enable: bool) -> Change {
+    let mode = |code| {
+        let mode = DecPrivateMode::Code(code);
+        let mode = if enable {
+            Mode::SetDecPrivateMode(mode)
+        } else {
+            Mode::ResetDecPrivateMode(mode)
+        };
+        CSI::Mode(mode).to_string()
+    };
+    Change::Text(mode(DecPrivateModeCode::ButtonEventMouse) + &mode(DecPrivateModeCode::SGRMouse))
+}
+
+/// Returns a change that saves the terminal's window title so that it can
+/// be restored later, or restores the saved title.
+pub(crate) fn save_window_title(save: bool) -> Change {
+    let window = if save {
+        Window::PushIconAndWindowTitle
+    } else {
+        Window::PopIconAndWindowTitle
+    };
+    Change::Text(CSI::Window(Box::new(window)).to_string())
+}
+
+/// Runs a shell command with the terminal restored to its normal state, and
+/// waits for a keypress once it has finished.
+///
+/// Returns a message for the user if the command could not be run or did
+/// not succeed.
+fn run_shell_command(
+    term: &mut dyn Terminal,
+    command: &str,
+    alternate_screen: bool,
+    mouse: bool,
+) -> Result<Option<String>, Error> {
+    let mut changes = vec![
+        Change::AllAttributes(CellAttributes::default()),
+        Change::CursorVisibility(CursorVisibility::Visible),
+    ];
+    if mouse {
+        changes.push(mouse_reporting(false));
+    }
+    if !alternate_screen {
+        // Run the command below the pager's screen, which it will scroll
+        // away.
+        let size = term.get_screen_size().map_err(Error::Termwiz)?;
+        changes.push(Change::CursorPosition {
+            x: Position::Absolute(0),
+            y: Position::Absolute(size.rows.saturating_sub(1)),
+        });
+        changes.push(Change::Text("\r\n".to_string()));
+    }
+    term.render(&changes).map_err(Error::Termwiz)?;
+    if alternate_screen {
+        term.exit_alternate_screen().map_err(Error::Termwiz)?;
+    }
+    term.set_cooked_mode().map_err(Error::Termwiz)?;
+    let mut shell = if cfg!(windows) {
+        let mut shell = Command::new("cmd.exe");
+        shell.arg("/C");
+        shell
+    } else {
+        let mut shell = Command::new(env::var_os("SHELL").unwrap_or_else(|| "/bin/sh".into()));
+        shell.arg("-c");
+        shell
+    };
+    let message = match shell.arg(command).stdin(terminal_input()).status() {
+        Ok(status) if status.success() => None,
+        Ok(status) => Some(format!("Command failed: {}", status)),
+        Err(err) => Some(format!("Failed to run command: {}", err)),
+    };
+    term.set_raw_mode().map_err(Error::Termwiz)?;
+    term.render(&[Change::Text("\r\nPress any key to continue".to_string())])
+        .map_err(Error::Termwiz)?;
+    while !matches!(
+        term.poll_input(None).map_err(Error::Termwiz)?,
+        Some(InputEvent::Key(_))
+    ) {}
+    if alternate_screen {
+        term.enter_alternate_screen().map_err(Error::Termwiz)?;
+    }
+    if mouse {
+        term.render(&[mouse_reporting(true)])
+            .map_err(Error::Termwiz)?;
+    }
+    Ok(message)
+}
+
+/// Returns the terminal for a shell command to read its input from.  The
+/// pager's own standard input may be the data being paged, so the command
+/// mustn't inherit it.
+fn terminal_input() -> Stdio {
+    let path = if cfg!(windows) { "CONIN$" } else { "/dev/tty" };
+    match std::fs::OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open(path)
+    {
+        Ok(file) => Stdio::from(file),
+        Err(_) => Stdio::null(),
+    }
+}
+
 /// An action that affects the display.
 pub(crate) enum DisplayAction {
     /// Do nothing.
@@ -76,14 +495,68 @@ pub(crate) enum DisplayAction {
     /// Move to the previous file.
     PreviousFile,
 
+    /// Move to the error file of the current file.
+    OpenErrorFile,
+
     /// Show the help screen.
     ShowHelp,
 
+    /// Show the error log of the current screen.
+    ShowErrorLog,
+
+    /// Show statistics about the line caches of the current file.
+    ShowCacheStats,
+
+    /// Show the earlier pages of the current file's progress indicator.
+    ShowProgressHistory,
+
+    /// Show the internal state of the pager, for diagnosing performance
+    /// problems.
+    ShowDiagnostics,
+
+    /// Re-read the config file and apply it to all screens.
+    ReloadConfig,
+
+    /// Release or recapture the mouse so that text can be selected.
+    ToggleMouseSelection,
+
+    /// Show or hide a view of the current file with its JSON pretty-printed.
+    ToggleJsonView,
+
+    /// Snapshot the current file into a new file, and move to it.
+    SnapshotFile,
+
+    /// Show the changes to the current file since its snapshot.
+    DiffSnapshot,
+
+    /// Run a shell command with the terminal restored.
+    RunShellCommand(String),
+
+    /// Run the command of the current file again.
+    RerunCommand(RerunMode),
+
+    /// Run the next command chained after the command of a file, which has
+    /// finished.
+    RunChainedCommand(FileIndex),
+
+    /// Open a file from disk as a new file, and move to it.
+    OpenFile(PathBuf),
+
     /// Clear the overlay.
     ClearOverlay,
 
+    /// Close the current file, or the program if it is the last file.
+    CloseFile,
+
+    /// Kill the commands of all files that are still running, and close
+    /// the program.
+    QuitAndKillChild,
+
     /// Close the program.
     Quit,
+
+    /// Close the program, printing the visible lines to the terminal.
+    QuitAndPrintScreen,
 }
 
 /// Container for all screens.
@@ -100,10 +573,27 @@ struct Screens {
     /// The file index of the overlay.  While overlays aren't part of the
     /// screens vector, we still need a file index so that the file loader can
     /// report loading completion and the search thread can report search
-    /// matches.  Use an index starting after the loaded files for this purpose.
-    /// Each time a new overlay is added, this index is incremented, so that
-    /// each overlay gets a unique index.
+    /// matches.  Each overlay takes a new index from `next_index`.
     overlay_index: FileIndex,
+
+    /// The next file index to give to an overlay or a file added while
+    /// paging.  Indexes start after the files given at startup and are never
+    /// reused, so events for one file can't be taken to be for another.
+    next_index: FileIndex,
+
+    /// True if the overlay is a view of the current file with its JSON
+    /// pretty-printed.
+    json_view: bool,
+
+    /// The progress indicator, for screens added later.
+    progress: Option<Progress>,
+
+    /// The last values entered in prompts on any screen.
+    last_values: LastValues,
+
+    /// True if mouse reporting is paused so that the terminal can select
+    /// text.  This is shown on every screen.
+    mouse_selection: bool,
 }
 
 impl Screens {
@@ -111,15 +601,16 @@ impl Screens {
     fn new(
         files: Vec<File>,
         mut error_files: VecMap<File>,
-        progress: Option<Progress>,
+        progress: ProgressStreams,
         config: Arc<Config>,
     ) -> Result<Screens, Error> {
         let count = files.len();
+        let last_values = LastValues::default();
         let mut screens = Vec::new();
         for file in files.into_iter() {
             let index = file.index();
-            let mut screen = Screen::new(file, config.clone())?;
-            screen.set_progress(progress.clone());
+            let mut screen = Screen::new(file, config.clone(), last_values.clone())?;
+            screen.set_progress(progress.get(index).cloned());
             screen.set_error_file(error_files.remove(index));
             screens.push(screen);
         }
@@ -128,9 +619,308 @@ impl Screens {
             overlay: None,
             current_index: 0,
             overlay_index: count,
+            next_index: count,
+            json_view: false,
+            progress: progress.shared().cloned(),
+            last_values,
+            mouse_selection: false,
         })
     }
 
+    /// Create a screen for a file, sharing the state common to all screens.
+    fn new_screen(&self, file: File, config: Arc<Config>) -> Result<Screen, Error> {
+        let mut screen = Screen::new(file, config, self.last_values.clone())?;
+        screen.set_mouse_selection(self.mouse_selection);
+        Ok(screen)
+    }
+
+    /// Set whether text is being selected with the mouse, and show it on
+    /// all screens.
+    fn set_mouse_selection(&mut self, selecting: bool) {
+        self.mouse_selection = selecting;
+        for screen in self.screens.iter_mut().chain(self.overlay.as_mut()) {
+            screen.set_mouse_selection(selecting);
+        }
+    }
+
+    /// Returns the first of `count` new consecutive file indexes, for
+    /// overlays or files added while paging.
+    fn next_index(&mut self, count: usize) -> FileIndex {
+        let index = self.next_index;
+        self.next_index += count;
+        index
+    }
+
+    /// Add a screen for a file that was added while paging.
+    ///
+    /// Files added while paging take their index from the same sequence as
+    /// overlays, so their indexes don't match their position in the list of
+    /// screens.  Returns the position of the new screen.
+    fn add<F>(&mut self, config: Arc<Config>, load: F) -> Result<usize, Error>
+    where
+        F: FnOnce(FileIndex) -> Result<LoadedFile, Error>,
+    {
+        let index = self.next_index(1);
+        let file = load(index)?;
+        file.set_encoding(config.encoding);
+        file.set_max_line_length(config.max_line_length);
+        file.set_line_transforms(config.line_transforms.clone());
+        let mut screen = self.new_screen(file.into(), config)?;
+        screen.set_progress(self.progress.clone());
+        self.screens.push(screen);
+        Ok(self.screens.len() - 1)
+    }
+
+    /// Make the error file of the current screen current.  Returns `false`
+    /// if the current screen has no error file.
+    fn open_error_file(&mut self, config: Arc<Config>) -> Result<bool, Error> {
+        let error_file = match self.current().error_file() {
+            Some(error_file) => error_file.clone(),
+            None => return Ok(false),
+        };
+        let position = self
+            .screens
+            .iter()
+            .position(|screen| screen.file.index() == error_file.index());
+        let position = match position {
+            Some(position) => position,
+            None => {
+                // The error file was only attached to this screen, so give
+                // it a screen of its own.
+                let mut screen = self.new_screen(error_file, config)?;
+                screen.set_progress(self.progress.clone());
+                self.screens.push(screen);
+                self.screens.len() - 1
+            }
+        };
+        self.overlay = None;
+        self.json_view = false;
+        self.current_index = position;
+        Ok(true)
+    }
+
+    /// Add a screen with a snapshot of the current screen's file, and make
+    /// it current.
+    fn snapshot(&mut self, config: Arc<Config>, event_sender: EventSender) -> Result<(), Error> {
+        let screen = self.current();
+        let live = screen.snapshot_of().unwrap_or(&screen.file).clone();
+        let title = format!("{} (snapshot)", live.title());
+        let data = screen.snapshot();
+        // The snapshot holds lines as they were shown, so they have already
+        // been decoded and transformed.
+        let index = self.next_index(1);
+        let file = LoadedFile::new_static(index, &title, data, event_sender);
+        file.set_max_line_length(config.max_line_length);
+        let mut screen = self.new_screen(file.into(), config)?;
+        screen.set_snapshot_of(live);
+        self.screens.push(screen);
+        self.overlay = None;
+        self.json_view = false;
+        self.current_index = self.screens.len() - 1;
+        Ok(())
+    }
+
+    /// Run the command the current file is the output of again, replacing
+    /// the screens of its output and error files.  Returns `false` if the
+    /// current file isn't the output of a command.
+    fn rerun_command(
+        &mut self,
+        mode: RerunMode,
+        config: Arc<Config>,
+        event_sender: EventSender,
+    ) -> Result<bool, Error> {
+        let (command, args) = match self.screens[self.current_index].file.command() {
+            Some(command) => command,
+            None => return Ok(false),
+        };
+        // The error file takes the index after the output file.
+        let index = self.next_index(2);
+        let previous = &self.screens[self.current_index];
+        previous.file.kill_command();
+        let prefix = match mode {
+            RerunMode::Replace => Vec::new(),
+            RerunMode::Append => {
+                // The new file decodes and transforms its data, so it
+                // starts with the old output as it was written rather than
+                // as it was shown.
+                let mut data = previous.file.command_output().unwrap_or_default();
+                if !data.is_empty() && !data.ends_with(b"\n") {
+                    data.push(b'\n');
+                }
+                let command_line = std::iter::once(&command)
+                    .chain(args.iter())
+                    .map(|arg| arg.to_string_lossy())
+                    .collect::<Vec<_>>()
+                    .join(" ");
+                data.extend_from_slice(
+                    format!(
+                        "\x1B[2m\u{2500}\u{2500} {} \u{2500}\u{2500}\x1B[m\n",
+                        command_line
+                    )
+                    .as_bytes(),
+                );
+                data
+            }
+        };
+        let (out_file, err_file) = LoadedFile::new_command_after(
+            index,
+            &command,
+            args,
+            &previous.file.title(),
+            prefix,
+            event_sender.clone(),
+        )?;
+        for file in [&out_file, &err_file] {
+            file.set_encoding(config.encoding);
+            file.set_max_line_length(config.max_line_length);
+            file.set_line_transforms(config.line_transforms.clone());
+        }
+        let err_file = File::from(err_file);
+        let mut screen = self.new_screen(out_file.into(), config.clone())?;
+        screen.set_progress(self.progress.clone());
+        screen.set_error_file(Some(err_file.clone()));
+        screen.continue_from(previous, event_sender.clone());
+
+        // The error file's own screen, if it has one, is replaced too.
+        if let Some(previous_err) = previous.error_file().map(|file| file.index()) {
+            if let Some(position) = self
+                .screens
+                .iter()
+                .position(|screen| screen.file.index() == previous_err)
+            {
+                let mut err_screen = self.new_screen(err_file, config)?;
+                err_screen.continue_from(&self.screens[position], event_sender);
+                self.screens[position] = err_screen;
+            }
+        }
+        self.screens[self.current_index] = screen;
+        self.overlay = None;
+        self.json_view = false;
+        Ok(true)
+    }
+
+    /// Run the next command chained after the command that file `index` is
+    /// the output of, adding its output and error files.  Returns true if the
+    /// output file was made current.
+    fn run_chained_command(
+        &mut self,
+        index: FileIndex,
+        config: Arc<Config>,
+        event_sender: EventSender,
+    ) -> Result<bool, Error> {
+        let file = match self
+            .screens
+            .iter()
+            .find(|screen| screen.file.index() == index)
+        {
+            Some(screen) => screen.file.clone(),
+            None => return Ok(false),
+        };
+        let mut chained = file.take_chained_commands().into_iter();
+        let next = match chained.next() {
+            Some(next) => next,
+            None => return Ok(false),
+        };
+        // The error file takes the index after the output file.
+        let index = self.next_index(2);
+        let (out_file, err_file) = LoadedFile::new_command_after(
+            index,
+            &next.command,
+            next.args,
+            &next.title,
+            Vec::new(),
+            event_sender,
+        )?;
+        for rest in chained {
+            out_file.chain_command(rest);
+        }
+        for file in [&out_file, &err_file] {
+            file.set_encoding(config.encoding);
+            file.set_max_line_length(config.max_line_length);
+            file.set_line_transforms(config.line_transforms.clone());
+        }
+        let err_file = File::from(err_file);
+        let mut screen = self.new_screen(out_file.into(), config.clone())?;
+        screen.set_progress(self.progress.clone());
+        screen.set_error_file(Some(err_file.clone()));
+        let mut err_screen = self.new_screen(err_file, config.clone())?;
+        err_screen.set_progress(self.progress.clone());
+        self.screens.push(screen);
+        self.screens.push(err_screen);
+        if !config.switch_to_chained_commands {
+            return Ok(false);
+        }
+        self.overlay = None;
+        self.json_view = false;
+        self.current_index = self.screens.len() - 2;
+        Ok(true)
+    }
+
+    /// Returns the snapshot and the live file to compare for the current
+    /// screen.  If the current screen is a snapshot, it is compared with its
+    /// file; otherwise the latest snapshot of the current file is used.
+    fn snapshot_files(&mut self) -> Option<(File, File)> {
+        let screen = self.current();
+        if let Some(live) = screen.snapshot_of() {
+            return Some((screen.file.clone(), live.clone()));
+        }
+        let live = screen.file.clone();
+        self.screens
+            .iter()
+            .rev()
+            .find_map(|screen| match screen.snapshot_of() {
+                Some(file) if file.index() == live.index() => {
+                    Some((screen.file.clone(), live.clone()))
+                }
+                _ => None,
+            })
+    }
+
+    /// Open a file from disk, and make it current.
+    fn open(
+        &mut self,
+        path: PathBuf,
+        config: Arc<Config>,
+        event_sender: EventSender,
+    ) -> Result<(), Error> {
+        // Show the parent directories of listings by name.
+        let path = if path.is_dir() {
+            path.canonicalize().unwrap_or(path)
+        } else {
+            path
+        };
+        let position = self.add(config, |index| {
+            LoadedFile::new_file(index, path.as_os_str(), event_sender)
+        })?;
+        self.overlay = None;
+        self.json_view = false;
+        self.current_index = position;
+        Ok(())
+    }
+
+    /// Close the current file's screen, moving to the next file, or the
+    /// previous one if it was the last.  The last file can't be closed.
+    fn close_current(&mut self) {
+        if self.screens.len() > 1 {
+            self.overlay = None;
+            self.json_view = false;
+            self.screens.remove(self.current_index);
+            self.current_index = self.current_index.min(self.screens.len() - 1);
+        }
+    }
+
+    /// Returns the ruler of the current file's screen, to show beneath an
+    /// overlay.
+    fn context_ruler(&self) -> Ruler {
+        self.screens[self.current_index].context_ruler()
+    }
+
+    /// Returns the title of the current file.  Overlays don't change the
+    /// title.
+    fn title(&self) -> String {
+        self.screens[self.current_index].file.title().into_owned()
+    }
+
     /// Get the current screen.
     fn current(&mut self) -> &mut Screen {
         if let Some(ref mut screen) = self.overlay {
@@ -144,52 +934,118 @@ impl Screens {
     fn is_current_index(&self, index: FileIndex) -> bool {
         match self.overlay {
             Some(_) => index == self.overlay_index,
-            None => index == self.current_index,
+            None => index == self.screens[self.current_index].file.index(),
+        }
+    }
+
+    /// Replace the configuration of all screens.  Screens that can't use
+    /// the new configuration keep their current one, and the first error is
+    /// returned.
+    fn set_config(&mut self, config: Arc<Config>) -> Result<(), Error> {
+        let mut result = Ok(());
+        for screen in self.screens.iter_mut().chain(self.overlay.as_mut()) {
+            if let Err(e) = screen.set_config(config.clone()) {
+                if result.is_ok() {
+                    result = Err(e);
+                }
+            }
+            screen.set_mouse_selection(self.mouse_selection);
+        }
+        result
+    }
+
+    /// Restores the screens of the files reopened from a saved session,
+    /// and switches to the one that was being shown.
+    fn restore(&mut self, resume: &Resume, event_sender: &EventSender) {
+        for (file_index, file) in resume.files.iter() {
+            if let Some(screen) = self.get(*file_index) {
+                screen.restore(file, event_sender.clone());
+            }
+        }
+        if let Some(current) = resume.current.and_then(|current| {
+            self.screens
+                .iter()
+                .position(|screen| screen.file.index() == current)
+        }) {
+            self.current_index = current;
+        }
+    }
+
+    /// Saves the files opened by name, and where they are scrolled to, as
+    /// the session to resume.  The previous session is kept if no files
+    /// were opened by name.  Failing to save the session is reported, but
+    /// doesn't stop the pager from quitting.
+    fn save_session(&self) {
+        let mut session = Session::default();
+        for (index, screen) in self.screens.iter().enumerate() {
+            if let Some(file) = screen.session_file() {
+                if index == self.current_index {
+                    session.current = session.files.len();
+                }
+                session.files.push(file);
+            }
+        }
+        if session.files.is_empty() {
+            return;
+        }
+        if let Err(e) = session.save() {
+            eprintln!("streampager: failed to save session: {}", e);
         }
     }
 
     /// Get the screen with the given index.
     fn get(&mut self, index: usize) -> Option<&mut Screen> {
-        if index == self.overlay_index {
-            self.overlay.as_mut()
-        } else if index < self.screens.len() {
-            Some(&mut self.screens[index])
-        } else {
-            None
+        match self.overlay {
+            Some(ref mut overlay) if index == self.overlay_index => Some(overlay),
+            _ => self
+                .screens
+                .iter_mut()
+                .find(|screen| screen.file.index() == index),
         }
     }
 }
 
 /// Start displaying files.
+///
+/// Returns the lines that should be printed to the terminal after the
+/// display has been closed.
 pub(crate) fn start(
-    mut term: impl Terminal,
+    term: &mut dyn Terminal,
     term_caps: TermCapabilities,
     mut events: EventStream,
     files: Vec<File>,
     error_files: VecMap<File>,
-    progress: Option<Progress>,
-    config: Config,
-) -> Result<(), Error> {
+    progress: ProgressStreams,
+    mut config: Config,
+    handle: Option<PagerHandle>,
+    direct_format: Option<&DirectFormat>,
+    resume: Option<Resume>,
+) -> Result<Vec<Vec<u8>>, Error> {
     let outcome = {
-        // Only take the first output and error. This emulates the behavior that
-        // the main pager can only display one stream at a time.
+        // Only take the first output and its error. This emulates the behavior
+        // that the main pager can only display one stream at a time.
         let output_files = &files[0..1.min(files.len())];
-        let error_files = match error_files.iter().next() {
+        let error_files = match output_files.first() {
+            Some(file) => error_files.get(file.index()).cloned().into_iter().collect(),
             None => Vec::new(),
-            Some((_i, file)) => vec![file.clone()],
         };
+        let progress = output_files
+            .first()
+            .and_then(|file| progress.get(file.index()));
         direct::direct(
-            &mut term,
+            term,
             output_files,
             &error_files[..],
-            progress.as_ref(),
+            progress,
             &mut events,
             config.interface_mode,
             config.startup_poll_input,
+            direct_format,
         )?
     };
+    let mut alternate_screen = false;
     match outcome {
-        direct::Outcome::RenderComplete | direct::Outcome::Interrupted => return Ok(()),
+        direct::Outcome::RenderComplete | direct::Outcome::Interrupted => return Ok(Vec::new()),
         direct::Outcome::RenderIncomplete(rows) => {
             // Push the rendered output up to the top of the screen, so that
             // when we start rendering full screen we don't overwrite output
@@ -203,17 +1059,63 @@ pub(crate) fn start(
                     .map_err(Error::Termwiz)?;
             }
         }
-        direct::Outcome::RenderNothing => term.enter_alternate_screen().map_err(Error::Termwiz)?,
+        direct::Outcome::RenderNothing
+            if config.interface_mode == InterfaceMode::FullScreenNoAlternate =>
+        {
+            // Push the current contents of the terminal into the scrollback
+            // so that the pager doesn't overwrite them.
+            let size = term.get_screen_size().map_err(Error::Termwiz)?;
+            term.render(&[Change::Text("\n".repeat(size.rows))])
+                .map_err(Error::Termwiz)?;
+        }
+        direct::Outcome::RenderNothing => {
+            term.enter_alternate_screen().map_err(Error::Termwiz)?;
+            alternate_screen = true;
+        }
     }
 
     let overlay_height = AtomicUsize::new(0);
-    let mut term = guard(term, |mut term| {
+    let mouse = config.mouse;
+    let clear_on_quit = config.interface_mode == InterfaceMode::FullScreenNoAlternate
+        && !config.keep_screen_on_quit;
+    if mouse {
+        term.render(&[mouse_reporting(true)])
+            .map_err(Error::Termwiz)?;
+    }
+    let set_terminal_title = config.set_terminal_title;
+    if set_terminal_title {
+        term.render(&[save_window_title(true)])
+            .map_err(Error::Termwiz)?;
+    }
+    let mut term = guard(term, |term| {
         // Clean up when exiting.  Most of this should be achieved by exiting
         // the alternate screen, but just in case it isn't, move to the
         // bottom of the screen and reset all attributes.
         let size = term.get_screen_size().unwrap();
         let overlay_height = overlay_height.load(Ordering::SeqCst);
         let scroll_count = 1usize.saturating_sub(overlay_height);
+        if mouse {
+            term.render(&[mouse_reporting(false)]).unwrap();
+        }
+        if set_terminal_title {
+            term.render(&[save_window_title(false)]).unwrap();
+        }
+        if clear_on_quit {
+            // There is no alternate screen to leave, so clear the pager's
+            // screen ourselves.  Only the terminal's previous contents are
+            // left in the scrollback.
+            term.render(&[
+                Change::CursorVisibility(CursorVisibility::Visible),
+                Change::AllAttributes(CellAttributes::default()),
+                Change::CursorPosition {
+                    x: Position::Absolute(0),
+                    y: Position::Absolute(0),
+                },
+                Change::ClearToEndOfScreen(ColorAttribute::default()),
+            ])
+            .unwrap();
+            return;
+        }
         term.render(&[
             Change::CursorVisibility(CursorVisibility::Visible),
             Change::AllAttributes(CellAttributes::default()),
@@ -230,27 +1132,116 @@ pub(crate) fn start(
         ])
         .unwrap();
     });
-    let config = Arc::new(config);
-    let caps = Capabilities::new(term_caps);
+    if !term_caps.hyperlinks() {
+        config.linkify_file_paths = false;
+    }
+    let keymap_error = check_keymap(&mut config);
+    let mut config = Arc::new(config);
+    let mut caps = Capabilities::new(term_caps);
+    if config.accessibility_mode || config.interface_mode == InterfaceMode::FullScreenNoAlternate {
+        // Scrolling regions move text around without rewriting it, which
+        // screen readers can't follow.  Without the alternate screen, some
+        // terminals also copy lines scrolled this way into the scrollback.
+        caps.scroll_up = false;
+        caps.scroll_down = false;
+    }
     let mut screens = Screens::new(files, error_files, progress, config.clone())?;
     let event_sender = events.sender();
     let render_unique = UniqueInstance::new();
     let refresh_unique = UniqueInstance::new();
+    let mut interrupted = None;
+    let mut render_limiter = RenderLimiter::new(MIN_RENDER_INTERVAL);
+    let mut animated = Instant::now();
+    let mut terminal_title = None;
+    if let Some(resume) = resume {
+        screens.restore(&resume, &event_sender);
+    }
+    let startup_actions = startup_actions(&mut screens, &config);
     {
         let screen = screens.current();
         let size = term.get_screen_size().map_err(Error::Termwiz)?;
         screen.resize(size.cols, size.rows);
         screen.maybe_load_more();
-        term.render(&screen.render(&caps)).map_err(Error::Termwiz)?;
+        render_screen(&mut **term, screen, &caps, &mut interrupted)?;
+    }
+    for action in startup_actions {
+        event_sender.send(Event::Action(action))?;
+    }
+    if let Some(report) = keymap_error {
+        show_overlay(
+            &mut **term,
+            &mut screens,
+            "KEYMAP ERROR",
+            report.into_bytes(),
+            &event_sender,
+            &config,
+            &caps,
+            &mut interrupted,
+        )?;
     }
     loop {
-        // Listen for an event or input.  If we are animating, put a timeout on the wait.
+        // Keep the terminal's title up to date with the current file.
+        if set_terminal_title {
+            let title = screens.title();
+            if terminal_title.as_ref() != Some(&title) {
+                term.render(&[Change::Title(title.clone())])
+                    .map_err(Error::Termwiz)?;
+                terminal_title = Some(title);
+            }
+        }
+
+        // Perform a deferred render once it is due.
+        let now = Instant::now();
+        if render_limiter.due(now) {
+            render_limiter.rendered(now);
+            render_screen(&mut **term, screens.current(), &caps, &mut interrupted)?;
+        }
+
+        // Listen for an event or input.  If we are animating, or a render
+        // has been deferred, put a timeout on the wait.
         let timeout = if screens.current().animate() {
-            Some(Duration::from_millis(100))
+            Some(
+                config
+                    .animation()
+                    .interval()
+                    .saturating_sub(now.duration_since(animated)),
+            )
         } else {
             None
         };
-        let event = events.get(&mut *term, timeout)?;
+        let timeout = [timeout, render_limiter.wait(now)]
+            .iter()
+            .flatten()
+            .min()
+            .copied();
+
+        // Report the state of the current file to the handle, if there is
+        // one, before waiting.
+        if let Some(handle) = handle.as_ref() {
+            let busy = screens.current().busy()
+                || render_limiter.wait(now).is_some()
+                || interrupted.is_some();
+            let screen = &screens.screens[screens.current_index];
+            handle.update(
+                screen.file.index(),
+                screen.top_line(),
+                (screen.width(), screen.height()),
+                screen.search_state(),
+                busy,
+            );
+        }
+
+        let event = match interrupted.take() {
+            Some(input) => {
+                // Finish the abandoned render after handling the input.
+                event_sender.send_unique(Event::Render, &render_unique)?;
+                Some(Event::Input(input))
+            }
+            None => events.get(&mut **term, timeout)?,
+        };
+        trace_event!(event = ?event, "event");
+
+        let key_pressed = matches!(event, Some(Event::Input(InputEvent::Key(_))));
 
         // Dispatch the event and receive an action to take.
         let mut action = {
@@ -258,57 +1249,165 @@ pub(crate) fn start(
             screen.maybe_load_more();
 
             match event {
-                None => screen.dispatch_animation(),
+                // Only step the animation once its interval has passed, not
+                // when waking up for a deferred render or resize check.
+                None if animated.elapsed() >= config.animation().interval() => {
+                    animated = Instant::now();
+                    screen.dispatch_animation()
+                }
+                None => DisplayAction::None,
                 Some(Event::Render) => {
-                    term.render(&screen.render(&caps)).map_err(Error::Termwiz)?;
+                    render_screen_limited(
+                        &mut **term,
+                        screen,
+                        &caps,
+                        &mut interrupted,
+                        &mut render_limiter,
+                    )?;
                     DisplayAction::None
                 }
                 Some(Event::Input(InputEvent::Resized { .. })) => {
                     let size = term.get_screen_size().map_err(Error::Termwiz)?;
                     screen.resize(size.cols, size.rows);
-                    term.render(&screen.render(&caps)).map_err(Error::Termwiz)?;
+                    render_screen(&mut **term, screen, &caps, &mut interrupted)?;
                     DisplayAction::None
                 }
                 Some(Event::Refresh) => {
                     let size = term.get_screen_size().map_err(Error::Termwiz)?;
                     screen.resize(size.cols, size.rows);
                     screen.refresh();
-                    term.render(&screen.render(&caps)).map_err(Error::Termwiz)?;
+                    render_screen_limited(
+                        &mut **term,
+                        screen,
+                        &caps,
+                        &mut interrupted,
+                        &mut render_limiter,
+                    )?;
                     DisplayAction::None
                 }
+                Some(Event::RefreshOverlay) => {
+                    // The information about a file has changed.
+                    screen.refresh_ruler();
+                    DisplayAction::Render
+                }
                 Some(Event::Progress) => {
                     screen.refresh_progress();
-                    term.render(&screen.render(&caps)).map_err(Error::Termwiz)?;
+                    render_screen_limited(
+                        &mut **term,
+                        screen,
+                        &caps,
+                        &mut interrupted,
+                        &mut render_limiter,
+                    )?;
+                    DisplayAction::None
+                }
+                Some(Event::InputFailed(error)) => {
+                    show_overlay(
+                        &mut **term,
+                        &mut screens,
+                        "NO KEYBOARD INPUT",
+                        input_failed_report(&error).into_bytes(),
+                        &event_sender,
+                        &config,
+                        &caps,
+                        &mut interrupted,
+                    )?;
                     DisplayAction::None
                 }
                 Some(Event::Action(action)) => screen.dispatch_action(action, &event_sender),
+                Some(Event::Connection(connection)) => {
+                    let title = connection.title;
+                    let stream = connection.stream;
+                    let sender = event_sender.clone();
+                    screens.add(config.clone(), |index| {
+                        Ok(LoadedFile::new_streamed(index, stream, &title, sender))
+                    })?;
+                    let screen = screens.current();
+                    screen.show_message(format!("New file from {}", title), Severity::Info);
+                    DisplayAction::Render
+                }
+                Some(Event::LinesRequest(request)) => {
+                    let index = request.file_index();
+                    request.respond(screens.get(index).map(|screen| &screen.file));
+                    DisplayAction::None
+                }
+                Some(Event::Input(InputEvent::Mouse(event))) if mouse => {
+                    if event.mouse_buttons.contains(MouseButtons::VERT_WHEEL) {
+                        let action = if event.mouse_buttons.contains(MouseButtons::WHEEL_POSITIVE) {
+                            Action::ScrollUpLines(3)
+                        } else {
+                            Action::ScrollDownLines(3)
+                        };
+                        screen.dispatch_action(action, &event_sender)
+                    } else {
+                        DisplayAction::None
+                    }
+                }
                 Some(Event::Input(InputEvent::Key(key))) => {
                     let width = screen.width();
+                    let last_values = screen.last_values().clone();
                     if let Some(prompt) = screen.prompt() {
-                        prompt.dispatch_key(key, width)
+                        prompt.dispatch_key(key, width, &last_values)
                     } else {
                         screen.dispatch_key(key, &event_sender)
                     }
                 }
                 Some(Event::Input(InputEvent::Paste(ref text))) => {
                     let width = screen.width();
-                    screen
-                        .prompt()
-                        .get_or_insert_with(|| {
+                    let (line, truncated) = prompt::paste_first_line(text);
+                    if truncated {
+                        screen.set_error(String::from(
+                            "Pasted text has multiple lines, only the first line was used",
+                        ));
+                    }
+                    match screen.prompt() {
+                        Some(prompt) => prompt.paste(line, width),
+                        None => {
                             // Assume the user wanted to search for what they're pasting.
-                            command::search(SearchKind::First, event_sender.clone())
-                        })
-                        .paste(text, width)
+                            let pattern = if config.literal_paste_search {
+                                regex::escape(line)
+                            } else {
+                                line.to_string()
+                            };
+                            let mut prompt =
+                                command::search(SearchKind::First, event_sender.clone());
+                            prompt.paste(&pattern, width);
+                            *screen.prompt() = Some(prompt);
+                            DisplayAction::Render
+                        }
+                    }
                 }
-                Some(Event::Loaded(index)) if screens.is_current_index(index) => {
-                    DisplayAction::Refresh
+                Some(Event::Loaded(index)) if screen.file.index() == index => {
+                    screen.refresh_loaded();
+                    DisplayAction::Render
+                }
+                Some(Event::Appending(index)) => {
+                    if let Some(screen) = screens.get(index) {
+                        screen.file_changed();
+                    }
+                    if screens.is_current_index(index) {
+                        DisplayAction::Refresh
+                    } else {
+                        DisplayAction::None
+                    }
                 }
-                Some(Event::Appending(index)) if screens.is_current_index(index) => {
-                    DisplayAction::Refresh
+                Some(Event::Appended(index)) => {
+                    // Show the new data without waiting for the animation,
+                    // which may be turned off.
+                    if screens.is_current_index(index) {
+                        screens.current().refresh_ruler();
+                        DisplayAction::Render
+                    } else if screens.current().error_file().map(File::index) == Some(index) {
+                        screens.current().refresh_overlay();
+                        DisplayAction::Render
+                    } else {
+                        DisplayAction::None
+                    }
                 }
                 Some(Event::Reloading(index)) => {
                     if let Some(screen) = screens.get(index) {
                         screen.flush_line_caches();
+                        screen.file_changed();
                     }
                     if screens.is_current_index(index) {
                         DisplayAction::Refresh
@@ -316,6 +1415,18 @@ pub(crate) fn start(
                         DisplayAction::None
                     }
                 }
+                Some(Event::CommandExited(index)) => DisplayAction::RunChainedCommand(index),
+                Some(Event::FileError(index)) => {
+                    if let Some(screen) = screens.get(index) {
+                        screen.check_file_errors();
+                        screen.refresh_ruler();
+                    }
+                    if screens.is_current_index(index) {
+                        DisplayAction::Render
+                    } else {
+                        DisplayAction::None
+                    }
+                }
                 Some(Event::SearchFirstMatch(index)) => {
                     if let Some(screen) = screens.get(index) {
                         screen.search_first_match()
@@ -334,6 +1445,19 @@ pub(crate) fn start(
             }
         };
 
+        // Any key ends a mouse selection and captures the mouse again.  The
+        // key is still handled as usual, unless it is the one that toggles
+        // the selection, which will end it anyway.
+        if screens.mouse_selection
+            && key_pressed
+            && !matches!(action, DisplayAction::ToggleMouseSelection)
+        {
+            screens.set_mouse_selection(false);
+            term.render(&[mouse_reporting(true)])
+                .map_err(Error::Termwiz)?;
+            event_sender.send_unique(Event::Render, &render_unique)?;
+        }
+
         // Process the action.  We may get new actions in return from the action.
         loop {
             match std::mem::replace(&mut action, DisplayAction::None) {
@@ -352,60 +1476,395 @@ pub(crate) fn start(
                 }
                 DisplayAction::NextFile => {
                     screens.overlay = None;
+                    screens.json_view = false;
                     if screens.current_index < screens.screens.len() - 1 {
                         screens.current_index += 1;
                         let screen = screens.current();
                         let size = term.get_screen_size().map_err(Error::Termwiz)?;
                         screen.resize(size.cols, size.rows);
                         screen.refresh();
-                        term.render(&screen.render(&caps)).map_err(Error::Termwiz)?;
+                        render_screen(&mut **term, screen, &caps, &mut interrupted)?;
                     }
                 }
                 DisplayAction::PreviousFile => {
                     screens.overlay = None;
+                    screens.json_view = false;
                     if screens.current_index > 0 {
                         screens.current_index -= 1;
                         let screen = screens.current();
                         let size = term.get_screen_size().map_err(Error::Termwiz)?;
                         screen.resize(size.cols, size.rows);
                         screen.refresh();
-                        term.render(&screen.render(&caps)).map_err(Error::Termwiz)?;
+                        render_screen(&mut **term, screen, &caps, &mut interrupted)?;
                     }
                 }
+                DisplayAction::OpenErrorFile => match screens.open_error_file(config.clone()) {
+                    Ok(true) => {
+                        let screen = screens.current();
+                        let size = term.get_screen_size().map_err(Error::Termwiz)?;
+                        screen.resize(size.cols, size.rows);
+                        screen.refresh();
+                        render_screen(&mut **term, screen, &caps, &mut interrupted)?;
+                    }
+                    Ok(false) => {
+                        screens
+                            .current()
+                            .show_message("No error stream for this file", Severity::Info);
+                        event_sender.send_unique(Event::Render, &render_unique)?;
+                    }
+                    Err(error) => {
+                        screens.current().set_error(error.to_string());
+                        event_sender.send_unique(Event::Render, &render_unique)?;
+                    }
+                },
                 DisplayAction::ShowHelp => {
-                    let overlay_index = screens.overlay_index + 1;
+                    let help = help_text(screens.current().keymap())?;
+                    show_overlay(
+                        &mut **term,
+                        &mut screens,
+                        "HELP",
+                        help.into_bytes(),
+                        &event_sender,
+                        &config,
+                        &caps,
+                        &mut interrupted,
+                    )?;
+                }
+                DisplayAction::ReloadConfig => {
+                    let mut keymap_error = None;
+                    let reloaded = config.reloaded().and_then(|mut new_config| {
+                        keymap_error = check_keymap(&mut new_config);
+                        let new_config = Arc::new(new_config);
+                        config = new_config.clone();
+                        screens.set_config(new_config).map_err(|e| e.to_string())
+                    });
+                    let screen = screens.current();
+                    match reloaded {
+                        Ok(()) if keymap_error.is_some() => screen.show_message(
+                            "Reloaded the configuration, but the keymap has errors",
+                            Severity::Warning,
+                        ),
+                        Ok(()) => screen.show_message("Reloaded the configuration", Severity::Info),
+                        Err(message) => screen.set_error(message),
+                    }
+                    if let Some(report) = keymap_error {
+                        show_overlay(
+                            &mut **term,
+                            &mut screens,
+                            "KEYMAP ERROR",
+                            report.into_bytes(),
+                            &event_sender,
+                            &config,
+                            &caps,
+                            &mut interrupted,
+                        )?;
+                    }
+                    event_sender.send_unique(Event::Refresh, &refresh_unique)?;
+                }
+                DisplayAction::ShowErrorLog => {
                     let screen = screens.current();
-                    let mut screen = Screen::new(
-                        LoadedFile::new_static(
-                            overlay_index,
-                            "HELP",
-                            help_text(screen.keymap())?.into_bytes(),
-                            event_sender.clone(),
-                        )
-                        .into(),
-                        config.clone(),
+                    match screen.error_log() {
+                        Some(log) => {
+                            let title = format!("{} (errors)", screen.file.title());
+                            show_overlay(
+                                &mut **term,
+                                &mut screens,
+                                &title,
+                                log.into_bytes(),
+                                &event_sender,
+                                &config,
+                                &caps,
+                                &mut interrupted,
+                            )?;
+                        }
+                        None => {
+                            screen.show_message("No errors", Severity::Info);
+                            event_sender.send_unique(Event::Render, &render_unique)?;
+                        }
+                    }
+                }
+                DisplayAction::ShowCacheStats => {
+                    let screen = screens.current();
+                    let title = format!("{} (cache statistics)", screen.file.title());
+                    let stats = screen.cache_stats();
+                    show_overlay(
+                        &mut **term,
+                        &mut screens,
+                        &title,
+                        stats.into_bytes(),
+                        &event_sender,
+                        &config,
+                        &caps,
+                        &mut interrupted,
                     )?;
+                }
+                DisplayAction::ShowProgressHistory => {
+                    let screen = screens.current();
+                    match screen.progress_history() {
+                        Some(history) => {
+                            let title = format!("{} (progress)", screen.file.title());
+                            show_overlay(
+                                &mut **term,
+                                &mut screens,
+                                &title,
+                                history,
+                                &event_sender,
+                                &config,
+                                &caps,
+                                &mut interrupted,
+                            )?;
+                        }
+                        None => {
+                            screen.show_message("No progress history", Severity::Info);
+                            event_sender.send_unique(Event::Render, &render_unique)?;
+                        }
+                    }
+                }
+                DisplayAction::ShowDiagnostics => {
+                    let text = diagnostics(&screens, &caps, &events, &config);
+                    show_overlay(
+                        &mut **term,
+                        &mut screens,
+                        "DIAGNOSTICS",
+                        text.into_bytes(),
+                        &event_sender,
+                        &config,
+                        &caps,
+                        &mut interrupted,
+                    )?;
+                }
+                DisplayAction::ToggleJsonView if screens.json_view => {
+                    action = DisplayAction::ClearOverlay;
+                }
+                DisplayAction::ToggleJsonView => {
+                    let screen = screens.current();
+                    match screen.pretty_printed_json() {
+                        Ok(data) => {
+                            let title = format!("{} (JSON)", screen.file.title());
+                            show_overlay(
+                                &mut **term,
+                                &mut screens,
+                                &title,
+                                data,
+                                &event_sender,
+                                &config,
+                                &caps,
+                                &mut interrupted,
+                            )?;
+                            screens.json_view = true;
+                        }
+                        Err(message) => {
+                            screen.set_error(message);
+                            event_sender.send_unique(Event::Render, &render_unique)?;
+                        }
+                    }
+                }
+                DisplayAction::RerunCommand(mode) => {
+                    match screens.rerun_command(mode, config.clone(), event_sender.clone()) {
+                        Ok(true) => {
+                            let screen = screens.current();
+                            let size = term.get_screen_size().map_err(Error::Termwiz)?;
+                            screen.resize(size.cols, size.rows);
+                            screen.refresh();
+                            render_screen(&mut **term, screen, &caps, &mut interrupted)?;
+                        }
+                        Ok(false) => {
+                            screens.current().show_message(
+                                "This file isn't the output of a command",
+                                Severity::Info,
+                            );
+                            event_sender.send_unique(Event::Render, &render_unique)?;
+                        }
+                        Err(error) => {
+                            screens.current().set_error(error.to_string());
+                            event_sender.send_unique(Event::Render, &render_unique)?;
+                        }
+                    }
+                }
+                DisplayAction::RunChainedCommand(index) => {
+                    match screens.run_chained_command(index, config.clone(), event_sender.clone()) {
+                        Ok(true) => {
+                            let screen = screens.current();
+                            let size = term.get_screen_size().map_err(Error::Termwiz)?;
+                            screen.resize(size.cols, size.rows);
+                            screen.refresh();
+                            render_screen(&mut **term, screen, &caps, &mut interrupted)?;
+                        }
+                        Ok(false) => {
+                            screens.current().refresh_ruler();
+                            event_sender.send_unique(Event::Render, &render_unique)?;
+                        }
+                        Err(error) => {
+                            screens.current().set_error(error.to_string());
+                            event_sender.send_unique(Event::Render, &render_unique)?;
+                        }
+                    }
+                }
+                DisplayAction::SnapshotFile => {
+                    match screens.snapshot(config.clone(), event_sender.clone()) {
+                        Ok(()) => {
+                            let screen = screens.current();
+                            let size = term.get_screen_size().map_err(Error::Termwiz)?;
+                            screen.resize(size.cols, size.rows);
+                            screen.refresh();
+                            render_screen(&mut **term, screen, &caps, &mut interrupted)?;
+                        }
+                        Err(error) => {
+                            screens.current().set_error(error.to_string());
+                            event_sender.send_unique(Event::Render, &render_unique)?;
+                        }
+                    }
+                }
+                DisplayAction::DiffSnapshot => {
+                    let diff = screens.snapshot_files().map(|(snapshot, live)| {
+                        let title = format!("{} (changes)", live.title());
+                        let diff = diff::unified_diff(
+                            &diff::file_lines(&snapshot),
+                            &diff::file_lines(&live),
+                            DIFF_CONTEXT,
+                        );
+                        (title, diff)
+                    });
+                    match diff {
+                        Some((title, Some(diff))) => {
+                            show_overlay(
+                                &mut **term,
+                                &mut screens,
+                                &title,
+                                diff,
+                                &event_sender,
+                                &config,
+                                &caps,
+                                &mut interrupted,
+                            )?;
+                        }
+                        Some((_, None)) => {
+                            screens
+                                .current()
+                                .show_message("No changes since the snapshot", Severity::Info);
+                            event_sender.send_unique(Event::Render, &render_unique)?;
+                        }
+                        None => {
+                            screens
+                                .current()
+                                .show_message("No snapshot of this file", Severity::Info);
+                            event_sender.send_unique(Event::Render, &render_unique)?;
+                        }
+                    }
+                }
+                DisplayAction::RunShellCommand(command) => {
+                    let message =
+                        run_shell_command(&mut **term, &command, alternate_screen, mouse)?;
+                    screens.set_mouse_selection(false);
+                    match message {
+                        Some(message) => screens.current().set_error(message),
+                        None => screens.current().clear_error(),
+                    }
+                    event_sender.send_unique(Event::Refresh, &refresh_unique)?;
+                }
+                DisplayAction::OpenFile(path) => {
+                    match screens.open(path, config.clone(), event_sender.clone()) {
+                        Ok(()) => {
+                            let screen = screens.current();
+                            let size = term.get_screen_size().map_err(Error::Termwiz)?;
+                            screen.resize(size.cols, size.rows);
+                            screen.refresh();
+                            render_screen(&mut **term, screen, &caps, &mut interrupted)?;
+                        }
+                        Err(error) => {
+                            screens.current().set_error(error.to_string());
+                            event_sender.send_unique(Event::Render, &render_unique)?;
+                        }
+                    }
+                }
+                DisplayAction::ToggleMouseSelection => {
+                    if mouse {
+                        let selecting = !screens.mouse_selection;
+                        screens.set_mouse_selection(selecting);
+                        term.render(&[mouse_reporting(!selecting)])
+                            .map_err(Error::Termwiz)?;
+                        event_sender.send_unique(Event::Render, &render_unique)?;
+                    } else {
+                        screens
+                            .current()
+                            .set_error(String::from("Mouse mode is not enabled"));
+                        event_sender.send_unique(Event::Render, &render_unique)?;
+                    }
+                }
+                DisplayAction::ClearOverlay => {
+                    screens.overlay = None;
+                    screens.json_view = false;
+                    let screen = screens.current();
                     let size = term.get_screen_size().map_err(Error::Termwiz)?;
                     screen.resize(size.cols, size.rows);
                     screen.refresh();
-                    term.render(&screen.render(&caps)).map_err(Error::Termwiz)?;
-                    screens.overlay = Some(screen);
-                    screens.overlay_index = overlay_index;
+                    render_screen(&mut **term, screen, &caps, &mut interrupted)?;
                 }
-                DisplayAction::ClearOverlay => {
-                    screens.overlay = None;
+                DisplayAction::CloseFile if screens.screens.len() > 1 => {
+                    screens.close_current();
                     let screen = screens.current();
                     let size = term.get_screen_size().map_err(Error::Termwiz)?;
                     screen.resize(size.cols, size.rows);
                     screen.refresh();
-                    term.render(&screen.render(&caps)).map_err(Error::Termwiz)?;
+                    render_screen(&mut **term, screen, &caps, &mut interrupted)?;
+                }
+                DisplayAction::CloseFile => {
+                    action = DisplayAction::Quit;
+                }
+                DisplayAction::QuitAndKillChild => {
+                    for screen in screens.screens.iter() {
+                        screen.file.kill_command();
+                    }
+                    action = DisplayAction::Quit;
+                }
+                DisplayAction::Quit if config.print_screen_on_quit => {
+                    action = DisplayAction::QuitAndPrintScreen;
                 }
                 DisplayAction::Quit => {
+                    if config.save_session {
+                        screens.save_session();
+                    }
                     let screen = screens.current();
                     overlay_height.store(screen.overlay_height(), Ordering::SeqCst);
-                    return Ok(());
+                    return Ok(Vec::new());
+                }
+                DisplayAction::QuitAndPrintScreen => {
+                    if config.save_session {
+                        screens.save_session();
+                    }
+                    let screen = screens.current();
+                    overlay_height.store(screen.overlay_height(), Ordering::SeqCst);
+                    return Ok(screen.visible_lines());
                 }
             }
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_render_limiter() {
+        let interval = Duration::from_millis(30);
+        let mut limiter = RenderLimiter::new(interval);
+        let start = Instant::now();
+        assert!(limiter.request(start));
+        limiter.rendered(start);
+        assert_eq!(limiter.wait(start), None);
+
+        // Renders within the interval are deferred until the end of it.
+        let soon = start + Duration::from_millis(10);
+        assert!(!limiter.request(soon));
+        assert!(!limiter.request(soon));
+        assert_eq!(limiter.wait(soon), Some(Duration::from_millis(20)));
+        assert!(!limiter.due(soon));
+        assert!(limiter.due(start + interval));
+
+        let later = start + Duration::from_millis(40);
+        limiter.rendered(later);
+        assert!(!limiter.due(later + interval));
+        assert!(limiter.request(later + interval));
+    }
+}
@@ -0,0 +1,136 @@
+//! Character Encodings
+//!
+//! Lines are split at `\n` bytes and then converted to UTF-8 before they are
+//! parsed, so that files in other encodings are shown as text.
+//!
+//! For UTF-16, splitting at `\n` bytes means that a little-endian newline is
+//! split across two lines, and that characters whose encoding contains a
+//! `0x0A` byte also start a new line.  The offset of each line in the file is
+//! used to keep the decoding aligned to code units, so these only affect
+//! where lines are broken.
+
+use std::char::{decode_utf16, REPLACEMENT_CHARACTER};
+
+use crate::config::Encoding;
+
+/// The number of bytes at the start of a file used to detect its encoding.
+pub(crate) const DETECT_SIZE: usize = 4096;
+
+/// Detect the encoding of a file from a sample of its start.
+pub(crate) fn detect(sample: &[u8]) -> Encoding {
+    if sample.starts_with(b"\xEF\xBB\xBF") {
+        return Encoding::Utf8;
+    }
+    if sample.starts_with(b"\xFF\xFE") {
+        return Encoding::Utf16Le;
+    }
+    if sample.starts_with(b"\xFE\xFF") {
+        return Encoding::Utf16Be;
+    }
+
+    // UTF-16 text that is mostly ASCII has a zero in every other byte.
+    let units = sample.len() / 2;
+    let zeros = |skip| {
+        sample
+            .iter()
+            .skip(skip)
+            .step_by(2)
+            .filter(|byte| **byte == 0)
+            .count()
+    };
+    let (even_zeros, odd_zeros) = (zeros(0), zeros(1));
+    if units > 0 && odd_zeros * 2 > units && even_zeros * 8 < units {
+        return Encoding::Utf16Le;
+    }
+    if units > 0 && even_zeros * 2 > units && odd_zeros * 8 < units {
+        return Encoding::Utf16Be;
+    }
+
+    match std::str::from_utf8(sample) {
+        Ok(_) => Encoding::Utf8,
+        // The sample ends part way through a character.
+        Err(e) if e.error_len().is_none() => Encoding::Utf8,
+        // Binary data is shown as it would be for UTF-8.
+        Err(_) if sample.contains(&0) => Encoding::Utf8,
+        Err(_) => Encoding::Latin1,
+    }
+}
+
+/// Convert a line that starts at `offset` in the file to UTF-8.
+///
+/// Returns `None` if the line doesn't need converting.
+pub(crate) fn decode(encoding: Encoding, data: &[u8], offset: usize) -> Option<Vec<u8>> {
+    match encoding {
+        Encoding::Utf8 | Encoding::Auto => None,
+        Encoding::Latin1 if data.is_ascii() => None,
+        Encoding::Latin1 => Some(
+            data.iter()
+                .map(|byte| char::from(*byte))
+                .collect::<String>()
+                .into_bytes(),
+        ),
+        Encoding::Utf16Le => Some(decode_utf16_line(data, offset, u16::from_le_bytes)),
+        Encoding::Utf16Be => Some(decode_utf16_line(data, offset, u16::from_be_bytes)),
+    }
+}
+
+/// Convert a line of UTF-16 to UTF-8, using `unit` to read each code unit.
+fn decode_utf16_line(data: &[u8], offset: usize, unit: fn([u8; 2]) -> u16) -> Vec<u8> {
+    // A line that starts at an odd offset starts with the second byte of a
+    // code unit that was split by the previous line break.
+    let data = if offset % 2 == 1 { &data[1..] } else { data };
+    let units = data.chunks_exact(2).map(|pair| unit([pair[0], pair[1]]));
+    let mut text: String = decode_utf16(units)
+        .map(|c| c.unwrap_or(REPLACEMENT_CHARACTER))
+        .collect();
+    if offset == 0 && text.starts_with('\u{FEFF}') {
+        text.remove(0);
+    }
+    // The first byte of a little-endian newline ends the line.
+    if data.len() % 2 == 1 && data.last() == Some(&b'\n') {
+        text.push('\n');
+    }
+    text.into_bytes()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_detect() {
+        assert_eq!(detect(b"plain text\n"), Encoding::Utf8);
+        assert_eq!(detect("caf\u{e9}\n".as_bytes()), Encoding::Utf8);
+        assert_eq!(detect(b"caf\xE9\n"), Encoding::Latin1);
+        assert_eq!(detect(b"\xFF\xFEa\x00"), Encoding::Utf16Le);
+        assert_eq!(detect(b"a\x00b\x00c\x00\n\x00"), Encoding::Utf16Le);
+        assert_eq!(detect(b"\x00a\x00b\x00c\x00\n"), Encoding::Utf16Be);
+        assert_eq!(
+            detect(b"\x7FELF\x02\x01\x01\x00\x00\x00\xB0"),
+            Encoding::Utf8
+        );
+    }
+
+    #[test]
+    fn test_decode() {
+        assert_eq!(decode(Encoding::Utf8, b"caf\xE9\n", 0), None);
+        assert_eq!(decode(Encoding::Latin1, b"cafe\n", 0), None);
+        assert_eq!(
+            decode(Encoding::Latin1, b"caf\xE9\n", 0).unwrap(),
+            "caf\u{e9}\n".as_bytes()
+        );
+
+        // "\u{FEFF}ab\n\u{e9}\n" split into lines at `\n` bytes.
+        let data = b"\xFF\xFEa\x00b\x00\n\x00\xE9\x00\n\x00";
+        assert_eq!(decode(Encoding::Utf16Le, &data[..7], 0).unwrap(), b"ab\n");
+        assert_eq!(
+            decode(Encoding::Utf16Le, &data[7..11], 7).unwrap(),
+            "\u{e9}\n".as_bytes()
+        );
+        assert_eq!(decode(Encoding::Utf16Le, &data[11..], 11).unwrap(), b"");
+
+        let data = b"\x00a\x00\n\x00b";
+        assert_eq!(decode(Encoding::Utf16Be, &data[..4], 0).unwrap(), b"a\n");
+        assert_eq!(decode(Encoding::Utf16Be, &data[4..], 4).unwrap(), b"b");
+    }
+}
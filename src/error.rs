@@ -20,6 +20,18 @@ pub enum Error {
     #[error("regex error")]
     Regex(#[from] regex::Error),
 
+    /// A color name that isn't recognized.
+    #[error("invalid color '{0}'")]
+    InvalidColor(String),
+
+    /// A file index that doesn't refer to a file that has been added.
+    #[error("no file with index {0}")]
+    InvalidFileIndex(usize),
+
+    /// A file index that doesn't refer to the output of a command.
+    #[error("file {0} is not the output of a command")]
+    NotACommand(usize),
+
     /// Generic I/O error.
     #[error("i/o error")]
     Io(#[from] std::io::Error),
@@ -52,10 +64,22 @@ pub enum Error {
     #[error("channel error")]
     ChannelSend,
 
+    /// A saved session that can't be read or written.
+    #[error("invalid session: {0}")]
+    Session(String),
+
+    /// Returned when resuming a session if there isn't one to resume.
+    #[error("no session to resume")]
+    NoSession,
+
     /// Error returned if the terminfo database is missing.
-    #[error("terminfo database not found (is $TERM correct?)")]
+    #[error("terminfo database not found (is $TERM correct? use --assume-ansi or SP_ASSUME_ANSI=1 to continue without it)")]
     TerminfoDatabaseMissing,
 
+    /// Error returned if there is no terminal to read keyboard input from.
+    #[error("no terminal available for keyboard input ({0})")]
+    NoTerminal(String),
+
     /// Wrapped error within the context of a command.
     #[error("error running command '{command}'")]
     WithCommand {
@@ -77,6 +101,17 @@ pub enum Error {
         /// File the error is about.
         file: String,
     },
+
+    /// Wrapped error within the context of an address being listened on.
+    #[error("error listening on '{address}'")]
+    WithAddress {
+        /// Wrapped error.
+        #[source]
+        error: Box<Self>,
+
+        /// Address the error is about.
+        address: String,
+    },
 }
 
 impl Error {
@@ -87,6 +122,13 @@ impl Error {
         }
     }
 
+    pub(crate) fn with_address(self, address: impl AsRef<str>) -> Self {
+        Self::WithAddress {
+            error: Box::new(self),
+            address: address.as_ref().to_owned(),
+        }
+    }
+
     pub(crate) fn with_command(self, command: impl AsRef<OsStr>) -> Self {
         Self::WithCommand {
             error: Box::new(self),
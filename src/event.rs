@@ -1,6 +1,6 @@
 //! Events.
 
-use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 use std::sync::mpsc;
 use std::sync::Arc;
 use std::time::Duration;
@@ -8,26 +8,37 @@ use std::time::Duration;
 use termwiz::input::InputEvent;
 use termwiz::terminal::{Terminal, TerminalWaker};
 
-use crate::action::{Action, ActionSender};
+use crate::action::{Action, ActionSender, LinesRequest};
 use crate::error::Error;
 use crate::file::FileIndex;
+use crate::listener::Connection;
 
 /// An event.
 ///
 /// Events drive most of the main processing of `sp`.  This includes user
 /// input, state changes, and display refresh requests.
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug)]
 pub(crate) enum Event {
     /// An action.
     Action(Action),
+    /// A request for the contents of lines of a file.
+    LinesRequest(LinesRequest),
     /// An input event.
     Input(InputEvent),
+    /// A connection has been accepted by a listener.
+    Connection(Connection),
     /// A file has finished loading.
     Loaded(FileIndex),
     /// A file has started loading more data.
     Appending(FileIndex),
+    /// More of a stream has been loaded.
+    Appended(FileIndex),
     /// A file has started reloading.
     Reloading(FileIndex),
+    /// A file has encountered an error while loading.
+    FileError(FileIndex),
+    /// The command that a file is the output of has exited.
+    CommandExited(FileIndex),
     /// Render an update to the screen.
     Render,
     /// Refresh the whole screen.
@@ -40,6 +51,8 @@ pub(crate) enum Event {
     SearchFirstMatch(FileIndex),
     /// Search has finished.
     SearchFinished(FileIndex),
+    /// Keyboard input can no longer be read from the terminal.
+    InputFailed(String),
 }
 
 #[derive(Debug, Clone)]
@@ -56,15 +69,42 @@ pub(crate) enum Envelope {
     Unique(Event, UniqueInstance),
 }
 
-/// An event sender endpoint.
+/// Wakes the display when an event is sent from another thread.
 #[derive(Clone)]
-pub(crate) struct EventSender(mpsc::Sender<Envelope>, TerminalWaker);
+pub(crate) enum Waker {
+    /// Wake a terminal that is waiting for input.
+    Terminal(TerminalWaker),
+
+    /// Call a function, for terminals that are woken some other way.
+    #[cfg_attr(not(any(test, feature = "test-harness")), allow(unused))]
+    Func(Arc<dyn Fn() + Send + Sync>),
+}
+
+impl Waker {
+    fn wake(&self) -> Result<(), Error> {
+        match self {
+            Waker::Terminal(waker) => waker.wake()?,
+            Waker::Func(wake) => wake(),
+        }
+        Ok(())
+    }
+}
+
+impl From<TerminalWaker> for Waker {
+    fn from(waker: TerminalWaker) -> Waker {
+        Waker::Terminal(waker)
+    }
+}
+
+/// An event sender endpoint.  The last field counts the events that are
+/// queued.
+#[derive(Clone)]
+pub(crate) struct EventSender(mpsc::Sender<Envelope>, Option<Waker>, Arc<AtomicUsize>);
 
 impl EventSender {
     pub(crate) fn send(&self, event: Event) -> Result<(), Error> {
-        self.0.send(Envelope::Normal(event))?;
-        self.1.wake()?;
-        Ok(())
+        self.enqueue(Envelope::Normal(event))?;
+        self.wake()
     }
     pub(crate) fn send_unique(&self, event: Event, unique: &UniqueInstance) -> Result<(), Error> {
         if unique
@@ -72,8 +112,24 @@ impl EventSender {
             .compare_exchange(false, true, Ordering::SeqCst, Ordering::SeqCst)
             .is_ok()
         {
-            self.0.send(Envelope::Unique(event, unique.clone()))?;
-            self.1.wake()?;
+            self.enqueue(Envelope::Unique(event, unique.clone()))?;
+            self.wake()?;
+        }
+        Ok(())
+    }
+    fn enqueue(&self, envelope: Envelope) -> Result<(), Error> {
+        // Count the event before sending it, so that the count never goes
+        // below zero when the receiver takes it straight away.
+        self.2.fetch_add(1, Ordering::SeqCst);
+        if let Err(e) = self.0.send(envelope) {
+            self.2.fetch_sub(1, Ordering::SeqCst);
+            return Err(e.into());
+        }
+        Ok(())
+    }
+    fn wake(&self) -> Result<(), Error> {
+        if let Some(waker) = &self.1 {
+            waker.wake()?;
         }
         Ok(())
     }
@@ -84,19 +140,43 @@ impl EventSender {
 pub(crate) struct EventStream {
     send: mpsc::Sender<Envelope>,
     recv: mpsc::Receiver<Envelope>,
-    waker: TerminalWaker,
+    waker: Option<Waker>,
+    queued: Arc<AtomicUsize>,
+    input_failed: bool,
 }
 
 impl EventStream {
     /// Create a new event stream.
-    pub(crate) fn new(waker: TerminalWaker) -> EventStream {
+    ///
+    /// If there is no waker, the terminal must not block indefinitely when
+    /// polling for input, as it will not be woken when events arrive.
+    pub(crate) fn new(waker: Option<Waker>) -> EventStream {
         let (send, recv) = mpsc::channel();
-        EventStream { send, recv, waker }
+        let queued = Arc::new(AtomicUsize::new(0));
+        EventStream {
+            send,
+            recv,
+            waker,
+            queued,
+            input_failed: false,
+        }
+    }
+
+    /// Stop reading input from the terminal, and queue an `InputFailed`
+    /// event to report why.
+    pub(crate) fn set_input_failed(&mut self, error: String) -> Result<(), Error> {
+        self.input_failed = true;
+        self.sender().send(Event::InputFailed(error))
     }
 
     /// Create a sender for the event stream.
     pub(crate) fn sender(&self) -> EventSender {
-        EventSender(self.send.clone(), self.waker.clone())
+        EventSender(self.send.clone(), self.waker.clone(), self.queued.clone())
+    }
+
+    /// Returns the number of events waiting to be handled.
+    pub(crate) fn queued(&self) -> usize {
+        self.queued.load(Ordering::SeqCst)
     }
 
     /// Create an action sender for the event stream.
@@ -104,21 +184,46 @@ impl EventStream {
         ActionSender::new(self.sender())
     }
 
-    pub(crate) fn try_recv(&self) -> Result<Option<Event>, Error> {
-        match self.recv.try_recv() {
-            Ok(Envelope::Normal(event)) => Ok(Some(event)),
-            Ok(Envelope::Unique(event, unique)) => {
+    /// Takes the event out of an envelope that has been received.
+    fn open(&self, envelope: Envelope) -> Event {
+        self.queued.fetch_sub(1, Ordering::SeqCst);
+        match envelope {
+            Envelope::Normal(event) => event,
+            Envelope::Unique(event, unique) => {
                 unique.0.store(false, Ordering::SeqCst);
-                Ok(Some(event))
+                event
             }
+        }
+    }
+
+    pub(crate) fn try_recv(&self) -> Result<Option<Event>, Error> {
+        match self.recv.try_recv() {
+            Ok(envelope) => Ok(Some(self.open(envelope))),
             Err(mpsc::TryRecvError::Empty) => Ok(None),
             Err(e) => Err(e.into()),
         }
     }
 
+    /// Wait for an event from the event stream only.
+    fn recv(&self, wait: Option<Duration>) -> Result<Option<Event>, Error> {
+        let envelope = match wait {
+            Some(wait) => match self.recv.recv_timeout(wait) {
+                Ok(envelope) => envelope,
+                Err(mpsc::RecvTimeoutError::Timeout) => return Ok(None),
+                Err(mpsc::RecvTimeoutError::Disconnected) => return Err(mpsc::RecvError.into()),
+            },
+            None => self.recv.recv()?,
+        };
+        Ok(Some(self.open(envelope)))
+    }
+
     /// Get an event, either from the event stream or from the terminal.
+    ///
+    /// If input can't be read from the terminal, an `InputFailed` event is
+    /// returned, and from then on only the event stream is waited on, so
+    /// that the pager keeps working without keyboard input.
     pub(crate) fn get(
-        &self,
+        &mut self,
         term: &mut dyn Terminal,
         wait: Option<Duration>,
     ) -> Result<Option<Event>, Error> {
@@ -126,12 +231,18 @@ impl EventStream {
             if let Some(event) = self.try_recv()? {
                 return Ok(Some(event));
             }
+            if self.input_failed {
+                return self.recv(wait);
+            }
 
             // The queue is empty.  Try to get an input event from the terminal.
-            match term.poll_input(wait).map_err(Error::Termwiz)? {
-                Some(InputEvent::Wake) => {}
-                Some(input_event) => return Ok(Some(Event::Input(input_event))),
-                None => return Ok(None),
+            match term.poll_input(wait) {
+                Ok(Some(InputEvent::Wake)) => {}
+                Ok(Some(input_event)) => return Ok(Some(Event::Input(input_event))),
+                Ok(None) => return Ok(None),
+                Err(e) => {
+                    self.set_input_failed(e.to_string())?;
+                }
             }
         }
     }
@@ -1,9 +1,17 @@
 //! Files.
 
 use std::borrow::Cow;
+use std::ffi::OsString;
+use std::ops::Range;
+use std::path::PathBuf;
 
 use enum_dispatch::enum_dispatch;
 
+use crate::config::Encoding;
+use crate::error::Result;
+use crate::tee::Tee;
+use crate::transform::LineTransforms;
+
 pub(crate) use crate::control::ControlledFile;
 pub(crate) use crate::loaded_file::LoadedFile;
 
@@ -13,6 +21,22 @@ pub type FileIndex = usize;
 /// Default value for `needed_lines`.
 pub(crate) const DEFAULT_NEEDED_LINES: usize = 5000;
 
+/// Information about how a file is stored, for diagnosing performance
+/// problems.
+#[derive(Debug, Default, Clone)]
+pub(crate) struct FileDiagnostics {
+    /// How the file's data is stored, e.g. "streamed" or "mapped".
+    pub(crate) storage: &'static str,
+    /// The number of bytes of the file that have been loaded.
+    pub(crate) bytes: usize,
+    /// The number of buffers holding the file's data.
+    pub(crate) buffers: usize,
+    /// The memory used by the file's data and line index, in bytes.
+    /// Memory-mapped files are paged in by the operating system, so their
+    /// data isn't counted.
+    pub(crate) memory: usize,
+}
+
 /// Trait for getting information from a file.
 #[enum_dispatch]
 pub(crate) trait FileInfo {
@@ -25,12 +49,26 @@ pub(crate) trait FileInfo {
     /// The file's info.
     fn info(&self) -> Cow<'_, str>;
 
+    /// The errors encountered loading the file, starting from error `start`.
+    fn errors(&self, start: usize) -> Vec<String>;
+
     /// True once the file is loaded and all newlines have been parsed.
     fn loaded(&self) -> bool;
 
     /// Returns the number of lines in the file.
     fn lines(&self) -> usize;
 
+    /// Returns the number of bytes of the file that have been loaded.
+    fn length(&self) -> usize;
+
+    /// Returns the byte offset of the start of line `index`, if the line has
+    /// been loaded.
+    fn line_offset(&self, index: usize) -> Option<usize>;
+
+    /// Returns the index of the line containing byte `offset`, if that part
+    /// of the file has been loaded.
+    fn line_at_offset(&self, offset: usize) -> Option<usize>;
+
     /// Runs the `call` function, passing it the contents of line `index`.
     /// Tries to avoid copying the data if possible, however the borrowed
     /// line only lasts as long as the function call.
@@ -38,6 +76,56 @@ pub(crate) trait FileInfo {
     where
         F: FnMut(Cow<'_, [u8]>) -> T;
 
+    /// Runs the `call` function, passing it the contents of the lines in
+    /// `lines` as one contiguous slice, along with the offsets of the start
+    /// of each line in the slice and the offset of the end of the last line.
+    ///
+    /// Returns `None` if the lines are not available as a single slice of
+    /// UTF-8 data, in which case `with_line` must be used instead.
+    fn with_lines<T, F>(&self, lines: Range<usize>, call: F) -> Option<T>
+    where
+        F: FnMut(&[u8], &[usize]) -> T;
+
+    /// Returns the character encoding of the file.  If the encoding is
+    /// being detected, returns the encoding detected so far.
+    fn encoding(&self) -> Encoding;
+
+    /// Set the character encoding of the file.  Lines are converted to
+    /// UTF-8 before they are passed to `with_line`.
+    fn set_encoding(&self, encoding: Encoding);
+
+    /// Set the longest line, in bytes, that is passed to `with_line` in
+    /// full.  Longer lines are truncated.  If 0, lines are never truncated.
+    fn set_max_line_length(&self, length: usize);
+
+    /// Set the transforms applied to lines after they are converted to
+    /// UTF-8, before they are passed to `with_line`.
+    fn set_line_transforms(&self, transforms: LineTransforms);
+
+    /// If the file is a directory listing, returns the path of the entry on
+    /// line `index`.
+    fn listed_path(&self, index: usize) -> Option<PathBuf>;
+
+    /// Returns true if `OpenListedFile` acts on the lines of the file, so
+    /// that Enter should be bound to it rather than to scrolling.
+    fn opens_lines(&self) -> bool;
+
+    /// If line `index` is the header of a section, collapse or expand the
+    /// section and return `true`.
+    fn toggle_section(&self, index: usize) -> bool;
+
+    /// If the file has a cursor, returns the line it is on.
+    fn cursor(&self) -> Option<usize>;
+
+    /// Move the cursor to line `index`, if the file has a cursor.
+    fn move_cursor(&self, index: usize);
+
+    /// If the file has a cursor, select the line it is on and return `true`.
+    fn select_cursor(&self) -> bool;
+
+    /// The path the file was opened from, if it was opened by name.
+    fn path(&self) -> Option<PathBuf>;
+
     /// Set how many lines are needed.
     ///
     /// If `self.lines()` exceeds that number, pause loading until
@@ -45,8 +133,123 @@ pub(crate) trait FileInfo {
     /// This is only effective for "streamed" input.
     fn set_needed_lines(&self, lines: usize);
 
+    /// Set whether loading pauses once the needed lines have been loaded.
+    /// Without backpressure, the whole input is read as it arrives.  This
+    /// is only effective for "streamed" input.
+    fn set_backpressure(&self, backpressure: bool);
+
     /// True if the loading thread has been paused.
     fn paused(&self) -> bool;
+
+    /// Suspend or resume loading.
+    ///
+    /// While loading is suspended, no more data is read from the input, even
+    /// if more lines are needed.  This is only effective for "streamed"
+    /// input.
+    fn set_loading_suspended(&self, suspended: bool);
+
+    /// True if loading has been suspended by `set_loading_suspended`.
+    fn loading_suspended(&self) -> bool;
+
+    /// True if the loading thread is waiting for more input to be written.
+    /// This is only effective for "streamed" input.
+    fn waiting_for_input(&self) -> bool;
+
+    /// True if the file is the output of a command that is still running.
+    fn running_command(&self) -> bool;
+
+    /// Kill the command the file is the output of, if it is still running.
+    fn kill_command(&self);
+
+    /// Interrupt the command the file is the output of, if it is still
+    /// running.  Returns true if there was a command to interrupt.
+    fn interrupt_command(&self) -> bool;
+
+    /// Returns the command the file is the output of, and its arguments.
+    fn command(&self) -> Option<(OsString, Vec<OsString>)>;
+
+    /// Returns what the command the file is the output of has written so
+    /// far, before it is decoded or transformed.
+    fn command_output(&self) -> Option<Vec<u8>>;
+
+    /// Write the file's data to `tee`, first the data loaded so far, and
+    /// then the rest as it is loaded.  The file is then loaded in full,
+    /// regardless of how many lines are needed.
+    fn set_tee(&self, tee: Tee) -> Result<()>;
+
+    /// Stop writing the file's data to its tee, writing anything it has
+    /// held back.
+    fn finish_tee(&self) -> Result<()>;
+
+    /// Run `chained` after the command the file is the output of, and any
+    /// commands already chained after it, have finished.  Returns false if
+    /// the file isn't the output of a command.
+    fn chain_command(&self, chained: ChainedCommand) -> bool;
+
+    /// Take the commands chained after the command the file is the output
+    /// of, in the order they should run.
+    fn take_chained_commands(&self) -> Vec<ChainedCommand>;
+
+    /// Returns information about how the file is stored.
+    fn diagnostics(&self) -> FileDiagnostics;
+}
+
+/// A command to run once the command that a file is the output of has
+/// finished.
+#[derive(Clone, Debug)]
+pub(crate) struct ChainedCommand {
+    /// The command to run.
+    pub(crate) command: OsString,
+
+    /// The arguments to run the command with.
+    pub(crate) args: Vec<OsString>,
+
+    /// The title of the command's output.
+    pub(crate) title: String,
+}
+
+/// A read-only view of the lines of a file the pager is paging, for mapping
+/// between lines and byte offsets the same way the pager does.
+///
+/// The view is live: while the file is loading, it includes each line as
+/// it is loaded.  Created by [`Pager::line_index`](crate::Pager::line_index).
+#[derive(Clone)]
+pub struct LineIndex {
+    file: File,
+}
+
+impl LineIndex {
+    pub(crate) fn new(file: File) -> LineIndex {
+        LineIndex { file }
+    }
+
+    /// Returns true once the whole file has been loaded.
+    pub fn loaded(&self) -> bool {
+        self.file.loaded()
+    }
+
+    /// Returns the number of lines that have been loaded.  The last line
+    /// may be incomplete while the file is loading.
+    pub fn lines(&self) -> usize {
+        self.file.lines()
+    }
+
+    /// Returns the number of bytes that have been loaded.
+    pub fn length(&self) -> usize {
+        self.file.length()
+    }
+
+    /// Returns the byte offset of the start of line `index`, if the line
+    /// has been loaded.
+    pub fn line_offset(&self, index: usize) -> Option<usize> {
+        self.file.line_offset(index)
+    }
+
+    /// Returns the index of the line containing byte `offset`, if that part
+    /// of the file has been loaded.
+    pub fn line_at_offset(&self, offset: usize) -> Option<usize> {
+        self.file.line_at_offset(offset)
+    }
 }
 
 /// A file.
@@ -0,0 +1,82 @@
+//! The line number gutter.
+
+use termwiz::cell::{CellAttributes, Intensity};
+use termwiz::surface::change::Change;
+use unicode_width::UnicodeWidthStr;
+
+use crate::config::Config;
+use crate::line;
+use crate::util::number_width;
+
+/// How line numbers are drawn to the left of the file's lines.
+pub(crate) struct Gutter {
+    /// The attributes of the gutter on rows that start a line.
+    attributes: CellAttributes,
+
+    /// The attributes of the gutter on rows that continue a wrapped line,
+    /// or whose number is not shown.
+    continuation_attributes: CellAttributes,
+
+    /// The text drawn between the gutter and the line.
+    separator: String,
+
+    /// Only the numbers of lines that are multiples of this are shown.
+    interval: usize,
+}
+
+impl Gutter {
+    /// Create the gutter specified by the configuration.  If the line number
+    /// style is invalid, line numbers are unstyled and the problem is added
+    /// to `errors`.
+    pub(crate) fn new(config: &Config, errors: &mut Vec<String>) -> Gutter {
+        let attributes =
+            line::text_style_attributes(&config.line_number_style).unwrap_or_else(|e| {
+                errors.push(format!("Invalid line_number_style: {}", e));
+                CellAttributes::default()
+            });
+        let continuation_attributes = if config.dim_line_number_continuation {
+            CellAttributes::default()
+                .set_intensity(Intensity::Half)
+                .clone()
+        } else {
+            attributes.clone()
+        };
+        Gutter {
+            attributes,
+            continuation_attributes,
+            separator: config.line_number_separator.clone(),
+            interval: config.line_number_interval.max(1),
+        }
+    }
+
+    /// Returns the width of the gutter for a file with `lines` lines.
+    pub(crate) fn width(&self, lines: usize) -> usize {
+        number_width(lines) + 2 + self.separator.width()
+    }
+
+    /// Renders the gutter for a row of line `line_index` of a file with
+    /// `lines` lines.  `first_portion` is the portion of the line that is
+    /// shown on the row; the number is only shown on the row that starts
+    /// the line.
+    pub(crate) fn render(
+        &self,
+        changes: &mut Vec<Change>,
+        line_index: usize,
+        first_portion: usize,
+        lines: usize,
+    ) {
+        let lw = number_width(lines);
+        let numbered = first_portion == 0 && line_index % self.interval == self.interval - 1;
+        if numbered {
+            changes.push(Change::AllAttributes(self.attributes.clone()));
+            changes.push(Change::Text(format!(" {:>1$} ", line_index + 1, lw)));
+        } else {
+            changes.push(Change::AllAttributes(self.continuation_attributes.clone()));
+            changes.push(Change::Text(" ".repeat(lw + 2)));
+        }
+        if !self.separator.is_empty() {
+            changes.push(Change::Text(self.separator.clone()));
+        }
+        changes.push(Change::AllAttributes(CellAttributes::default()));
+    }
+}
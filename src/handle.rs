@@ -0,0 +1,139 @@
+//! Handles for querying the pager while it runs.
+
+use std::ops::Range;
+use std::sync::{Arc, RwLock};
+
+use crate::file::FileIndex;
+use crate::search::SearchMatches;
+
+/// The state of a search, as seen through a [`PagerHandle`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SearchState {
+    /// The pattern being searched for.
+    pub pattern: String,
+
+    /// The number of matches found so far.
+    pub matches: usize,
+
+    /// The index of the currently selected match, if any.
+    pub current_match: Option<usize>,
+
+    /// True once the whole file has been searched.
+    pub finished: bool,
+}
+
+/// A match found by a search, as seen through a [`PagerHandle`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SearchMatch {
+    /// The index of the line containing the match.
+    pub line: usize,
+
+    /// The range of bytes of the line that matched.  Offsets are into the
+    /// line with escape sequences and overstriking removed.
+    pub range: Range<usize>,
+}
+
+/// The most recently displayed state of the pager.
+#[derive(Clone, Debug, Default)]
+struct PagerState {
+    /// The index of the file being displayed.
+    file_index: FileIndex,
+
+    /// The file line at the top of the screen.
+    top_line: usize,
+
+    /// The size of the screen as (width, height).
+    screen_size: (usize, usize),
+
+    /// The search in the file being displayed.
+    search: Option<SearchState>,
+
+    /// The matches found by the search in the file being displayed.
+    search_matches: Option<SearchMatches>,
+
+    /// Whether the screen is about to change.
+    busy: bool,
+}
+
+/// A handle that can be used to query a pager from another thread.
+///
+/// Created by [`Pager::start`](crate::Pager::start).  All methods return
+/// `None` until the pager has started displaying in full screen mode.
+#[derive(Clone, Default)]
+pub struct PagerHandle {
+    state: Arc<RwLock<Option<PagerState>>>,
+}
+
+impl PagerHandle {
+    /// Returns the index of the file currently being displayed.
+    pub fn file_index(&self) -> Option<FileIndex> {
+        self.with_state(|state| state.file_index)
+    }
+
+    /// Returns the index of the line at the top of the screen.
+    pub fn top_line(&self) -> Option<usize> {
+        self.with_state(|state| state.top_line)
+    }
+
+    /// Returns the state of the search in the file currently being
+    /// displayed, if there is one.
+    pub fn search(&self) -> Option<SearchState> {
+        self.with_state(|state| state.search.clone()).flatten()
+    }
+
+    /// Returns the matches found so far by the search in the file currently
+    /// being displayed, starting with match number `start`.
+    ///
+    /// Matches are reported in the order they are found, so embedders can
+    /// fetch new matches as the search progresses by passing the number of
+    /// matches they have already seen.
+    pub fn search_matches(&self, start: usize) -> Option<Vec<SearchMatch>> {
+        self.with_state(|state| {
+            state
+                .search_matches
+                .as_ref()
+                .map(|matches| matches.get(start))
+        })
+        .flatten()
+    }
+
+    /// Returns the size of the screen as (width, height).
+    pub fn screen_size(&self) -> Option<(usize, usize)> {
+        self.with_state(|state| state.screen_size)
+    }
+
+    /// Returns true while the file being displayed is being read or
+    /// searched, or the screen is about to be rendered.
+    ///
+    /// Once this is false, the screen only changes when more input is
+    /// written to the file or the user does something.
+    pub fn busy(&self) -> Option<bool> {
+        self.with_state(|state| state.busy)
+    }
+
+    fn with_state<T>(&self, call: impl FnOnce(&PagerState) -> T) -> Option<T> {
+        let state = self.state.read().unwrap();
+        state.as_ref().map(call)
+    }
+
+    /// Update the state seen through this handle.
+    pub(crate) fn update(
+        &self,
+        file_index: FileIndex,
+        top_line: usize,
+        screen_size: (usize, usize),
+        search: Option<(SearchState, SearchMatches)>,
+        busy: bool,
+    ) {
+        let (search, search_matches) = search.unzip();
+        let mut state = self.state.write().unwrap();
+        *state = Some(PagerState {
+            file_index,
+            top_line,
+            screen_size,
+            search,
+            search_matches,
+            busy,
+        });
+    }
+}
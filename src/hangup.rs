@@ -0,0 +1,61 @@
+//! Hangup Signals
+//!
+//! When enabled, the pager reloads its configuration file when the process
+//! receives `SIGHUP`, like many long-running programs.
+
+#[cfg(unix)]
+use std::thread;
+
+#[cfg(unix)]
+use signal_hook::consts::SIGHUP;
+#[cfg(unix)]
+use signal_hook::iterator::{Handle, Signals};
+
+#[cfg(unix)]
+use crate::action::Action;
+use crate::error::Result;
+#[cfg(unix)]
+use crate::event::Event;
+use crate::event::EventSender;
+
+/// A listener for `SIGHUP`.  Until it is dropped, each signal received
+/// makes the pager reload its configuration.
+pub(crate) struct HangupListener {
+    #[cfg(unix)]
+    handle: Handle,
+}
+
+impl HangupListener {
+    /// Start listening for `SIGHUP`.  On platforms without signals, this
+    /// does nothing.
+    #[cfg(unix)]
+    pub(crate) fn new(event_sender: EventSender) -> Result<HangupListener> {
+        let mut signals = Signals::new([SIGHUP])?;
+        let handle = signals.handle();
+        thread::Builder::new()
+            .name(String::from("sp-hangup"))
+            .spawn(move || {
+                for _signal in signals.forever() {
+                    let reload = Event::Action(Action::ReloadConfig);
+                    if event_sender.send(reload).is_err() {
+                        break;
+                    }
+                }
+            })?;
+        Ok(HangupListener { handle })
+    }
+
+    /// Start listening for `SIGHUP`.  On platforms without signals, this
+    /// does nothing.
+    #[cfg(not(unix))]
+    pub(crate) fn new(_event_sender: EventSender) -> Result<HangupListener> {
+        Ok(HangupListener {})
+    }
+}
+
+#[cfg(unix)]
+impl Drop for HangupListener {
+    fn drop(&mut self) {
+        self.handle.close();
+    }
+}
@@ -3,14 +3,42 @@
 use std::fmt::Write;
 
 use termwiz::input::{KeyCode, Modifiers};
-use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
+use unicode_width::UnicodeWidthStr;
 
 use crate::bindings::{Category, Keymap};
 use crate::error::Result;
 
-fn write_key_names(text: &mut String, keys: &[(Modifiers, KeyCode)]) -> Result<usize> {
+/// Describes a key combination, e.g. `Ctrl-Left`.
+pub(crate) fn key_name(modifiers: Modifiers, keycode: KeyCode) -> String {
+    let mut name = String::new();
+    for (modifier, desc) in [
+        (Modifiers::CTRL, "Ctrl-"),
+        (Modifiers::ALT, "Alt-"),
+        (Modifiers::SUPER, "Super-"),
+        (Modifiers::SHIFT, "Shift-"),
+    ]
+    .iter()
+    {
+        if modifiers.contains(*modifier) {
+            name.push_str(desc);
+        }
+    }
+    match keycode {
+        KeyCode::Char(' ') => name.push_str("Space"),
+        KeyCode::Char(c) => name.push(c),
+        KeyCode::Function(n) => name.push_str(&format!("F{}", n)),
+        KeyCode::UpArrow => name.push_str("Up"),
+        KeyCode::DownArrow => name.push_str("Down"),
+        KeyCode::LeftArrow => name.push_str("Left"),
+        KeyCode::RightArrow => name.push_str("Right"),
+        keycode => name.push_str(&format!("{:?}", keycode)),
+    }
+    name
+}
+
+fn write_key_names(text: &mut String, keys: &[(Modifiers, KeyCode)]) -> usize {
     let mut w = 0;
-    for (index, (modifiers, keycode)) in keys.iter().enumerate() {
+    for (index, &(modifiers, keycode)) in keys.iter().enumerate() {
         if index > 0 {
             if index == keys.len() - 1 {
                 text.push_str("\x1B[0;2m or ");
@@ -20,61 +48,13 @@ fn write_key_names(text: &mut String, keys: &[(Modifiers, KeyCode)]) -> Result<u
                 w += 2;
             }
         }
+        let name = key_name(modifiers, keycode);
         text.push_str("\x1B[1m");
-        for (modifier, desc) in [
-            (Modifiers::CTRL, "Ctrl-"),
-            (Modifiers::ALT, "Alt-"),
-            (Modifiers::SUPER, "Super-"),
-            (Modifiers::SHIFT, "Shift-"),
-        ]
-        .iter()
-        {
-            if modifiers.contains(*modifier) {
-                text.push_str(desc);
-                w += desc.width();
-            }
-        }
-        match keycode {
-            KeyCode::Char(' ') => {
-                text.push_str("Space");
-                w += 5;
-            }
-            KeyCode::Char(c) => {
-                text.push(*c);
-                w += c.width().unwrap_or(0);
-            }
-            KeyCode::Function(n) => {
-                let n_string = n.to_string();
-                text.push('F');
-                text.push_str(&n_string);
-                w += n_string.width() + 1;
-            }
-            KeyCode::UpArrow => {
-                text.push_str("Up");
-                w += 2;
-            }
-            KeyCode::DownArrow => {
-                text.push_str("Down");
-                w += 4;
-            }
-            KeyCode::LeftArrow => {
-                text.push_str("Left");
-                w += 4;
-            }
-            KeyCode::RightArrow => {
-                text.push_str("Right");
-                w += 5;
-            }
-            keycode => {
-                let mut key_string = String::new();
-                write!(key_string, "{:?}", keycode)?;
-                text.push_str(&key_string);
-                w += key_string.width();
-            }
-        }
+        text.push_str(&name);
+        w += name.width();
     }
     text.push_str("\x1B[m");
-    Ok(w)
+    w
 }
 
 pub(crate) fn help_text(keymap: &Keymap) -> Result<String> {
@@ -87,13 +67,15 @@ pub(crate) fn help_text(keymap: &Keymap) -> Result<String> {
         let mut title = false;
 
         for (binding, keys) in keymap.iter_keys() {
-            if binding.category() == category {
+            // Bindings whose keys have all been unbound by an override are
+            // not reachable, so leave them out.
+            if binding.category() == category && !keys.is_empty() {
                 if !title {
                     write!(text, "\n  \x1B[1;4;33;38;5;130m{}\x1B[m\n\n", category)?;
                     title = true;
                 }
                 text.push_str("    ");
-                let w = write_key_names(&mut text, keys)?;
+                let w = write_key_names(&mut text, keys);
                 if w < 34 {
                     text.push_str(&prefix[w..]);
                 } else {
@@ -107,3 +89,34 @@ pub(crate) fn help_text(keymap: &Keymap) -> Result<String> {
 
     Ok(text)
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::action::Action;
+
+    #[test]
+    fn test_help_text_reflects_overrides() {
+        let mut keymap = Keymap::default();
+        let text = help_text(&keymap).unwrap();
+        assert!(text.contains("Toggle the ruler"));
+
+        // Unbinding every key for an action removes it from the help.
+        keymap.bind(Modifiers::CTRL, KeyCode::Char('R'), None);
+        let text = help_text(&keymap).unwrap();
+        assert!(!text.contains("Toggle the ruler"));
+
+        // Rebinding it to a new key brings it back.
+        keymap.bind(Modifiers::NONE, KeyCode::Char('R'), Action::ToggleRuler);
+        let text = help_text(&keymap).unwrap();
+        assert!(text.contains("Toggle the ruler"));
+    }
+
+    #[test]
+    fn test_help_text_hides_diagnostics() {
+        let keymap = Keymap::default();
+        let text = help_text(&keymap).unwrap();
+        assert!(!text.contains(&Action::ShowDiagnostics.to_string()));
+        assert!(text.contains(&Action::ShowErrorLog.to_string()));
+    }
+}
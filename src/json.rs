@@ -0,0 +1,151 @@
+//! JSON Pretty-Printing
+//!
+//! JSON is often produced without any line breaks, e.g. by web APIs, which
+//! makes it hard to read in a pager.  Re-indent it so that each member of an
+//! object or array is on its own line.
+//!
+//! This works on the tokens of the JSON without building a document, so
+//! that very large documents can be handled.  The input can contain several
+//! documents, e.g. JSON lines, each of which must be an object or an array.
+
+/// The indentation for each level of nesting.
+const INDENT: &[u8] = b"  ";
+
+/// The escape sequence that starts the color of object keys.
+const KEY_COLOR: &[u8] = b"\x1B[34m";
+
+/// The escape sequence that ends the color of object keys.
+const KEY_COLOR_END: &[u8] = b"\x1B[39m";
+
+/// Returns the index of the first non-whitespace byte at or after `start`.
+fn skip_whitespace(data: &[u8], start: usize) -> usize {
+    let mut index = start;
+    while index < data.len() && matches!(data[index], b' ' | b'\t' | b'\r' | b'\n') {
+        index += 1;
+    }
+    index
+}
+
+/// Returns true if the byte can be part of a number, `true`, `false` or
+/// `null`.
+fn is_literal_byte(byte: u8) -> bool {
+    matches!(byte, b'-' | b'+' | b'.' | b'0'..=b'9' | b'a'..=b'z' | b'A'..=b'Z')
+}
+
+/// Start a new line at the given depth of nesting.
+fn newline(out: &mut Vec<u8>, depth: usize) {
+    out.push(b'\n');
+    for _ in 0..depth {
+        out.extend_from_slice(INDENT);
+    }
+}
+
+/// Pretty-print JSON data.  If `color_keys` is true, the keys of objects are
+/// colored.
+///
+/// Returns `None` if the data is not JSON.
+pub(crate) fn pretty_print(data: &[u8], color_keys: bool) -> Option<Vec<u8>> {
+    let mut out = Vec::with_capacity(data.len() * 2);
+    // The closing brackets of the objects and arrays currently open.
+    let mut closers = Vec::new();
+    let mut index = skip_whitespace(data, 0);
+    if index == data.len() {
+        return None;
+    }
+    while index < data.len() {
+        let byte = data[index];
+        match byte {
+            b'{' | b'[' => {
+                if closers.is_empty() && !out.is_empty() {
+                    out.push(b'\n');
+                }
+                let closer = if byte == b'{' { b'}' } else { b']' };
+                out.push(byte);
+                let next = skip_whitespace(data, index + 1);
+                if data.get(next) == Some(&closer) {
+                    out.push(closer);
+                    index = next;
+                } else {
+                    closers.push(closer);
+                    newline(&mut out, closers.len());
+                }
+            }
+            b'}' | b']' => {
+                if closers.pop() != Some(byte) {
+                    return None;
+                }
+                newline(&mut out, closers.len());
+                out.push(byte);
+            }
+            _ if closers.is_empty() => return None,
+            b',' => {
+                out.push(b',');
+                newline(&mut out, closers.len());
+            }
+            b':' => {
+                if closers.last() != Some(&b'}') {
+                    return None;
+                }
+                out.extend_from_slice(b": ");
+            }
+            b'"' => {
+                let mut end = index + 1;
+                loop {
+                    match data.get(end) {
+                        Some(b'"') => break,
+                        Some(b'\\') => end += 2,
+                        Some(_) => end += 1,
+                        None => return None,
+                    }
+                }
+                let is_key = closers.last() == Some(&b'}')
+                    && data.get(skip_whitespace(data, end + 1)) == Some(&b':');
+                if is_key && color_keys {
+                    out.extend_from_slice(KEY_COLOR);
+                    out.extend_from_slice(&data[index..=end]);
+                    out.extend_from_slice(KEY_COLOR_END);
+                } else {
+                    out.extend_from_slice(&data[index..=end]);
+                }
+                index = end;
+            }
+            _ if is_literal_byte(byte) => {
+                let end = data[index..]
+                    .iter()
+                    .position(|&b| !is_literal_byte(b))
+                    .map_or(data.len(), |length| index + length);
+                out.extend_from_slice(&data[index..end]);
+                index = end - 1;
+            }
+            _ => return None,
+        }
+        index = skip_whitespace(data, index + 1);
+    }
+    if !closers.is_empty() {
+        return None;
+    }
+    out.push(b'\n');
+    Some(out)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_pretty_print() {
+        assert_eq!(
+            pretty_print(br#"{"a": [1, 2.5e3, {}], "b": {"c": "x\"y", "d": null}}"#, false)
+                .unwrap(),
+            b"{\n  \"a\": [\n    1,\n    2.5e3,\n    {}\n  ],\n  \"b\": {\n    \"c\": \"x\\\"y\",\n    \"d\": null\n  }\n}\n"
+        );
+        assert_eq!(
+            pretty_print(b"{\"a\":1}\n[]\n", true).unwrap(),
+            b"{\n  \x1B[34m\"a\"\x1B[39m: 1\n}\n[]\n"
+        );
+        assert_eq!(pretty_print(b"not json", false), None);
+        assert_eq!(pretty_print(b"{\"a\": 1", false), None);
+        assert_eq!(pretty_print(b"[1}", false), None);
+        assert_eq!(pretty_print(b"", false), None);
+    }
+}
@@ -28,10 +28,38 @@ pub enum KeymapError {
     #[error("parse error")]
     Parse(#[from] pest::error::Error<crate::keymap_file::Rule>),
 
+    /// Error when keymaps include each other.
+    #[error("keymap includes itself: {0}")]
+    IncludeCycle(String),
+
+    /// Error when a key is bound to different bindings.
+    #[error("{key} is bound to both {first} and {second}")]
+    Conflict {
+        /// The key that is bound more than once.
+        key: String,
+
+        /// The first binding for the key.
+        first: String,
+
+        /// The other binding for the key.
+        second: String,
+    },
+
     /// Error related to parsing a binding within a keymap.
     #[error("keybinding error")]
     Binding(#[from] crate::bindings::BindingError),
 
+    /// Wrapped error at a line of a keymap file.
+    #[error("error on line {line}")]
+    AtLine {
+        /// Wrapped error.
+        #[source]
+        error: Box<KeymapError>,
+
+        /// Line number the error is on, starting from 1.
+        line: usize,
+    },
+
     /// Wrapped error within the context of a file.
     #[error("error loading file '{file}'")]
     WithFile {
@@ -52,6 +80,59 @@ impl KeymapError {
             file: file.as_ref().to_owned(),
         }
     }
+
+    /// Wrap the error with the line of the keymap file it is on.
+    #[cfg_attr(not(feature = "keymap-file"), allow(unused))]
+    pub(crate) fn at_line(self, line: usize) -> Self {
+        Self::AtLine {
+            error: Box::new(self),
+            line,
+        }
+    }
+
+    /// The keymap file the error is in, if any.
+    pub(crate) fn file(&self) -> Option<&Path> {
+        match self {
+            Self::WithFile { file, .. } => Some(file),
+            Self::AtLine { error, .. } => error.file(),
+            _ => None,
+        }
+    }
+
+    /// The line of the keymap file the error is on, if known.
+    pub(crate) fn line(&self) -> Option<usize> {
+        match self {
+            #[cfg(feature = "keymap-file")]
+            Self::Parse(error) => match error.line_col {
+                pest::error::LineColLocation::Pos((line, _))
+                | pest::error::LineColLocation::Span((line, _), _) => Some(line),
+            },
+            Self::AtLine { line, .. } => Some(*line),
+            Self::WithFile { error, .. } => error.line(),
+            _ => None,
+        }
+    }
+
+    /// Describes the underlying error, without the file and line it is in.
+    pub(crate) fn description(&self) -> String {
+        match self {
+            #[cfg(feature = "keymap-file")]
+            Self::Parse(error) => error.to_string(),
+            Self::AtLine { error, .. } => error.description(),
+            Self::WithFile { error, file } => {
+                format!("in {}: {}", file.display(), error.description())
+            }
+            error => {
+                let mut description = error.to_string();
+                let mut source = std::error::Error::source(error);
+                while let Some(error) = source {
+                    description.push_str(&format!(": {}", error));
+                    source = error.source();
+                }
+                description
+            }
+        }
+    }
 }
 
 pub(crate) type Result<T> = std::result::Result<T, KeymapError>;
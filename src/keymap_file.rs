@@ -3,11 +3,14 @@
 // Upper-case acronyms are generated by pest.
 #![allow(clippy::upper_case_acronyms)]
 
+use std::collections::HashMap;
+
 use pest::Parser;
 use pest_derive::Parser;
 use termwiz::input::{KeyCode, Modifiers};
 
 use crate::bindings::{Binding, BindingConfig, BindingError};
+use crate::help::key_name;
 use crate::keymap_error::{KeymapError, Result};
 
 #[derive(Parser)]
@@ -15,7 +18,13 @@ use crate::keymap_error::{KeymapError, Result};
 struct KeymapFileParser;
 
 // File data to generate a keymap
-pub(crate) struct KeymapFile(Vec<((Modifiers, KeyCode), BindingConfig)>);
+pub(crate) struct KeymapFile {
+    /// Keymaps to include, and the lines they are included on.
+    includes: Vec<(String, usize)>,
+
+    /// Bindings defined in the file, which override the included keymaps.
+    bindings: Vec<((Modifiers, KeyCode), BindingConfig)>,
+}
 
 impl KeymapFile {
     fn parse_keycode(ident: &str) -> Option<KeyCode> {
@@ -144,6 +153,19 @@ impl KeymapFile {
         Err(KeymapError::MissingDefinition)
     }
 
+    /// Removes the quotes and backslash escapes from a string parameter.
+    fn parse_string(quoted: &str) -> String {
+        let mut value = String::new();
+        let mut chars = quoted[1..quoted.len() - 1].chars();
+        while let Some(c) = chars.next() {
+            match c {
+                '\\' => value.extend(chars.next()),
+                c => value.push(c),
+            }
+        }
+        value
+    }
+
     fn parse_binding(pair: pest::iterators::Pair<Rule>) -> Result<Binding> {
         let mut ident = None;
         let mut params = Vec::new();
@@ -152,6 +174,7 @@ impl KeymapFile {
             match item.as_rule() {
                 Rule::ident => ident = Some(String::from(item.as_str())),
                 Rule::binding_param => params.push(String::from(item.as_str())),
+                Rule::binding_string => params.push(Self::parse_string(item.as_str())),
                 other => panic!("Unexpected rule inside binding: {:?}", other),
             }
         }
@@ -160,20 +183,42 @@ impl KeymapFile {
     }
 
     pub(crate) fn parse(data: &str) -> Result<KeymapFile> {
+        let mut includes = Vec::new();
         let mut keymap = Vec::new();
+        let mut bound: HashMap<(Modifiers, KeyCode), (Binding, usize)> = HashMap::new();
         let mut parsed = KeymapFileParser::parse(Rule::file, data)?;
         if let Some(file) = parsed.next() {
             for item in file.into_inner() {
                 let mut keys = Vec::new();
-                if item.as_rule() == Rule::item {
+                if item.as_rule() == Rule::include {
+                    let line = item.as_span().start_pos().line_col().0;
+                    let name = item
+                        .into_inner()
+                        .next()
+                        .expect("include should contain a keymap name");
+                    includes.push((name.as_str().to_string(), line));
+                } else if item.as_rule() == Rule::item {
                     for part in item.into_inner() {
+                        let line = part.as_span().start_pos().line_col().0;
                         match part.as_rule() {
                             Rule::key => {
-                                keys.push(Self::parse_key(part)?);
+                                keys.push(Self::parse_key(part).map_err(|e| e.at_line(line))?);
                             }
                             Rule::binding => {
-                                let binding = Self::parse_binding(part)?;
+                                let binding =
+                                    Self::parse_binding(part).map_err(|e| e.at_line(line))?;
                                 for (key, visible) in keys.into_iter() {
+                                    if let Some((other, other_line)) = bound.get(&key) {
+                                        if *other != binding {
+                                            let error = KeymapError::Conflict {
+                                                key: key_name(key.0, key.1),
+                                                first: format!("{} (line {})", other, other_line),
+                                                second: binding.to_string(),
+                                            };
+                                            return Err(error.at_line(line));
+                                        }
+                                    }
+                                    bound.insert(key, (binding.clone(), line));
                                     let binding = binding.clone();
                                     let binding_config = BindingConfig { binding, visible };
                                     keymap.push((key, binding_config));
@@ -186,10 +231,19 @@ impl KeymapFile {
                 }
             }
         }
-        Ok(KeymapFile(keymap))
+        Ok(KeymapFile {
+            includes,
+            bindings: keymap,
+        })
+    }
+
+    pub(crate) fn includes(&self) -> impl Iterator<Item = (&str, usize)> {
+        self.includes
+            .iter()
+            .map(|(name, line)| (name.as_str(), *line))
     }
 
     pub(crate) fn iter(&self) -> impl IntoIterator<Item = &((Modifiers, KeyCode), BindingConfig)> {
-        self.0.iter()
+        self.bindings.iter()
     }
 }
@@ -3,6 +3,8 @@
 use termwiz::input::{KeyCode, Modifiers};
 
 use crate::bindings::{BindingConfig, Keymap};
+#[cfg(feature = "keymap-file")]
+use crate::help::key_name;
 use crate::keymap_error::{KeymapError, Result};
 
 // Static data to generate a keymap.
@@ -23,6 +25,13 @@ keymaps! {
 }
 
 pub(crate) fn load(name: &str) -> Result<Keymap> {
+    load_included(name, &mut Vec::new())
+}
+
+/// Loads a keymap, given the names of the keymap files that are including
+/// it.
+#[cfg_attr(not(feature = "keymap-file"), allow(unused_variables))]
+fn load_included(name: &str, loading: &mut Vec<String>) -> Result<Keymap> {
     for (keymap_name, keymap_data) in KEYMAPS {
         if &name == keymap_name {
             return Ok(Keymap::from(keymap_data.iter()));
@@ -36,12 +45,70 @@ pub(crate) fn load(name: &str) -> Result<Keymap> {
             path.push("keymaps");
             path.push(name);
             if let Ok(keymap_data) = std::fs::read_to_string(&path) {
-                let keymap_file = crate::keymap_file::KeymapFile::parse(&keymap_data)
-                    .map_err(|err| err.with_file(path))?;
-                return Ok(Keymap::from(keymap_file.iter()));
+                loading.push(name.to_string());
+                let keymap = load_file(&keymap_data, loading).map_err(|err| err.with_file(path));
+                loading.pop();
+                return keymap;
             }
         }
     }
 
     Err(KeymapError::MissingKeymap(name.to_string()))
 }
+
+/// Builds the keymap for a keymap file.  Included keymaps are merged first,
+/// and then the file's own bindings are added over them.
+#[cfg(feature = "keymap-file")]
+fn load_file(data: &str, loading: &mut Vec<String>) -> Result<Keymap> {
+    let keymap_file = crate::keymap_file::KeymapFile::parse(data)?;
+    let mut keymap = Keymap::new();
+    for (include, line) in keymap_file.includes() {
+        if loading.iter().any(|name| name == include) {
+            return Err(KeymapError::IncludeCycle(include.to_string()).at_line(line));
+        }
+        let included = load_included(include, loading).map_err(|err| err.at_line(line))?;
+        if let Some(((modifiers, keycode), first, second)) = keymap.conflicts(&included).next() {
+            let error = KeymapError::Conflict {
+                key: key_name(modifiers, keycode),
+                first: first.to_string(),
+                second: format!("{} (from {})", second, include),
+            };
+            return Err(error.at_line(line));
+        }
+        keymap = keymap.merge(&included);
+    }
+    Ok(keymap.merge(&Keymap::from(keymap_file.iter())))
+}
+
+/// Describes an error in a keymap file, followed by the contents of the
+/// file with the line the error is on highlighted.  Returns `None` if the
+/// error isn't about a keymap file.
+pub(crate) fn error_report(error: &KeymapError) -> Option<String> {
+    let path = error.file()?;
+    let description = match error {
+        KeymapError::WithFile { error, .. } => error.description(),
+        error => error.description(),
+    };
+    let mut report = format!(
+        "Failed to load the keymap in {}\n\n{}\n\nThe default keymap is being used instead.\n\n",
+        path.display(),
+        description,
+    );
+    if let Ok(data) = std::fs::read_to_string(path) {
+        let lines: Vec<&str> = data.lines().collect();
+        let width = lines.len().to_string().len();
+        for (index, line) in lines.iter().enumerate() {
+            if error.line() == Some(index + 1) {
+                report.push_str(&format!(
+                    "\x1B[7m{:>2$}  {}\x1B[m\n",
+                    index + 1,
+                    line,
+                    width
+                ));
+            } else {
+                report.push_str(&format!("{:>2$}  {}\n", index + 1, line, width));
+            }
+        }
+    }
+    Some(report)
+}
@@ -1,16 +1,34 @@
 //! Default keymap
 
+use crate::action::RerunMode;
+
 keymap! {
-    CTRL 'C', 'q', ('Q') => Quit;
+    'q', ('Q') => Quit;
+    CTRL 'C' => Interrupt;
+    'X' => RerunCommand(RerunMode::Replace);
+    ALT 'X' => RerunCommand(RerunMode::Append);
+    ALT 'q' => QuitAndPrintScreen;
+    CTRL 'W' => QuitCurrentFile;
     Escape => Cancel;
+    ALT 'x' => ShowCommandPalette;
+    ALT 'p' => PauseLoading;
+    ALT 'r' => ResumeLoading;
+    'S' => SnapshotFile;
+    'D' => DiffSnapshot;
+    ALT 'm' => ToggleMouseSelection;
     CTRL 'L', 'r' => Refresh;
+    'R' => ReloadConfig;
     CTRL 'R' => ToggleRuler;
     UpArrow, 'k', (CTRL 'K'), (CTRL 'P') => ScrollUpLines(1);
     DownArrow, 'j', (CTRL 'N'), Enter => ScrollDownLines(1);
     SHIFT UpArrow, (ApplicationUpArrow) => ScrollUpScreenFraction(4);
     SHIFT DownArrow, (ApplicationDownArrow) => ScrollDownScreenFraction(4);
-    CTRL UpArrow, 'u', CTRL 'U' => ScrollUpScreenFraction(2);
-    CTRL DownArrow, 'd', CTRL 'D' => ScrollDownScreenFraction(2);
+    CTRL UpArrow => ScrollUpScreenFraction(2);
+    CTRL DownArrow => ScrollDownScreenFraction(2);
+    'u', CTRL 'U' => ScrollUpHalfScreen;
+    'd', CTRL 'D' => ScrollDownHalfScreen;
+    'w' => ScrollUpWindow;
+    'z' => ScrollDownWindow;
     PageUp, Backspace, 'b', CTRL 'B', ALT 'v' => ScrollUpScreenFraction(1);
     PageDown, ' ', 'f', CTRL 'F', CTRL 'V' => ScrollDownScreenFraction(1);
     Home, 'g', '<' => ScrollToTop;
@@ -22,9 +40,18 @@ keymap! {
     '[', SHIFT Tab => PreviousFile;
     ']', Tab => NextFile;
     'h', F 1 => Help;
+    'L' => ShowErrorLog;
+    'e' => ToggleErrorFile;
+    'O' => OpenErrorFile;
+    (ALT 'D') => ShowDiagnostics;
     '#' => ToggleLineNumbers;
     '\\' => ToggleLineWrapping;
+    'M' => ToggleMarkdown;
+    'J' => ToggleJsonView;
+    'E' => ToggleLineEndings;
+    'W' => ToggleWhitespace;
     ':', '%' => PromptGoToLine;
+    '!' => PromptShellCommand;
     '/' => PromptSearchForwards;
     '?' => PromptSearchBackwards;
     ',' => PreviousMatch;
@@ -33,6 +60,8 @@ keymap! {
     'n' => NextMatchScreen;
     '(' => FirstMatch;
     ')' => LastMatch;
+    ALT 'n' => RepeatLastSearch;
+    ALT '/' => ToggleSearchVisibleColumns;
     '0' => AppendDigitToRepeatCount(0);
     '1' => AppendDigitToRepeatCount(1);
     '2' => AppendDigitToRepeatCount(2);
@@ -6,19 +6,32 @@
 #![allow(clippy::comparison_chain)]
 
 pub mod action;
+#[cfg(feature = "tokio")]
+mod async_stream;
 mod bar;
 pub mod bindings;
 mod buffer;
 mod buffer_cache;
 mod command;
 pub mod config;
+#[cfg(all(windows, feature = "windows-console"))]
+mod console;
 pub mod control;
+mod diff;
 mod direct;
+mod directory;
 mod display;
+mod encoding;
 pub mod error;
 mod event;
 pub mod file;
+mod gutter;
+pub mod handle;
+mod hangup;
+#[cfg(any(test, feature = "test-harness"))]
+pub mod harness;
 mod help;
+mod json;
 mod keymap_error;
 #[cfg(feature = "keymap-file")]
 mod keymap_file;
@@ -28,18 +41,31 @@ mod keymaps;
 mod line;
 mod line_cache;
 mod line_drawing;
+mod listener;
 mod loaded_file;
+mod normalize;
 mod overstrike;
 pub mod pager;
+mod palette;
 mod progress;
 mod prompt;
 mod prompt_history;
+mod redact;
 mod refresh;
 mod ruler;
 mod screen;
+mod script;
 mod search;
+#[cfg(unix)]
+#[doc(hidden)]
+pub mod send;
+mod session;
+mod tee;
+mod trace;
+pub mod transform;
 mod util;
+mod watch;
 
 pub use error::{Error, Result};
-pub use file::FileIndex;
+pub use file::{FileIndex, LineIndex};
 pub use pager::Pager;
@@ -2,14 +2,16 @@
 
 use std::borrow::Cow;
 use std::cmp::Ordering;
+use std::path::{Path, PathBuf};
 use std::str;
 use std::sync::{Arc, Mutex};
 
+use lazy_static::lazy_static;
 use lru::LruCache;
-use regex::bytes::{NoExpand, Regex};
+use regex::bytes::Regex;
 use smallvec::SmallVec;
-use termwiz::cell::{CellAttributes, Intensity};
-use termwiz::color::{AnsiColor, ColorAttribute};
+use termwiz::cell::{CellAttributes, Intensity, Underline};
+use termwiz::color::{AnsiColor, ColorAttribute, ColorSpec, RgbColor};
 use termwiz::escape::csi::{Edit, EraseInLine, Sgr, CSI};
 use termwiz::escape::esc::{Esc, EscCode};
 use termwiz::escape::osc::OperatingSystemCommand;
@@ -20,18 +22,53 @@ use termwiz::surface::{change::Change, Position};
 use unicode_segmentation::UnicodeSegmentation;
 use unicode_width::UnicodeWidthStr;
 
-use crate::config::WrappingMode;
+use crate::config::{Config, HighlightRule, Profile, SearchNormalization, TextStyle, WrappingMode};
+use crate::error::Error;
 use crate::line_drawing;
 use crate::overstrike;
-use crate::search::{trim_trailing_newline, ESCAPE_SEQUENCE};
+use crate::search::{find_matches, trim_trailing_newline, EscapeOffsets, SearchColumns};
 use crate::util;
 
 const LEFT_ARROW: &str = "<";
 const RIGHT_ARROW: &str = ">";
 const TAB_SPACES: &str = "        ";
 
+/// The default number of columns between tab stops.
+pub(crate) const DEFAULT_TAB_WIDTH: usize = TAB_SPACES.len();
+
 const WRAPS_CACHE_SIZE: usize = 4;
 
+/// How many paths of file-line references to remember the files of.
+const FILE_PATHS_CACHE_SIZE: usize = 256;
+
+/// The background of the selected line, when colors are shown.
+const SELECTED_BACKGROUND: AnsiColor = AnsiColor::Grey;
+
+lazy_static! {
+    /// Regex for detecting references to a line in a file, like
+    /// `path/to/file.rs:123`.
+    static ref FILE_LINE_REFERENCE: regex::Regex =
+        regex::Regex::new(r"(?:~?/|\.{1,2}/)?(?:[\w.-]+/)*[\w.-]*\w\.\w+:(\d+)(?::\d+)?\b")
+            .unwrap();
+
+    /// Regex for detecting markdown headings, matching the heading marker.
+    static ref MARKDOWN_HEADING: regex::Regex =
+        regex::Regex::new(r"^ {0,3}#{1,6}(?:[ \t]+|$)").unwrap();
+
+    /// Regex for detecting inline markdown code, strong emphasis and
+    /// emphasis, capturing the text within the markers.
+    static ref MARKDOWN_INLINE: regex::Regex = regex::Regex::new(
+        r"`([^`]+)`|\*\*(\S(?:[^*]*\S)?)\*\*|__(\S(?:[^_]*\S)?)__|\*(\S(?:[^*]*\S)?)\*|_(\S(?:[^_]*\S)?)_"
+    )
+    .unwrap();
+
+    /// The files that the paths of file-line references were found to be,
+    /// so that the file system is only checked once for each path, rather
+    /// than each time a line that mentions it is drawn.
+    static ref FILE_PATHS: Mutex<LruCache<String, Option<PathBuf>>> =
+        Mutex::new(LruCache::new(FILE_PATHS_CACHE_SIZE));
+}
+
 /// Line wrap in the cache are uniquely identified by index and wrapping mode.
 type WrapCacheIndex = (usize, WrappingMode);
 
@@ -46,6 +83,43 @@ type WrapCacheItemRef<'a> = &'a [(usize, usize)];
 pub(crate) struct Line {
     spans: Box<[Span]>,
     wraps: Arc<Mutex<LruCache<WrapCacheIndex, WrapCacheItem>>>,
+    tab_width: usize,
+    rendition: Rendition,
+}
+
+/// How the styles of lines are rendered.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub(crate) enum Rendition {
+    /// Colors and attributes are rendered.
+    #[default]
+    Color,
+    /// Colors are dropped, but attributes like bold and underline are kept.
+    Monochrome,
+    /// Colors and attributes are dropped, leaving only inverse video.
+    Plain,
+}
+
+impl Rendition {
+    /// Remove the parts of a set of attributes that aren't rendered.
+    fn apply(self, attrs: &mut CellAttributes) {
+        match self {
+            Rendition::Color => {}
+            Rendition::Monochrome => {
+                attrs
+                    .set_foreground(ColorAttribute::Default)
+                    .set_background(ColorAttribute::Default)
+                    .set_underline_color(ColorAttribute::Default);
+            }
+            Rendition::Plain => {
+                let mut plain = CellAttributes::default();
+                plain
+                    .set_reverse(attrs.reverse())
+                    .set_invisible(attrs.invisible())
+                    .set_hyperlink(attrs.hyperlink().cloned());
+                *attrs = plain;
+            }
+        }
+    }
 }
 
 /// Style that is being applied.
@@ -59,6 +133,59 @@ enum OutputStyle {
     Match,
     /// The currently selected search match.
     CurrentMatch,
+    /// Line ending markers (dim).
+    LineEnding,
+}
+
+/// Apply a sequence of Sgr escape codes onto a set of attributes.
+fn apply_sgr_sequence(attrs: &mut CellAttributes, sgr_sequence: &[Sgr]) {
+    for sgr in sgr_sequence.iter() {
+        match *sgr {
+            Sgr::Reset => {
+                // Reset doesn't clear the hyperlink.
+                let hyperlink = attrs.hyperlink().cloned();
+                *attrs = CellAttributes::default();
+                attrs.set_hyperlink(hyperlink);
+            }
+            Sgr::Intensity(intensity) => {
+                attrs.set_intensity(intensity);
+            }
+            Sgr::Underline(underline) => {
+                attrs.set_underline(underline);
+            }
+            Sgr::Blink(blink) => {
+                attrs.set_blink(blink);
+            }
+            Sgr::Italic(italic) => {
+                attrs.set_italic(italic);
+            }
+            Sgr::Inverse(inverse) => {
+                attrs.set_reverse(inverse);
+            }
+            Sgr::Invisible(invis) => {
+                attrs.set_invisible(invis);
+            }
+            Sgr::StrikeThrough(strike) => {
+                attrs.set_strikethrough(strike);
+            }
+            Sgr::Foreground(color) => {
+                attrs.set_foreground(color);
+            }
+            Sgr::Background(color) => {
+                attrs.set_background(color);
+            }
+            Sgr::Font(_) => {}
+            Sgr::UnderlineColor(color) => {
+                attrs.set_underline_color(color);
+            }
+            Sgr::Overline(enable) => {
+                attrs.set_overline(enable);
+            }
+            Sgr::VerticalAlign(align) => {
+                attrs.set_vertical_align(align);
+            }
+        }
+    }
 }
 
 /// Tracker of current attributes state.
@@ -73,69 +200,43 @@ struct AttributeState {
     style: OutputStyle,
     /// What color the end of the line should be
     end_of_line: ColorAttribute,
+    /// The style of the highlight rule that applies, if any
+    highlight: Option<Arc<[Sgr]>>,
+    /// How styles are rendered
+    rendition: Rendition,
+    /// Whether the line is the selected line, which is shown highlighted
+    selected: bool,
 }
 
 impl AttributeState {
     /// Create a new color state tracker.
-    fn new() -> AttributeState {
+    fn new(rendition: Rendition, selected: bool) -> AttributeState {
+        let end_of_line = if selected && rendition == Rendition::Color {
+            SELECTED_BACKGROUND.into()
+        } else {
+            ColorAttribute::default()
+        };
         AttributeState {
             attrs: CellAttributes::default(),
             line_drawing: false,
             changed: false,
             style: OutputStyle::File,
-            end_of_line: ColorAttribute::default(),
+            end_of_line,
+            highlight: None,
+            rendition,
+            selected,
         }
     }
 
     /// Apply a sequence of Sgr escape codes onto the attribute state.
     fn apply_sgr_sequence(&mut self, sgr_sequence: &[Sgr]) {
-        for sgr in sgr_sequence.iter() {
-            match *sgr {
-                Sgr::Reset => {
-                    // Reset doesn't clear the hyperlink.
-                    let hyperlink = self.attrs.hyperlink().cloned();
-                    self.attrs = CellAttributes::default();
-                    self.attrs.set_hyperlink(hyperlink);
-                }
-                Sgr::Intensity(intensity) => {
-                    self.attrs.set_intensity(intensity);
-                }
-                Sgr::Underline(underline) => {
-                    self.attrs.set_underline(underline);
-                }
-                Sgr::Blink(blink) => {
-                    self.attrs.set_blink(blink);
-                }
-                Sgr::Italic(italic) => {
-                    self.attrs.set_italic(italic);
-                }
-                Sgr::Inverse(inverse) => {
-                    self.attrs.set_reverse(inverse);
-                }
-                Sgr::Invisible(invis) => {
-                    self.attrs.set_invisible(invis);
-                }
-                Sgr::StrikeThrough(strike) => {
-                    self.attrs.set_strikethrough(strike);
-                }
-                Sgr::Foreground(color) => {
-                    self.attrs.set_foreground(color);
-                }
-                Sgr::Background(color) => {
-                    self.attrs.set_background(color);
-                }
-                Sgr::Font(_) => {}
-                Sgr::UnderlineColor(color) => {
-                    self.attrs.set_underline_color(color);
-                }
-                Sgr::Overline(enable) => {
-                    self.attrs.set_overline(enable);
-                }
-                Sgr::VerticalAlign(align) => {
-                    self.attrs.set_vertical_align(align);
-                }
-            }
-        }
+        apply_sgr_sequence(&mut self.attrs, sgr_sequence);
+        self.changed = true;
+    }
+
+    /// Start or stop applying the style of a highlight rule.
+    fn apply_highlight(&mut self, highlight: Option<Arc<[Sgr]>>) {
+        self.highlight = highlight;
         self.changed = true;
     }
 
@@ -148,23 +249,45 @@ impl AttributeState {
     /// Switch to the given style.  The correct escape color sequences will be emitted.
     fn style(&mut self, style: OutputStyle) -> Option<Change> {
         if self.style != style || self.changed {
+            let mut file_attrs = self.attrs.clone();
+            if let Some(highlight) = self.highlight.as_ref() {
+                apply_sgr_sequence(&mut file_attrs, highlight);
+            }
+            self.rendition.apply(&mut file_attrs);
+            let monochrome = self.rendition != Rendition::Color;
+            if self.selected && monochrome {
+                let reverse = !file_attrs.reverse();
+                file_attrs.set_reverse(reverse);
+            } else if self.selected {
+                file_attrs.set_background(SELECTED_BACKGROUND);
+            }
             let attrs = match style {
-                OutputStyle::File => self.attrs.clone(),
+                OutputStyle::File => file_attrs,
                 OutputStyle::Control => CellAttributes::default().set_reverse(true).clone(),
-                OutputStyle::Match => self
-                    .attrs
-                    .clone()
+                OutputStyle::Match if monochrome => {
+                    let reverse = !file_attrs.reverse();
+                    file_attrs.set_reverse(reverse).clone()
+                }
+                OutputStyle::CurrentMatch if monochrome => {
+                    let reverse = !file_attrs.reverse();
+                    file_attrs
+                        .set_reverse(reverse)
+                        .set_underline(Underline::Single)
+                        .clone()
+                }
+                OutputStyle::Match => file_attrs
                     .set_foreground(AnsiColor::Black)
                     .set_background(AnsiColor::Olive)
                     .set_intensity(Intensity::Normal)
                     .clone(),
-                OutputStyle::CurrentMatch => self
-                    .attrs
-                    .clone()
+                OutputStyle::CurrentMatch => file_attrs
                     .set_foreground(AnsiColor::Black)
                     .set_background(AnsiColor::Teal)
                     .set_intensity(Intensity::Normal)
                     .clone(),
+                OutputStyle::LineEnding => CellAttributes::default()
+                    .set_intensity(Intensity::Half)
+                    .clone(),
             };
             self.style = style;
             self.changed = false;
@@ -192,6 +315,10 @@ enum Span {
     SgrSequence(SmallVec<[Sgr; 5]>),
     /// A hyperlink escape code.
     Hyperlink(Option<Arc<Hyperlink>>),
+    /// The start of text that matches a highlight rule, and the rule's style.
+    HighlightStart(Arc<[Sgr]>),
+    /// The end of text that matches a highlight rule.
+    HighlightEnd,
     /// A DEC line drawing mode escape code.
     LineDrawing(bool),
     /// Data that should be ignored.
@@ -202,6 +329,12 @@ enum Span {
     CrLf,
     /// A terminating LF sequence.
     Lf,
+    /// A visible marker for the line ending, e.g. `␍␊` for CRLF.
+    LineEnding(&'static str),
+    /// A tab control character shown with a marker.
+    MarkedTab,
+    /// A span shown in a particular style, e.g. highlighted whitespace.
+    Styled(Box<Span>, Arc<[Sgr]>),
     /// An erase-to-end-of-line sequence.
     EraseToEndOfLine,
 }
@@ -278,6 +411,19 @@ impl<'t> Iterator for SplitWords<'t> {
 }
 
 impl Span {
+    /// Returns the number of bytes the span has allocated.
+    fn heap_size(&self) -> usize {
+        match self {
+            Span::Text(text) | Span::Match(text, _) | Span::Unprintable(text) => text.capacity(),
+            Span::SgrSequence(sgrs) if sgrs.spilled() => {
+                sgrs.capacity() * std::mem::size_of::<Sgr>()
+            }
+            Span::Ignore(data) if data.spilled() => data.capacity(),
+            Span::Styled(span, _) => std::mem::size_of::<Span>() + span.heap_size(),
+            _ => 0,
+        }
+    }
+
     /// Render the span at the given position in the terminal.
     fn render(
         &self,
@@ -287,6 +433,7 @@ impl Span {
         end: usize,
         mut position: usize,
         search_index: Option<usize>,
+        tab_width: usize,
     ) -> usize {
         match *self {
             Span::Text(ref t) => {
@@ -326,13 +473,45 @@ impl Span {
                     position,
                 );
             }
+            Span::MarkedTab => {
+                let tabchars = tab_width - position % tab_width;
+                let marker = format!("\u{2192}{:1$}", "", tabchars - 1);
+                position = write_truncated(
+                    changes,
+                    attr_state,
+                    OutputStyle::File,
+                    &marker,
+                    start,
+                    end,
+                    position,
+                );
+            }
+            Span::Styled(ref span, ref style) => {
+                let highlight = attr_state.highlight.take();
+                attr_state.apply_highlight(Some(style.clone()));
+                position = span.render(
+                    changes,
+                    attr_state,
+                    start,
+                    end,
+                    position,
+                    search_index,
+                    tab_width,
+                );
+                attr_state.apply_highlight(highlight);
+            }
             Span::Tab => {
-                let tabchars = 8 - position % 8;
+                let tabchars = tab_width - position % tab_width;
+                let spaces = if tabchars <= TAB_SPACES.len() {
+                    Cow::Borrowed(&TAB_SPACES[..tabchars])
+                } else {
+                    Cow::Owned(" ".repeat(tabchars))
+                };
                 position = write_truncated(
                     changes,
                     attr_state,
                     OutputStyle::File,
-                    &TAB_SPACES[..tabchars],
+                    spaces.as_ref(),
                     start,
                     end,
                     position,
@@ -362,10 +541,27 @@ impl Span {
                     );
                 }
             }
+            Span::LineEnding(marker) => {
+                position = write_truncated(
+                    changes,
+                    attr_state,
+                    OutputStyle::LineEnding,
+                    marker,
+                    start,
+                    end,
+                    position,
+                );
+            }
             Span::SgrSequence(ref s) => attr_state.apply_sgr_sequence(s),
             Span::Hyperlink(ref l) => attr_state.apply_hyperlink(l),
+            Span::HighlightStart(ref h) => attr_state.apply_highlight(Some(h.clone())),
+            Span::HighlightEnd => attr_state.apply_highlight(None),
             Span::LineDrawing(e) => attr_state.line_drawing = e,
-            Span::EraseToEndOfLine => attr_state.end_of_line = attr_state.attrs.background(),
+            Span::EraseToEndOfLine
+                if attr_state.rendition == Rendition::Color && !attr_state.selected =>
+            {
+                attr_state.end_of_line = attr_state.attrs.background()
+            }
             _ => {}
         }
         position
@@ -378,6 +574,7 @@ impl Span {
         position: usize,
         width: usize,
         words: bool,
+        tab_width: usize,
     ) -> (usize, usize) {
         match self {
             Span::Text(text) | Span::Match(text, _) => {
@@ -432,8 +629,9 @@ impl Span {
                 }
                 (start, position)
             }
-            Span::Tab => {
-                let tabchars = 8 - position % 8;
+            Span::Styled(span, _) => span.split(rows, start, position, width, words, tab_width),
+            Span::Tab | Span::MarkedTab => {
+                let tabchars = tab_width - position % tab_width;
                 let end = position + tabchars;
                 if end - start <= width {
                     // This tab fits within this row
@@ -455,6 +653,17 @@ impl Span {
                     (position, end)
                 }
             }
+            Span::LineEnding(marker) => {
+                let end = position + marker.width();
+                if end - start <= width {
+                    // The marker fits within this row
+                    (start, end)
+                } else {
+                    // The marker wraps to the next row
+                    rows.push((start, position));
+                    (position, end)
+                }
+            }
             Span::Unprintable(_) => {
                 let end = position + 8;
                 if end - start <= width {
@@ -629,76 +838,857 @@ fn parse_spans(data: &[u8], match_index: Option<usize>) -> Vec<Span> {
     spans
 }
 
+/// Returns `data` with its escape sequences removed, leaving only the text.
+pub(crate) fn strip_escapes(data: &[u8]) -> Vec<u8> {
+    let mut stripped = Vec::with_capacity(data.len());
+    for span in parse_spans(&strip_operating_system_commands(data), None) {
+        match span {
+            Span::Text(t) | Span::Match(t, _) | Span::Unprintable(t) => {
+                stripped.extend_from_slice(t.as_bytes())
+            }
+            Span::Control(c) | Span::Invalid(c) => stripped.push(c),
+            Span::Tab => stripped.push(b'\t'),
+            Span::CrLf => stripped.extend_from_slice(b"\r\n"),
+            Span::Lf => stripped.push(b'\n'),
+            Span::SgrSequence(_)
+            | Span::Hyperlink(_)
+            | Span::HighlightStart(_)
+            | Span::HighlightEnd
+            | Span::LineDrawing(_)
+            | Span::Ignore(_)
+            | Span::LineEnding(_)
+            | Span::MarkedTab
+            | Span::Styled(..)
+            | Span::EraseToEndOfLine => {}
+        }
+    }
+    stripped
+}
+
+/// Returns `data` with its operating system commands (e.g. setting the
+/// terminal's title) removed.  Other than hyperlinks, the pager shows these
+/// as they are, so `parse_spans` leaves them in the text.
+fn strip_operating_system_commands(data: &[u8]) -> Cow<'_, [u8]> {
+    let mut stripped = Vec::new();
+    let mut copied = 0;
+    let mut index = 0;
+    while let Some(offset) = data[index..].windows(2).position(|w| w == b"\x1B]") {
+        index += offset;
+        let mut parser = Parser::new();
+        match parser.parse_first_as_vec(&data[index..]) {
+            Some((actions, len))
+                if matches!(actions.first(), Some(Action::OperatingSystemCommand(_))) =>
+            {
+                stripped.extend_from_slice(&data[copied..index]);
+                index += len;
+                copied = index;
+            }
+            _ => index += 1,
+        }
+    }
+    if copied == 0 {
+        return Cow::Borrowed(data);
+    }
+    stripped.extend_from_slice(&data[copied..]);
+    Cow::Owned(stripped)
+}
+
+/// The styles used to show whitespace.
+#[derive(Debug, Clone)]
+struct WhitespaceStyles {
+    /// The style of whitespace at the end of a line.
+    trailing: Arc<[Sgr]>,
+
+    /// The style of indentation that mixes tabs and spaces.
+    mixed_indent: Arc<[Sgr]>,
+
+    /// The style of the markers shown for tabs.
+    tab: Arc<[Sgr]>,
+}
+
+/// Decorations that are applied to lines when they are loaded.
+#[derive(Debug, Default, Clone)]
+pub(crate) struct LineDecorations {
+    /// Regular expressions to highlight, and the style to highlight them in.
+    highlights: Vec<(regex::Regex, Arc<[Sgr]>)>,
+
+    /// Whether references to lines in files are turned into hyperlinks.
+    linkify_file_paths: bool,
+
+    /// The number of columns between tab stops.
+    tab_width: usize,
+
+    /// Whether markdown constructs are rendered.
+    markdown: bool,
+
+    /// Whether the type of each line's ending is shown.
+    show_line_endings: bool,
+
+    /// Whether carriage returns at the end of lines are hidden.
+    hide_trailing_cr: bool,
+
+    /// Whether whitespace is shown.
+    show_whitespace: bool,
+
+    /// The styles used to show whitespace.
+    whitespace_styles: Option<WhitespaceStyles>,
+
+    /// How the styles of lines are rendered.
+    rendition: Rendition,
+}
+
+impl LineDecorations {
+    /// Create the line decorations specified by the configuration and the
+    /// file's profile, if it has one.  The profile's highlight rules take
+    /// precedence over the configuration's.
+    ///
+    /// Highlight rules with an invalid pattern or color are skipped, as are
+    /// invalid whitespace colors, and a description of each problem is added
+    /// to `errors`.
+    pub(crate) fn new(
+        config: &Config,
+        profile: Option<&Profile>,
+        errors: &mut Vec<String>,
+    ) -> LineDecorations {
+        let profile_rules = profile.map(|profile| profile.highlight_rules.as_slice());
+        let mut highlights = Vec::new();
+        for rule in profile_rules
+            .unwrap_or_default()
+            .iter()
+            .chain(config.highlight_rules.iter())
+        {
+            let highlight = regex::Regex::new(&rule.pattern)
+                .map_err(Error::from)
+                .and_then(|regex| Ok((regex, highlight_style(rule)?)));
+            match highlight {
+                Ok(highlight) => highlights.push(highlight),
+                Err(e) => errors.push(format!(
+                    "Invalid highlight rule '{}': {}",
+                    rule.pattern,
+                    match e {
+                        Error::Regex(e) => e.to_string(),
+                        e => e.to_string(),
+                    }
+                )),
+            }
+        }
+        let tab_width = profile
+            .and_then(|profile| profile.tab_width)
+            .unwrap_or(config.tab_width)
+            .max(1);
+        let rendition = if config.use_color() {
+            Rendition::Color
+        } else if config.monochrome_attributes {
+            Rendition::Monochrome
+        } else {
+            Rendition::Plain
+        };
+        // Without color, whitespace is highlighted in inverse video.  If a
+        // color is invalid, that kind of whitespace is shown without it.
+        let mut whitespace_color = |setting, name| -> Arc<[Sgr]> {
+            match rendition {
+                Rendition::Color => setting_color(setting, name, errors)
+                    .map(Sgr::Background)
+                    .into_iter()
+                    .collect(),
+                _ => vec![Sgr::Inverse(true)].into(),
+            }
+        };
+        let trailing = whitespace_color(
+            "trailing_whitespace_color",
+            &config.trailing_whitespace_color,
+        );
+        let mixed_indent = whitespace_color("mixed_indent_color", &config.mixed_indent_color);
+        let tab = setting_color("tab_marker_color", &config.tab_marker_color, errors)
+            .map(Sgr::Foreground)
+            .into_iter()
+            .collect();
+        LineDecorations {
+            highlights,
+            linkify_file_paths: config.linkify_file_paths,
+            tab_width,
+            markdown: false,
+            show_line_endings: config.show_line_endings,
+            hide_trailing_cr: config.hide_trailing_cr,
+            show_whitespace: config.show_whitespace,
+            whitespace_styles: Some(WhitespaceStyles {
+                trailing,
+                mixed_indent,
+                tab,
+            }),
+            rendition,
+        }
+    }
+
+    /// Returns the number of columns between tab stops.
+    pub(crate) fn tab_width(&self) -> usize {
+        self.tab_width
+    }
+
+    /// Returns true if markdown constructs are rendered.
+    pub(crate) fn markdown(&self) -> bool {
+        self.markdown
+    }
+
+    /// Set whether markdown constructs are rendered.
+    pub(crate) fn set_markdown(&mut self, markdown: bool) {
+        self.markdown = markdown;
+    }
+
+    /// Returns true if the type of each line's ending is shown.
+    pub(crate) fn show_line_endings(&self) -> bool {
+        self.show_line_endings
+    }
+
+    /// Set whether the type of each line's ending is shown.
+    pub(crate) fn set_show_line_endings(&mut self, show_line_endings: bool) {
+        self.show_line_endings = show_line_endings;
+    }
+
+    /// Returns true if whitespace is shown.
+    pub(crate) fn show_whitespace(&self) -> bool {
+        self.show_whitespace
+    }
+
+    /// Set whether whitespace is shown.
+    pub(crate) fn set_show_whitespace(&mut self, show_whitespace: bool) {
+        self.show_whitespace = show_whitespace;
+    }
+
+    /// Returns true if there are no decorations to apply.
+    pub(crate) fn is_empty(&self) -> bool {
+        self.highlights.is_empty()
+            && !self.linkify_file_paths
+            && self.tab_width == DEFAULT_TAB_WIDTH
+            && !self.markdown
+            && !self.show_line_endings
+            && !self.hide_trailing_cr
+            && !self.show_whitespace
+            && self.rendition == Rendition::Color
+    }
+}
+
+/// Parse a color name from a highlight rule.
+fn parse_color(name: &str) -> Result<ColorSpec, Error> {
+    let color = match name.to_lowercase().as_ref() {
+        "black" => AnsiColor::Black,
+        "red" => AnsiColor::Maroon,
+        "green" => AnsiColor::Green,
+        "yellow" => AnsiColor::Olive,
+        "blue" => AnsiColor::Navy,
+        "magenta" => AnsiColor::Purple,
+        "cyan" => AnsiColor::Teal,
+        "white" => AnsiColor::Silver,
+        "bright-black" => AnsiColor::Grey,
+        "bright-red" => AnsiColor::Red,
+        "bright-green" => AnsiColor::Lime,
+        "bright-yellow" => AnsiColor::Yellow,
+        "bright-blue" => AnsiColor::Blue,
+        "bright-magenta" => AnsiColor::Fuchsia,
+        "bright-cyan" => AnsiColor::Aqua,
+        "bright-white" => AnsiColor::White,
+        _ => {
+            return RgbColor::from_rgb_str(name)
+                .map(ColorSpec::from)
+                .ok_or_else(|| Error::InvalidColor(name.to_string()))
+        }
+    };
+    Ok(color.into())
+}
+
+/// Parses the color given for the setting `setting`.  If it is invalid, a
+/// description of the problem is added to `errors`.
+fn setting_color(setting: &str, name: &str, errors: &mut Vec<String>) -> Option<ColorSpec> {
+    match parse_color(name) {
+        Ok(color) => Some(color),
+        Err(e) => {
+            errors.push(format!("Invalid {}: {}", setting, e));
+            None
+        }
+    }
+}
+
+/// Returns the escape codes that produce a highlight rule's style.
+fn highlight_style(rule: &HighlightRule) -> Result<Arc<[Sgr]>, Error> {
+    style_sgrs(
+        rule.foreground.as_deref(),
+        rule.background.as_deref(),
+        rule.bold,
+        rule.underline,
+    )
+}
+
+/// Returns the escape codes that produce a style with the given colors and
+/// attributes.
+fn style_sgrs(
+    foreground: Option<&str>,
+    background: Option<&str>,
+    bold: bool,
+    underline: bool,
+) -> Result<Arc<[Sgr]>, Error> {
+    let mut style = Vec::new();
+    if let Some(foreground) = foreground {
+        style.push(Sgr::Foreground(parse_color(foreground)?));
+    }
+    if let Some(background) = background {
+        style.push(Sgr::Background(parse_color(background)?));
+    }
+    if bold {
+        style.push(Sgr::Intensity(Intensity::Bold));
+    }
+    if underline {
+        style.push(Sgr::Underline(Underline::Single));
+    }
+    Ok(style.into())
+}
+
+/// Returns the cell attributes that show text in `style`.
+pub(crate) fn text_style_attributes(style: &TextStyle) -> Result<CellAttributes, Error> {
+    let mut attributes = CellAttributes::default();
+    if let Some(foreground) = style.foreground.as_ref() {
+        attributes.set_foreground(parse_color(foreground)?);
+    }
+    if let Some(background) = style.background.as_ref() {
+        attributes.set_background(parse_color(background)?);
+    }
+    if style.bold {
+        attributes.set_intensity(Intensity::Bold);
+    }
+    if style.underline {
+        attributes.set_underline(Underline::Single);
+    }
+    Ok(attributes)
+}
+
+/// Returns the escape sequence that starts text in `style`, to be added to
+/// the start of lines that should be shown in that style.
+pub(crate) fn text_style_prefix(style: &TextStyle) -> Result<Vec<u8>, Error> {
+    let sgrs = style_sgrs(
+        style.foreground.as_deref(),
+        style.background.as_deref(),
+        style.bold,
+        style.underline,
+    )?;
+    Ok(sgrs
+        .iter()
+        .map(|sgr| CSI::Sgr(sgr.clone()).to_string())
+        .collect::<String>()
+        .into_bytes())
+}
+
+/// Marks the text that matches highlight rules with the rules' styles.
+///
+/// Rules are matched against the text of the whole line, ignoring escape
+/// sequences, so highlighted text may span multiple text spans.
+fn highlight_spans(spans: Vec<Span>, highlights: &[(regex::Regex, Arc<[Sgr]>)]) -> Vec<Span> {
+    let text: String = spans
+        .iter()
+        .filter_map(|span| match span {
+            Span::Text(t) | Span::Match(t, _) => Some(t.as_str()),
+            _ => None,
+        })
+        .collect();
+
+    // Find the ranges to highlight.  Earlier rules take precedence.
+    let mut ranges: Vec<(usize, usize, &Arc<[Sgr]>)> = Vec::new();
+    for (regex, style) in highlights {
+        for m in regex.find_iter(&text) {
+            if m.start() < m.end()
+                && ranges
+                    .iter()
+                    .all(|&(start, end, _)| m.end() <= start || m.start() >= end)
+            {
+                ranges.push((m.start(), m.end(), style));
+            }
+        }
+    }
+    if ranges.is_empty() {
+        return spans;
+    }
+    ranges.sort_by_key(|&(start, _, _)| start);
+    let mut boundaries = ranges
+        .into_iter()
+        .flat_map(|(start, end, style)| {
+            vec![
+                (start, Span::HighlightStart(style.clone())),
+                (end, Span::HighlightEnd),
+            ]
+        })
+        .peekable();
+
+    let mut highlighted_spans = Vec::with_capacity(spans.len() + 2);
+    let mut offset = 0;
+    for span in spans {
+        let (text, match_index) = match span {
+            Span::Text(t) => (t, None),
+            Span::Match(t, match_index) => (t, Some(match_index)),
+            span => {
+                highlighted_spans.push(span);
+                continue;
+            }
+        };
+        let end = offset + text.len();
+        let mut position = offset;
+        let push_text = |spans: &mut Vec<Span>, start: usize, end: usize| {
+            if start < end {
+                let piece = text[start - offset..end - offset].to_string();
+                spans.push(match match_index {
+                    Some(match_index) => Span::Match(piece, match_index),
+                    None => Span::Text(piece),
+                });
+            }
+        };
+        while let Some((boundary, _)) = boundaries.peek() {
+            if *boundary >= end {
+                break;
+            }
+            let (boundary, boundary_span) = boundaries.next().expect("boundary was peeked");
+            push_text(&mut highlighted_spans, position, boundary);
+            highlighted_spans.push(boundary_span);
+            position = boundary;
+        }
+        push_text(&mut highlighted_spans, position, end);
+        offset = end;
+    }
+    highlighted_spans.extend(boundaries.map(|(_, span)| span));
+    highlighted_spans
+}
+
+/// Returns a `file://` URL for the line `line_number` of the file at `path`,
+/// if it exists.
+fn file_line_url(path: &str, line_number: &str) -> Option<String> {
+    let mut file_paths = FILE_PATHS.lock().unwrap();
+    let file = match file_paths.get(path) {
+        Some(file) => file.clone(),
+        None => {
+            let file = file_path(path);
+            file_paths.put(path.to_string(), file.clone());
+            file
+        }
+    };
+    Some(format!("file://{}#L{}", file?.display(), line_number))
+}
+
+/// Returns the absolute path of the file at `path`, if it exists.
+fn file_path(path: &str) -> Option<PathBuf> {
+    let path = match path.strip_prefix("~/") {
+        Some(rest) => dirs::home_dir()?.join(rest),
+        None => Path::new(path).to_path_buf(),
+    };
+    let path = std::env::current_dir().ok()?.join(path);
+    if path.is_file() {
+        Some(path)
+    } else {
+        None
+    }
+}
+
+/// Removes carriage returns at the end of the line, which would otherwise be
+/// shown as `<0D>`.  These are left behind by, for example, lines ending in
+/// CRCRLF, or output that ends with a carriage return.
+fn hide_trailing_cr(mut spans: Vec<Span>) -> Vec<Span> {
+    let mut index = spans.len();
+    while index > 0 {
+        index -= 1;
+        match spans[index] {
+            Span::Control(b'\r') => {
+                spans.remove(index);
+            }
+            Span::Lf | Span::CrLf | Span::SgrSequence(_) | Span::EraseToEndOfLine => {}
+            _ => break,
+        }
+    }
+    spans
+}
+
+/// Highlights whitespace at the end of the line and indentation that mixes
+/// tabs and spaces, and shows tabs with a marker.
+fn mark_whitespace(spans: Vec<Span>, styles: &WhitespaceStyles) -> Vec<Span> {
+    // Split the spaces at the start and end of text into their own spans.
+    let mut split = Vec::with_capacity(spans.len());
+    for span in spans {
+        match span {
+            Span::Text(text) => {
+                let start = text.len() - text.trim_start_matches(' ').len();
+                let end = text.trim_end_matches(' ').len().max(start);
+                for piece in [&text[..start], &text[start..end], &text[end..]].iter() {
+                    if !piece.is_empty() {
+                        split.push(Span::Text(piece.to_string()));
+                    }
+                }
+            }
+            span => split.push(span),
+        }
+    }
+
+    // Whitespace is any text or tab span before the first or after the last
+    // span with visible content.  Lines with no content are all trailing
+    // whitespace.
+    let is_content = |span: &Span| match span {
+        Span::Text(text) => !text.bytes().all(|b| b == b' '),
+        Span::Match(..) | Span::Control(_) | Span::Invalid(_) | Span::Unprintable(_) => true,
+        _ => false,
+    };
+    let first = split.iter().position(is_content).unwrap_or(split.len());
+    let last = split
+        .iter()
+        .rposition(is_content)
+        .map_or(0, |index| index + 1);
+    let indent = &split[..first];
+    let mixed = indent.iter().any(|span| matches!(span, Span::Tab))
+        && indent.iter().any(|span| matches!(span, Span::Text(_)));
+
+    split
+        .into_iter()
+        .enumerate()
+        .map(|(index, span)| {
+            let style = if index >= last {
+                Some(&styles.trailing)
+            } else if index < first && mixed {
+                Some(&styles.mixed_indent)
+            } else {
+                None
+            };
+            match (span, style) {
+                (Span::Tab, Some(style)) => Span::Styled(
+                    Box::new(Span::MarkedTab),
+                    style.iter().chain(styles.tab.iter()).cloned().collect(),
+                ),
+                (Span::Tab, None) => Span::Styled(Box::new(Span::MarkedTab), styles.tab.clone()),
+                (span @ Span::Text(_), Some(style)) => Span::Styled(Box::new(span), style.clone()),
+                (span, _) => span,
+            }
+        })
+        .collect()
+}
+
+/// Replaces each line's ending with a marker showing its type.
+fn show_line_endings(spans: Vec<Span>) -> Vec<Span> {
+    spans
+        .into_iter()
+        .map(|span| match span {
+            Span::Lf => Span::LineEnding("\u{240A}"),
+            Span::CrLf => Span::LineEnding("\u{240D}\u{240A}"),
+            span => span,
+        })
+        .collect()
+}
+
+/// Wraps references to lines in existing files that are not already part of
+/// a hyperlink in hyperlinks to those files.
+fn linkify_file_paths(spans: Vec<Span>) -> Vec<Span> {
+    let mut linked_spans = Vec::with_capacity(spans.len());
+    let mut in_hyperlink = false;
+    for span in spans {
+        match span {
+            Span::Hyperlink(ref link) => {
+                in_hyperlink = link.is_some();
+                linked_spans.push(span);
+            }
+            Span::Text(ref text) if !in_hyperlink => {
+                let mut start = 0;
+                for captures in FILE_LINE_REFERENCE.captures_iter(text) {
+                    let reference = captures.get(0).expect("regex should have matched");
+                    let line_number = captures.get(1).expect("regex should have matched");
+                    // Skip things that look like parts of URLs or addresses,
+                    // e.g. `example.com:8080`.
+                    if matches!(
+                        text[..reference.start()].chars().last(),
+                        Some(':' | '/' | '@')
+                    ) {
+                        continue;
+                    }
+                    let path = &text[reference.start()..line_number.start() - 1];
+                    if let Some(url) = file_line_url(path, line_number.as_str()) {
+                        if start < reference.start() {
+                            linked_spans
+                                .push(Span::Text(text[start..reference.start()].to_string()));
+                        }
+                        linked_spans.push(Span::Hyperlink(Some(Arc::new(Hyperlink::new(url)))));
+                        linked_spans.push(Span::Text(reference.as_str().to_string()));
+                        linked_spans.push(Span::Hyperlink(None));
+                        start = reference.end();
+                    }
+                }
+                if start == 0 {
+                    linked_spans.push(span);
+                } else if start < text.len() {
+                    linked_spans.push(Span::Text(text[start..].to_string()));
+                }
+            }
+            span => linked_spans.push(span),
+        }
+    }
+    linked_spans
+}
+
+/// Returns true if the line is a markdown code fence, which starts or ends
+/// a code block.
+pub(crate) fn is_code_fence(data: &[u8]) -> bool {
+    let data = &data[..trim_trailing_newline(data)];
+    let indent = data.iter().take_while(|&&b| b == b' ').count();
+    indent < 4 && (data[indent..].starts_with(b"```") || data[indent..].starts_with(b"~~~"))
+}
+
+/// Replaces ranges of the text of a line with other spans.  Each edit
+/// removes the text between its start and end offsets, and inserts its spans
+/// in its place.  Edits must be sorted and must not overlap.
+fn edit_spans(spans: Vec<Span>, edits: Vec<(usize, usize, Vec<Span>)>) -> Vec<Span> {
+    let mut edits = edits.into_iter().peekable();
+    let mut edited_spans = Vec::with_capacity(spans.len() + 4);
+    let mut offset = 0;
+    let mut removed_until = 0;
+    for span in spans {
+        let (text, match_index) = match span {
+            Span::Text(t) => (t, None),
+            Span::Match(t, match_index) => (t, Some(match_index)),
+            span => {
+                edited_spans.push(span);
+                continue;
+            }
+        };
+        let end = offset + text.len();
+        let mut position = removed_until.clamp(offset, end);
+        let push_text = |spans: &mut Vec<Span>, start: usize, end: usize| {
+            if start < end {
+                let piece = text[start - offset..end - offset].to_string();
+                spans.push(match match_index {
+                    Some(match_index) => Span::Match(piece, match_index),
+                    None => Span::Text(piece),
+                });
+            }
+        };
+        while let Some((start, _, _)) = edits.peek() {
+            if *start >= end {
+                break;
+            }
+            let (start, edit_end, replacement) = edits.next().expect("edit was peeked");
+            push_text(&mut edited_spans, position, start.max(position));
+            edited_spans.extend(replacement);
+            removed_until = edit_end;
+            position = edit_end.clamp(position, end);
+        }
+        push_text(&mut edited_spans, position, end);
+        offset = end;
+    }
+    edited_spans.extend(edits.flat_map(|(_, _, replacement)| replacement));
+    edited_spans
+}
+
+/// Renders simple markdown constructs in a line using terminal attributes.
+///
+/// Headings are shown in bold, and level 1 headings are also underlined.
+/// Emphasis is shown in italics, strong emphasis in bold, and code in
+/// color.  The markers for these constructs are removed, except for code
+/// fences.  Lines in code blocks are shown as code, without any other
+/// markdown rendering.
+fn render_markdown(spans: Vec<Span>, in_code_block: bool) -> Vec<Span> {
+    let code_start = || {
+        vec![Span::SgrSequence(smallvec::smallvec![Sgr::Foreground(
+            AnsiColor::Teal.into()
+        )])]
+    };
+    let code_end = || {
+        vec![Span::SgrSequence(smallvec::smallvec![Sgr::Foreground(
+            ColorSpec::Default
+        )])]
+    };
+    let text: String = spans
+        .iter()
+        .filter_map(|span| match span {
+            Span::Text(t) | Span::Match(t, _) => Some(t.as_str()),
+            _ => None,
+        })
+        .collect();
+
+    let mut edits = Vec::new();
+    if in_code_block || is_code_fence(text.as_bytes()) {
+        edits.push((0, 0, code_start()));
+        edits.push((text.len(), text.len(), code_end()));
+        return edit_spans(spans, edits);
+    }
+
+    let mut heading = false;
+    if let Some(m) = MARKDOWN_HEADING.find(&text) {
+        heading = true;
+        let mut style = smallvec::smallvec![Sgr::Intensity(Intensity::Bold)];
+        if m.as_str().matches('#').count() == 1 {
+            style.push(Sgr::Underline(Underline::Single));
+        }
+        edits.push((0, m.end(), vec![Span::SgrSequence(style)]));
+    }
+    for captures in MARKDOWN_INLINE.captures_iter(&text) {
+        let whole = captures.get(0).expect("regex should have matched");
+        let (inner, start, end) = if let Some(code) = captures.get(1) {
+            (code, code_start(), code_end())
+        } else if let Some(strong) = captures.get(2).or_else(|| captures.get(3)) {
+            // Strong emphasis in a heading would end the heading's boldness.
+            let end = if heading {
+                Intensity::Bold
+            } else {
+                Intensity::Normal
+            };
+            (
+                strong,
+                vec![Span::SgrSequence(smallvec::smallvec![Sgr::Intensity(
+                    Intensity::Bold
+                )])],
+                vec![Span::SgrSequence(smallvec::smallvec![Sgr::Intensity(end)])],
+            )
+        } else if let Some(emphasis) = captures.get(4).or_else(|| captures.get(5)) {
+            // Underscores within words, like `snake_case`, are not emphasis.
+            if captures.get(5).is_some()
+                && (text[..whole.start()].ends_with(|c: char| c.is_alphanumeric())
+                    || text[whole.end()..].starts_with(|c: char| c.is_alphanumeric()))
+            {
+                continue;
+            }
+            (
+                emphasis,
+                vec![Span::SgrSequence(smallvec::smallvec![Sgr::Italic(true)])],
+                vec![Span::SgrSequence(smallvec::smallvec![Sgr::Italic(false)])],
+            )
+        } else {
+            continue;
+        };
+        edits.push((whole.start(), inner.start(), start));
+        edits.push((inner.end(), whole.end(), end));
+    }
+    if heading {
+        edits.push((
+            text.len(),
+            text.len(),
+            vec![Span::SgrSequence(smallvec::smallvec![
+                Sgr::Intensity(Intensity::Normal),
+                Sgr::Underline(Underline::None)
+            ])],
+        ));
+    }
+    if edits.is_empty() {
+        return spans;
+    }
+    edit_spans(spans, edits)
+}
+
 impl Line {
     pub(crate) fn new(_index: usize, data: impl AsRef<[u8]>) -> Line {
         let data = overstrike::convert_overstrike(data.as_ref());
         let spans = parse_spans(&data[..], None).into_boxed_slice();
         let wraps = Arc::new(Mutex::new(LruCache::new(WRAPS_CACHE_SIZE)));
-        Line { spans, wraps }
+        Line {
+            spans,
+            wraps,
+            tab_width: DEFAULT_TAB_WIDTH,
+            rendition: Rendition::Color,
+        }
     }
 
-    pub(crate) fn new_search(_index: usize, data: impl AsRef<[u8]>, regex: &Regex) -> Line {
+    /// Create a line with the matches of a search marked.  If the search is
+    /// restricted to some columns, only matches within them are marked.
+    pub(crate) fn new_search(
+        _index: usize,
+        data: impl AsRef<[u8]>,
+        regex: &Regex,
+        columns: Option<&SearchColumns>,
+        normalization: SearchNormalization,
+    ) -> Line {
         let data = overstrike::convert_overstrike(data.as_ref());
         let len = trim_trailing_newline(data.as_ref());
         let mut spans = Vec::new();
         let mut start = 0;
-        let (data_without_escapes, convert_offset) = if ESCAPE_SEQUENCE.is_match(&data[..len]) {
-            let mut escape_ranges = Vec::new();
-            for match_range in ESCAPE_SEQUENCE.find_iter(&data[..len]) {
-                escape_ranges.push((match_range.start(), match_range.end()));
-            }
-            (
-                ESCAPE_SEQUENCE.replace_all(&data[..len], NoExpand(b"")),
-                Some(move |offset| {
-                    let mut original_offset = 0;
-                    let mut remaining_offset = offset;
-                    for (escape_start, escape_end) in escape_ranges.iter() {
-                        if original_offset + remaining_offset < *escape_start {
-                            break;
-                        } else {
-                            remaining_offset -= escape_start - original_offset;
-                            original_offset = *escape_end;
-                        }
-                    }
-                    original_offset + remaining_offset
-                }),
-            )
-        } else {
-            (Cow::Borrowed(&data[..len]), None)
-        };
-        for (match_index, match_range) in regex.find_iter(&data_without_escapes[..]).enumerate() {
-            let (match_start, match_end) = if let Some(ref convert) = convert_offset {
-                (convert(match_range.start()), convert(match_range.end()))
-            } else {
-                (match_range.start(), match_range.end())
-            };
-            if start < match_start {
-                spans.append(&mut parse_spans(&data[start..match_start], None));
+        let (data_without_escapes, escape_offsets) = EscapeOffsets::strip(&data[..len]);
+        let matches = find_matches(&data_without_escapes, regex, columns, normalization);
+        for (match_index, match_range) in matches.into_iter().enumerate() {
+            let match_range = escape_offsets.range(match_range);
+            if start < match_range.start {
+                spans.append(&mut parse_spans(&data[start..match_range.start], None));
             }
             spans.append(&mut parse_spans(
-                &data[match_start..match_end],
+                &data[match_range.clone()],
                 Some(match_index),
             ));
-            start = match_end;
+            start = match_range.end;
         }
         if start < data.len() {
             spans.append(&mut parse_spans(&data[start..], None));
         }
         let spans = spans.into_boxed_slice();
         let wraps = Arc::new(Mutex::new(LruCache::new(WRAPS_CACHE_SIZE)));
-        Line { spans, wraps }
+        Line {
+            spans,
+            wraps,
+            tab_width: DEFAULT_TAB_WIDTH,
+            rendition: Rendition::Color,
+        }
+    }
+
+    /// Returns an estimate of the memory used by the line, in bytes,
+    /// including the wrapping of the line that has been cached so far.
+    pub(crate) fn memory_size(&self) -> usize {
+        let wraps = self.wraps.lock().unwrap();
+        std::mem::size_of::<Line>()
+            + self
+                .spans
+                .iter()
+                .map(|span| std::mem::size_of::<Span>() + span.heap_size())
+                .sum::<usize>()
+            + wraps
+                .iter()
+                .map(|(_, rows)| {
+                    std::mem::size_of::<(WrapCacheIndex, WrapCacheItem)>()
+                        + rows.capacity() * std::mem::size_of::<(usize, usize)>()
+                })
+                .sum::<usize>()
+    }
+
+    /// Applies highlight rules and other decorations to the line.
+    /// `in_code_block` is whether the line is within a markdown code block.
+    pub(crate) fn decorate(self, decorations: &LineDecorations, in_code_block: bool) -> Line {
+        if decorations.is_empty() {
+            return self;
+        }
+        let mut spans = self.spans.into_vec();
+        if decorations.markdown {
+            spans = render_markdown(spans, in_code_block);
+        }
+        if !decorations.highlights.is_empty() {
+            spans = highlight_spans(spans, &decorations.highlights);
+        }
+        if decorations.linkify_file_paths {
+            spans = linkify_file_paths(spans);
+        }
+        if decorations.hide_trailing_cr {
+            spans = hide_trailing_cr(spans);
+        }
+        if decorations.show_line_endings {
+            spans = show_line_endings(spans);
+        }
+        if let (true, Some(styles)) = (
+            decorations.show_whitespace,
+            decorations.whitespace_styles.as_ref(),
+        ) {
+            spans = mark_whitespace(spans, styles);
+        }
+        Line {
+            spans: spans.into_boxed_slice(),
+            wraps: self.wraps,
+            tab_width: decorations.tab_width,
+            rendition: decorations.rendition,
+        }
     }
 
     /// Produce the `Change`s needed to render a slice of the line on a terminal.
+    /// The selected line is highlighted.
     pub(crate) fn render(
         &self,
         changes: &mut Vec<Change>,
         start: usize,
         end: usize,
         search_index: Option<usize>,
+        selected: bool,
     ) {
         let mut start = start;
-        let mut attr_state = AttributeState::new();
+        let mut attr_state = AttributeState::new(self.rendition, selected);
         let mut position = 0;
         if start > 0 {
             changes.push(Change::AllAttributes(
@@ -712,7 +1702,15 @@ impl Line {
             start += 1;
         }
         for span in self.spans.iter() {
-            position = span.render(changes, &mut attr_state, start, end, position, search_index);
+            position = span.render(
+                changes,
+                &mut attr_state,
+                start,
+                end,
+                position,
+                search_index,
+                self.tab_width,
+            );
         }
         match position.cmp(&end) {
             Ordering::Greater => {
@@ -751,6 +1749,7 @@ impl Line {
         width: usize,
         wrapping: WrappingMode,
         search_index: Option<usize>,
+        selected: bool,
     ) {
         let (start, end) = {
             fn wrap_bounds_for_rows(
@@ -774,10 +1773,18 @@ impl Line {
                 (start, end)
             }
         };
-        let mut attr_state = AttributeState::new();
+        let mut attr_state = AttributeState::new(self.rendition, selected);
         let mut position = 0;
         for span in self.spans.iter() {
-            position = span.render(changes, &mut attr_state, start, end, position, search_index);
+            position = span.render(
+                changes,
+                &mut attr_state,
+                start,
+                end,
+                position,
+                search_index,
+                self.tab_width,
+            );
         }
         if end - start < width * row_count {
             changes.push(Change::ClearToEndOfLine(attr_state.end_of_line));
@@ -792,7 +1799,7 @@ impl Line {
             WrappingMode::Unwrapped => {
                 rows.push((0, std::usize::MAX));
             }
-            WrappingMode::GraphemeBoundary | WrappingMode::WordBoundary => {
+            WrappingMode::GraphemeBoundary | WrappingMode::WordBoundary | WrappingMode::Column => {
                 let mut start = 0;
                 let mut position = 0;
                 for span in self.spans.iter() {
@@ -801,7 +1808,8 @@ impl Line {
                         start,
                         position,
                         width,
-                        wrapping == WrappingMode::WordBoundary,
+                        wrapping != WrappingMode::GraphemeBoundary,
+                        self.tab_width,
                     );
                     start = new_start;
                     position = new_position;
@@ -814,6 +1822,105 @@ impl Line {
         rows
     }
 
+    /// Returns the number of columns the line occupies if it is not wrapped.
+    pub(crate) fn width(&self) -> usize {
+        let mut rows = Vec::new();
+        self.spans.iter().fold(0, |position, span| {
+            span.split(&mut rows, 0, position, usize::MAX, false, self.tab_width)
+                .1
+        })
+    }
+
+    /// Returns the start and end columns of the search match with the given
+    /// index, if the line contains it.
+    pub(crate) fn match_columns(&self, match_index: usize) -> Option<(usize, usize)> {
+        let mut rows = Vec::new();
+        let mut position = 0;
+        let mut columns: Option<(usize, usize)> = None;
+        for span in self.spans.iter() {
+            let end = span
+                .split(&mut rows, 0, position, usize::MAX, false, self.tab_width)
+                .1;
+            if let Span::Match(_, index) = span {
+                if *index == match_index {
+                    columns = Some((columns.map_or(position, |(start, _)| start), end));
+                }
+            }
+            position = end;
+        }
+        columns
+    }
+
+    /// Returns the columns at which a field of the line starts, i.e. where
+    /// non-blank text follows blank text, in order.
+    pub(crate) fn column_starts(&self) -> Vec<usize> {
+        let mut starts = Vec::new();
+        let mut rows = Vec::new();
+        let mut position = 0;
+        let mut blank = true;
+        for span in self.spans.iter() {
+            let span = match span {
+                Span::Styled(span, _) => span.as_ref(),
+                span => span,
+            };
+            match span {
+                Span::Text(text) | Span::Match(text, _) => {
+                    for grapheme in text.graphemes(true) {
+                        let is_blank = grapheme.chars().all(char::is_whitespace);
+                        if blank && !is_blank && position > 0 {
+                            starts.push(position);
+                        }
+                        blank = is_blank;
+                        position += grapheme.width();
+                    }
+                }
+                Span::Tab | Span::MarkedTab => {
+                    blank = true;
+                    position = span
+                        .split(&mut rows, 0, position, usize::MAX, false, self.tab_width)
+                        .1;
+                }
+                span => {
+                    let end = span
+                        .split(&mut rows, 0, position, usize::MAX, false, self.tab_width)
+                        .1;
+                    if end > position {
+                        if blank && position > 0 {
+                            starts.push(position);
+                        }
+                        blank = false;
+                        position = end;
+                    }
+                }
+            }
+        }
+        starts
+    }
+
+    /// Returns the row that `column` is on when this line is wrapped at the
+    /// given width.
+    pub(crate) fn row_at_column(
+        &self,
+        column: usize,
+        width: usize,
+        wrapping: WrappingMode,
+    ) -> usize {
+        if wrapping == WrappingMode::Unwrapped {
+            return 0;
+        }
+        let mut wraps = self.wraps.lock().unwrap();
+        if wraps.get(&(width, wrapping)).is_none() {
+            let rows = self.make_wrap(width, wrapping);
+            wraps.put((width, wrapping), rows);
+        }
+        let rows = wraps
+            .get(&(width, wrapping))
+            .expect("wrap should be cached");
+        rows.iter()
+            .position(|&(_, end)| column < end)
+            .unwrap_or_else(|| rows.len().saturating_sub(1))
+    }
+
     /// Returns the number of rows for this line if wrapped at the given width
     pub(crate) fn height(&self, width: usize, wrapping: WrappingMode) -> usize {
         if wrapping == WrappingMode::Unwrapped {
@@ -1011,4 +2118,353 @@ mod test {
             vec![(0, 38), (38, 60)],
         );
     }
+
+    #[test]
+    fn test_strip_escapes() {
+        assert_eq!(strip_escapes(b"plain text\n"), b"plain text\n");
+        assert_eq!(
+            strip_escapes(b"\x1B[1;31mred\x1B[m and\tbold\r\n"),
+            b"red and\tbold\r\n"
+        );
+        assert_eq!(
+            strip_escapes(b"\x1B]8;;http://example.com/\x1B\\link\x1B]8;;\x1B\\"),
+            b"link"
+        );
+        assert_eq!(strip_escapes(b"\x1B]0;title\x07text\x1B[K"), b"text");
+        assert_eq!(strip_escapes(b"\x1B]"), b"\x1B]");
+
+        // The pager shows operating system commands other than hyperlinks.
+        assert_eq!(
+            parse_spans(b"\x1B]0;title\x07text", None),
+            [
+                Span::Control(0x1B),
+                Span::Text("]0;title".to_string()),
+                Span::Control(0x07),
+                Span::Text("text".to_string()),
+            ]
+        );
+        assert_eq!(strip_escapes(b"bad\xE0\x01"), b"bad\xE0\x01");
+    }
+
+    #[test]
+    fn test_new_search() {
+        let regex = Regex::new("fo+|\tb|\u{4E2D}").unwrap();
+        let line = Line::new_search(
+            0,
+            "\x1B[31mfo\x1B[1mo\x1B[m bar\tbaz \u{4E2D}\u{6587}\n".as_bytes(),
+            &regex,
+            None,
+            SearchNormalization::None,
+        );
+        assert_eq!(
+            line.spans.into_vec(),
+            vec![
+                SgrSequence(smallvec::smallvec![Sgr::Foreground(
+                    AnsiColor::Maroon.into()
+                )]),
+                Match("fo".to_string(), 0),
+                SgrSequence(smallvec::smallvec![Sgr::Intensity(Intensity::Bold)]),
+                Match("o".to_string(), 0),
+                SgrSequence(smallvec::smallvec![Sgr::Reset]),
+                Text(" bar".to_string()),
+                Tab,
+                Match("b".to_string(), 1),
+                Text("az ".to_string()),
+                Match("\u{4E2D}".to_string(), 2),
+                Text("\u{6587}".to_string()),
+                Lf,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_text_style_prefix() {
+        let style = TextStyle {
+            foreground: Some(String::from("red")),
+            bold: true,
+            ..Default::default()
+        };
+        assert_eq!(text_style_prefix(&style).unwrap(), b"\x1b[31m\x1b[1m");
+        assert_eq!(text_style_prefix(&TextStyle::default()).unwrap(), b"");
+        let invalid = TextStyle {
+            background: Some(String::from("mauve")),
+            ..Default::default()
+        };
+        assert!(text_style_prefix(&invalid).is_err());
+    }
+
+    #[test]
+    fn test_highlight_spans() {
+        let red: Arc<[Sgr]> = vec![Sgr::Foreground(AnsiColor::Maroon.into())].into();
+        let bold: Arc<[Sgr]> = vec![Sgr::Intensity(Intensity::Bold)].into();
+        let highlights = vec![
+            (regex::Regex::new("ERROR.*").unwrap(), red.clone()),
+            (regex::Regex::new("bad|worse").unwrap(), bold.clone()),
+        ];
+        assert_eq!(
+            highlight_spans(
+                parse_spans(b"bad: \x1B[1mERROR\x1B[m worse\n", None),
+                &highlights
+            ),
+            vec![
+                HighlightStart(bold),
+                Text("bad".to_string()),
+                HighlightEnd,
+                Text(": ".to_string()),
+                SgrSequence(smallvec::smallvec![Sgr::Intensity(Intensity::Bold)]),
+                HighlightStart(red),
+                Text("ERROR".to_string()),
+                SgrSequence(smallvec::smallvec![Sgr::Reset]),
+                Text(" worse".to_string()),
+                Lf,
+                HighlightEnd,
+            ]
+        );
+        assert_eq!(
+            highlight_spans(parse_spans(b"fine\n", None), &highlights),
+            vec![Text("fine".to_string()), Lf]
+        );
+    }
+
+    #[test]
+    fn test_line_endings() {
+        assert_eq!(
+            hide_trailing_cr(parse_spans(b"a\rb\r\r\n", None)),
+            vec![
+                Text("a".to_string()),
+                Control(13),
+                Text("b".to_string()),
+                CrLf
+            ]
+        );
+        assert_eq!(
+            hide_trailing_cr(parse_spans(b"done\r\x1B[m", None)),
+            vec![
+                Text("done".to_string()),
+                SgrSequence(smallvec::smallvec![Sgr::Reset])
+            ]
+        );
+        assert_eq!(
+            show_line_endings(parse_spans(b"unix\n", None)),
+            vec![Text("unix".to_string()), LineEnding("\u{240A}")]
+        );
+        assert_eq!(
+            show_line_endings(parse_spans(b"windows\r\n", None)),
+            vec![Text("windows".to_string()), LineEnding("\u{240D}\u{240A}")]
+        );
+        assert_eq!(
+            show_line_endings(parse_spans(b"unterminated", None)),
+            vec![Text("unterminated".to_string())]
+        );
+    }
+
+    #[test]
+    fn test_invalid_highlight_rules() {
+        let rule = |pattern: &str, foreground: &str| HighlightRule {
+            pattern: pattern.to_string(),
+            foreground: Some(foreground.to_string()),
+            ..HighlightRule::default()
+        };
+        let config = Config {
+            highlight_rules: vec![
+                rule("(unclosed", "red"),
+                rule("error", "no-such-color"),
+                rule("warning", "yellow"),
+            ],
+            ..Config::default()
+        };
+        let mut errors = Vec::new();
+        let decorations = LineDecorations::new(&config, None, &mut errors);
+        assert_eq!(decorations.highlights.len(), 1);
+        assert_eq!(decorations.highlights[0].0.as_str(), "warning");
+        assert_eq!(errors.len(), 2);
+        assert!(errors[0].starts_with("Invalid highlight rule '(unclosed': regex parse error"));
+        assert_eq!(
+            errors[1],
+            "Invalid highlight rule 'error': invalid color 'no-such-color'"
+        );
+    }
+
+    #[test]
+    fn test_invalid_whitespace_colors() {
+        let config = Config {
+            color: crate::config::ColorMode::Always,
+            trailing_whitespace_color: String::from("no-such-color"),
+            tab_marker_color: String::from("#12345"),
+            ..Config::default()
+        };
+        let mut errors = Vec::new();
+        let decorations = LineDecorations::new(&config, None, &mut errors);
+        let styles = decorations.whitespace_styles.unwrap();
+        assert!(styles.trailing.is_empty());
+        assert_eq!(styles.mixed_indent.len(), 1);
+        assert!(styles.tab.is_empty());
+        assert_eq!(
+            errors,
+            vec![
+                "Invalid trailing_whitespace_color: invalid color 'no-such-color'",
+                "Invalid tab_marker_color: invalid color '#12345'",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_rendition() {
+        let attributes = |config: &Config| {
+            let decorations = LineDecorations::new(config, None, &mut Vec::new());
+            let line = Line::new(0, b"\x1B[1;31mred\x1B[m plain\n").decorate(&decorations, false);
+            let mut changes = Vec::new();
+            line.render(&mut changes, 0, 20, None, false);
+            changes
+                .into_iter()
+                .filter_map(|change| match change {
+                    Change::AllAttributes(attrs) => Some(attrs),
+                    _ => None,
+                })
+                .collect::<Vec<_>>()
+        };
+        let mut config = Config {
+            color: crate::config::ColorMode::Always,
+            ..Config::default()
+        };
+        let attrs = attributes(&config);
+        assert_eq!(attrs[0].foreground(), AnsiColor::Maroon.into());
+        assert_eq!(attrs[0].intensity(), Intensity::Bold);
+
+        config.color = crate::config::ColorMode::Never;
+        let attrs = attributes(&config);
+        assert_eq!(attrs[0].foreground(), ColorAttribute::Default);
+        assert_eq!(attrs[0].intensity(), Intensity::Bold);
+
+        config.monochrome_attributes = false;
+        let attrs = attributes(&config);
+        assert_eq!(attrs[0], CellAttributes::default());
+    }
+
+    #[test]
+    fn test_mark_whitespace() {
+        let styles = WhitespaceStyles {
+            trailing: vec![Sgr::Background(ColorSpec::PaletteIndex(1))].into(),
+            mixed_indent: vec![Sgr::Background(ColorSpec::PaletteIndex(3))].into(),
+            tab: vec![Sgr::Foreground(ColorSpec::PaletteIndex(8))].into(),
+        };
+        let text = |s: &str| Text(s.to_string());
+        let styled = |span, style: &Arc<[Sgr]>| Styled(Box::new(span), style.clone());
+        assert_eq!(
+            mark_whitespace(parse_spans(b"a\tb  \n", None), &styles),
+            vec![
+                text("a"),
+                styled(MarkedTab, &styles.tab),
+                text("b"),
+                styled(text("  "), &styles.trailing),
+                Lf
+            ]
+        );
+        assert_eq!(
+            mark_whitespace(parse_spans(b" \tx\n", None), &styles),
+            vec![
+                styled(text(" "), &styles.mixed_indent),
+                Styled(
+                    Box::new(MarkedTab),
+                    vec![
+                        Sgr::Background(ColorSpec::PaletteIndex(3)),
+                        Sgr::Foreground(ColorSpec::PaletteIndex(8))
+                    ]
+                    .into()
+                ),
+                text("x"),
+                Lf
+            ]
+        );
+        assert_eq!(
+            mark_whitespace(parse_spans(b"    x\n", None), &styles),
+            vec![text("    "), text("x"), Lf]
+        );
+        assert_eq!(
+            mark_whitespace(parse_spans(b"  \n", None), &styles),
+            vec![styled(text("  "), &styles.trailing), Lf]
+        );
+    }
+
+    #[test]
+    fn test_render_markdown() {
+        let bold = |sgr| SgrSequence(smallvec::smallvec![Sgr::Intensity(sgr)]);
+        let italic = |on| SgrSequence(smallvec::smallvec![Sgr::Italic(on)]);
+        assert_eq!(
+            render_markdown(parse_spans(b"a **b** _c_ snake_case_name\n", None), false),
+            vec![
+                Text("a ".to_string()),
+                bold(Intensity::Bold),
+                Text("b".to_string()),
+                bold(Intensity::Normal),
+                Text(" ".to_string()),
+                italic(true),
+                Text("c".to_string()),
+                italic(false),
+                Text(" snake_case_name".to_string()),
+                Lf,
+            ]
+        );
+        assert_eq!(
+            render_markdown(parse_spans(b"# Title\n", None), false),
+            vec![
+                SgrSequence(smallvec::smallvec![
+                    Sgr::Intensity(Intensity::Bold),
+                    Sgr::Underline(Underline::Single)
+                ]),
+                Text("Title".to_string()),
+                Lf,
+                SgrSequence(smallvec::smallvec![
+                    Sgr::Intensity(Intensity::Normal),
+                    Sgr::Underline(Underline::None)
+                ]),
+            ]
+        );
+        let code = render_markdown(parse_spans(b"# **not** a heading\n", None), true);
+        assert_eq!(code[1], Text("# **not** a heading".to_string()));
+        assert!(is_code_fence(b"```rust\n"));
+        assert!(!is_code_fence(b"    ```\n"));
+    }
+
+    #[test]
+    fn test_parse_color() {
+        assert_eq!(parse_color("Red").unwrap(), AnsiColor::Maroon.into());
+        assert_eq!(parse_color("bright-blue").unwrap(), AnsiColor::Blue.into());
+        assert!(matches!(
+            parse_color("#ff8000").unwrap(),
+            ColorSpec::TrueColor(_)
+        ));
+        assert!(parse_color("bogus").is_err());
+    }
+
+    #[test]
+    fn test_linkify_file_paths() {
+        let url = format!(
+            "file://{}#L12",
+            std::env::current_dir()
+                .unwrap()
+                .join("src/line.rs")
+                .display()
+        );
+        assert_eq!(
+            linkify_file_paths(parse_spans(b"error at src/line.rs:12:5: oops", None)),
+            vec![
+                Text("error at ".to_string()),
+                Hyperlink(Some(Arc::new(termwiz::hyperlink::Hyperlink::new(url)))),
+                Text("src/line.rs:12:5".to_string()),
+                Hyperlink(None),
+                Text(": oops".to_string()),
+            ]
+        );
+        for text in ["missing/file.rs:12", "http://example.com:8080", "at 12:30"] {
+            assert_eq!(
+                linkify_file_paths(parse_spans(text.as_bytes(), None)),
+                vec![Text(text.to_string())]
+            );
+        }
+        // The file system is only checked once for each path.
+        let file_paths = FILE_PATHS.lock().unwrap();
+        assert!(matches!(file_paths.peek("src/line.rs"), Some(Some(_))));
+        assert_eq!(file_paths.peek("missing/file.rs"), Some(&None));
+    }
 }
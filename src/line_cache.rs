@@ -1,22 +1,172 @@
 //! Line Cache
 //!
-//! An LRU-cache for lines.
+//! An LRU-cache for lines, limited by the memory the lines use rather than
+//! the number of lines, so that a few very wide lines can't use too much
+//! memory, and many short lines don't push each other out.
 
 use std::borrow::Cow;
+use std::sync::{Arc, Mutex};
 
 use lru::LruCache;
-use regex::bytes::Regex;
 
 use crate::file::{File, FileInfo};
-use crate::line::Line;
+use crate::line::{self, Line, LineDecorations};
+use crate::search::Search;
+
+/// Whether each line of a file starts within a markdown code block, for the
+/// lines that have been scanned so far.  Code blocks are found by scanning
+/// the file for code fences from the start, so this is shared by the line
+/// caches of a screen, and kept when lines are evicted or the caches are
+/// cleared, until the file is reloaded.
+#[derive(Clone)]
+pub(crate) struct CodeBlocks(Arc<Mutex<Vec<bool>>>);
+
+impl CodeBlocks {
+    /// Create the code block state for a file that hasn't been scanned.
+    pub(crate) fn new() -> CodeBlocks {
+        CodeBlocks(Arc::new(Mutex::new(vec![false])))
+    }
+
+    /// Returns true if line `line_index` is within a code block, scanning
+    /// the file as far as that line if it hasn't been scanned already.
+    fn contains(&self, file: &File, line_index: usize) -> bool {
+        let mut code_blocks = self.0.lock().unwrap();
+        while code_blocks.len() <= line_index {
+            let scanned = code_blocks.len() - 1;
+            // The last line of a file that is still loading might not be
+            // complete.
+            if !file.loaded() && scanned + 1 >= file.lines() {
+                break;
+            }
+            match file.with_line(scanned, |data| line::is_code_fence(&data)) {
+                Some(fence) => {
+                    let in_code_block = code_blocks[scanned];
+                    code_blocks.push(in_code_block != fence);
+                }
+                None => break,
+            }
+        }
+        code_blocks
+            .get(line_index)
+            .copied()
+            .unwrap_or_else(|| *code_blocks.last().expect("first line is always scanned"))
+    }
+
+    /// Forget the scanned lines, as the file has changed.
+    pub(crate) fn reset(&self) {
+        self.0.lock().unwrap().truncate(1);
+    }
+}
+
+/// Statistics about how well a line cache is working.
+#[derive(Debug, Default, Clone, Copy)]
+pub(crate) struct LineCacheStats {
+    /// The number of lines found in the cache.
+    pub(crate) hits: u64,
+    /// The number of lines that had to be created.
+    pub(crate) misses: u64,
+    /// The number of lines removed to make room for other lines.
+    pub(crate) evictions: u64,
+    /// The number of lines that were too large to fit in the cache at all.
+    pub(crate) too_large: u64,
+}
 
 /// An LRU-cache for Lines.
-pub(crate) struct LineCache(LruCache<usize, Line>);
+pub(crate) struct LineCache {
+    /// The cached lines, and the estimated memory each one uses.
+    lines: LruCache<usize, (Line, usize)>,
+
+    /// The most memory the cached lines may use, in bytes.
+    budget: usize,
+
+    /// The estimated memory the cached lines use, in bytes.
+    used: usize,
+
+    /// Statistics about how well the cache is working.
+    stats: LineCacheStats,
+
+    /// Decorations applied to lines when they are created.
+    decorations: Arc<LineDecorations>,
+
+    /// Whether each line starts within a markdown code block.
+    code_blocks: CodeBlocks,
+}
 
 impl LineCache {
-    /// Create a new LineCache with the given capacity.
-    pub(crate) fn new(capacity: usize) -> LineCache {
-        LineCache(LruCache::new(capacity))
+    /// Create a new LineCache whose lines may use up to `budget` bytes.
+    pub(crate) fn new(
+        budget: usize,
+        decorations: Arc<LineDecorations>,
+        code_blocks: CodeBlocks,
+    ) -> LineCache {
+        LineCache {
+            lines: LruCache::unbounded(),
+            budget,
+            used: 0,
+            stats: LineCacheStats::default(),
+            decorations,
+            code_blocks,
+        }
+    }
+
+    /// Change the most memory the cached lines may use.  Lines are evicted
+    /// if they no longer fit.
+    pub(crate) fn set_budget(&mut self, budget: usize) {
+        self.budget = budget;
+        self.evict(0, 0);
+    }
+
+    /// The most memory the cached lines may use, in bytes.
+    pub(crate) fn budget(&self) -> usize {
+        self.budget
+    }
+
+    /// The estimated memory the cached lines use, in bytes.
+    pub(crate) fn used(&self) -> usize {
+        self.used
+    }
+
+    /// The number of cached lines.
+    pub(crate) fn len(&self) -> usize {
+        self.lines.len()
+    }
+
+    /// Statistics about how well the cache is working.
+    pub(crate) fn stats(&self) -> LineCacheStats {
+        self.stats
+    }
+
+    /// Evict the least recently used lines until there is room for a line
+    /// of the given size, keeping at least `keep` of the most recently used
+    /// lines.
+    fn evict(&mut self, size: usize, keep: usize) {
+        while self.used + size > self.budget && self.lines.len() > keep {
+            match self.lines.pop_lru() {
+                Some((_, (_, evicted_size))) => {
+                    self.used -= evicted_size;
+                    self.stats.evictions += 1;
+                }
+                None => break,
+            }
+        }
+    }
+
+    /// The decorations applied to lines when they are created.
+    pub(crate) fn decorations(&self) -> &Arc<LineDecorations> {
+        &self.decorations
+    }
+
+    /// Change the decorations applied to lines.  Cached lines are
+    /// discarded.
+    pub(crate) fn set_decorations(&mut self, decorations: Arc<LineDecorations>) {
+        self.decorations = decorations;
+        self.clear();
+    }
+
+    /// Returns true if the line is within a markdown code block.  This is
+    /// only needed when rendering markdown.
+    fn in_code_block(&self, file: &File, line_index: usize) -> bool {
+        self.decorations.markdown() && self.code_blocks.contains(file, line_index)
     }
 
     /// Get a line out of the line cache, or create it if it is not
@@ -25,25 +175,47 @@ impl LineCache {
         &'a mut self,
         file: &File,
         line_index: usize,
-        regex: Option<&Regex>,
+        search: Option<&Search>,
     ) -> Option<Cow<'a, Line>> {
-        let cache = &mut self.0;
-        if cache.contains(&line_index) {
-            Some(Cow::Borrowed(cache.get_mut(&line_index).unwrap()))
+        if let Some((line, size)) = self.lines.get_mut(&line_index) {
+            self.stats.hits += 1;
+            // The line grows as its wrapping is cached, so measure it again.
+            let new_size = line.memory_size();
+            self.used = self.used - *size + new_size;
+            *size = new_size;
+            self.evict(0, 1);
+            Some(Cow::Borrowed(&self.lines.get_mut(&line_index).unwrap().0))
         } else {
+            self.stats.misses += 1;
+            let in_code_block = self.in_code_block(file, line_index);
+            let decorations = &self.decorations;
             let line = file.with_line(line_index, |line| {
-                if let Some(ref regex) = regex {
-                    Line::new_search(line_index, line, regex)
+                if let Some(search) = search {
+                    Line::new_search(
+                        line_index,
+                        line,
+                        search.regex(),
+                        search.columns(),
+                        search.normalization(),
+                    )
+                    .decorate(decorations, in_code_block)
                 } else {
-                    Line::new(line_index, line)
+                    Line::new(line_index, line).decorate(decorations, in_code_block)
                 }
             });
             if let Some(line) = line {
                 // Don't cache the line if it's the last line of the file
                 // and the file is still loading.  It might not be complete.
                 if file.loaded() || line_index + 1 < file.lines() {
-                    cache.put(line_index, line);
-                    Some(Cow::Borrowed(cache.get_mut(&line_index).unwrap()))
+                    let size = line.memory_size();
+                    if size > self.budget {
+                        self.stats.too_large += 1;
+                        return Some(Cow::Owned(line));
+                    }
+                    self.evict(size, 0);
+                    self.used += size;
+                    self.lines.put(line_index, (line, size));
+                    Some(Cow::Borrowed(&self.lines.get_mut(&line_index).unwrap().0))
                 } else {
                     Some(Cow::Owned(line))
                 }
@@ -55,6 +227,160 @@ impl LineCache {
 
     /// Clear all entries in the line cache.
     pub(crate) fn clear(&mut self) {
-        self.0.clear();
+        self.lines.clear();
+        self.used = 0;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::thread;
+    use std::time::{Duration, Instant};
+
+    use super::*;
+    use crate::config::WrappingMode;
+    use crate::event::EventStream;
+    use crate::loaded_file::LoadedFile;
+
+    fn load(data: Vec<u8>) -> File {
+        let events = EventStream::new(None);
+        let file: File = LoadedFile::new_static(0, "test", data, events.sender()).into();
+        while !file.loaded() {
+            thread::sleep(Duration::from_millis(1));
+        }
+        file
+    }
+
+    fn lines(count: usize, width: usize) -> Vec<u8> {
+        let mut data = Vec::new();
+        for index in 0..count {
+            let line = format!("line {} ", index);
+            data.extend(line.bytes().cycle().take(width));
+            data.push(b'\n');
+        }
+        data
+    }
+
+    #[test]
+    fn test_wide_lines() {
+        let file = load(lines(2, 2000));
+        let mut cache = LineCache::new(0, Arc::default(), CodeBlocks::new());
+        let size = cache.get_or_create(&file, 0, None).unwrap().memory_size();
+
+        // A line that takes up most of the budget is still cached.
+        cache.set_budget(size + size / 2);
+        cache.get_or_create(&file, 0, None).unwrap();
+        assert_eq!(cache.len(), 1);
+        assert_eq!(cache.used(), size);
+
+        // Caching another line evicts it.
+        cache.get_or_create(&file, 1, None).unwrap();
+        assert_eq!(cache.len(), 1);
+        let stats = cache.stats();
+        assert_eq!((stats.hits, stats.misses), (0, 3));
+        assert_eq!((stats.evictions, stats.too_large), (1, 1));
+
+        // Lines that don't fit at all are not cached.
+        cache.set_budget(size - 1);
+        assert_eq!(cache.len(), 0);
+        assert_eq!(cache.used(), 0);
+        cache.get_or_create(&file, 0, None).unwrap();
+        assert_eq!(cache.len(), 0);
+        assert_eq!(cache.stats().too_large, 2);
+    }
+
+    #[test]
+    fn test_wrapping_size() {
+        let file = load(lines(3, 2000));
+        let mut cache = LineCache::new(1 << 20, Arc::default(), CodeBlocks::new());
+        let size = {
+            let line = cache.get_or_create(&file, 0, None).unwrap();
+            let size = line.memory_size();
+            assert_eq!(line.height(10, WrappingMode::GraphemeBoundary), 200);
+            size
+        };
+        assert_eq!(cache.used(), size);
+
+        // The cached wrapping is counted the next time the line is used.
+        let wrapped_size = cache.get_or_create(&file, 0, None).unwrap().memory_size();
+        assert!(wrapped_size > size + 200 * std::mem::size_of::<(usize, usize)>());
+        assert_eq!(cache.used(), wrapped_size);
+        assert_eq!(cache.stats().hits, 1);
+
+        // If the line no longer fits with the other lines once it has been
+        // wrapped, they are evicted.
+        let mut cache = LineCache::new(2 * size, Arc::default(), CodeBlocks::new());
+        cache.get_or_create(&file, 1, None).unwrap();
+        let line = cache.get_or_create(&file, 2, None).unwrap();
+        assert_eq!(line.height(10, WrappingMode::GraphemeBoundary), 200);
+        assert_eq!((cache.len(), cache.used()), (2, 2 * size));
+        cache.get_or_create(&file, 2, None).unwrap();
+        assert_eq!((cache.len(), cache.used()), (1, wrapped_size));
+        assert_eq!(cache.stats().evictions, 1);
+    }
+
+    #[test]
+    fn test_code_blocks() {
+        let file = load(b"text\n```\ncode\n```\ntext\n".to_vec());
+        let mut decorations = LineDecorations::default();
+        decorations.set_markdown(true);
+        let decorations = Arc::new(decorations);
+        let code_blocks = CodeBlocks::new();
+        let mut cache = LineCache::new(1 << 20, decorations.clone(), code_blocks.clone());
+        let search_cache = LineCache::new(1 << 20, decorations, code_blocks.clone());
+        let scanned = || code_blocks.0.lock().unwrap().len();
+        assert!(cache.in_code_block(&file, 2));
+        assert_eq!(scanned(), 3);
+
+        // The scan is kept when the cache is cleared, and shared with other
+        // caches.
+        cache.clear();
+        assert!(!search_cache.in_code_block(&file, 4));
+        assert_eq!(scanned(), 5);
+        assert!(search_cache.in_code_block(&file, 2));
+        assert_eq!(scanned(), 5);
+
+        // Reloading the file starts the scan again.
+        code_blocks.reset();
+        assert_eq!(scanned(), 1);
+        assert!(cache.in_code_block(&file, 3));
+        assert_eq!(scanned(), 4);
+    }
+
+    /// Measures how long it takes to scroll through a file with different
+    /// cache budgets.  Run with:
+    ///
+    /// ```text
+    /// cargo test --release bench_line_cache -- --ignored --nocapture
+    /// ```
+    #[test]
+    #[ignore]
+    fn bench_line_cache() {
+        const HEIGHT: usize = 25;
+        for &width in &[80, 1000, 5000] {
+            let file = load(lines(500, width));
+            for &budget in &[0, 64 << 10, 1 << 20, 16 << 20] {
+                let mut cache = LineCache::new(budget, Arc::default(), CodeBlocks::new());
+                let start = Instant::now();
+                // Scroll down a line at a time, then back up again.
+                for top in (0..file.lines() - HEIGHT).chain((0..file.lines() - HEIGHT).rev()) {
+                    for line_index in top..top + HEIGHT {
+                        let line = cache.get_or_create(&file, line_index, None).unwrap();
+                        line.height(120, WrappingMode::WordBoundary);
+                    }
+                }
+                let stats = cache.stats();
+                println!(
+                    "width {:>5} budget {:>8}: {:>8.1?} hits {:>6} misses {:>6} evictions {:>6} too large {:>6}",
+                    width,
+                    budget,
+                    start.elapsed(),
+                    stats.hits,
+                    stats.misses,
+                    stats.evictions,
+                    stats.too_large,
+                );
+            }
+        }
     }
 }
@@ -0,0 +1,345 @@
+//! Socket Listeners
+//!
+//! A listener accepts connections on a TCP port or a Unix socket.  The data
+//! written to each connection is paged as a new streamed file.
+
+use std::fmt;
+#[cfg(unix)]
+use std::fs;
+use std::io::Read;
+use std::net::{SocketAddr, TcpListener, TcpStream};
+#[cfg(unix)]
+use std::os::unix::fs::PermissionsExt;
+#[cfg(unix)]
+use std::os::unix::net::{UnixListener, UnixStream};
+#[cfg(unix)]
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+
+use crate::error::{Error, Result};
+use crate::event::{Event, EventSender};
+#[cfg(unix)]
+use crate::send;
+
+/// A connection that has been accepted by a listener.
+pub(crate) struct Connection {
+    /// The data written to the connection.
+    pub(crate) stream: Box<dyn Read + Send>,
+
+    /// The title for the connection's file.
+    pub(crate) title: String,
+}
+
+impl fmt::Debug for Connection {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Connection")
+            .field("title", &self.title)
+            .finish()
+    }
+}
+
+/// Where a listener is listening.
+enum Address {
+    /// A TCP address.
+    Tcp(SocketAddr),
+
+    /// The path of a Unix socket, which is removed when the listener is
+    /// dropped.
+    #[cfg(unix)]
+    Unix(PathBuf),
+}
+
+/// A listener for connections.
+pub(crate) struct Listener {
+    /// Where the listener is listening.
+    address: Address,
+
+    /// Set when the listener is dropped, to stop its thread.
+    stopped: Arc<AtomicBool>,
+
+    /// The thread accepting connections.
+    thread: Option<JoinHandle<Result<()>>>,
+
+    /// The lock held while listening on the per-user socket.  It is
+    /// released after the socket has been removed.
+    #[cfg(unix)]
+    lock: Option<fs::File>,
+}
+
+impl Listener {
+    /// Start listening on `address`, which is either a path to a Unix socket
+    /// (any address containing `/`) or a TCP address like `localhost:4000`.
+    ///
+    /// Connections aren't authenticated, so only the user can connect to a
+    /// Unix socket, and TCP addresses must be loopback addresses.
+    ///
+    /// Each connection is sent to the display as a `Connection` event.
+    pub(crate) fn new(address: &str, event_sender: EventSender) -> Result<Listener> {
+        let with_address = |err: std::io::Error| Error::from(err).with_address(address);
+        #[cfg(unix)]
+        {
+            if address.contains('/') {
+                let path = PathBuf::from(address);
+                let listener = bind_private(&path).map_err(with_address)?;
+                let title = address.to_string();
+                return Listener::spawn(
+                    Address::Unix(path),
+                    event_sender,
+                    move || Ok(listener.accept()?.0),
+                    move |stream, count| {
+                        Ok(Connection {
+                            stream: Box::new(stream),
+                            title: format!("{} #{}", title, count),
+                        })
+                    },
+                );
+            }
+        }
+        let listener = TcpListener::bind(address).map_err(with_address)?;
+        let local_addr = listener.local_addr().map_err(with_address)?;
+        if !local_addr.ip().is_loopback() {
+            return Err(with_address(std::io::Error::new(
+                std::io::ErrorKind::PermissionDenied,
+                "only loopback addresses can be listened on, as connections are not authenticated",
+            )));
+        }
+        Listener::spawn(
+            Address::Tcp(local_addr),
+            event_sender,
+            move || listener.accept(),
+            |(stream, peer), _count| {
+                Ok(Connection {
+                    stream: Box::new(stream),
+                    title: peer.to_string(),
+                })
+            },
+        )
+    }
+
+    /// Spawn a thread that waits for connections with `accept`, and then
+    /// calls `connect` for each one, counting from 1.  The thread stops when
+    /// the listener has gone away.
+    ///
+    /// Each connection is set up on a thread of its own, so that a client
+    /// that is slow to send what `connect` reads doesn't hold up the others.
+    fn spawn<S, A, C>(
+        address: Address,
+        event_sender: EventSender,
+        mut accept: A,
+        connect: C,
+    ) -> Result<Listener>
+    where
+        S: Send + 'static,
+        A: FnMut() -> std::io::Result<S> + Send + 'static,
+        C: Fn(S, usize) -> std::io::Result<Connection> + Send + Sync + 'static,
+    {
+        let connect = Arc::new(connect);
+        let stopped = Arc::new(AtomicBool::new(false));
+        let thread = thread::Builder::new()
+            .name(String::from("sp-listen"))
+            .spawn({
+                let stopped = stopped.clone();
+                move || -> Result<()> {
+                    let mut count = 0;
+                    loop {
+                        let accepted = accept();
+                        if stopped.load(Ordering::SeqCst) {
+                            return Ok(());
+                        }
+                        match accepted {
+                            Ok(stream) => {
+                                count += 1;
+                                let connect = connect.clone();
+                                let event_sender = event_sender.clone();
+                                thread::Builder::new()
+                                    .name(String::from("sp-connect"))
+                                    .spawn(move || {
+                                        // A bad connection doesn't stop the
+                                        // listener, and if the display has
+                                        // gone away there is nothing to do.
+                                        if let Ok(connection) = connect(stream, count) {
+                                            let _ =
+                                                event_sender.send(Event::Connection(connection));
+                                        }
+                                    })?;
+                            }
+                            Err(ref e) if e.kind() == std::io::ErrorKind::Interrupted => {}
+                            Err(e) => return Err(e.into()),
+                        }
+                    }
+                }
+            })?;
+        Ok(Listener {
+            address,
+            stopped,
+            thread: Some(thread),
+            #[cfg(unix)]
+            lock: None,
+        })
+    }
+}
+
+#[cfg(unix)]
+impl Listener {
+    /// Start listening on the per-user socket for streams sent by `sp
+    /// --send`.  Each stream starts with its title.
+    ///
+    /// Returns `None` if another pager is already listening.  A socket left
+    /// behind by a pager that has gone away is replaced.
+    pub(crate) fn new_send(event_sender: EventSender) -> Result<Option<Listener>> {
+        let path = send::socket_path()?;
+        // Only the pager holding the lock listens, so that pagers starting
+        // at the same time don't remove each other's sockets.
+        let lock = match send::lock(&path.with_extension("lock"))? {
+            Some(lock) => lock,
+            None => return Ok(None),
+        };
+        let with_address =
+            |err: std::io::Error| Error::from(err).with_address(path.to_string_lossy());
+        match fs::remove_file(&path) {
+            Ok(()) => {}
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => return Err(with_address(e)),
+        }
+        let listener = UnixListener::bind(&path).map_err(with_address)?;
+        let mut listener = Listener::spawn(
+            Address::Unix(path),
+            event_sender,
+            move || Ok(listener.accept()?.0),
+            |stream, _count| {
+                let (title, stream) = send::receive(stream)?;
+                Ok(Connection { stream, title })
+            },
+        )?;
+        listener.lock = Some(lock);
+        Ok(Some(listener))
+    }
+}
+
+/// Bind a Unix socket at `path` that only the user can connect to.
+///
+/// The socket is bound in a private directory, and then linked into place
+/// once its permissions are set, so other users can't connect to it even
+/// briefly.  Like binding, this fails if `path` already exists.
+#[cfg(unix)]
+fn bind_private(path: &Path) -> std::io::Result<UnixListener> {
+    let parent = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    let dir = tempfile::Builder::new()
+        .prefix(".sp-listen")
+        .tempdir_in(parent)?;
+    let private = dir.path().join("socket");
+    let listener = UnixListener::bind(&private)?;
+    fs::set_permissions(&private, fs::Permissions::from_mode(0o600))?;
+    fs::hard_link(&private, path)?;
+    Ok(listener)
+}
+
+impl Drop for Listener {
+    fn drop(&mut self) {
+        // Wake the thread by connecting to the listener, so that it sees
+        // that it has been stopped.  If the connection fails, the thread
+        // has already stopped.
+        self.stopped.store(true, Ordering::SeqCst);
+        let woken = match &self.address {
+            Address::Tcp(address) => TcpStream::connect(address).is_ok(),
+            #[cfg(unix)]
+            Address::Unix(path) => UnixStream::connect(path).is_ok(),
+        };
+        if let Some(thread) = self.thread.take() {
+            if woken {
+                let _ = thread.join();
+            }
+        }
+        #[cfg(unix)]
+        if let Address::Unix(path) = &self.address {
+            let _ = fs::remove_file(path);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::event::EventStream;
+
+    #[test]
+    fn test_tcp_listener() -> Result<()> {
+        let events = EventStream::new(None);
+        assert!(Listener::new("0.0.0.0:0", events.sender()).is_err());
+        let listener = Listener::new("127.0.0.1:0", events.sender())?;
+        let address = match listener.address {
+            Address::Tcp(address) => address,
+            #[cfg(unix)]
+            Address::Unix(_) => panic!("expected a TCP listener"),
+        };
+        TcpStream::connect(address)?;
+        // Dropping the listener stops its thread and closes the socket.
+        drop(listener);
+        assert!(TcpStream::connect(address).is_err());
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_unix_listener() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let socket = dir.path().join("sp.sock");
+        let events = EventStream::new(None);
+        let listener = Listener::new(socket.to_str().unwrap(), events.sender())?;
+        let mode = fs::metadata(&socket)?.permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+        UnixStream::connect(&socket)?;
+        // The private directory the socket was bound in is removed, and an
+        // existing socket isn't replaced.
+        assert_eq!(fs::read_dir(dir.path())?.count(), 1);
+        assert!(Listener::new(socket.to_str().unwrap(), events.sender()).is_err());
+        assert_eq!(fs::read_dir(dir.path())?.count(), 1);
+        UnixStream::connect(&socket)?;
+        drop(listener);
+        assert!(!socket.exists());
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_slow_connection() -> Result<()> {
+        use std::io::Write;
+        use std::time::{Duration, Instant};
+
+        let dir = tempfile::tempdir()?;
+        let socket = dir.path().join("send.sock");
+        let events = EventStream::new(None);
+        let listener = UnixListener::bind(&socket)?;
+        let _listener = Listener::spawn(
+            Address::Unix(socket.clone()),
+            events.sender(),
+            move || Ok(listener.accept()?.0),
+            |stream, _count| {
+                let (title, stream) = send::receive(stream)?;
+                Ok(Connection { stream, title })
+            },
+        )?;
+        // A client that never sends its title doesn't hold up the next one.
+        let _silent = UnixStream::connect(&socket)?;
+        UnixStream::connect(&socket)?.write_all(b"second\ndata\n")?;
+        let deadline = Instant::now() + Duration::from_secs(5);
+        loop {
+            match events.try_recv()? {
+                Some(Event::Connection(connection)) => {
+                    assert_eq!(connection.title, "second");
+                    break;
+                }
+                _ => {
+                    assert!(Instant::now() < deadline, "no connection was received");
+                    thread::sleep(Duration::from_millis(10));
+                }
+            }
+        }
+        Ok(())
+    }
+}
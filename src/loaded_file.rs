@@ -4,25 +4,34 @@
 
 use std::borrow::Cow;
 use std::cmp::{max, min};
-use std::ffi::OsStr;
+use std::ffi::{OsStr, OsString};
 use std::fs::File as StdFile;
-use std::io::{Read, Seek, SeekFrom};
-use std::path::Path;
-use std::process::{Command, Stdio};
+use std::io::{Cursor, Read, Seek, SeekFrom};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, ExitStatus, Stdio};
 use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 use std::sync::mpsc;
 use std::sync::{Arc, Condvar, Mutex, RwLock};
 use std::thread;
 use std::time::Duration;
 
+use lru::LruCache;
 use memmap2::Mmap;
 use notify::{DebouncedEvent, RecommendedWatcher, RecursiveMode, Watcher};
 
+use crate::action::{Action, Severity};
 use crate::buffer::Buffer;
 use crate::buffer_cache::BufferCache;
+use crate::config::Encoding;
+use crate::directory::Listing;
+use crate::encoding;
 use crate::error::{Error, Result};
 use crate::event::{Event, EventSender, UniqueInstance};
-use crate::file::{FileIndex, FileInfo, DEFAULT_NEEDED_LINES};
+use crate::file::{ChainedCommand, FileDiagnostics, FileIndex, FileInfo, DEFAULT_NEEDED_LINES};
+use crate::tee::Tee;
+use crate::trace::{trace_event, trace_span};
+use crate::transform::LineTransforms;
 
 /// Buffer size to use when loading and parsing files.  This is also the block
 /// size when parsing memory mapped files or caching files read from disk.
@@ -31,6 +40,30 @@ const BUFFER_SIZE: usize = 1024 * 1024;
 /// Size of the file cache in buffers.
 const CACHE_SIZE: usize = 16;
 
+/// Number of transformed lines cached for each file.
+const TRANSFORM_CACHE_SIZE: usize = 1000;
+
+/// File information shown once an error has been encountered loading the
+/// file.
+const ERROR_INFO: &str = "[error]";
+
+/// Number of times a read that fails with a transient error is retried.
+const READ_RETRIES: u32 = 5;
+
+/// How long to wait before the first retry of a read that failed with a
+/// transient error.  Later retries wait longer.
+const READ_RETRY_DELAY: Duration = Duration::from_millis(100);
+
+/// File information shown while waiting for a file to be created.
+const WAITING_FOR_FILE: &str = "waiting for file";
+
+/// How often to check whether a file that doesn't exist has been created.
+const FILE_POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// File information shown while a named pipe has no writer.
+#[cfg(unix)]
+const WAITING_FOR_WRITER: &str = "waiting for writer";
+
 /// The data content of the file.
 #[derive(Clone)]
 enum FileData {
@@ -71,6 +104,26 @@ struct FileMeta {
     /// The offset of each newline in the file.
     newlines: RwLock<Vec<usize>>,
 
+    /// The character encoding of the file.  `Encoding::Auto` is replaced by
+    /// the detected encoding once enough of the file has been loaded.
+    encoding: RwLock<Encoding>,
+
+    /// The encoding detected from the start of the file while it is too
+    /// short to be sure of it, and the length of the file when it was
+    /// detected.
+    detected_encoding: Mutex<Option<(usize, Encoding)>>,
+
+    /// The longest line, in bytes, that is passed to `with_line` in full,
+    /// or 0 if lines are never truncated.
+    max_line_length: AtomicUsize,
+
+    /// The transforms applied to lines after they are decoded.
+    transforms: RwLock<LineTransforms>,
+
+    /// The transformed contents of recently used lines, or `None` for lines
+    /// the transforms didn't change.  Only complete lines are cached.
+    transformed: Mutex<LruCache<usize, Option<Arc<[u8]>>>>,
+
     /// During reload, the number of lines the file had before reloading.
     reload_old_line_count: RwLock<Option<usize>>,
 
@@ -81,17 +134,38 @@ struct FileMeta {
     /// threads to exit early.
     dropped: AtomicBool,
 
-    /// The most recent error encountered when loading the file.
-    error: RwLock<Option<Error>>,
+    /// The errors encountered when loading the file, or by the command
+    /// producing it, oldest first.
+    errors: RwLock<Vec<String>>,
 
     /// If needed_lines > newlines.len(), pause loading.
     needed_lines: AtomicUsize,
 
+    /// Set to false to keep loading even when enough lines have been
+    /// loaded.
+    backpressure: AtomicBool,
+
+    /// Set to true while loading is suspended by the user.
+    suspended: AtomicBool,
+
+    /// Set to true while the loading thread is paused, waiting for more
+    /// lines to be needed or for loading to be resumed.
+    paused: AtomicBool,
+
+    /// Set to true while the loading thread is waiting for more input to be
+    /// written to a stream.
+    waiting: AtomicBool,
+
     /// CondVar to wake up file loading.
     waker: Condvar,
 
     /// Mutex used by waker.
     waker_mutex: Mutex<()>,
+
+    /// Where the data is written as it is loaded, if anywhere.  This is
+    /// locked while the length is updated, so that each block of data is
+    /// written exactly once.
+    tee: Mutex<Option<Tee>>,
 }
 
 /// Event triggered by changes to a file on disk.
@@ -104,6 +178,33 @@ pub(crate) enum FileEvent {
     Reload,
 }
 
+/// A reader for a named pipe, which is opened when it is first read, as
+/// opening a named pipe blocks until something opens it for writing.
+#[cfg(unix)]
+struct FifoReader {
+    path: PathBuf,
+    file: Option<StdFile>,
+    meta: Arc<FileMeta>,
+    event_sender: EventSender,
+}
+
+#[cfg(unix)]
+impl Read for FifoReader {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        if self.file.is_none() {
+            let file = StdFile::open(&self.path)?;
+            self.meta
+                .info
+                .write()
+                .unwrap()
+                .retain(|info| info != WAITING_FOR_WRITER);
+            let _ = self.event_sender.send(Event::RefreshOverlay);
+            self.file = Some(file);
+        }
+        self.file.as_mut().unwrap().read(buf)
+    }
+}
+
 /// Guard to stop reading from a file when it is dropped
 struct FileGuard {
     meta: Arc<FileMeta>,
@@ -118,14 +219,67 @@ impl FileMeta {
             info: RwLock::new(Vec::new()),
             length: AtomicUsize::new(0usize),
             newlines: RwLock::new(Vec::new()),
+            encoding: RwLock::new(Encoding::Utf8),
+            detected_encoding: Mutex::new(None),
+            max_line_length: AtomicUsize::new(0),
+            transforms: RwLock::new(LineTransforms::default()),
+            transformed: Mutex::new(LruCache::new(TRANSFORM_CACHE_SIZE)),
             reload_old_line_count: RwLock::new(None),
             finished: AtomicBool::new(false),
             dropped: AtomicBool::new(false),
-            error: RwLock::new(None),
+            errors: RwLock::new(Vec::new()),
             needed_lines: AtomicUsize::new(DEFAULT_NEEDED_LINES),
+            backpressure: AtomicBool::new(true),
+            suspended: AtomicBool::new(false),
+            paused: AtomicBool::new(false),
+            waiting: AtomicBool::new(false),
             waker: Condvar::new(),
             waker_mutex: Mutex::new(()),
+            tee: Mutex::new(None),
+        }
+    }
+}
+
+impl FileMeta {
+    /// Writes data that has just been loaded to the tee, if there is one.
+    /// If writing fails, the error is recorded and the tee is removed.
+    fn write_tee(
+        &self,
+        tee: &mut Option<Tee>,
+        data: &[u8],
+        event_sender: &EventSender,
+    ) -> Result<()> {
+        if let Some(writer) = tee.as_mut() {
+            if let Err(e) = writer.write(data) {
+                *tee = None;
+                self.add_error(e.to_string(), event_sender)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Writes the rest of the data to the tee, if there is one, and removes
+    /// it.
+    fn finish_tee(&self, event_sender: &EventSender) -> Result<()> {
+        if let Some(mut tee) = self.tee.lock().unwrap().take() {
+            if let Err(e) = tee.finish() {
+                self.add_error(e.to_string(), event_sender)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Records an error encountered loading the file, marks the file's
+    /// information to show that it has failed, and notifies the display.
+    fn add_error(&self, error: String, event_sender: &EventSender) -> Result<()> {
+        self.errors.write().unwrap().push(error);
+        {
+            let mut info = self.info.write().unwrap();
+            if !info.iter().any(|item| item == ERROR_INFO) {
+                info.push(ERROR_INFO.to_string());
+            }
         }
+        event_sender.send(Event::FileError(self.index))
     }
 }
 
@@ -148,9 +302,11 @@ impl FileData {
             .spawn({
                 let buffers = buffers.clone();
                 move || -> Result<()> {
+                    let _span = trace_span!("load", file = meta.index, kind = "streamed");
                     let mut offset = 0usize;
                     let mut total_buffer_size = 0usize;
                     let mut waker_mutex = meta.waker_mutex.lock().unwrap();
+                    let appended = UniqueInstance::new();
                     loop {
                         // Check if a new buffer must be allocated.
                         if offset == total_buffer_size {
@@ -160,9 +316,11 @@ impl FileData {
                         }
                         let buffers = buffers.read().unwrap();
                         let mut write = buffers.last().unwrap().write();
-                        match input.read(&mut write) {
+                        match read_retrying(&mut input, &mut write, Some(&meta.waiting)) {
                             Ok(0) => {
                                 // The end of the file has been reached.  Complete.
+                                trace_event!(bytes = offset, "loaded");
+                                meta.finish_tee(&event_sender)?;
                                 meta.finished.store(true, Ordering::SeqCst);
                                 event_sender.send(Event::Loaded(meta.index))?;
                                 return Ok(());
@@ -171,14 +329,14 @@ impl FileData {
                                 if meta.dropped.load(Ordering::SeqCst) {
                                     return Ok(());
                                 }
-                                // Some data has been read.  Parse its newlines.
+                                // Some data has been read.  Parse its newlines,
+                                // then add them to the index in one batch.
+                                let found = newline_offsets(&write[..len], offset);
+                                let mut tee = meta.tee.lock().unwrap();
+                                meta.write_tee(&mut tee, &write[..len], &event_sender)?;
                                 let line_count = {
                                     let mut newlines = meta.newlines.write().unwrap();
-                                    for i in 0..len {
-                                        if write[i] == b'\n' {
-                                            newlines.push(offset + i);
-                                        }
-                                    }
+                                    newlines.extend(found);
                                     // Mark that the data has been written.  This
                                     // needs to be done here before we drop the
                                     // lock for `newlines`.
@@ -187,18 +345,37 @@ impl FileData {
                                     meta.length.fetch_add(len, Ordering::SeqCst);
                                     newlines.len()
                                 };
-                                while line_count >= meta.needed_lines.load(Ordering::SeqCst) {
-                                    // Enough data is loaded. Pause.
+                                // Files that are being written out are
+                                // loaded in full.
+                                let teeing = tee.is_some();
+                                drop(tee);
+                                event_sender.send_unique(Event::Appended(meta.index), &appended)?;
+                                while (line_count >= meta.needed_lines.load(Ordering::SeqCst)
+                                    && meta.backpressure.load(Ordering::SeqCst)
+                                    && !teeing)
+                                    || meta.suspended.load(Ordering::SeqCst)
+                                {
+                                    // Enough data is loaded, or loading has
+                                    // been suspended. Pause.
+                                    trace_event!(lines = line_count, "paused");
+                                    meta.paused.store(true, Ordering::SeqCst);
                                     waker_mutex = meta.waker.wait(waker_mutex).unwrap();
+                                    meta.paused.store(false, Ordering::SeqCst);
+                                    trace_event!("resumed");
                                     if meta.dropped.load(Ordering::SeqCst) {
                                         return Ok(());
                                     }
                                 }
                             }
-                            Err(ref e) if e.kind() == std::io::ErrorKind::Interrupted => {}
                             Err(e) => {
-                                let mut error = meta.error.write().unwrap();
-                                *error = Some(e.into());
+                                // Reading has failed.  Keep the data that
+                                // was read, but stop loading.
+                                trace_event!(error = %e, "read failed");
+                                meta.add_error(e.to_string(), &event_sender)?;
+                                meta.finish_tee(&event_sender)?;
+                                meta.finished.store(true, Ordering::SeqCst);
+                                event_sender.send(Event::Loaded(meta.index))?;
+                                return Ok(());
                             }
                         }
                     }
@@ -209,13 +386,16 @@ impl FileData {
     }
 
     /// Create a new file from disk.
+    ///
+    /// If `file` is `None`, the file doesn't exist yet, and is loaded once
+    /// it has been created.
     fn new_file<P: AsRef<Path>>(
         path: P,
+        mut file: Option<StdFile>,
         meta: Arc<FileMeta>,
         event_sender: EventSender,
-    ) -> Result<FileData> {
+    ) -> FileData {
         let path = path.as_ref();
-        let mut file = Some(StdFile::open(path)?);
         let (events, event_rx) = mpsc::channel();
         let appending = Arc::new(AtomicBool::new(false));
         let buffer_cache = Arc::new(Mutex::new(BufferCache::new(path, BUFFER_SIZE, CACHE_SIZE)));
@@ -228,14 +408,36 @@ impl FileData {
                 let appending = appending.clone();
                 let meta = meta.clone();
                 let path = path.to_path_buf();
+                let event_sender = event_sender.clone();
+                let mut waiting = file.is_none();
                 move || -> Result<()> {
                     loop {
+                        // Files can't be watched until they exist, so wait
+                        // for the file to be (re)created, then load it.
+                        while !path.exists() {
+                            if meta.dropped.load(Ordering::SeqCst) {
+                                return Ok(());
+                            }
+                            waiting = true;
+                            thread::sleep(FILE_POLL_INTERVAL);
+                        }
+                        if waiting {
+                            waiting = false;
+                            events.send(FileEvent::Reload)?;
+                        }
                         let (tx, rx) = mpsc::channel();
                         let mut watcher: RecommendedWatcher =
-                            Watcher::new(tx, Duration::from_millis(500)).expect("create watcher");
-                        watcher
-                            .watch(path.clone(), RecursiveMode::NonRecursive)
-                            .expect("watch file");
+                            match Watcher::new(tx, Duration::from_millis(500)) {
+                                Ok(watcher) => watcher,
+                                Err(e) => {
+                                    let error = format!("can't watch for changes: {}", e);
+                                    return meta.add_error(error, &event_sender);
+                                }
+                            };
+                        if let Err(e) = watcher.watch(path.clone(), RecursiveMode::NonRecursive) {
+                            let error = format!("can't watch for changes: {}", e);
+                            return meta.add_error(error, &event_sender);
+                        }
                         loop {
                             if meta.dropped.load(Ordering::SeqCst) {
                                 return Ok(());
@@ -282,6 +484,7 @@ impl FileData {
                 let buffer_cache = buffer_cache.clone();
                 let path = path.to_path_buf();
                 move || -> Result<()> {
+                    let _span = trace_span!("load", file = meta.index, kind = "file");
                     let loaded_instance = UniqueInstance::new();
                     let appending_instance = UniqueInstance::new();
                     let reloading_instance = UniqueInstance::new();
@@ -293,25 +496,22 @@ impl FileData {
                             let mut buffer = Vec::new();
                             buffer.resize(BUFFER_SIZE, 0);
                             loop {
-                                match file.read(buffer.as_mut_slice()) {
+                                match read_retrying(&mut file, buffer.as_mut_slice(), None) {
                                     Ok(0) => break,
                                     Ok(len) => {
                                         if meta.dropped.load(Ordering::SeqCst) {
                                             return Ok(());
                                         }
-                                        let mut newlines = meta.newlines.write().unwrap();
-                                        for (i, byte) in buffer.iter().enumerate().take(len) {
-                                            if *byte == b'\n' {
-                                                newlines.push(total_length + i);
-                                            }
-                                        }
+                                        let found = newline_offsets(&buffer[..len], total_length);
+                                        let mut tee = meta.tee.lock().unwrap();
+                                        meta.write_tee(&mut tee, &buffer[..len], &event_sender)?;
+                                        meta.newlines.write().unwrap().extend(found);
                                         total_length += len;
                                         meta.length.store(total_length, Ordering::SeqCst);
                                     }
-                                    Err(ref e) if e.kind() == std::io::ErrorKind::Interrupted => {}
                                     Err(e) => {
-                                        let mut error = meta.error.write().unwrap();
-                                        *error = Some(e.into());
+                                        meta.add_error(e.to_string(), &event_sender)?;
+                                        break;
                                     }
                                 }
                             }
@@ -347,14 +547,17 @@ impl FileData {
                                 Ok(FileEvent::Append) => (true, end_data.is_empty()),
                                 Ok(FileEvent::Reload) => (true, true),
                                 Err(e) => {
-                                    let mut error = meta.error.write().unwrap();
-                                    *error = Some(e.into());
+                                    meta.add_error(e.to_string(), &event_sender)?;
                                     return Ok(());
                                 }
                             }
                         };
                         match StdFile::open(&path) {
                             Ok(mut f) => {
+                                meta.info
+                                    .write()
+                                    .unwrap()
+                                    .retain(|info| info != WAITING_FOR_FILE);
                                 if !reload {
                                     let mut new_data = Vec::new();
                                     new_data.resize(end_data.len(), 0);
@@ -377,6 +580,7 @@ impl FileData {
                         }
                         if reload {
                             buffer_cache.lock().unwrap().clear();
+                            meta.transformed.lock().unwrap().clear();
                             let mut reload_old_line_count =
                                 meta.reload_old_line_count.write().unwrap();
                             let mut newlines = meta.newlines.write().unwrap();
@@ -403,10 +607,10 @@ impl FileData {
             })
             .unwrap();
 
-        Ok(FileData::File {
+        FileData::File {
             buffer_cache,
             events,
-        })
+        }
     }
 
     /// Create a new memory mapped file.
@@ -434,18 +638,17 @@ impl FileData {
             .spawn({
                 let mmap = mmap.clone();
                 move || -> Result<()> {
+                    let _span = trace_span!("load", file = meta.index, kind = "mapped");
                     let len = mmap.len();
                     let blocks = (len + BUFFER_SIZE - 1) / BUFFER_SIZE;
                     for block in 0..blocks {
                         if meta.dropped.load(Ordering::SeqCst) {
                             return Ok(());
                         }
-                        let mut newlines = meta.newlines.write().unwrap();
-                        for i in block * BUFFER_SIZE..min((block + 1) * BUFFER_SIZE, len) {
-                            if mmap[i] == b'\n' {
-                                newlines.push(i);
-                            }
-                        }
+                        let start = block * BUFFER_SIZE;
+                        let end = min(start + BUFFER_SIZE, len);
+                        let found = newline_offsets(&mmap[start..end], start);
+                        meta.newlines.write().unwrap().extend(found);
                     }
                     meta.length.store(len, Ordering::SeqCst);
                     meta.finished.store(true, Ordering::SeqCst);
@@ -477,17 +680,10 @@ impl FileData {
                         if meta.dropped.load(Ordering::SeqCst) {
                             return Ok(());
                         }
-                        let mut newlines = meta.newlines.write().unwrap();
-                        for (i, byte) in data
-                            .iter()
-                            .enumerate()
-                            .skip(block * BUFFER_SIZE)
-                            .take(BUFFER_SIZE)
-                        {
-                            if *byte == b'\n' {
-                                newlines.push(i);
-                            }
-                        }
+                        let start = block * BUFFER_SIZE;
+                        let end = min(start + BUFFER_SIZE, len);
+                        let found = newline_offsets(&data[start..end], start);
+                        meta.newlines.write().unwrap().extend(found);
                     }
                     meta.length.store(len, Ordering::SeqCst);
                     meta.finished.store(true, Ordering::SeqCst);
@@ -499,9 +695,9 @@ impl FileData {
         FileData::Static { data }
     }
 
-    /// Runs the `call` function, passing it a slice of the data from `start` to `end`.
-    /// Tries to avoid copying the data if possible.
-    fn with_slice<T, F>(&self, start: usize, end: usize, mut call: F) -> T
+    /// Runs the `call` function, passing it a slice of the data from `start` to `end`,
+    /// which should contain `lines` lines.  Tries to avoid copying the data if possible.
+    fn with_slice<T, F>(&self, start: usize, end: usize, lines: usize, mut call: F) -> T
     where
         F: FnMut(Cow<'_, [u8]>) -> T,
     {
@@ -534,10 +730,14 @@ impl FileData {
                 let mut buffer_cache = buffer_cache.lock().unwrap();
                 buffer_cache
                     .with_slice(start, end, |data| {
+                        // If the lines have changed on disk, the file needs
+                        // to be reloaded.
                         if data
                             .iter()
                             .take(data.len().saturating_sub(1))
-                            .any(|c| *c == b'\n')
+                            .filter(|c| **c == b'\n')
+                            .count()
+                            != lines - 1
                         {
                             events.send(FileEvent::Reload).unwrap();
                         }
@@ -552,6 +752,25 @@ impl FileData {
     }
 }
 
+/// The process of a command that a file is the output of.
+struct CommandProcess {
+    /// The command that was run.
+    command: OsString,
+
+    /// The arguments the command was run with.
+    args: Vec<OsString>,
+
+    /// The process id of the command, while it is still running.  The
+    /// process itself is owned by the thread waiting for it to exit.
+    pid: Option<u32>,
+
+    /// True if the user has interrupted the command.
+    interrupted: bool,
+
+    /// The commands to run once this one has finished.
+    chained: Vec<ChainedCommand>,
+}
+
 /// A loaded file.
 pub(crate) struct LoadedFile {
     /// The data for the file.
@@ -562,6 +781,16 @@ pub(crate) struct LoadedFile {
 
     /// Guard to stop loading the file when the original reference to it is dropped.
     _guard: Option<FileGuard>,
+
+    /// The command the file is the output of.
+    process: Option<Arc<Mutex<CommandProcess>>>,
+
+    /// If the file is a directory listing, the path of the entry on each
+    /// line.
+    listing: Option<Arc<Vec<PathBuf>>>,
+
+    /// The path the file was opened from, if it was opened by name.
+    path: Option<PathBuf>,
 }
 
 impl Clone for LoadedFile {
@@ -570,6 +799,9 @@ impl Clone for LoadedFile {
             data: self.data.clone(),
             meta: self.meta.clone(),
             _guard: None,
+            process: self.process.clone(),
+            listing: self.listing.clone(),
+            path: self.path.clone(),
         }
     }
 }
@@ -577,7 +809,17 @@ impl Clone for LoadedFile {
 impl LoadedFile {
     fn new(data: FileData, meta: Arc<FileMeta>) -> Self {
         let _guard = Some(FileGuard { meta: meta.clone() });
-        LoadedFile { data, meta, _guard }
+        let process = None;
+        let listing = None;
+        let path = None;
+        LoadedFile {
+            data,
+            meta,
+            _guard,
+            process,
+            listing,
+            path,
+        }
     }
 
     /// Load stream.
@@ -592,24 +834,96 @@ impl LoadedFile {
         LoadedFile::new(data, meta)
     }
 
+    /// Load a file or directory by name.
     pub(crate) fn new_file(
         index: FileIndex,
         filename: &OsStr,
         event_sender: EventSender,
     ) -> Result<LoadedFile> {
+        let mut file = LoadedFile::open_file(index, filename, event_sender)?;
+        file.path = Some(PathBuf::from(filename));
+        Ok(file)
+    }
+
+    fn open_file(
+        index: FileIndex,
+        filename: &OsStr,
+        event_sender: EventSender,
+    ) -> Result<LoadedFile> {
+        if Path::new(filename).is_dir() {
+            return LoadedFile::new_directory(index, Path::new(filename), event_sender);
+        }
         let title = filename.to_string_lossy().into_owned();
         let meta = Arc::new(FileMeta::new(index, title.to_string()));
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::FileTypeExt;
+            // Named pipes can't be watched or read at will, so stream them.
+            // Opening one blocks until there is a writer, so do that on the
+            // loading thread.
+            let metadata =
+                std::fs::metadata(filename).map_err(|err| Error::from(err).with_file(&title))?;
+            if metadata.file_type().is_fifo() {
+                meta.info
+                    .write()
+                    .unwrap()
+                    .push(WAITING_FOR_WRITER.to_string());
+                let reader = FifoReader {
+                    path: PathBuf::from(filename),
+                    file: None,
+                    meta: meta.clone(),
+                    event_sender: event_sender.clone(),
+                };
+                let data = FileData::new_streamed(reader, meta.clone(), event_sender);
+                return Ok(LoadedFile::new(data, meta));
+            }
+        }
         let mut file = StdFile::open(filename).map_err(|err| Error::from(err).with_file(title))?;
         // Determine whether this file is a real file, or some kind of pipe, by
         // attempting to do a no-op seek.  If it fails, we won't be able to seek
         // around and load parts of the file at will, so treat it as a stream.
         let data = match file.seek(SeekFrom::Current(0)) {
-            Ok(_) => FileData::new_file(filename, meta.clone(), event_sender)?,
+            Ok(_) => FileData::new_file(filename, Some(file), meta.clone(), event_sender),
             Err(_) => FileData::new_streamed(file, meta.clone(), event_sender),
         };
         Ok(LoadedFile::new(data, meta))
     }
 
+    /// Load a file by name, waiting for it to be created if it doesn't
+    /// exist yet.
+    pub(crate) fn new_file_or_wait(
+        index: FileIndex,
+        filename: &OsStr,
+        event_sender: EventSender,
+    ) -> Result<LoadedFile> {
+        if Path::new(filename).exists() {
+            return LoadedFile::new_file(index, filename, event_sender);
+        }
+        let title = filename.to_string_lossy().into_owned();
+        let meta = Arc::new(FileMeta::new(index, title));
+        meta.info
+            .write()
+            .unwrap()
+            .push(WAITING_FOR_FILE.to_string());
+        let data = FileData::new_file(filename, None, meta.clone(), event_sender);
+        let mut file = LoadedFile::new(data, meta);
+        file.path = Some(PathBuf::from(filename));
+        Ok(file)
+    }
+
+    /// Load a listing of the contents of a directory.
+    pub(crate) fn new_directory(
+        index: FileIndex,
+        path: &Path,
+        event_sender: EventSender,
+    ) -> Result<LoadedFile> {
+        let title = path.to_string_lossy().into_owned();
+        let listing = Listing::new(path).map_err(|err| Error::from(err).with_file(&title))?;
+        let mut file = LoadedFile::new_static(index, &title, listing.data, event_sender);
+        file.listing = Some(Arc::new(listing.paths));
+        Ok(file)
+    }
+
     /// Load a file by memory mapping it if possible.
     #[allow(unused)]
     pub(crate) fn new_mapped(
@@ -642,33 +956,116 @@ impl LoadedFile {
         I: IntoIterator<Item = S>,
         S: AsRef<OsStr>,
     {
+        let args = args
+            .into_iter()
+            .map(|arg| arg.as_ref().to_os_string())
+            .collect();
+        LoadedFile::new_command_after(index, command, args, title, Vec::new(), event_sender)
+    }
+
+    /// Load the output and error of a command, with the output following
+    /// `prefix`, e.g. the output of an earlier run of the command.
+    pub(crate) fn new_command_after(
+        index: FileIndex,
+        command: &OsStr,
+        args: Vec<OsString>,
+        title: &str,
+        prefix: Vec<u8>,
+        event_sender: EventSender,
+    ) -> Result<(LoadedFile, LoadedFile)> {
         let title_err = format!("STDERR for {}", title);
-        let mut process = Command::new(command)
-            .args(args)
+        let command_name = command.to_string_lossy().into_owned();
+        let mut process = Command::new(command);
+        process
+            .args(&args)
             .stdin(Stdio::null())
             .stdout(Stdio::piped())
-            .stderr(Stdio::piped())
+            .stderr(Stdio::piped());
+        #[cfg(unix)]
+        {
+            // Run the command in its own process group, so that interrupting
+            // it also interrupts any processes it starts.  The group is in
+            // the background, so the command is stopped if it tries to use
+            // the terminal, and isn't sent `SIGHUP` if the terminal hangs up.
+            use std::os::unix::process::CommandExt;
+            process.process_group(0);
+        }
+        let mut process = process
             .spawn()
             .map_err(|err| Error::from(err).with_command(command))?;
-        let out = process.stdout.take().unwrap();
+        let out = Cursor::new(prefix).chain(process.stdout.take().unwrap());
         let err = process.stderr.take().unwrap();
-        let out_file = LoadedFile::new_streamed(index, out, &title, event_sender.clone());
-        let err_file = LoadedFile::new_streamed(index + 1, err, &title_err, event_sender.clone());
+        let mut out_file = LoadedFile::new_streamed(index, out, title, event_sender.clone());
+        let mut err_file =
+            LoadedFile::new_streamed(index + 1, err, &title_err, event_sender.clone());
+        let command_process = Arc::new(Mutex::new(CommandProcess {
+            command: command.to_os_string(),
+            args,
+            pid: Some(process.id()),
+            interrupted: false,
+            chained: Vec::new(),
+        }));
+        out_file.process = Some(command_process.clone());
+        err_file.process = Some(command_process.clone());
         thread::Builder::new()
             .name(format!("sp-cmd-{}", index))
             .spawn({
                 let out_file = out_file.clone();
+                let child = process;
+                let process = command_process.clone();
                 move || -> Result<()> {
-                    if let Ok(rc) = process.wait() {
+                    let status = wait_for_command(child, |signal| {
+                        // The command can't be resumed while the pager is
+                        // using the terminal, so let the user know why it
+                        // isn't making progress.
+                        let message = if is_terminal_stop(signal) {
+                            format!(
+                                "{} was stopped because it tried to use the terminal",
+                                command_name
+                            )
+                        } else {
+                            format!("{} was stopped", command_name)
+                        };
+                        let _ = event_sender.send(Event::Action(Action::ShowMessage(
+                            message,
+                            Severity::Warning,
+                        )));
+                    });
+                    let interrupted = {
+                        let mut process = process.lock().unwrap();
+                        process.pid = None;
+                        process.interrupted
+                    };
+                    trace_event!(command = %command_name, status = ?status, "command exited");
+                    if interrupted {
+                        // Let the user know how the command they interrupted
+                        // finished.
+                        let message = match status {
+                            Some(status) => format!("{} exited: {}", command_name, status),
+                            None => format!("{} exited", command_name),
+                        };
+                        event_sender
+                            .send(Event::Action(Action::ShowMessage(message, Severity::Info)))?;
+                    }
+                    if let Some(rc) = status {
                         if !rc.success() {
                             let mut info = out_file.meta.info.write().unwrap();
+                            let mut errors = out_file.meta.errors.write().unwrap();
                             match rc.code() {
-                                Some(code) => info.push(format!("rc: {}", code)),
-                                None => info.push("killed!".to_string()),
+                                Some(code) => {
+                                    info.push(format!("rc: {}", code));
+                                    errors
+                                        .push(format!("{} exited with rc {}", command_name, code));
+                                }
+                                None => {
+                                    info.push("killed!".to_string());
+                                    errors.push(format!("{} was killed", command_name));
+                                }
                             }
-                            event_sender.send(Event::RefreshOverlay)?;
+                            event_sender.send(Event::FileError(index))?;
                         }
                     }
+                    event_sender.send(Event::CommandExited(index))?;
                     Ok(())
                 }
             })
@@ -676,6 +1073,65 @@ impl LoadedFile {
         Ok((out_file, err_file))
     }
 
+    /// Returns the range of the file that contains line `index`, including
+    /// its newline.
+    fn line_range(&self, index: usize) -> Option<Range<usize>> {
+        let newlines = self.meta.newlines.read().unwrap();
+        if index > newlines.len() {
+            return None;
+        }
+        let start = if index == 0 {
+            0
+        } else {
+            newlines[index - 1] + 1
+        };
+        let end = if index < newlines.len() {
+            newlines[index] + 1
+        } else {
+            self.meta.length.load(Ordering::SeqCst)
+        };
+        if start == end {
+            return None;
+        }
+        Some(start..end)
+    }
+
+    /// Runs the `call` function, passing it the undecoded contents of line
+    /// `index` and its offset in the file.
+    fn raw_line<T, F>(&self, index: usize, mut call: F) -> Option<T>
+    where
+        F: FnMut(Cow<'_, [u8]>, usize) -> T,
+    {
+        let range = self.line_range(index)?;
+        Some(
+            self.data
+                .with_slice(range.start, range.end, 1, |data| call(data, range.start)),
+        )
+    }
+
+    /// Returns the contents of line `index`, whose decoded contents are
+    /// `data`, after applying `transforms`, or `None` if they don't change
+    /// it.  Complete lines are cached, so they are only transformed once.
+    fn transform_line(
+        &self,
+        index: usize,
+        data: &[u8],
+        transforms: &LineTransforms,
+    ) -> Option<Arc<[u8]>> {
+        if let Some(transformed) = self.meta.transformed.lock().unwrap().get(&index) {
+            return transformed.clone();
+        }
+        let transformed: Option<Arc<[u8]>> = transforms.apply(data).map(Into::into);
+        if data.ends_with(b"\n") {
+            self.meta
+                .transformed
+                .lock()
+                .unwrap()
+                .put(index, transformed.clone());
+        }
+        transformed
+    }
+
     /// Load a file from static data.
     pub(crate) fn new_static(
         index: FileIndex,
@@ -706,6 +1162,12 @@ impl FileInfo for LoadedFile {
         Cow::Owned(info.join(" "))
     }
 
+    /// The errors encountered loading the file, starting from error `start`.
+    fn errors(&self, start: usize) -> Vec<String> {
+        let errors = self.meta.errors.read().unwrap();
+        errors.get(start..).unwrap_or_default().to_vec()
+    }
+
     /// True once the file is loaded and all newlines have been parsed.
     fn loaded(&self) -> bool {
         self.meta.finished.load(Ordering::SeqCst)
@@ -713,6 +1175,7 @@ impl FileInfo for LoadedFile {
 
     /// Returns the number of lines in the file.
     fn lines(&self) -> usize {
+        let encoding = self.encoding();
         let lines = if !self.meta.finished.load(Ordering::SeqCst) {
             let reload_old_line_count = self.meta.reload_old_line_count.read().unwrap();
             reload_old_line_count.unwrap_or(0)
@@ -720,37 +1183,225 @@ impl FileInfo for LoadedFile {
             0
         };
         let newlines = self.meta.newlines.read().unwrap();
-        max(
-            lines,
-            line_count(newlines.as_slice(), self.meta.length.load(Ordering::SeqCst)),
-        )
+        let mut length = self.meta.length.load(Ordering::SeqCst);
+        if encoding == Encoding::Utf16Le
+            && newlines.last().map(|newline| newline + 2) == Some(length)
+        {
+            // The last byte is the end of the final newline.
+            length -= 1;
+        }
+        max(lines, line_count(newlines.as_slice(), length))
+    }
+
+    /// Returns the number of bytes of the file that have been loaded.
+    fn length(&self) -> usize {
+        self.meta.length.load(Ordering::SeqCst)
+    }
+
+    /// Returns the byte offset of the start of line `index`, if the line has
+    /// been loaded.
+    fn line_offset(&self, index: usize) -> Option<usize> {
+        let newlines = self.meta.newlines.read().unwrap();
+        if index == 0 {
+            Some(0)
+        } else {
+            newlines.get(index - 1).map(|offset| offset + 1)
+        }
+    }
+
+    /// Returns the index of the line containing byte `offset`, if that part
+    /// of the file has been loaded.
+    fn line_at_offset(&self, offset: usize) -> Option<usize> {
+        let newlines = self.meta.newlines.read().unwrap();
+        if offset < self.meta.length.load(Ordering::SeqCst) {
+            // The line index is the number of newlines before the offset.
+            Some(newlines.partition_point(|&newline| newline < offset))
+        } else {
+            None
+        }
     }
 
     /// Runs the `call` function, passing it the contents of line `index`.
     /// Tries to avoid copying the data if possible, however the borrowed
     /// line only lasts as long as the function call.
-    fn with_line<T, F>(&self, index: usize, call: F) -> Option<T>
+    fn with_line<T, F>(&self, index: usize, mut call: F) -> Option<T>
     where
         F: FnMut(Cow<'_, [u8]>) -> T,
     {
-        let newlines = self.meta.newlines.read().unwrap();
-        if index > newlines.len() {
-            return None;
-        }
-        let start = if index == 0 {
-            0
+        let encoding = self.encoding();
+        let transforms = self.meta.transforms.read().unwrap();
+        let range = self.line_range(index)?;
+        let max_line_length = self.meta.max_line_length.load(Ordering::SeqCst);
+        let end = if max_line_length != 0 && range.len() > max_line_length {
+            range.start + max_line_length
         } else {
-            newlines[index - 1] + 1
+            range.end
         };
-        let end = if index < newlines.len() {
-            newlines[index] + 1
-        } else {
-            self.meta.length.load(Ordering::SeqCst)
+        let newline = index < self.meta.newlines.read().unwrap().len();
+        Some(self.data.with_slice(range.start, end, 1, |data| {
+            let mut data = match encoding::decode(encoding, &data, range.start) {
+                Some(decoded) => Cow::Owned(decoded),
+                None => data,
+            };
+            if end != range.end {
+                let omitted = range.end - end - usize::from(newline);
+                data = Cow::Owned(truncate_line(&data, omitted, newline));
+            }
+            if transforms.is_empty() {
+                return call(data);
+            }
+            match self.transform_line(index, &data, &transforms) {
+                Some(transformed) => call(Cow::Borrowed(&transformed)),
+                None => call(data),
+            }
+        }))
+    }
+
+    /// Runs the `call` function, passing it the contents of the lines in
+    /// `lines` as one contiguous slice, along with the offsets of the start
+    /// of each line in the slice and the offset of the end of the last line.
+    ///
+    /// Returns `None` if the lines would need decoding or truncating, or
+    /// haven't been loaded.
+    fn with_lines<T, F>(&self, lines: Range<usize>, mut call: F) -> Option<T>
+    where
+        F: FnMut(&[u8], &[usize]) -> T,
+    {
+        if lines.is_empty()
+            || self.encoding() != Encoding::Utf8
+            || !self.meta.transforms.read().unwrap().is_empty()
+        {
+            return None;
+        }
+        let (start, offsets) = {
+            let newlines = self.meta.newlines.read().unwrap();
+            if lines.end > newlines.len() + 1 {
+                return None;
+            }
+            let start = if lines.start == 0 {
+                0
+            } else {
+                newlines[lines.start - 1] + 1
+            };
+            let mut offsets = std::iter::once(start)
+                .chain(
+                    newlines[lines.start..min(lines.end, newlines.len())]
+                        .iter()
+                        .map(|newline| newline + 1),
+                )
+                .map(|offset| offset - start)
+                .collect::<Vec<_>>();
+            if lines.end > newlines.len() {
+                // The last line doesn't end with a newline.
+                offsets.push(self.meta.length.load(Ordering::SeqCst) - start);
+            }
+            (start, offsets)
         };
-        if start == end {
+        let end = start + offsets[offsets.len() - 1];
+        if end == start {
+            return None;
+        }
+        let max_line_length = self.meta.max_line_length.load(Ordering::SeqCst);
+        if max_line_length != 0
+            && offsets
+                .windows(2)
+                .any(|line| line[1] - line[0] > max_line_length)
+        {
+            // Long lines must be truncated a line at a time.
             return None;
         }
-        Some(self.data.with_slice(start, end, call))
+        Some(
+            self.data
+                .with_slice(start, end, lines.len(), |data| call(&data, &offsets)),
+        )
+    }
+
+    /// Returns the character encoding of the file, detecting it from the
+    /// start of the file if necessary.
+    fn encoding(&self) -> Encoding {
+        let encoding = *self.meta.encoding.read().unwrap();
+        if encoding != Encoding::Auto {
+            return encoding;
+        }
+        // Until the encoding is settled, detection only needs to be repeated
+        // when more of the file has been loaded.
+        let length = self.meta.length.load(Ordering::SeqCst);
+        if let Some((detected_length, detected)) = *self.meta.detected_encoding.lock().unwrap() {
+            if detected_length == length {
+                return detected;
+            }
+        }
+        // Gather a sample from the start of the file a line at a time, as
+        // some kinds of file data don't expect slices to span lines.
+        let mut sample = Vec::new();
+        let mut index = 0;
+        while sample.len() < encoding::DETECT_SIZE {
+            let start = sample.len();
+            match self.raw_line(index, |data, _offset| sample.extend_from_slice(&data)) {
+                Some(()) if sample.len() > start => index += 1,
+                _ => break,
+            }
+        }
+        let detected = encoding::detect(&sample[..sample.len().min(encoding::DETECT_SIZE)]);
+        if sample.len() >= encoding::DETECT_SIZE || self.loaded() {
+            *self.meta.encoding.write().unwrap() = detected;
+        } else {
+            *self.meta.detected_encoding.lock().unwrap() = Some((length, detected));
+        }
+        detected
+    }
+
+    /// Set the character encoding of the file.
+    fn set_encoding(&self, encoding: Encoding) {
+        *self.meta.encoding.write().unwrap() = encoding;
+    }
+
+    /// Set the longest line that is passed to `with_line` in full.
+    fn set_max_line_length(&self, length: usize) {
+        if self.meta.max_line_length.swap(length, Ordering::SeqCst) != length {
+            self.meta.transformed.lock().unwrap().clear();
+        }
+    }
+
+    /// Set the transforms applied to lines after they are decoded.
+    fn set_line_transforms(&self, transforms: LineTransforms) {
+        let mut current = self.meta.transforms.write().unwrap();
+        if *current != transforms {
+            *current = transforms;
+            self.meta.transformed.lock().unwrap().clear();
+        }
+    }
+
+    /// If the file is a directory listing, returns the path of the entry on
+    /// line `index`.
+    fn listed_path(&self, index: usize) -> Option<PathBuf> {
+        self.listing.as_ref()?.get(index).cloned()
+    }
+
+    /// Only directory listings have lines that can be opened.
+    fn opens_lines(&self) -> bool {
+        self.listing.is_some()
+    }
+
+    /// Loaded files don't have sections.
+    fn toggle_section(&self, _index: usize) -> bool {
+        false
+    }
+
+    /// Loaded files don't have a cursor.
+    fn cursor(&self) -> Option<usize> {
+        None
+    }
+
+    fn move_cursor(&self, _index: usize) {}
+
+    fn select_cursor(&self) -> bool {
+        false
+    }
+
+    /// The path the file was opened from, if it was opened by name.
+    fn path(&self) -> Option<PathBuf> {
+        self.path.clone()
     }
 
     /// Set how many lines are needed.
@@ -767,9 +1418,171 @@ impl FileInfo for LoadedFile {
         self.meta.waker.notify_all();
     }
 
+    /// Set whether loading pauses once enough lines have been loaded.
+    fn set_backpressure(&self, backpressure: bool) {
+        if self.meta.backpressure.swap(backpressure, Ordering::SeqCst) != backpressure {
+            self.meta.waker.notify_all();
+        }
+    }
+
     /// True if the loading thread has been paused.
     fn paused(&self) -> bool {
-        !self.loaded() && self.meta.waker_mutex.try_lock().is_ok()
+        !self.loaded() && self.meta.paused.load(Ordering::SeqCst)
+    }
+
+    /// Suspend or resume loading.
+    fn set_loading_suspended(&self, suspended: bool) {
+        if self.meta.suspended.swap(suspended, Ordering::SeqCst) != suspended {
+            self.meta.waker.notify_all();
+        }
+    }
+
+    /// True if loading has been suspended.
+    fn loading_suspended(&self) -> bool {
+        self.meta.suspended.load(Ordering::SeqCst)
+    }
+
+    /// True if the loading thread is waiting for more input.
+    fn waiting_for_input(&self) -> bool {
+        !self.loaded() && self.meta.waiting.load(Ordering::SeqCst)
+    }
+
+    /// True if the file is the output of a command that is still running.
+    fn running_command(&self) -> bool {
+        match self.process {
+            Some(ref process) => process.lock().unwrap().pid.is_some(),
+            None => false,
+        }
+    }
+
+    /// Kill the command the file is the output of, if it is still running,
+    /// along with any other processes in its process group.
+    fn kill_command(&self) {
+        if let Some(ref process) = self.process {
+            if let Some(pid) = process.lock().unwrap().pid {
+                #[cfg(unix)]
+                unsafe {
+                    libc::kill(-(pid as libc::pid_t), libc::SIGKILL);
+                }
+                #[cfg(not(unix))]
+                kill_process_tree(pid);
+            }
+        }
+    }
+
+    /// Returns the command the file is the output of, and its arguments.
+    fn command(&self) -> Option<(OsString, Vec<OsString>)> {
+        let process = self.process.as_ref()?.lock().unwrap();
+        Some((process.command.clone(), process.args.clone()))
+    }
+
+    /// Returns what the command the file is the output of has written so
+    /// far, before it is decoded or transformed.
+    fn command_output(&self) -> Option<Vec<u8>> {
+        self.process.as_ref()?;
+        let length = self.meta.length.load(Ordering::SeqCst);
+        if length == 0 {
+            return Some(Vec::new());
+        }
+        Some(
+            self.data
+                .with_slice(0, length, self.lines(), |data| data.into_owned()),
+        )
+    }
+
+    /// Write the file's data to `tee` as it is loaded.
+    fn set_tee(&self, mut tee: Tee) -> Result<()> {
+        let mut current = self.meta.tee.lock().unwrap();
+        let length = self.meta.length.load(Ordering::SeqCst);
+        if length > 0 {
+            self.data
+                .with_slice(0, length, self.lines(), |data| tee.write(&data))?;
+        }
+        *current = Some(tee);
+        self.meta.waker.notify_all();
+        Ok(())
+    }
+
+    /// Stop writing the file's data to its tee.
+    fn finish_tee(&self) -> Result<()> {
+        if let Some(mut tee) = self.meta.tee.lock().unwrap().take() {
+            tee.finish()?;
+        }
+        Ok(())
+    }
+
+    /// Run `chained` after the command the file is the output of, and any
+    /// commands already chained after it, have finished.
+    fn chain_command(&self, chained: ChainedCommand) -> bool {
+        match self.process {
+            Some(ref process) => {
+                process.lock().unwrap().chained.push(chained);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Take the commands chained after the command the file is the output
+    /// of.
+    fn take_chained_commands(&self) -> Vec<ChainedCommand> {
+        match self.process {
+            Some(ref process) => std::mem::take(&mut process.lock().unwrap().chained),
+            None => Vec::new(),
+        }
+    }
+
+    /// Interrupt the command the file is the output of, if it is still
+    /// running.  On Unix, the command's process group is sent `SIGINT`,
+    /// and then `SIGCONT` in case it has been stopped.  Elsewhere, the
+    /// command is killed.
+    fn interrupt_command(&self) -> bool {
+        let mut process = match self.process {
+            Some(ref process) => process.lock().unwrap(),
+            None => return false,
+        };
+        match process.pid {
+            Some(pid) => {
+                process.interrupted = true;
+                #[cfg(unix)]
+                unsafe {
+                    libc::kill(-(pid as libc::pid_t), libc::SIGINT);
+                    libc::kill(-(pid as libc::pid_t), libc::SIGCONT);
+                }
+                #[cfg(not(unix))]
+                kill_process_tree(pid);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Returns information about how the file is stored.
+    fn diagnostics(&self) -> FileDiagnostics {
+        let bytes = self.meta.length.load(Ordering::SeqCst);
+        let index_memory =
+            self.meta.newlines.read().unwrap().capacity() * std::mem::size_of::<usize>();
+        let (storage, buffers, data_memory) = match self.data {
+            FileData::Streamed { ref buffers } => {
+                let buffers = buffers.read().unwrap().len();
+                ("streamed", buffers, buffers * BUFFER_SIZE)
+            }
+            FileData::File {
+                ref buffer_cache, ..
+            } => {
+                let buffers = buffer_cache.lock().unwrap().len();
+                ("file", buffers, buffers * BUFFER_SIZE)
+            }
+            FileData::Mapped { .. } => ("mapped", 0, 0),
+            FileData::Empty => ("empty", 0, 0),
+            FileData::Static { ref data } => ("static", 1, data.len()),
+        };
+        FileDiagnostics {
+            storage,
+            bytes,
+            buffers,
+            memory: index_memory + data_memory,
+        }
     }
 }
 
@@ -782,6 +1595,132 @@ impl Drop for FileGuard {
     }
 }
 
+/// Returns `data`, the start of a line that was too long, followed by a
+/// marker saying that `omitted` bytes were left out, and a newline if the
+/// line ended with one.
+fn truncate_line(data: &[u8], omitted: usize, newline: bool) -> Vec<u8> {
+    // Drop any incomplete character at the end of the data.
+    let mut len = data.len();
+    if let Some(start) = (len.saturating_sub(3)..len)
+        .rev()
+        .find(|&offset| data[offset] & 0xC0 != 0x80)
+    {
+        let char_len = match data[start] {
+            0xC0..=0xDF => 2,
+            0xE0..=0xEF => 3,
+            0xF0..=0xF7 => 4,
+            _ => 1,
+        };
+        if start + char_len > len {
+            len = start;
+        }
+    }
+    let mut line = data[..len].to_vec();
+    line.extend_from_slice(format!("\u{2026} [{} more bytes]", omitted).as_bytes());
+    if newline {
+        line.push(b'\n');
+    }
+    line
+}
+
+/// Wait for a command to exit, returning how it exited, or `None` if that
+/// can't be determined.  `stopped` is called with the signal each time the
+/// command is stopped.
+#[cfg(unix)]
+fn wait_for_command(child: Child, mut stopped: impl FnMut(i32)) -> Option<ExitStatus> {
+    use std::os::unix::process::ExitStatusExt;
+
+    let pid = child.id() as libc::pid_t;
+    loop {
+        let mut status = 0;
+        if unsafe { libc::waitpid(pid, &mut status, libc::WUNTRACED) } == -1 {
+            if std::io::Error::last_os_error().kind() == std::io::ErrorKind::Interrupted {
+                continue;
+            }
+            return None;
+        }
+        if libc::WIFSTOPPED(status) {
+            stopped(libc::WSTOPSIG(status));
+        } else {
+            return Some(ExitStatus::from_raw(status));
+        }
+    }
+}
+
+/// Wait for a command to exit, returning how it exited, or `None` if that
+/// can't be determined.
+#[cfg(not(unix))]
+fn wait_for_command(mut child: Child, _stopped: impl FnMut(i32)) -> Option<ExitStatus> {
+    child.wait().ok()
+}
+
+/// True if `signal` stops a background process that uses the terminal.
+#[cfg(unix)]
+fn is_terminal_stop(signal: i32) -> bool {
+    signal == libc::SIGTTIN || signal == libc::SIGTTOU
+}
+
+#[cfg(not(unix))]
+fn is_terminal_stop(_signal: i32) -> bool {
+    false
+}
+
+/// Kill a process and the processes it has started.
+#[cfg(not(unix))]
+fn kill_process_tree(pid: u32) {
+    let _ = Command::new("taskkill")
+        .args(["/PID", &pid.to_string(), "/T", "/F"])
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status();
+}
+
+/// Reads from `input` into `buffer`, retrying reads that fail with transient
+/// errors, such as timeouts, a few times before giving up.
+///
+/// If `waiting` is given, it is set while waiting for `input` to be read.
+fn read_retrying(
+    input: &mut impl Read,
+    buffer: &mut [u8],
+    waiting: Option<&AtomicBool>,
+) -> std::io::Result<usize> {
+    let mut retries = 0;
+    loop {
+        if let Some(waiting) = waiting {
+            waiting.store(true, Ordering::SeqCst);
+        }
+        let result = input.read(buffer);
+        if let Some(waiting) = waiting {
+            waiting.store(false, Ordering::SeqCst);
+        }
+        match result {
+            Err(e) if e.kind() == std::io::ErrorKind::Interrupted => {}
+            Err(e)
+                if matches!(
+                    e.kind(),
+                    std::io::ErrorKind::WouldBlock | std::io::ErrorKind::TimedOut
+                ) && retries < READ_RETRIES =>
+            {
+                retries += 1;
+                trace_event!(error = %e, retries, "retrying read");
+                thread::sleep(READ_RETRY_DELAY * retries);
+            }
+            result => return result,
+        }
+    }
+}
+
+/// Returns the offsets of the newlines in `data`, which starts at `offset`
+/// in the file.  Loaders find the newlines in each block before locking the
+/// newline index, so that the display isn't blocked while they scan.
+fn newline_offsets(data: &[u8], offset: usize) -> Vec<usize> {
+    data.iter()
+        .enumerate()
+        .filter(|(_, byte)| **byte == b'\n')
+        .map(|(i, _)| offset + i)
+        .collect()
+}
+
 fn line_count(newlines: &[usize], length: usize) -> usize {
     let mut lines = newlines.len();
     let after_last_newline_offset = if lines == 0 {
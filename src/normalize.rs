@@ -0,0 +1,219 @@
+//! Search Normalization
+//!
+//! When searches are normalization insensitive, lines are converted to a
+//! Unicode normalization form before they are searched, and the literal
+//! characters of the pattern are converted to the same form, so that text
+//! that is written with different but equivalent characters still matches.
+//! For example, `é` written as one character matches `e` followed by a
+//! combining acute accent, and with NFKC, full-width `Ａ` matches `A`.
+//!
+//! Normalization can change the length of the text, so the offset of each
+//! segment of the line is recorded, so that matches can be mapped back to
+//! the original line for highlighting.
+
+use std::ops::Range;
+use std::str;
+
+use unicode_normalization::char::canonical_combining_class;
+use unicode_normalization::UnicodeNormalization;
+
+use crate::config::SearchNormalization;
+
+/// A table for translating offsets in a normalized line back to offsets in
+/// the original line.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub(crate) struct NormalizedOffsets {
+    /// The offset of each segment in the normalized line and in the original
+    /// line.  The last entry is the end of the line.
+    segments: Vec<(usize, usize)>,
+}
+
+impl NormalizedOffsets {
+    /// Translates `range`, a range of the normalized line, to the range of
+    /// the original line.  Ranges that start or end within a segment are
+    /// extended to include the whole segment.
+    pub(crate) fn range(&self, range: Range<usize>) -> Range<usize> {
+        let segment = |offset: usize| {
+            self.segments
+                .partition_point(|&(normalized, _)| normalized <= offset)
+                .saturating_sub(1)
+        };
+        let start = self.segments[segment(range.start)].1;
+        let end = if range.end > range.start {
+            self.segments[segment(range.end - 1) + 1].1
+        } else {
+            start
+        };
+        start..end
+    }
+}
+
+/// Appends the segments of `text`, which starts at `offset` in the original
+/// line, to `normalized` in the normalization form.  Each segment is a
+/// character that doesn't combine with the characters before it, followed by
+/// the characters that do.
+fn normalize_str(
+    text: &str,
+    offset: usize,
+    normalization: SearchNormalization,
+    normalized: &mut Vec<u8>,
+    segments: &mut Vec<(usize, usize)>,
+) {
+    let mut starts = text
+        .char_indices()
+        .filter(|(_, c)| canonical_combining_class(*c) == 0)
+        .map(|(index, _)| index)
+        .peekable();
+    if !text.is_empty() && starts.peek() != Some(&0) {
+        // The text starts with combining characters.
+        segments.push((normalized.len(), offset));
+        normalize_segment(
+            &text[..starts.peek().copied().unwrap_or(text.len())],
+            normalization,
+            normalized,
+        );
+    }
+    while let Some(start) = starts.next() {
+        let end = starts.peek().copied().unwrap_or(text.len());
+        segments.push((normalized.len(), offset + start));
+        normalize_segment(&text[start..end], normalization, normalized);
+    }
+}
+
+/// Appends `segment` to `normalized` in the normalization form.
+fn normalize_segment(segment: &str, normalization: SearchNormalization, normalized: &mut Vec<u8>) {
+    let mut buffer = [0; 4];
+    let mut push = |c: char| normalized.extend_from_slice(c.encode_utf8(&mut buffer).as_bytes());
+    match normalization {
+        SearchNormalization::None => normalized.extend_from_slice(segment.as_bytes()),
+        SearchNormalization::Nfc => segment.nfc().for_each(&mut push),
+        SearchNormalization::Nfkc => segment.nfkc().for_each(&mut push),
+    }
+}
+
+/// Converts `data`, a line with its escape sequences removed, to the
+/// normalization form, returning the normalized line and the table for
+/// translating offsets in it back to offsets in `data`.
+///
+/// Returns `None` if normalization would not change the line.
+pub(crate) fn normalize(
+    data: &[u8],
+    normalization: SearchNormalization,
+) -> Option<(Vec<u8>, NormalizedOffsets)> {
+    if normalization == SearchNormalization::None || data.is_ascii() {
+        return None;
+    }
+    let mut normalized = Vec::with_capacity(data.len());
+    let mut segments = Vec::new();
+    let mut input = data;
+    while !input.is_empty() {
+        let offset = data.len() - input.len();
+        let (valid, invalid) = match str::from_utf8(input) {
+            Ok(valid) => (valid, 0),
+            Err(error) => {
+                // The bytes up to the error are valid, so this can't fail.
+                let valid = str::from_utf8(&input[..error.valid_up_to()]).unwrap_or_default();
+                (
+                    valid,
+                    error.error_len().unwrap_or(input.len() - valid.len()),
+                )
+            }
+        };
+        normalize_str(valid, offset, normalization, &mut normalized, &mut segments);
+        // Invalid bytes are kept as they are.
+        let invalid_start = valid.len();
+        for (index, byte) in input[invalid_start..invalid_start + invalid]
+            .iter()
+            .enumerate()
+        {
+            segments.push((normalized.len(), offset + invalid_start + index));
+            normalized.push(*byte);
+        }
+        input = &input[invalid_start + invalid..];
+    }
+    segments.push((normalized.len(), data.len()));
+    Some((normalized, NormalizedOffsets { segments }))
+}
+
+/// Converts the literal characters of `pattern` to the normalization form.
+///
+/// Only characters outside ASCII are converted, so that the regex syntax of
+/// the pattern is unchanged.  Converted characters are escaped, in case they
+/// become characters that have special meaning in a pattern.
+pub(crate) fn normalize_pattern(pattern: &str, normalization: SearchNormalization) -> String {
+    if normalization == SearchNormalization::None || pattern.is_ascii() {
+        return pattern.to_string();
+    }
+    let mut normalized = String::with_capacity(pattern.len());
+    let mut rest = pattern;
+    while let Some(start) = rest.find(|c: char| !c.is_ascii()) {
+        // Include the character before if the non-ASCII characters combine
+        // with it.
+        let start = match rest[start..].chars().next() {
+            Some(c) if canonical_combining_class(c) != 0 && start > 0 => start - 1,
+            _ => start,
+        };
+        let end = rest[start..]
+            .char_indices()
+            .skip(1)
+            .find(|(_, c)| c.is_ascii())
+            .map_or(rest.len(), |(index, _)| start + index);
+        normalized.push_str(&rest[..start]);
+        let mut segment = Vec::new();
+        normalize_segment(&rest[start..end], normalization, &mut segment);
+        let segment = String::from_utf8(segment).expect("normalized text should be UTF-8");
+        normalized.push_str(&regex::escape(&segment));
+        rest = &rest[end..];
+    }
+    normalized.push_str(rest);
+    normalized
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_normalize() {
+        assert_eq!(normalize(b"plain", SearchNormalization::Nfc), None);
+        assert_eq!(
+            normalize("caf\u{e9}".as_bytes(), SearchNormalization::None),
+            None
+        );
+
+        let data = "cafe\u{301} \u{FF21}\u{FF22}".as_bytes();
+        let (normalized, offsets) = normalize(data, SearchNormalization::Nfc).unwrap();
+        assert_eq!(normalized, "caf\u{e9} \u{FF21}\u{FF22}".as_bytes());
+        // The composed character maps back to both original characters.
+        assert_eq!(offsets.range(3..5), 3..6);
+        assert_eq!(offsets.range(6..9), 7..10);
+
+        let (normalized, offsets) = normalize(data, SearchNormalization::Nfkc).unwrap();
+        assert_eq!(normalized, "caf\u{e9} AB".as_bytes());
+        assert_eq!(offsets.range(6..8), 7..13);
+        assert_eq!(offsets.range(7..7), 10..10);
+
+        // Invalid bytes are kept.
+        let (normalized, offsets) =
+            normalize(b"\xFFe\xCC\x81\xFF", SearchNormalization::Nfc).unwrap();
+        assert_eq!(normalized, b"\xFF\xC3\xA9\xFF");
+        assert_eq!(offsets.range(1..3), 1..4);
+        assert_eq!(offsets.range(3..4), 4..5);
+    }
+
+    #[test]
+    fn test_normalize_pattern() {
+        assert_eq!(
+            normalize_pattern("caf\u{e9}|x+", SearchNormalization::None),
+            "caf\u{e9}|x+"
+        );
+        assert_eq!(
+            normalize_pattern("cafe\u{301}|x+", SearchNormalization::Nfc),
+            "caf\u{e9}|x+"
+        );
+        assert_eq!(
+            normalize_pattern("\u{FF08}\u{FF21}\u{FF09}+", SearchNormalization::Nfkc),
+            "\\(A\\)+"
+        );
+    }
+}
@@ -1,27 +1,47 @@
 //! The pager.
 
+use std::borrow::Cow;
 use std::ffi::OsStr;
 use std::io::Read;
+use std::path::PathBuf;
 use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
 
 use termwiz::caps::ColorLevel;
 use termwiz::caps::{Capabilities, ProbeHints};
 use termwiz::terminal::{SystemTerminal, Terminal};
 use vec_map::VecMap;
 
-use crate::action::ActionSender;
+use crate::action::{Action, ActionSender, Severity};
+#[cfg(feature = "tokio")]
+use crate::async_stream::AsyncStream;
 use crate::bindings::Keymap;
-use crate::config::{Config, InterfaceMode, KeymapConfig, WrappingMode};
+use crate::config::{
+    Animation, Backpressure, ColorDepth, ColorMode, Config, Encoding, HighlightRule, InterfaceMode,
+    KeymapConfig, Profile, ReadAheadProfile, Renderer, RulerFormat, SearchNormalization, TextStyle,
+    WatchAlert, WrappingMode,
+};
 use crate::control::Controller;
 use crate::error::{Error, Result};
-use crate::event::EventStream;
-use crate::file::{ControlledFile, File, FileIndex, FileInfo, LoadedFile};
-use crate::progress::Progress;
+use crate::event::{Event, EventStream, Waker};
+use crate::file::{
+    ChainedCommand, ControlledFile, File, FileIndex, FileInfo, LineIndex, LoadedFile,
+};
+use crate::handle::PagerHandle;
+use crate::hangup::HangupListener;
+use crate::listener::Listener;
+use crate::progress::{Progress, ProgressStreams};
+use crate::redact::Redaction;
+use crate::session::{Resume, Session};
+use crate::tee::Tee;
+use crate::trace;
+use crate::transform::LineTransform;
 
 /// The main pager state.
 pub struct Pager {
     /// The Terminal.
-    term: SystemTerminal,
+    term: Box<dyn Terminal + Send>,
 
     /// The Terminal's capabilites.
     caps: Capabilities,
@@ -36,39 +56,202 @@ pub struct Pager {
     error_files: VecMap<File>,
 
     /// Progress indicators to display.
-    progress: Option<Progress>,
+    progress: ProgressStreams,
 
     /// Configuration.
     config: Config,
+
+    /// Handle for querying the pager's state, if one has been requested.
+    handle: Option<PagerHandle>,
+
+    /// Where to write the primary stream when the pager exits.
+    write_output: Option<WriteOutput>,
+
+    /// Listeners for connections whose data is paged as new files.
+    listeners: Vec<Listener>,
+
+    /// How to format lines printed directly to the terminal.
+    direct_format: Option<Box<DirectFormat>>,
+
+    /// Whether to reload the configuration when the process receives
+    /// `SIGHUP`.
+    reload_on_hangup: bool,
+
+    /// The saved session being resumed, if any.
+    resume: Option<Resume>,
 }
 
-/// Determine terminal capabilities.
-fn termcaps() -> Result<Capabilities> {
+/// The stream that a line printed directly to the terminal came from.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum DirectStream {
+    /// The primary output stream.
+    Output,
+
+    /// The error stream.
+    Error,
+
+    /// The progress indicator.
+    Progress,
+}
+
+/// A function that formats lines printed directly to the terminal.
+pub(crate) type DirectFormat = dyn Fn(DirectStream, &[u8]) -> Vec<u8> + Send + Sync;
+
+/// Destination for writing the primary stream.
+struct WriteOutput {
+    /// The file to write to.
+    path: PathBuf,
+
+    /// Whether to remove escape sequences from the output.
+    strip_escapes: bool,
+}
+
+/// Determine terminal capabilities hints.
+fn probe_hints() -> ProbeHints {
     // Get terminal capabilities from the environment, but disable mouse
     // reporting, as we don't want to change the terminal's mouse handling.
     // Enable TrueColor support, which is backwards compatible with 16
     // or 256 colors. Applications can still limit themselves to 16 or
     // 256 colors if they want.
-    let hints = ProbeHints::new_from_env()
+    ProbeHints::new_from_env()
         .color_level(Some(ColorLevel::TrueColor))
-        .mouse_reporting(Some(false));
-    let caps = Capabilities::new_with_hints(hints).map_err(Error::Termwiz)?;
+        .mouse_reporting(Some(false))
+}
+
+/// Determine terminal capabilities.
+///
+/// If the terminfo database is missing, the terminal is assumed to be a
+/// basic ANSI terminal if the configuration allows it.  Without terminfo,
+/// termwiz falls back to ANSI escape sequences, limited to 16 colors.
+fn termcaps(config: &Config) -> Result<Capabilities> {
+    let caps = Capabilities::new_with_hints(probe_hints()).map_err(Error::Termwiz)?;
     if cfg!(unix) && caps.terminfo_db().is_none() {
-        Err(Error::TerminfoDatabaseMissing)
+        if config.assume_ansi {
+            let hints = probe_hints()
+                .terminfo_db(None)
+                .color_level(Some(ColorLevel::Sixteen));
+            Capabilities::new_with_hints(hints).map_err(Error::Termwiz)
+        } else {
+            Err(Error::TerminfoDatabaseMissing)
+        }
     } else {
         Ok(caps)
     }
 }
 
+/// Opens the terminal the pager is controlled from.
+///
+/// The controlling terminal is preferred, as it can be used even when the
+/// standard streams are redirected.  Some environments don't have one (e.g.
+/// `sudo` with `use_pty`, or minimal containers), so any standard stream
+/// that is attached to a terminal is tried next.  Terminals are opened for
+/// both reading and writing, so an output stream can also provide input.
+///
+/// If the only terminal found can't be read from, it is still used for
+/// output, and the reason there is no keyboard input is also returned.
+#[cfg(unix)]
+fn open_terminal(caps: Capabilities) -> Result<(SystemTerminal, Option<String>)> {
+    use std::os::unix::io::{AsRawFd, RawFd};
+    use termwiz::istty::IsTty;
+
+    struct Fd(RawFd);
+
+    impl AsRawFd for Fd {
+        fn as_raw_fd(&self) -> RawFd {
+            self.0
+        }
+    }
+
+    fn readable(fd: &Fd) -> bool {
+        let flags = unsafe { libc::fcntl(fd.0, libc::F_GETFL) };
+        flags != -1 && flags & libc::O_ACCMODE != libc::O_WRONLY
+    }
+
+    let mut failures = Vec::new();
+    match SystemTerminal::new(caps.clone()) {
+        Ok(term) => return Ok((term, None)),
+        Err(e) => failures.push(format!("/dev/tty: {}", e)),
+    }
+    let streams = [
+        ("stdin", Fd(std::io::stdin().as_raw_fd())),
+        ("stderr", Fd(std::io::stderr().as_raw_fd())),
+        ("stdout", Fd(std::io::stdout().as_raw_fd())),
+    ];
+    let mut output_only = None;
+    for (name, fd) in streams.iter() {
+        if !fd.is_tty() {
+            failures.push(format!("{}: not a terminal", name));
+        } else if !readable(fd) {
+            failures.push(format!("{}: not open for reading", name));
+            output_only = output_only.or(Some(fd));
+        } else {
+            match SystemTerminal::new_with(caps.clone(), fd, fd) {
+                Ok(term) => return Ok((term, None)),
+                Err(e) => failures.push(format!("{}: {}", name, e)),
+            }
+        }
+    }
+    let failures = failures.join(", ");
+    match output_only {
+        Some(fd) => {
+            let term = SystemTerminal::new_with(caps, fd, fd).map_err(Error::Termwiz)?;
+            Ok((term, Some(failures)))
+        }
+        None => Err(Error::NoTerminal(failures)),
+    }
+}
+
+/// Opens the console the pager is controlled from.
+///
+/// The console is opened directly, as the standard streams may be
+/// redirected.  If that fails, the standard streams are tried instead.
+#[cfg(windows)]
+fn open_terminal(caps: Capabilities) -> Result<(SystemTerminal, Option<String>)> {
+    let term = SystemTerminal::new(caps.clone()).or_else(|console_error| {
+        SystemTerminal::new_from_stdio(caps).map_err(|stdio_error| {
+            Error::NoTerminal(format!(
+                "console: {}, stdio: {}",
+                console_error, stdio_error
+            ))
+        })
+    })?;
+    Ok((term, None))
+}
+
 impl Pager {
     /// Build a `Pager` using the system terminal.
     pub fn new_using_system_terminal() -> Result<Self> {
-        Self::new_with_terminal_func(move |caps| SystemTerminal::new(caps).map_err(Error::Termwiz))
+        let config = Config::from_config_file().with_env();
+        Self::new_using_system_terminal_with_config(config)
+    }
+
+    /// Build a `Pager` using the system terminal and the given
+    /// configuration.
+    ///
+    /// Settings that affect how the terminal is set up, like `assume_ansi`,
+    /// must be provided here, as they can't be changed once the pager has
+    /// been built.
+    pub fn new_using_system_terminal_with_config(config: Config) -> Result<Self> {
+        let caps = termcaps(&config)?;
+        let (term, input_error) = open_terminal(caps.clone())?;
+        #[cfg(all(windows, feature = "windows-console"))]
+        let (term, caps) =
+            crate::console::enable_virtual_terminal(term, caps, probe_hints(), |caps| {
+                Ok(open_terminal(caps)?.0)
+            })?;
+        let waker = term.waker();
+        let mut pager =
+            Self::new_with_boxed_terminal(caps, Box::new(term), Some(waker.into()), config)?;
+        if let Some(error) = input_error {
+            pager.events.set_input_failed(error)?;
+        }
+        Ok(pager)
     }
 
     /// Build a `Pager` using the system stdio.
     pub fn new_using_stdio() -> Result<Self> {
-        Self::new_with_terminal_func(move |caps| {
+        let config = Config::from_config_file().with_env();
+        Self::new_with_terminal_func(config, move |caps| {
             SystemTerminal::new_from_stdio(caps).map_err(Error::Termwiz)
         })
     }
@@ -79,7 +262,8 @@ impl Pager {
         input: &impl std::os::unix::io::AsRawFd,
         output: &impl std::os::unix::io::AsRawFd,
     ) -> Result<Self> {
-        Self::new_with_terminal_func(move |caps| {
+        let config = Config::from_config_file().with_env();
+        Self::new_with_terminal_func(config, move |caps| {
             SystemTerminal::new_with(caps, input, output).map_err(Error::Termwiz)
         })
     }
@@ -90,23 +274,58 @@ impl Pager {
         input: impl std::io::Read + termwiz::istty::IsTty + std::os::windows::io::AsRawHandle,
         output: impl std::io::Write + termwiz::istty::IsTty + std::os::windows::io::AsRawHandle,
     ) -> Result<Self> {
-        Self::new_with_terminal_func(move |caps| {
+        let config = Config::from_config_file().with_env();
+        Self::new_with_terminal_func(config, move |caps| {
             SystemTerminal::new_with(caps, input, output).map_err(Error::Termwiz)
         })
     }
 
+    /// Build a `Pager` using an arbitrary terminal implementation.
+    ///
+    /// This allows the pager to run on terminals other than the system
+    /// terminal, for example termwiz's `BufferedTerminal`.  The terminal's
+    /// waker is used to wake the pager when events arrive from other threads.
+    pub fn new_with_terminal<T: Terminal + Send + 'static>(term: T) -> Result<Self> {
+        // The terminal is responsible for its own rendering, so the terminfo
+        // database is not required.
+        let caps = Capabilities::new_with_hints(probe_hints()).map_err(Error::Termwiz)?;
+        let waker = term.waker();
+        let config = Config::from_config_file().with_env();
+        Self::new_with_boxed_terminal(caps, Box::new(term), Some(waker.into()), config)
+    }
+
     fn new_with_terminal_func(
+        config: Config,
         create_term: impl FnOnce(Capabilities) -> Result<SystemTerminal>,
     ) -> Result<Self> {
-        let caps = termcaps()?;
-        let mut term = create_term(caps.clone())?;
+        let caps = termcaps(&config)?;
+        let term = create_term(caps.clone())?;
+        let waker = term.waker();
+        Self::new_with_boxed_terminal(caps, Box::new(term), Some(waker.into()), config)
+    }
+
+    /// Build a `Pager` on a terminal with the given configuration.
+    ///
+    /// If no waker is provided, the terminal's `poll_input` must return
+    /// periodically so that events from other threads are processed.
+    pub(crate) fn new_with_boxed_terminal(
+        caps: Capabilities,
+        mut term: Box<dyn Terminal + Send>,
+        waker: Option<Waker>,
+        config: Config,
+    ) -> Result<Self> {
         term.set_raw_mode().map_err(Error::Termwiz)?;
 
-        let events = EventStream::new(term.waker());
+        let events = EventStream::new(waker);
         let files = Vec::new();
         let error_files = VecMap::new();
-        let progress = None;
-        let config = Config::from_config_file().with_env();
+        let progress = ProgressStreams::default();
+        let handle = None;
+        let write_output = None;
+        let listeners = Vec::new();
+        let direct_format = None;
+        let reload_on_hangup = false;
+        let resume = None;
 
         Ok(Self {
             term,
@@ -116,6 +335,12 @@ impl Pager {
             error_files,
             progress,
             config,
+            handle,
+            write_output,
+            listeners,
+            direct_format,
+            reload_on_hangup,
+            resume,
         })
     }
 
@@ -132,6 +357,48 @@ impl Pager {
         Ok(index)
     }
 
+    /// Add a stream that implements `tokio::io::AsyncRead` to be paged.
+    ///
+    /// The stream is read on a thread of its own, like any other stream.  If
+    /// this is called from within a tokio runtime, the reads run on that
+    /// runtime, which must keep running to drive the stream.  Otherwise the
+    /// stream is given a runtime of its own.
+    #[cfg(feature = "tokio")]
+    pub fn add_async_stream(
+        &mut self,
+        stream: impl tokio::io::AsyncRead + Send + 'static,
+        title: &str,
+    ) -> Result<FileIndex> {
+        let stream = AsyncStream::new(stream)?;
+        self.add_stream(stream, title)
+    }
+
+    /// Add data that is already in memory to be paged, such as a generated
+    /// report.  The data can be borrowed for `'static` or owned, e.g. a
+    /// `&'static [u8]` or a `Vec<u8>`.
+    pub fn add_static(
+        &mut self,
+        data: impl Into<Cow<'static, [u8]>>,
+        title: &str,
+    ) -> Result<FileIndex> {
+        let index = self.files.len();
+        let event_sender = self.events.sender();
+        let file = LoadedFile::new_static(index, title, data, event_sender);
+        self.files.push(file.into());
+        Ok(index)
+    }
+
+    /// Returns a view of the lines of file `index`, for mapping between
+    /// lines and byte offsets.  The view remains valid, and keeps up with
+    /// the file as it loads, while the pager runs.
+    pub fn line_index(&self, index: FileIndex) -> Result<LineIndex> {
+        let file = self
+            .files
+            .get(index)
+            .ok_or(Error::InvalidFileIndex(index))?;
+        Ok(LineIndex::new(file.clone()))
+    }
+
     /// Attach an error stream to the previously added output stream.
     pub fn add_error_stream(
         &mut self,
@@ -149,6 +416,40 @@ impl Pager {
         Ok(index)
     }
 
+    /// Attach an error stream to the file with index `index`, which may be
+    /// any file that has already been added, including a controlled file.
+    pub fn add_error_stream_for(
+        &mut self,
+        index: FileIndex,
+        stream: impl Read + Send + 'static,
+        title: &str,
+    ) -> Result<FileIndex> {
+        if index >= self.files.len() {
+            return Err(Error::InvalidFileIndex(index));
+        }
+        let error_index = self.files.len();
+        let event_sender = self.events.sender();
+        let file = LoadedFile::new_streamed(error_index, stream, title, event_sender);
+        self.error_files.insert(index, file.clone().into());
+        self.files.push(file.into());
+        Ok(error_index)
+    }
+
+    /// Show the file with index `error_index` as the error file of the file
+    /// with index `index`, replacing any error file it already has.  Both
+    /// files must already have been added.
+    pub fn set_error_file(&mut self, index: FileIndex, error_index: FileIndex) -> Result<()> {
+        if index >= self.files.len() {
+            return Err(Error::InvalidFileIndex(index));
+        }
+        let error_file = self
+            .files
+            .get(error_index)
+            .ok_or(Error::InvalidFileIndex(error_index))?;
+        self.error_files.insert(index, error_file.clone());
+        Ok(())
+    }
+
     /// Attach a file from disk.
     pub fn add_file(&mut self, filename: &OsStr) -> Result<FileIndex> {
         let index = self.files.len();
@@ -158,6 +459,42 @@ impl Pager {
         Ok(index)
     }
 
+    /// Add a file to be displayed, like `add_file`, but if it doesn't exist
+    /// yet, wait for it to be created and load it then, like `tail -F`.
+    pub fn add_file_or_wait(&mut self, filename: &OsStr) -> Result<FileIndex> {
+        let index = self.files.len();
+        let event_sender = self.events.sender();
+        let file = LoadedFile::new_file_or_wait(index, filename, event_sender)?;
+        self.files.push(file.into());
+        Ok(index)
+    }
+
+    /// Reopen the files that were open when the pager last quit, restoring
+    /// where each one was scrolled to and what was being searched for.
+    /// Sessions are saved when the `save_session` option is set, and only
+    /// include files opened by name.  Files that can no longer be opened are
+    /// skipped.
+    pub fn resume_session(&mut self) -> Result<()> {
+        let session = Session::load()?.ok_or(Error::NoSession)?;
+        let mut resume = Resume {
+            current: None,
+            files: Vec::new(),
+        };
+        for (index, file) in session.files.into_iter().enumerate() {
+            if let Ok(file_index) = self.add_file(file.path.as_os_str()) {
+                if index == session.current {
+                    resume.current = Some(file_index);
+                }
+                resume.files.push((file_index, file));
+            }
+        }
+        if resume.files.is_empty() {
+            return Err(Error::NoSession);
+        }
+        self.resume = Some(resume);
+        Ok(())
+    }
+
     /// Attach a controlled file.
     pub fn add_controlled_file(&mut self, controller: &Controller) -> Result<FileIndex> {
         let index = self.files.len();
@@ -190,10 +527,94 @@ impl Pager {
         Ok((index, index + 1))
     }
 
-    /// Set the progress stream.
+    /// Run a subprocess once the subprocess whose output is file `after`,
+    /// and any subprocesses already chained after it, have finished.  Its
+    /// output and error streams are attached as new files when it starts,
+    /// and the pager switches to its output unless
+    /// [`Pager::set_switch_to_chained_commands`] turns this off.
+    pub fn add_subprocess_then<I, S>(
+        &mut self,
+        after: FileIndex,
+        command: &OsStr,
+        args: I,
+        title: &str,
+    ) -> Result<()>
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<OsStr>,
+    {
+        let file = self
+            .files
+            .get(after)
+            .ok_or(Error::InvalidFileIndex(after))?;
+        let chained = ChainedCommand {
+            command: command.to_os_string(),
+            args: args
+                .into_iter()
+                .map(|arg| arg.as_ref().to_os_string())
+                .collect(),
+            title: title.to_string(),
+        };
+        if !file.chain_command(chained) {
+            return Err(Error::NotACommand(after));
+        }
+        Ok(())
+    }
+
+    /// Listen for connections on a Unix socket or TCP port.
+    ///
+    /// `address` is either the path of a Unix socket to create, which must
+    /// contain a `/` (e.g. `./sp.sock`), or a TCP address to bind to (e.g.
+    /// `localhost:4000`).  The data written to each connection is paged as a
+    /// new file, titled by the peer's address.
+    ///
+    /// Connections are not authenticated, so the Unix socket is only
+    /// accessible to the user, and TCP addresses must be loopback addresses.
+    pub fn add_socket_listener(&mut self, address: &str) -> Result<()> {
+        let event_sender = self.events.sender();
+        self.listeners.push(Listener::new(address, event_sender)?);
+        Ok(())
+    }
+
+    /// Listen for streams sent by other invocations with `sp --send`, on a
+    /// socket private to the user.  Each stream is paged as a new file.
+    ///
+    /// Returns `false` without listening if another pager is already
+    /// listening.
+    #[cfg(unix)]
+    pub fn add_send_listener(&mut self) -> Result<bool> {
+        let event_sender = self.events.sender();
+        match Listener::new_send(event_sender)? {
+            Some(listener) => {
+                self.listeners.push(listener);
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    /// Set the progress stream.  Its progress indicators are shown on every
+    /// screen that doesn't have a progress stream of its own.
     pub fn set_progress_stream(&mut self, stream: impl Read + Send + 'static) {
         let event_sender = self.events.sender();
-        self.progress = Some(Progress::new(stream, event_sender));
+        self.progress
+            .set_shared(Progress::new(stream, event_sender));
+    }
+
+    /// Attach a progress stream to the file with index `index`.  Its progress
+    /// indicators are only shown on that file's screen.
+    pub fn add_progress_stream_for(
+        &mut self,
+        index: FileIndex,
+        stream: impl Read + Send + 'static,
+    ) -> Result<()> {
+        if index >= self.files.len() {
+            return Err(Error::InvalidFileIndex(index));
+        }
+        let event_sender = self.events.sender();
+        self.progress
+            .insert(index, Progress::new(stream, event_sender));
+        Ok(())
     }
 
     /// Set when to use full screen mode. See [`InterfaceMode`] for details.
@@ -211,6 +632,20 @@ impl Pager {
         self.config.read_ahead_lines = lines;
     }
 
+    /// Set how far ahead of the screen to read from streams, overriding the
+    /// number of lines set by [`Pager::set_read_ahead_lines`].  See
+    /// [`ReadAheadProfile`] for details.
+    pub fn set_read_ahead_profile(&mut self, profile: ReadAheadProfile) {
+        self.config.read_ahead_profile = Some(profile);
+    }
+
+    /// Set when reading from streams stops once enough has been read ahead
+    /// of the screen.  See [`Backpressure`] for details.  Profiles can
+    /// override this for particular files.
+    pub fn set_backpressure(&mut self, backpressure: Backpressure) {
+        self.config.backpressure = backpressure;
+    }
+
     /// Set whether to poll input during start-up (delayed or direct mode).
     pub fn set_startup_poll_input(&mut self, poll_input: bool) {
         self.config.startup_poll_input = poll_input;
@@ -221,6 +656,300 @@ impl Pager {
         self.config.show_ruler = show_ruler;
     }
 
+    /// Set the items shown in the ruler. See [`RulerFormat`] for details.
+    pub fn set_ruler_format(&mut self, value: impl Into<RulerFormat>) {
+        self.config.ruler_format = value.into();
+    }
+
+    /// Set whether to ask for confirmation before quitting while the current
+    /// file is still loading.
+    pub fn set_confirm_quit_while_loading(&mut self, value: bool) {
+        self.config.confirm_quit_while_loading = value;
+    }
+
+    /// Set whether text pasted to start a search is searched for literally,
+    /// rather than as a regular expression.
+    pub fn set_literal_paste_search(&mut self, value: bool) {
+        self.config.literal_paste_search = value;
+    }
+
+    /// Set whether searches only look at the columns of each line that are
+    /// visible when lines are not wrapped.
+    pub fn set_search_visible_columns(&mut self, value: bool) {
+        self.config.search_visible_columns = value;
+    }
+
+    /// Set whether the pager captures the mouse, so that the mouse wheel
+    /// scrolls the file.
+    pub fn set_mouse(&mut self, value: bool) {
+        self.config.mouse = value;
+    }
+
+    /// Set whether to leave the contents of the screen in the terminal when
+    /// quitting from [`InterfaceMode::FullScreenNoAlternate`].
+    pub fn set_keep_screen_on_quit(&mut self, value: bool) {
+        self.config.keep_screen_on_quit = value;
+    }
+
+    /// Set whether to print the lines of the file that are visible on the
+    /// screen to the terminal after quitting.
+    pub fn set_print_screen_on_quit(&mut self, value: bool) {
+        self.config.print_screen_on_quit = value;
+    }
+
+    /// Set whether to set the terminal's window title to the title of the
+    /// current file while paging.  The previous title is restored when the
+    /// pager quits.
+    pub fn set_terminal_title(&mut self, value: bool) {
+        self.config.set_terminal_title = value;
+    }
+
+    /// Set whether to switch to the output of each command added with
+    /// [`Pager::add_subprocess_then`] when it starts running.
+    pub fn set_switch_to_chained_commands(&mut self, value: bool) {
+        self.config.switch_to_chained_commands = value;
+    }
+
+    /// Set whether to turn references to lines in files, like
+    /// `src/main.rs:12`, into hyperlinks to those files.
+    pub fn set_linkify_file_paths(&mut self, value: bool) {
+        self.config.linkify_file_paths = value;
+    }
+
+    /// Set the rules for highlighting text in every line.  See
+    /// [`HighlightRule`] for details.
+    pub fn set_highlight_rules(&mut self, rules: Vec<HighlightRule>) {
+        self.config.highlight_rules = rules;
+    }
+
+    /// Set whether to render simple markdown constructs, like headings and
+    /// emphasis, in markdown files.
+    pub fn set_render_markdown(&mut self, value: bool) {
+        self.config.render_markdown = value;
+    }
+
+    /// Set whether to color the keys of objects when pretty-printing JSON.
+    pub fn set_color_json_keys(&mut self, value: bool) {
+        self.config.color_json_keys = value;
+    }
+
+    /// Set whether scrolling right in unwrapped mode snaps to the starts of
+    /// fields in the visible lines.
+    pub fn set_snap_to_columns(&mut self, value: bool) {
+        self.config.snap_to_columns = value;
+    }
+
+    /// Set whether to show a scrollbar in the rightmost column, which also
+    /// marks where search matches are.
+    pub fn set_show_scrollbar(&mut self, value: bool) {
+        self.config.show_scrollbar = value;
+    }
+
+    /// Add commands to run once the first screen is shown, such as
+    /// `search ERROR; scroll-to-end`.  Commands are the names of bindings in
+    /// kebab case, followed by their parameter, if any.  They run after the
+    /// commands in the user's init script.
+    pub fn add_startup_commands(&mut self, commands: impl Into<String>) {
+        self.config.startup_commands.push(commands.into());
+    }
+
+    /// Set how long messages sent with `Action::ShowMessage` are shown for.
+    pub fn set_message_timeout(&mut self, timeout: Duration) {
+        self.config.message_timeout_ms = timeout.as_millis() as u64;
+    }
+
+    /// Set the most rows the progress indicator may use.  If 0, whole
+    /// progress pages are shown.
+    pub fn set_progress_max_height(&mut self, height: usize) {
+        self.config.progress_max_height = height;
+    }
+
+    /// Set the column to wrap lines at in the `column` wrapping mode.  If 0,
+    /// lines are wrapped at the edge of the screen.
+    pub fn set_wrap_column(&mut self, column: usize) {
+        self.config.wrap_column = column;
+    }
+
+    /// Set the character encoding of files.
+    pub fn set_encoding(&mut self, encoding: Encoding) {
+        self.config.encoding = encoding;
+    }
+
+    /// Set the longest line, in bytes, that is shown in full.  Longer lines
+    /// are truncated.  If 0, lines are never truncated.
+    pub fn set_max_line_length(&mut self, length: usize) {
+        self.config.max_line_length = length;
+    }
+
+    /// Add a transform that rewrites the contents of lines before they are
+    /// shown or searched.  Transforms are applied in the order they are
+    /// added, after lines are converted to UTF-8.
+    pub fn add_line_transform(&mut self, transform: impl LineTransform + 'static) {
+        self.config.line_transforms.push(Arc::new(transform));
+    }
+
+    /// Set whether to show the type of each line's ending (LF or CRLF) at
+    /// the end of the line.
+    pub fn set_show_line_endings(&mut self, value: bool) {
+        self.config.show_line_endings = value;
+    }
+
+    /// Set whether to hide carriage returns at the end of lines, rather than
+    /// showing them as `<0D>`.
+    pub fn set_hide_trailing_cr(&mut self, value: bool) {
+        self.config.hide_trailing_cr = value;
+    }
+
+    /// Set the style of line numbers.  See [`TextStyle`] for details.
+    pub fn set_line_number_style(&mut self, style: TextStyle) {
+        self.config.line_number_style = style;
+    }
+
+    /// Set text to draw between line numbers and lines, such as `│`.
+    pub fn set_line_number_separator(&mut self, separator: impl Into<String>) {
+        self.config.line_number_separator = separator.into();
+    }
+
+    /// Set whether the line number gutter is dimmed on the rows that
+    /// continue a wrapped line.
+    pub fn set_dim_line_number_continuation(&mut self, value: bool) {
+        self.config.dim_line_number_continuation = value;
+    }
+
+    /// Set how often line numbers are shown.  If more than 1, only the
+    /// numbers of lines that are a multiple of it are shown.
+    pub fn set_line_number_interval(&mut self, interval: usize) {
+        self.config.line_number_interval = interval;
+    }
+
+    /// Set the most rows the error file may use below the ruler.  If 0, the
+    /// error file may use the whole screen.
+    pub fn set_error_file_max_height(&mut self, height: usize) {
+        self.config.error_file_max_height = height;
+    }
+
+    /// Set the style of the lines of error files.  See [`TextStyle`] for
+    /// details.
+    pub fn set_error_file_style(&mut self, style: TextStyle) {
+        self.config.error_file_style = style;
+    }
+
+    /// Set whether to highlight trailing whitespace and indentation that
+    /// mixes tabs and spaces, and show tabs with a marker.
+    pub fn set_show_whitespace(&mut self, value: bool) {
+        self.config.show_whitespace = value;
+    }
+
+    /// Set the colors used when whitespace is shown: the background of
+    /// trailing whitespace, the background of indentation that mixes tabs
+    /// and spaces, and the tab markers.  Colors are named as in
+    /// [`HighlightRule`].
+    pub fn set_whitespace_colors(
+        &mut self,
+        trailing: impl Into<String>,
+        mixed_indent: impl Into<String>,
+        tab_marker: impl Into<String>,
+    ) {
+        self.config.trailing_whitespace_color = trailing.into();
+        self.config.mixed_indent_color = mixed_indent.into();
+        self.config.tab_marker_color = tab_marker.into();
+    }
+
+    /// Set the number of columns between tab stops.
+    pub fn set_tab_width(&mut self, width: usize) {
+        self.config.tab_width = width;
+    }
+
+    /// Set the settings for particular kinds of file.  See [`Profile`] for
+    /// details.
+    pub fn set_profiles(&mut self, profiles: Vec<Profile>) {
+        self.config.profiles = profiles;
+    }
+
+    /// Set how often the screen is updated for animations.  See
+    /// [`Animation`] for details.
+    pub fn set_animation(&mut self, animation: Animation) {
+        self.config.animation = animation;
+    }
+
+    /// Set when to use color.  See [`ColorMode`] for details.
+    pub fn set_color(&mut self, color: ColorMode) {
+        self.config.color = color;
+    }
+
+    /// Set whether attributes like bold and underline are kept when color
+    /// is not used.
+    pub fn set_monochrome_attributes(&mut self, value: bool) {
+        self.config.monochrome_attributes = value;
+    }
+
+    /// Set the most memory, in bytes, that each cache of rendered lines may
+    /// use.
+    pub fn set_line_cache_bytes(&mut self, bytes: usize) {
+        self.config.line_cache_bytes = bytes;
+    }
+
+    /// Set how many threads each search uses.  If 0, one thread is used for
+    /// each CPU.
+    pub fn set_search_threads(&mut self, threads: usize) {
+        self.config.search_threads = threads;
+    }
+
+    /// Set whether searches ignore differences in case and in how equivalent
+    /// characters are written.  See [`SearchNormalization`] for details.
+    pub fn set_search_normalization(&mut self, normalization: SearchNormalization) {
+        self.config.search_normalization = normalization;
+    }
+
+    /// Set whether to mask text that looks like a secret, such as access keys
+    /// and bearer tokens.  The data of the files is unchanged.
+    pub fn set_redact_secrets(&mut self, redact: bool) {
+        self.config.redact_secrets = redact;
+    }
+
+    /// Set regular expressions for additional text to mask when secrets are
+    /// redacted.  If a pattern has a capture group, only the text it captures
+    /// is masked.
+    pub fn set_redact_patterns(&mut self, patterns: Vec<String>) {
+        self.config.redact_patterns = patterns;
+    }
+
+    /// Set regular expressions to watch for while following the end of a
+    /// file.  When a line that arrives matches one of them, the user is
+    /// alerted.
+    pub fn set_watch_patterns(&mut self, patterns: Vec<String>) {
+        self.config.watch_patterns = patterns;
+    }
+
+    /// Set how the user is alerted when a watch pattern matches.  See
+    /// [`WatchAlert`] for details.
+    pub fn set_watch_alert(&mut self, alert: WatchAlert) {
+        self.config.watch_alert = alert;
+    }
+
+    /// Set how many colors the terminal can show, overriding what the
+    /// terminal reports.  See [`ColorDepth`] for details.
+    pub fn set_color_depth(&mut self, depth: ColorDepth) {
+        self.config.color_depth = depth;
+    }
+
+    /// Set how the screen is drawn.  See [`Renderer`] for details.
+    pub fn set_renderer(&mut self, renderer: Renderer) {
+        self.config.renderer = renderer;
+    }
+
+    /// Set whether to save the files opened by name, and where they were
+    /// scrolled to, when the pager quits.  See [`Pager::resume_session`].
+    pub fn set_save_session(&mut self, save_session: bool) {
+        self.config.save_session = save_session;
+    }
+
+    /// Set whether to render in a way that is easier for screen readers to
+    /// follow.
+    pub fn set_accessibility_mode(&mut self, value: bool) {
+        self.config.accessibility_mode = value;
+    }
+
     /// Set default wrapping mode. See [`WrappingMode`] for details.
     pub fn set_wrapping_mode(&mut self, value: impl Into<WrappingMode>) {
         self.config.wrapping_mode = value.into();
@@ -236,21 +965,150 @@ impl Pager {
         self.config.keymap = KeymapConfig::Keymap(Arc::new(keymap));
     }
 
+    /// Set additional key bindings for a file.  These are merged over the
+    /// keymap while the file's screen is current, and are included in the
+    /// help screen shown from it.
+    pub fn set_file_keymap(&mut self, index: FileIndex, keymap: Keymap) {
+        self.config.file_keymaps.insert(index, Arc::new(keymap));
+    }
+
+    /// Write the primary stream to a file as it is loaded, like `tee`.
+    ///
+    /// The stream is loaded in full while the pager runs, rather than only
+    /// as far as is shown, and the file contains everything that had been
+    /// loaded when the pager exits.  The data is written as it was read,
+    /// before it is decoded or transformed.  If `strip_escapes` is set,
+    /// escape sequences (e.g. colors and hyperlinks) are removed, leaving
+    /// plain text.
+    pub fn set_write_output(&mut self, path: impl Into<PathBuf>, strip_escapes: bool) {
+        self.write_output = Some(WriteOutput {
+            path: path.into(),
+            strip_escapes,
+        });
+    }
+
+    /// Set how lines are formatted when they are printed directly to the
+    /// terminal, before or instead of entering full screen (see
+    /// [`InterfaceMode`]).
+    ///
+    /// `format` is called once for each line as it is printed, with the
+    /// stream it came from and its contents without the trailing newline,
+    /// and returns the text to print instead.  This can be used to add a
+    /// prefix to error lines, or a timestamp to each line.  The text may
+    /// contain escape sequences for colors and other attributes.  Lines
+    /// shown in full screen are not affected.
+    pub fn set_direct_format(
+        &mut self,
+        format: impl Fn(DirectStream, &[u8]) -> Vec<u8> + Send + Sync + 'static,
+    ) {
+        self.direct_format = Some(Box::new(format));
+    }
+
+    /// Set whether to reload the configuration file when the process
+    /// receives `SIGHUP`.  This only has an effect on Unix platforms, and
+    /// stops `SIGHUP` from terminating the process while the pager runs.
+    pub fn set_reload_config_on_hangup(&mut self, value: bool) {
+        self.reload_on_hangup = value;
+    }
+
     /// Create an action sender which can be used to send `Action`s to this pager.
     pub fn action_sender(&self) -> ActionSender {
         self.events.action_sender()
     }
 
+    /// Start sharing the pager's state, returning a handle which can be
+    /// used to query it from another thread while the pager runs.
+    ///
+    /// This does not consume the pager, so it should be called before
+    /// [`Pager::run`].  Calling it again returns another handle to the same
+    /// state.
+    pub fn start(&mut self) -> PagerHandle {
+        self.handle.get_or_insert_with(PagerHandle::default).clone()
+    }
+
     /// Run Stream Pager.
     pub fn run(self) -> Result<()> {
-        crate::display::start(
-            self.term,
-            self.caps,
-            self.events,
-            self.files,
-            self.error_files,
-            self.progress,
-            self.config,
-        )
+        let Pager {
+            mut term,
+            caps,
+            events,
+            files,
+            error_files,
+            progress,
+            mut config,
+            handle,
+            write_output,
+            listeners,
+            direct_format,
+            reload_on_hangup,
+            resume,
+        } = self;
+        trace::init();
+        if config.redact_secrets {
+            // Secrets are redacted after the other transforms, so that they
+            // are masked in whatever the other transforms produce.
+            let (redaction, errors) = Redaction::new(&config.redact_patterns);
+            config.line_transforms.push(Arc::new(redaction));
+            for error in errors {
+                let message = Action::ShowMessage(error, Severity::Error);
+                events.sender().send(Event::Action(message))?;
+            }
+        }
+        // The primary file is written out as it is loaded.
+        let output_file = match (write_output, files.first()) {
+            (Some(write_output), Some(file)) => {
+                file.set_tee(Tee::create(&write_output.path, write_output.strip_escapes)?)?;
+                Some(file.clone())
+            }
+            _ => None,
+        };
+        for file in files.iter().chain(error_files.values()) {
+            file.set_encoding(config.encoding);
+            file.set_max_line_length(config.max_line_length);
+            file.set_line_transforms(config.line_transforms.clone());
+        }
+        let hangup_listener = if reload_on_hangup {
+            Some(HangupListener::new(events.sender())?)
+        } else {
+            None
+        };
+        let printed_lines = crate::display::start(
+            &mut *term,
+            caps,
+            events,
+            files,
+            error_files,
+            progress,
+            config,
+            handle,
+            direct_format.as_deref(),
+            resume,
+        )?;
+        if !printed_lines.is_empty() {
+            crate::display::print_lines(&mut *term, &printed_lines)?;
+        }
+
+        // Stop accepting connections once the display has finished.
+        drop(listeners);
+        drop(hangup_listener);
+
+        // Anything that hasn't been loaded yet isn't waited for.
+        if let Some(file) = output_file {
+            file.finish_tee()?;
+        }
+        Ok(())
+    }
+
+    /// Run Stream Pager on a background thread.
+    ///
+    /// Returns a handle for joining the thread, which returns the result of
+    /// running the pager, and an action sender which can be used to control
+    /// the pager while it runs, e.g. to make it quit.
+    pub fn run_in_background(self) -> Result<(JoinHandle<Result<()>>, ActionSender)> {
+        let action_sender = self.action_sender();
+        let join_handle = thread::Builder::new()
+            .name(String::from("sp-pager"))
+            .spawn(move || self.run())?;
+        Ok((join_handle, action_sender))
     }
 }
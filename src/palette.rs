@@ -0,0 +1,167 @@
+//! Color Palettes
+//!
+//! Files can use colors from the 256-color palette or arbitrary RGB colors,
+//! but not every terminal can show them, and terminal multiplexers don't
+//! always report what the terminal underneath supports.
+//!
+//! When the color depth is forced, convert each color to the nearest color
+//! available at that depth before it is sent to the terminal.
+
+use termwiz::color::{ColorAttribute, RgbColor};
+use termwiz::surface::change::Change;
+
+use crate::config::ColorDepth;
+
+/// The RGB values of the 16 standard colors, as used by xterm.
+const STANDARD_COLORS: [(u8, u8, u8); 16] = [
+    (0, 0, 0),
+    (128, 0, 0),
+    (0, 128, 0),
+    (128, 128, 0),
+    (0, 0, 128),
+    (128, 0, 128),
+    (0, 128, 128),
+    (192, 192, 192),
+    (128, 128, 128),
+    (255, 0, 0),
+    (0, 255, 0),
+    (255, 255, 0),
+    (0, 0, 255),
+    (255, 0, 255),
+    (0, 255, 255),
+    (255, 255, 255),
+];
+
+/// The levels of each component in the 6x6x6 color cube of the 256-color
+/// palette.
+const CUBE_LEVELS: [u8; 6] = [0, 95, 135, 175, 215, 255];
+
+/// Returns the RGB value of a color in the 256-color palette.
+fn palette_rgb(index: u8) -> (u8, u8, u8) {
+    match index {
+        0..=15 => STANDARD_COLORS[index as usize],
+        16..=231 => {
+            let index = index - 16;
+            (
+                CUBE_LEVELS[(index / 36) as usize],
+                CUBE_LEVELS[(index / 6 % 6) as usize],
+                CUBE_LEVELS[(index % 6) as usize],
+            )
+        }
+        _ => {
+            let level = 8 + 10 * (index - 232);
+            (level, level, level)
+        }
+    }
+}
+
+/// Returns the squared distance between two colors.
+fn distance(a: (u8, u8, u8), b: (u8, u8, u8)) -> u32 {
+    let d = |x: u8, y: u8| (i32::from(x) - i32::from(y)).pow(2) as u32;
+    d(a.0, b.0) + d(a.1, b.1) + d(a.2, b.2)
+}
+
+/// Returns the index of the nearest of the 16 standard colors.
+fn nearest_16(rgb: (u8, u8, u8)) -> u8 {
+    (0..16u8)
+        .min_by_key(|&index| distance(rgb, palette_rgb(index)))
+        .unwrap_or(0)
+}
+
+/// Returns the index of the nearest color in the color cube or grey ramp
+/// of the 256-color palette.  The 16 standard colors are skipped, as
+/// terminals often change them.
+fn nearest_256(rgb: (u8, u8, u8)) -> u8 {
+    let level = |c: u8| {
+        (0..6u8)
+            .min_by_key(|&i| (i32::from(c) - i32::from(CUBE_LEVELS[i as usize])).abs())
+            .unwrap_or(0)
+    };
+    let cube = 16 + 36 * level(rgb.0) + 6 * level(rgb.1) + level(rgb.2);
+    let average = (u32::from(rgb.0) + u32::from(rgb.1) + u32::from(rgb.2)) / 3;
+    let grey = 232 + (average.saturating_sub(3) / 10).min(23) as u8;
+    if distance(rgb, palette_rgb(grey)) < distance(rgb, palette_rgb(cube)) {
+        grey
+    } else {
+        cube
+    }
+}
+
+/// Converts a color to the nearest color available at the given depth.
+pub(crate) fn downgrade(color: ColorAttribute, depth: ColorDepth) -> ColorAttribute {
+    let rgb = |tuple| RgbColor::from(tuple).to_tuple_rgb8();
+    match (depth, color) {
+        (ColorDepth::Auto, _) | (ColorDepth::TrueColor, _) | (_, ColorAttribute::Default) => color,
+        (ColorDepth::Colors256, ColorAttribute::TrueColorWithPaletteFallback(_, index))
+        | (ColorDepth::Colors256, ColorAttribute::PaletteIndex(index)) => {
+            ColorAttribute::PaletteIndex(index)
+        }
+        (ColorDepth::Colors256, ColorAttribute::TrueColorWithDefaultFallback(tuple)) => {
+            ColorAttribute::PaletteIndex(nearest_256(rgb(tuple)))
+        }
+        (ColorDepth::Colors16, ColorAttribute::TrueColorWithDefaultFallback(tuple)) => {
+            ColorAttribute::PaletteIndex(nearest_16(rgb(tuple)))
+        }
+        (ColorDepth::Colors16, ColorAttribute::TrueColorWithPaletteFallback(_, index))
+        | (ColorDepth::Colors16, ColorAttribute::PaletteIndex(index)) => {
+            if index < 16 {
+                ColorAttribute::PaletteIndex(index)
+            } else {
+                ColorAttribute::PaletteIndex(nearest_16(palette_rgb(index)))
+            }
+        }
+    }
+}
+
+/// Converts the colors of rendered changes to the nearest colors available
+/// at the given depth.
+pub(crate) fn downgrade_changes(changes: &mut [Change], depth: ColorDepth) {
+    for change in changes.iter_mut() {
+        match change {
+            Change::AllAttributes(attrs) => {
+                let foreground = downgrade(attrs.foreground(), depth);
+                let background = downgrade(attrs.background(), depth);
+                let underline_color = downgrade(attrs.underline_color(), depth);
+                attrs
+                    .set_foreground(foreground)
+                    .set_background(background)
+                    .set_underline_color(underline_color);
+            }
+            Change::ClearToEndOfLine(color) | Change::ClearToEndOfScreen(color) => {
+                *color = downgrade(*color, depth);
+            }
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use termwiz::color::SrgbaTuple;
+
+    fn rgb(r: u8, g: u8, b: u8) -> ColorAttribute {
+        let tuple: SrgbaTuple = RgbColor::new_8bpc(r, g, b).into();
+        ColorAttribute::TrueColorWithDefaultFallback(tuple)
+    }
+
+    #[test]
+    fn test_downgrade() {
+        let index = ColorAttribute::PaletteIndex;
+        assert_eq!(downgrade(rgb(1, 2, 3), ColorDepth::TrueColor), rgb(1, 2, 3));
+        assert_eq!(downgrade(rgb(255, 0, 0), ColorDepth::Colors256), index(196));
+        assert_eq!(
+            downgrade(rgb(100, 100, 100), ColorDepth::Colors256),
+            index(241)
+        );
+        assert_eq!(downgrade(rgb(250, 10, 10), ColorDepth::Colors16), index(9));
+        assert_eq!(downgrade(rgb(120, 0, 130), ColorDepth::Colors16), index(5));
+        assert_eq!(downgrade(index(196), ColorDepth::Colors16), index(9));
+        assert_eq!(downgrade(index(208), ColorDepth::Colors256), index(208));
+        assert_eq!(downgrade(index(3), ColorDepth::Colors16), index(3));
+        assert_eq!(
+            downgrade(ColorAttribute::Default, ColorDepth::Colors16),
+            ColorAttribute::Default
+        );
+    }
+}
@@ -6,18 +6,31 @@
 //!
 //! Progress indicator pages are blocks of text terminated by an ASCII form-feed
 //! character.  The progress indicator will display the most recently received
-//! page.
+//! page.  Earlier pages are kept in a history, which can be viewed on its own
+//! screen.
 
+use std::collections::VecDeque;
 use std::io::{BufRead, BufReader, Read};
 use std::sync::{Arc, RwLock};
 use std::thread;
 
+use vec_map::VecMap;
+
 use crate::error::Result;
 use crate::event::{Event, EventSender, UniqueInstance};
+use crate::file::FileIndex;
 
 /// Initial buffer size for progress indicator pages.
 const PROGRESS_BUFFER_SIZE: usize = 4096;
 
+/// Most bytes of earlier progress indicator pages kept in the history.  The
+/// oldest pages are dropped to stay within this, although the most recent
+/// page is always kept.
+const PROGRESS_HISTORY_BYTES: usize = 1 << 20;
+
+/// Line that separates the pages in the progress history.
+const PROGRESS_HISTORY_SEPARATOR: &str = "\x1B[2m\u{2500}\u{2500}\u{2500}\u{2500}\x1B[m\n";
+
 /// Inner struct for the progress indicator.
 pub(crate) struct ProgressInner {
     /// Buffer containing the currently displayed page.
@@ -26,11 +39,33 @@ pub(crate) struct ProgressInner {
     /// Offsets of all the newlines in the current page.
     newlines: Vec<usize>,
 
+    /// The pages received before the current page, oldest first.
+    history: VecDeque<Vec<u8>>,
+
+    /// The total size of the pages in the history, in bytes.
+    history_bytes: usize,
+
     /// Whether the progress indicator is finished because the other
     /// end of the pipe closed.
     finished: bool,
 }
 
+impl ProgressInner {
+    /// Adds a page that is no longer current to the history.
+    fn add_history(&mut self, page: Vec<u8>) {
+        if page.is_empty() {
+            return;
+        }
+        self.history_bytes += page.len();
+        self.history.push_back(page);
+        while self.history_bytes > PROGRESS_HISTORY_BYTES && self.history.len() > 1 {
+            if let Some(oldest) = self.history.pop_front() {
+                self.history_bytes -= oldest.len();
+            }
+        }
+    }
+}
+
 /// A progress indicator.
 #[derive(Clone)]
 pub(crate) struct Progress {
@@ -46,6 +81,8 @@ impl Progress {
         let inner = Arc::new(RwLock::new(ProgressInner {
             buffer: Vec::new(),
             newlines: Vec::new(),
+            history: VecDeque::new(),
+            history_bytes: 0,
             finished: false,
         }));
         let mut input = BufReader::new(reader);
@@ -60,7 +97,8 @@ impl Progress {
                         match input.read_until(b'\x0C', &mut buffer) {
                             Ok(0) | Err(_) => {
                                 let mut inner = inner.write().unwrap();
-                                inner.buffer = Vec::new();
+                                let page = std::mem::take(&mut inner.buffer);
+                                inner.add_history(page);
                                 inner.newlines = Vec::new();
                                 inner.finished = true;
                                 return Ok(());
@@ -74,7 +112,8 @@ impl Progress {
                                     }
                                 }
                                 let mut inner = inner.write().unwrap();
-                                inner.buffer = buffer;
+                                let page = std::mem::replace(&mut inner.buffer, buffer);
+                                inner.add_history(page);
                                 inner.newlines = newlines;
                                 event_sender.send_unique(Event::Progress, &progress_unique)?;
                             }
@@ -128,4 +167,92 @@ impl Progress {
         }
         Some(call(&inner.buffer[start..end]))
     }
+
+    /// Returns the earlier pages and the current page, oldest first, with a
+    /// separator line after each page.  Returns `None` if no pages have been
+    /// received.
+    pub(crate) fn history(&self) -> Option<Vec<u8>> {
+        let inner = self.inner.read().unwrap();
+        let mut history = Vec::new();
+        for page in inner.history.iter().chain(std::iter::once(&inner.buffer)) {
+            if page.is_empty() {
+                continue;
+            }
+            history.extend_from_slice(page);
+            if !page.ends_with(b"\n") {
+                history.push(b'\n');
+            }
+            history.extend_from_slice(PROGRESS_HISTORY_SEPARATOR.as_bytes());
+        }
+        if history.is_empty() {
+            None
+        } else {
+            Some(history)
+        }
+    }
+}
+
+/// The progress indicators for the pager's files.
+#[derive(Clone, Default)]
+pub(crate) struct ProgressStreams {
+    /// The progress indicator shown on screens that don't have their own.
+    shared: Option<Progress>,
+
+    /// Progress indicators for individual files, by file index.
+    files: VecMap<Progress>,
+}
+
+impl ProgressStreams {
+    /// Set the progress indicator shown on screens that don't have their own.
+    pub(crate) fn set_shared(&mut self, progress: Progress) {
+        self.shared = Some(progress);
+    }
+
+    /// Set the progress indicator for the file with index `index`.
+    pub(crate) fn insert(&mut self, index: FileIndex, progress: Progress) {
+        self.files.insert(index, progress);
+    }
+
+    /// The progress indicator shown on screens that don't have their own.
+    pub(crate) fn shared(&self) -> Option<&Progress> {
+        self.shared.as_ref()
+    }
+
+    /// The progress indicator to show on the screen for the file with index
+    /// `index`.
+    pub(crate) fn get(&self, index: FileIndex) -> Option<&Progress> {
+        self.files.get(index).or(self.shared.as_ref())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_history_bytes() {
+        let mut inner = ProgressInner {
+            buffer: Vec::new(),
+            newlines: Vec::new(),
+            history: VecDeque::new(),
+            history_bytes: 0,
+            finished: false,
+        };
+        let page_size = PROGRESS_HISTORY_BYTES / 4;
+        for page in 0..6 {
+            inner.add_history(vec![b'0' + page; page_size]);
+        }
+        assert_eq!(inner.history.len(), 4);
+        assert_eq!(inner.history_bytes, PROGRESS_HISTORY_BYTES);
+        assert_eq!(inner.history[0][0], b'2');
+
+        // A page larger than the whole history replaces it.
+        inner.add_history(vec![b'x'; PROGRESS_HISTORY_BYTES * 2]);
+        assert_eq!(inner.history.len(), 1);
+        assert_eq!(inner.history_bytes, PROGRESS_HISTORY_BYTES * 2);
+
+        // Empty pages aren't kept.
+        inner.add_history(Vec::new());
+        assert_eq!(inner.history.len(), 1);
+    }
 }
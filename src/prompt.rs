@@ -3,7 +3,7 @@
 use std::char;
 use std::fmt::Write;
 
-use termwiz::cell::{AttributeChange, CellAttributes};
+use termwiz::cell::{AttributeChange, CellAttributes, Intensity};
 use termwiz::color::{AnsiColor, ColorAttribute};
 use termwiz::input::KeyEvent;
 use termwiz::surface::change::Change;
@@ -12,12 +12,14 @@ use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
 
 use crate::display::DisplayAction;
 use crate::error::Error;
-use crate::prompt_history::PromptHistory;
+use crate::prompt_history::{LastValues, PromptHistory};
 use crate::screen::Screen;
 use crate::util;
 
 type PromptRunFn = dyn FnMut(&mut Screen, &str) -> Result<DisplayAction, Error>;
 
+type PromptCompletionFn = dyn Fn(&str) -> Vec<String>;
+
 /// A prompt for input from the user.
 pub(crate) struct Prompt {
     /// The text of the prompt to display to the user.
@@ -28,6 +30,15 @@ pub(crate) struct Prompt {
 
     /// The closure to run when the user presses Return.  Will only be called once.
     run: Option<Box<PromptRunFn>>,
+
+    /// The closure to call to get completions for the current value.  The
+    /// completions are shown after the value, and the user can press Tab to
+    /// accept the first one.
+    completion: Option<Box<PromptCompletionFn>>,
+
+    /// Whether the prompt finishes as soon as the user types a character,
+    /// rather than waiting for the user to press Return.
+    single_key: bool,
 }
 
 pub(crate) struct PromptState {
@@ -95,6 +106,11 @@ impl PromptState {
         data
     }
 
+    /// Returns the value the user has typed in.
+    pub(crate) fn value(&self) -> String {
+        self.value.iter().collect()
+    }
+
     /// Returns the column for the cursor.
     pub(crate) fn cursor_position(&self) -> usize {
         let mut position = 0;
@@ -119,8 +135,16 @@ impl PromptState {
         }
     }
 
-    /// Renders the prompt onto the terminal.
-    fn render(&mut self, changes: &mut Vec<Change>, mut position: usize, width: usize) {
+    /// Replace the value with a new value, moving the cursor to the end.
+    fn set_value(&mut self, value: &str) -> DisplayAction {
+        self.value = value.chars().collect();
+        self.position = self.value.len();
+        DisplayAction::RefreshPrompt
+    }
+
+    /// Renders the prompt onto the terminal.  Returns the column after the
+    /// rendered value.
+    fn render(&mut self, changes: &mut Vec<Change>, mut position: usize, width: usize) -> usize {
         let mut start = self.offset;
         let mut end = self.offset;
         while end < self.value.len() {
@@ -154,9 +178,7 @@ impl PromptState {
             let value: String = self.value[start..end].iter().collect();
             changes.push(Change::Text(value));
         }
-        if position < width {
-            changes.push(Change::ClearToEndOfLine(ColorAttribute::default()));
-        }
+        position
     }
 
     /// Insert a character at the current position.
@@ -330,6 +352,32 @@ impl Prompt {
             prompt: prompt.to_string(),
             history: PromptHistory::open(ident),
             run: Some(run),
+            completion: None,
+            single_key: false,
+        }
+    }
+
+    /// Finish the prompt as soon as the user types a character.  The value
+    /// is not saved to the prompt history.
+    pub(crate) fn with_single_key(mut self) -> Prompt {
+        self.single_key = true;
+        self
+    }
+
+    /// Show completions for the value as the user types.
+    pub(crate) fn with_completion(mut self, completion: Box<PromptCompletionFn>) -> Prompt {
+        self.completion = Some(completion);
+        self
+    }
+
+    /// Returns the completions for the current value.
+    fn completions(&self) -> Vec<String> {
+        match self.completion {
+            Some(ref completion) => {
+                let value: String = self.state().value[..].iter().collect();
+                completion(&value)
+            }
+            None => Vec::new(),
         }
     }
 
@@ -362,11 +410,37 @@ impl Prompt {
         changes.push(Change::AllAttributes(CellAttributes::default()));
         changes.push(Change::Text(" ".into()));
         let offset = self.prompt.width() + 4;
-        self.state_mut().render(changes, offset, width);
+        let mut position = self.state_mut().render(changes, offset, width);
+        for (index, completion) in self.completions().iter().enumerate() {
+            if position + 4 >= width {
+                break;
+            }
+            let text = util::truncate_string(format!("  {}", completion), 0, width - position);
+            position += text.width();
+            let attributes = CellAttributes::default()
+                .set_intensity(if index == 0 {
+                    Intensity::Bold
+                } else {
+                    Intensity::Half
+                })
+                .clone();
+            changes.push(Change::AllAttributes(attributes));
+            changes.push(Change::Text(text));
+        }
+        changes.push(Change::AllAttributes(CellAttributes::default()));
+        if position < width {
+            changes.push(Change::ClearToEndOfLine(ColorAttribute::default()));
+        }
     }
 
-    /// Dispatch a key press to the prompt.
-    pub(crate) fn dispatch_key(&mut self, key: KeyEvent, width: usize) -> DisplayAction {
+    /// Dispatch a key press to the prompt.  When the prompt is finished, its
+    /// value is recorded in `last_values`.
+    pub(crate) fn dispatch_key(
+        &mut self,
+        key: KeyEvent,
+        width: usize,
+        last_values: &LastValues,
+    ) -> DisplayAction {
         use termwiz::input::{KeyCode::*, Modifiers};
         const CTRL: Modifiers = Modifiers::CTRL;
         const NONE: Modifiers = Modifiers::NONE;
@@ -375,18 +449,13 @@ impl Prompt {
         let action = match (key.modifiers, key.key) {
             (NONE, Enter) | (CTRL, Char('J')) | (CTRL, Char('M')) => {
                 // Finish.
-                let _ = self.history.save();
-                let mut run = self.run.take();
+                if !self.single_key {
+                    let _ = self.history.save(last_values);
+                }
                 let value: String = self.state().value[..].iter().collect();
-                return DisplayAction::Run(Box::new(move |screen: &mut Screen| {
-                    screen.clear_prompt();
-                    if let Some(ref mut run) = run {
-                        run(screen, &value)
-                    } else {
-                        Ok(DisplayAction::Render)
-                    }
-                }));
+                return self.finish(value);
             }
+            (NONE, Char(c)) if self.single_key => return self.finish(c.to_string()),
             (NONE, Escape) | (CTRL, Char('C')) => {
                 // Cancel.
                 return DisplayAction::Run(Box::new(|screen: &mut Screen| {
@@ -410,21 +479,58 @@ impl Prompt {
             (CTRL, Char('T')) => self.state_mut().transpose_chars(),
             (NONE, UpArrow) => self.history.previous(),
             (NONE, DownArrow) => self.history.next(),
+            (NONE, Tab) => match self.completions().first() {
+                Some(completion) => self.state_mut().set_value(completion),
+                None => DisplayAction::None,
+            },
             _ => return DisplayAction::None,
         };
         self.state_mut().clamp_offset(value_width);
-        action
+        match action {
+            // Completions change with the value, so the whole prompt must be
+            // re-rendered.
+            DisplayAction::Change(_) if self.completion.is_some() => DisplayAction::RefreshPrompt,
+            action => action,
+        }
+    }
+
+    /// Finish the prompt, running it with `value`.
+    fn finish(&mut self, value: String) -> DisplayAction {
+        let mut run = self.run.take();
+        DisplayAction::Run(Box::new(move |screen: &mut Screen| {
+            screen.clear_prompt();
+            if let Some(ref mut run) = run {
+                run(screen, &value)
+            } else {
+                Ok(DisplayAction::Render)
+            }
+        }))
     }
 
-    /// Paste some text into the prompt.
+    /// Paste some text into the prompt.  Only the first line of the text is
+    /// used.
     pub(crate) fn paste(&mut self, text: &str, width: usize) -> DisplayAction {
         let value_width = width - self.prompt.width() - 4;
+        let (text, _truncated) = paste_first_line(text);
         let action = self.state_mut().insert_str(text);
         self.state_mut().clamp_offset(value_width);
         action
     }
 }
 
+/// Returns the first line of some pasted text, and whether there were any
+/// further lines.
+///
+/// Prompts only hold a single line, so trailing line endings are removed,
+/// and anything after the first line ending is discarded.
+pub(crate) fn paste_first_line(text: &str) -> (&str, bool) {
+    let text = text.trim_end_matches(['\r', '\n']);
+    match text.find(['\r', '\n']) {
+        Some(end) => (&text[..end], true),
+        None => (text, false),
+    }
+}
+
 fn move_word_forwards(value: &[char], mut position: usize) -> usize {
     let len = value.len();
     while position < len && value[position].is_whitespace() {
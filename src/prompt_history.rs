@@ -1,7 +1,9 @@
 //! Prompt History.
 
+use std::collections::HashMap;
 use std::fs::File;
 use std::io::{BufRead, BufReader, Write};
+use std::sync::{Arc, Mutex};
 
 use tempfile::NamedTempFile;
 
@@ -11,6 +13,34 @@ use crate::prompt::PromptState;
 
 const HISTORY_LENGTH: usize = 1000;
 
+/// The last value entered for each history ident while a pager has been
+/// running.  This is used in preference to the history file, which may not
+/// be writable and may also be written to by other sessions.
+///
+/// The values are shared by the screens of one pager, so other pagers in
+/// the same process don't see them.
+#[derive(Clone, Default)]
+pub(crate) struct LastValues(Arc<Mutex<HashMap<String, String>>>);
+
+impl LastValues {
+    /// Peek the last value entered for `ident`, either while this pager has
+    /// been running or, if there hasn't been one, in a previous session.
+    pub(crate) fn peek(&self, ident: &str) -> Option<String> {
+        if let Some(value) = self.0.lock().unwrap().get(ident).cloned() {
+            return Some(value);
+        }
+        let mut history = PromptHistory::open(ident);
+        history.previous();
+        history
+            .stored()
+            .map(|stored| PromptState::load(&stored).value())
+    }
+
+    fn insert(&self, ident: &str, value: String) {
+        self.0.lock().unwrap().insert(ident.to_string(), value);
+    }
+}
+
 struct HistoryEntry {
     /// The stored state of the history entry.
     stored: Option<String>,
@@ -123,11 +153,13 @@ impl PromptHistory {
         }
     }
 
-    pub(crate) fn save(&mut self) -> Result<(), Error> {
+    pub(crate) fn save(&mut self, last_values: &LastValues) -> Result<(), Error> {
         if let Some(data) = self.entries[self.active_index].save() {
             if data.is_empty() {
                 return Ok(());
             }
+            let value = self.entries[self.active_index].state().value();
+            last_values.insert(&self.ident, value);
             if self.entries.len() > 1 {
                 if let Some(previous_data) = &self.entries[self.entries.len() - 2].stored {
                     if data == *previous_data {
@@ -158,9 +190,18 @@ impl PromptHistory {
     }
 }
 
-/// Peak the last entry from history.
-pub(crate) fn peek_last(ident: &str) -> Option<String> {
-    let mut history = PromptHistory::open(ident);
-    history.previous();
-    history.stored()
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_last_values() {
+        let ident = "streampager-test-last-values";
+        let first = LastValues::default();
+        let second = LastValues::default();
+        first.insert(ident, String::from("value"));
+        assert_eq!(first.clone().peek(ident).as_deref(), Some("value"));
+        // Each pager has its own values.
+        assert_eq!(second.peek(ident), None);
+    }
 }
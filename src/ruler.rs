@@ -3,43 +3,87 @@
 use std::cmp::{max, min};
 use std::fmt::Write;
 use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
-use std::sync::Arc;
-use std::time::Instant;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime};
 
 use termwiz::surface::change::Change;
 use unicode_width::UnicodeWidthStr;
 
-use crate::bar::{Bar, BarItem, BarString, BarStyle};
-use crate::config::WrappingMode;
+use crate::bar::{Bar, BarItem, BarPriority, BarString, BarStyle};
+use crate::config::{Config, RulerItem, WrappingMode};
 use crate::file::{File, FileInfo};
 use crate::util;
 
+#[derive(Clone)]
 pub(crate) struct Ruler {
     position: Arc<PositionIndicator>,
     loading: Arc<LoadingIndicator>,
     repeat_count: Arc<RepeatCountIndicator>,
+    mouse_selection: Arc<MouseSelectionIndicator>,
+    disk: Arc<DiskMetadata>,
     ruler_bar: Bar,
 }
 
 impl Ruler {
-    pub(crate) fn new(file: File) -> Self {
-        let title = Arc::new(BarString::new(file.title().to_string()));
+    pub(crate) fn new(file: File, config: &Config) -> Self {
+        let format = &config.ruler_format;
+        let accessible = config.accessibility_mode;
+        let title =
+            Arc::new(BarString::new(file.title().to_string()).with_priority(BarPriority::Low));
         let file_info = Arc::new(FileInformationIndicator::new(file.clone()));
+        let disk = Arc::new(DiskMetadata::new(file.clone()));
+        let encoding = Arc::new(EncodingIndicator(file.clone()));
         let position = Arc::new(PositionIndicator::new(file.clone()));
-        let loading = Arc::new(LoadingIndicator::new(file));
+        let loading = Arc::new(LoadingIndicator::new(
+            file,
+            config.animation().frame_duration(),
+        ));
         let repeat_count = Arc::new(RepeatCountIndicator::default());
+        let mouse_selection = Arc::new(MouseSelectionIndicator::default());
+        let percent = Arc::new(PercentIndicator::new(position.clone()));
+        let byte_offset = Arc::new(ByteOffsetIndicator::new(position.clone()));
+
+        let ruler_item = |item: &RulerItem| -> Arc<dyn BarItem> {
+            match item {
+                RulerItem::Title => title.clone(),
+                RulerItem::Info => file_info.clone(),
+                RulerItem::Position => position.clone(),
+                RulerItem::Loading => loading.clone(),
+                RulerItem::RepeatCount => repeat_count.clone(),
+                RulerItem::Percent => percent.clone(),
+                RulerItem::ByteOffset => byte_offset.clone(),
+                RulerItem::MouseSelection => mouse_selection.clone(),
+                RulerItem::Size => Arc::new(FileSizeIndicator(disk.clone())),
+                RulerItem::Modified => Arc::new(ModifiedIndicator(disk.clone())),
+                RulerItem::Encoding => encoding.clone(),
+                RulerItem::Text(text) => Arc::new(BarString::new(text.clone())),
+            }
+        };
 
         let mut ruler_bar = Bar::new(BarStyle::Normal);
-        ruler_bar.add_left_item(title);
-        ruler_bar.add_right_item(repeat_count.clone());
-        ruler_bar.add_right_item(file_info);
-        ruler_bar.add_right_item(position.clone());
-        ruler_bar.add_right_item(loading.clone());
+        if accessible {
+            // Always show the position first, so that it is at a fixed place
+            // on the screen.
+            ruler_bar.add_left_item(position.clone());
+        }
+        let shown = |item: &&RulerItem| !accessible || **item != RulerItem::Position;
+        for item in format.left.iter().filter(shown) {
+            ruler_bar.add_left_item(ruler_item(item));
+        }
+        if config.redact_secrets {
+            // Show that what is on the screen may differ from the file.
+            ruler_bar.add_right_item(Arc::new(BarString::new("[redacted]")));
+        }
+        for item in format.right.iter().filter(shown) {
+            ruler_bar.add_right_item(ruler_item(item));
+        }
 
         Ruler {
             position,
             loading,
             repeat_count,
+            mouse_selection,
+            disk,
             ruler_bar,
         }
     }
@@ -62,6 +106,9 @@ impl Ruler {
             None => (0, true),
         };
         self.position.bottom.store(bottom, Ordering::SeqCst);
+        self.position
+            .following_end
+            .store(following_end, Ordering::SeqCst);
         self.position.line_wrapping.store(
             wrapping_mode == WrappingMode::GraphemeBoundary,
             Ordering::SeqCst,
@@ -70,6 +117,9 @@ impl Ruler {
             wrapping_mode == WrappingMode::WordBoundary,
             Ordering::SeqCst,
         );
+        self.position
+            .column_wrapping
+            .store(wrapping_mode == WrappingMode::Column, Ordering::SeqCst);
         self.loading
             .following_end
             .store(following_end, Ordering::SeqCst);
@@ -80,6 +130,28 @@ impl Ruler {
             .count
             .store(count.unwrap_or(0), Ordering::Relaxed);
     }
+
+    /// Flashes the ruler, e.g. to alert the user that a watch pattern
+    /// matched.
+    pub(crate) fn set_flashing(&mut self, flashing: bool) {
+        self.ruler_bar.set_style(if flashing {
+            BarStyle::Warning
+        } else {
+            BarStyle::Normal
+        });
+    }
+
+    pub(crate) fn set_mouse_selection(&self, selecting: bool) {
+        self.mouse_selection
+            .selecting
+            .store(selecting, Ordering::Relaxed);
+    }
+
+    /// Looks up the file's metadata on disk again the next time it is
+    /// shown, e.g. because the file has changed.
+    pub(crate) fn refresh_disk_metadata(&self) {
+        *self.disk.cached.lock().unwrap() = None;
+    }
 }
 
 /// Shows the file's additional information.
@@ -107,14 +179,110 @@ impl BarItem for FileInformationIndicator {
     }
 }
 
+/// The file's metadata on disk.  The metadata is looked up when it is first
+/// shown, and again once it is refreshed after the file changes.
+struct DiskMetadata {
+    file: File,
+
+    /// The metadata, once it has been looked up.
+    cached: Mutex<Option<Option<std::fs::Metadata>>>,
+}
+
+impl DiskMetadata {
+    fn new(file: File) -> Self {
+        DiskMetadata {
+            file,
+            cached: Mutex::new(None),
+        }
+    }
+
+    fn metadata(&self) -> Option<std::fs::Metadata> {
+        let mut cached = self.cached.lock().unwrap();
+        cached
+            .get_or_insert_with(|| {
+                let path = self.file.path()?;
+                std::fs::metadata(path)
+                    .ok()
+                    .filter(|metadata| metadata.is_file())
+            })
+            .clone()
+    }
+}
+
+/// Shows the size of the file on disk.
+struct FileSizeIndicator(Arc<DiskMetadata>);
+
+impl FileSizeIndicator {
+    fn content(&self) -> Option<String> {
+        let metadata = self.0.metadata()?;
+        Some(util::format_size(metadata.len()))
+    }
+}
+
+impl BarItem for FileSizeIndicator {
+    fn width(&self) -> usize {
+        self.content().map_or(0, |content| content.width())
+    }
+
+    fn render(&self, changes: &mut Vec<Change>, width: usize) {
+        if let Some(content) = self.content() {
+            changes.push(Change::Text(util::truncate_string(content, 0, width)));
+        }
+    }
+}
+
+/// Shows how long ago the file on disk was modified.
+struct ModifiedIndicator(Arc<DiskMetadata>);
+
+impl ModifiedIndicator {
+    fn content(&self) -> Option<String> {
+        let modified = self.0.metadata()?.modified().ok()?;
+        let age = SystemTime::now()
+            .duration_since(modified)
+            .unwrap_or_default();
+        Some(format!("modified {}", util::format_age(age)))
+    }
+}
+
+impl BarItem for ModifiedIndicator {
+    fn width(&self) -> usize {
+        self.content().map_or(0, |content| content.width())
+    }
+
+    fn render(&self, changes: &mut Vec<Change>, width: usize) {
+        if let Some(content) = self.content() {
+            changes.push(Change::Text(util::truncate_string(content, 0, width)));
+        }
+    }
+}
+
+/// Shows the character encoding of the file.
+struct EncodingIndicator(File);
+
+impl BarItem for EncodingIndicator {
+    fn width(&self) -> usize {
+        self.0.encoding().name().width()
+    }
+
+    fn render(&self, changes: &mut Vec<Change>, width: usize) {
+        changes.push(Change::Text(util::truncate_string(
+            self.0.encoding().name(),
+            0,
+            width,
+        )));
+    }
+}
+
 /// Indicates the current position within the file.
 struct PositionIndicator {
     file: File,
     top: AtomicUsize,
     left: AtomicUsize,
     bottom: AtomicUsize,
+    following_end: AtomicBool,
     line_wrapping: AtomicBool,
     word_wrapping: AtomicBool,
+    column_wrapping: AtomicBool,
 }
 
 impl PositionIndicator {
@@ -124,8 +292,10 @@ impl PositionIndicator {
             top: AtomicUsize::new(0),
             left: AtomicUsize::new(0),
             bottom: AtomicUsize::new(0),
+            following_end: AtomicBool::new(false),
             line_wrapping: AtomicBool::new(false),
             word_wrapping: AtomicBool::new(false),
+            column_wrapping: AtomicBool::new(false),
         }
     }
 }
@@ -137,11 +307,12 @@ impl BarItem for PositionIndicator {
         let bottom = self.bottom.load(Ordering::SeqCst);
         let line_wrapping = self.line_wrapping.load(Ordering::SeqCst);
         let word_wrapping = self.word_wrapping.load(Ordering::SeqCst);
+        let column_wrapping = self.column_wrapping.load(Ordering::SeqCst);
         let mut width = 0;
         let file_lines = self.file.lines();
         let nw = max(3, util::number_width(max(file_lines, max(bottom, top + 1))));
 
-        if line_wrapping || word_wrapping {
+        if line_wrapping || word_wrapping || column_wrapping {
             width += 6;
         } else if left > 1 {
             // Indicate horizontal position as "+N" if we are not at the very left.
@@ -163,8 +334,10 @@ impl BarItem for PositionIndicator {
         let top = self.top.load(Ordering::SeqCst);
         let left = self.left.load(Ordering::SeqCst);
         let bottom = self.bottom.load(Ordering::SeqCst);
+        let following_end = self.following_end.load(Ordering::SeqCst);
         let line_wrapping = self.line_wrapping.load(Ordering::SeqCst);
         let word_wrapping = self.word_wrapping.load(Ordering::SeqCst);
+        let column_wrapping = self.column_wrapping.load(Ordering::SeqCst);
         let file_lines = self.file.lines();
         let mut out = String::new();
         let nw = max(3, util::number_width(max(file_lines, max(bottom, top + 1))));
@@ -173,13 +346,15 @@ impl BarItem for PositionIndicator {
             write!(out, "wrap  ").expect("writes to strings should not fail");
         } else if word_wrapping {
             write!(out, "word  ").expect("writes to strings should not fail");
+        } else if column_wrapping {
+            write!(out, "col   ").expect("writes to strings should not fail");
         } else if left > 0 {
             write!(out, "{:+}  ", left + 1,).expect("writes to strings should not fail");
         }
 
         if top > file_lines {
             write!(out, "line {1:0}/{2:0$}", nw, top + 1, file_lines)
-        } else if bottom > 0 {
+        } else if !following_end {
             write!(
                 out,
                 "lines {1:0$}-{2:0$}/{3:0$.0$}",
@@ -202,6 +377,79 @@ impl BarItem for PositionIndicator {
 
         changes.push(Change::Text(util::truncate_string(&out, 0, width)));
     }
+
+    fn priority(&self) -> BarPriority {
+        BarPriority::High
+    }
+}
+
+/// Shows how far through the file the bottom of the screen is.
+struct PercentIndicator {
+    position: Arc<PositionIndicator>,
+}
+
+impl PercentIndicator {
+    fn new(position: Arc<PositionIndicator>) -> Self {
+        PercentIndicator { position }
+    }
+
+    fn content(&self) -> String {
+        let bottom = self.position.bottom.load(Ordering::SeqCst);
+        let following_end = self.position.following_end.load(Ordering::SeqCst);
+        let file_lines = self.position.file.lines();
+        let percent = if following_end || bottom >= file_lines {
+            // Following the end, or the end of the file is visible.
+            100
+        } else {
+            bottom * 100 / file_lines
+        };
+        format!("{}%", percent)
+    }
+}
+
+impl BarItem for PercentIndicator {
+    fn width(&self) -> usize {
+        self.content().width()
+    }
+
+    fn render(&self, changes: &mut Vec<Change>, width: usize) {
+        changes.push(Change::Text(util::truncate_string(
+            self.content(),
+            0,
+            width,
+        )));
+    }
+}
+
+/// Shows the byte offset of the top line of the screen.
+struct ByteOffsetIndicator {
+    position: Arc<PositionIndicator>,
+}
+
+impl ByteOffsetIndicator {
+    fn new(position: Arc<PositionIndicator>) -> Self {
+        ByteOffsetIndicator { position }
+    }
+
+    fn content(&self) -> Option<String> {
+        let top = self.position.top.load(Ordering::SeqCst);
+        self.position
+            .file
+            .line_offset(top)
+            .map(|offset| format!("byte {}", offset))
+    }
+}
+
+impl BarItem for ByteOffsetIndicator {
+    fn width(&self) -> usize {
+        self.content().map_or(0, |content| content.width())
+    }
+
+    fn render(&self, changes: &mut Vec<Change>, width: usize) {
+        if let Some(content) = self.content() {
+            changes.push(Change::Text(util::truncate_string(content, 0, width)));
+        }
+    }
 }
 
 /// Shows whether or not the file is loading.
@@ -209,24 +457,29 @@ struct LoadingIndicator {
     file: File,
     following_end: AtomicBool,
     animation_start: Instant,
+    frame_duration: Option<Duration>,
 }
 
 impl LoadingIndicator {
-    fn new(file: File) -> Self {
+    fn new(file: File, frame_duration: Option<Duration>) -> Self {
         LoadingIndicator {
             file,
             following_end: AtomicBool::new(false),
             animation_start: Instant::now(),
+            frame_duration,
         }
     }
 
     fn content(&self) -> Option<&'static str> {
         if self.file.loaded() {
             None
+        } else if self.file.loading_suspended() {
+            Some("[paused by user]")
         } else if self.file.paused() && !self.following_end.load(Ordering::SeqCst) {
             Some("[loading paused]")
-        } else {
-            let frame_index = (self.animation_start.elapsed().subsec_millis() / 200) as usize;
+        } else if let Some(frame_duration) = self.frame_duration {
+            let elapsed = self.animation_start.elapsed().as_millis();
+            let frame_index = (elapsed / frame_duration.as_millis() % 5) as usize;
             let frame = [
                 "[loading •     ]",
                 "[loading  •    ]",
@@ -235,6 +488,8 @@ impl LoadingIndicator {
                 "[loading     • ]",
             ][frame_index];
             Some(frame)
+        } else {
+            Some("[loading]")
         }
     }
 }
@@ -255,6 +510,32 @@ impl BarItem for LoadingIndicator {
     }
 }
 
+/// Shows when mouse capture is paused for selecting text.
+#[derive(Default)]
+struct MouseSelectionIndicator {
+    selecting: AtomicBool,
+}
+
+impl BarItem for MouseSelectionIndicator {
+    fn width(&self) -> usize {
+        if self.selecting.load(Ordering::Relaxed) {
+            14
+        } else {
+            0
+        }
+    }
+
+    fn render(&self, changes: &mut Vec<Change>, width: usize) {
+        if self.selecting.load(Ordering::Relaxed) {
+            changes.push(Change::Text(util::truncate_string(
+                "[mouse select]",
+                0,
+                width,
+            )));
+        }
+    }
+}
+
 #[derive(Default)]
 struct RepeatCountIndicator {
     count: AtomicUsize,
@@ -279,3 +560,33 @@ impl BarItem for RepeatCountIndicator {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use std::thread;
+
+    use super::*;
+    use crate::event::EventStream;
+    use crate::file::LoadedFile;
+
+    #[test]
+    fn test_percent() {
+        let events = EventStream::new(None);
+        let file: File =
+            LoadedFile::new_static(0, "test", &b"1\n2\n3\n4\n"[..], events.sender()).into();
+        while !file.loaded() {
+            thread::sleep(Duration::from_millis(1));
+        }
+        let ruler = Ruler::new(file, &Config::default());
+        let percent = PercentIndicator::new(ruler.position.clone());
+        let position = |bottom| {
+            ruler.set_position(0, 0, bottom, WrappingMode::Unwrapped);
+            percent.content()
+        };
+        // Without rows for the file, none of it is shown.
+        assert_eq!(position(Some(0)), "0%");
+        assert_eq!(position(Some(1)), "25%");
+        assert_eq!(position(Some(4)), "100%");
+        assert_eq!(position(None), "100%");
+    }
+}
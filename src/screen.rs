@@ -27,34 +27,59 @@
 //! ```
 
 use std::cmp::{max, min};
+use std::collections::VecDeque;
+use std::ops::Range;
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 
 use termwiz::cell::{CellAttributes, Intensity};
 use termwiz::color::{AnsiColor, ColorAttribute};
-use termwiz::input::KeyEvent;
+use termwiz::input::{KeyCode, KeyEvent, Modifiers};
 use termwiz::surface::change::Change;
-use termwiz::surface::{CursorVisibility, Position};
+use termwiz::surface::{CursorVisibility, Position, Surface};
 
-use crate::action::Action;
+use crate::action::{Action, Severity};
 use crate::bindings::{Binding, Keymap};
 use crate::command;
-use crate::config::{Config, WrappingMode};
+use crate::config::{
+    Animation, Backpressure, ColorDepth, Config, Renderer, WatchAlert, WrappingMode,
+};
 use crate::display::Capabilities;
 use crate::display::DisplayAction;
 use crate::error::Error;
 use crate::event::EventSender;
 use crate::file::{File, FileInfo};
-use crate::line::Line;
-use crate::line_cache::LineCache;
+use crate::gutter::Gutter;
+use crate::handle::SearchState;
+use crate::json;
+use crate::line::{self, Line, LineDecorations};
+use crate::line_cache::{CodeBlocks, LineCache};
+use crate::palette;
 use crate::progress::Progress;
 use crate::prompt::Prompt;
-use crate::prompt_history;
+use crate::prompt_history::LastValues;
 use crate::refresh::Refresh;
 use crate::ruler::Ruler;
-use crate::search::{MatchMotion, Search, SearchKind};
-use crate::util::number_width;
+use crate::search::{MatchMotion, Search, SearchColumns, SearchKind, SearchMatches};
+use crate::session::SessionFile;
+use crate::util;
+use crate::watch::Watch;
 
-const LINE_CACHE_SIZE: usize = 1000;
+/// The number of columns to show either side of a search match when
+/// scrolling horizontally to it.
+const MATCH_MARGIN: usize = 8;
+
+/// The maximum number of rows used to show the error message.
+const MAX_ERROR_HEIGHT: usize = 4;
+
+/// The margin drawn after the wrap column when wrapping at a fixed column.
+const MARGIN: &str = "\u{2502}";
+
+/// How long the ruler flashes for when a watch pattern matches.
+const RULER_FLASH_DURATION: Duration = Duration::from_millis(500);
+
+/// Rings the terminal bell.
+const BELL: &str = "\x07";
 
 /// The state of the previous render.
 #[derive(Clone, Debug, Default)]
@@ -77,6 +102,13 @@ struct RenderState {
     /// The column at the left of the screen.
     left: usize,
 
+    /// The number of columns available for the file's lines.
+    file_width: usize,
+
+    /// The width of the longest line on screen, if lines are unwrapped.
+    /// Scrolling right stops at the end of this line.
+    max_line_width: usize,
+
     /// The height of the overlay.
     overlay_height: usize,
 
@@ -102,17 +134,30 @@ struct RenderState {
     /// The row the ruler was rendered to.
     ruler_row: Option<usize>,
 
+    /// The row the ruler of the screen beneath an overlay was rendered to.
+    context_ruler_row: Option<usize>,
+
     /// The row the prompt was rendered to.
     prompt_row: Option<usize>,
 
-    /// The row the error message was rendered to.
+    /// The first row the error message was rendered to.
     error_row: Option<usize>,
 
+    /// The number of rows showing the error message.
+    error_height: usize,
+
+    /// The row the current message was rendered to.
+    message_row: Option<usize>,
+
     /// The row search status was rendered to.
     search_row: Option<usize>,
 
     /// The start and end row of each file line in view.
     file_line_rows: Vec<(usize, usize)>,
+
+    /// Whether each row of the scrollbar was drawn as part of the thumb,
+    /// and whether it was marked as having search matches.
+    scrollbar: Vec<(bool, bool)>,
 }
 
 impl RenderState {
@@ -129,6 +174,36 @@ impl RenderState {
     }
 }
 
+/// The last line of a file that is still loading, as it was last drawn.
+struct GrowingLine {
+    /// The index of the line in the file.
+    line: usize,
+
+    /// The row of the screen the line was drawn from.
+    row: usize,
+
+    /// The leftmost column of the line that was drawn.
+    left: usize,
+
+    /// The rows the line was drawn on.
+    frame: Surface,
+}
+
+/// How long rendering a screen has taken.
+#[derive(Debug, Default, Clone, Copy)]
+pub(crate) struct RenderTimings {
+    /// The number of renders.
+    pub(crate) count: u32,
+    /// How long the last render took.
+    pub(crate) last: Duration,
+    /// How long all renders took.
+    pub(crate) total: Duration,
+    /// How long the slowest render took.
+    pub(crate) slowest: Duration,
+    /// The number of changes sent to the terminal by the last render.
+    pub(crate) last_changes: usize,
+}
+
 /// A screen that is displaying a single file.
 pub(crate) struct Screen {
     /// The file being displayed.
@@ -161,20 +236,51 @@ pub(crate) struct Screen {
     /// Wrapping mode.
     wrapping_mode: WrappingMode,
 
+    /// When loading the file stops once enough has been read ahead.
+    backpressure: Backpressure,
+
     /// The state of the previous render.
     rendered: RenderState,
 
+    /// The screen as it was last drawn by the surface renderer.
+    frame: Option<Surface>,
+
+    /// The last line of the file as it was last drawn, while the file is
+    /// loading.
+    growing_line: Option<GrowingLine>,
+
     /// Whether line numbers are being displayed.
     line_numbers: bool,
 
+    /// How line numbers are drawn.
+    gutter: Gutter,
+
     /// Cache of `Line`s to display.
     line_cache: LineCache,
 
     /// Cache of `Line`s for the current search.
     search_line_cache: LineCache,
 
+    /// Which lines are within markdown code blocks, shared by the line
+    /// caches.
+    code_blocks: CodeBlocks,
+
     /// The current error that should be displayed to the user.
-    pub(crate) error: Option<String>,
+    error: Option<String>,
+
+    /// All of the errors that have been displayed to the user, oldest first.
+    error_log: Vec<String>,
+
+    /// The number of the file's loading errors that have been added to the
+    /// error log.
+    file_errors: usize,
+
+    /// Messages waiting to be shown to the user.  The first message is the
+    /// one currently shown.
+    messages: VecDeque<(String, Severity)>,
+
+    /// When the first message was first shown.
+    message_shown: Option<Instant>,
 
     /// The current prompt that the user is entering a response into.
     prompt: Option<Prompt>,
@@ -182,18 +288,27 @@ pub(crate) struct Screen {
     /// The current ongoing search.
     search: Option<Search>,
 
+    /// Whether the search was restored from a session, so finding its first
+    /// match shouldn't move away from the restored position.
+    restoring_search: bool,
+
     /// The ruler.
     ruler: Ruler,
 
     /// Whether the ruler should be shown.
     show_ruler: bool,
 
+    /// For overlays, the ruler of the screen beneath, which is shown below
+    /// the overlay's own ruler so that it is clear which file the overlay
+    /// relates to.
+    context_ruler: Option<Ruler>,
+
     /// Whether we are following the end of the file.  If `true`, we will scroll down to the
     /// end as new input arrives.
     following_end: bool,
 
-    /// Scroll to a particular line in the file.
-    pending_absolute_scroll: Option<usize>,
+    /// Scroll to a particular line in the file, and a portion of that line.
+    pending_absolute_scroll: Option<(usize, usize)>,
 
     /// Scroll relative number of rows.
     pending_relative_scroll: isize,
@@ -206,30 +321,106 @@ pub(crate) struct Screen {
 
     /// Repeat the next operation for the given times.
     repeat_count: Option<usize>,
+
+    /// Whether searches only look at the visible columns of unwrapped lines.
+    search_visible_columns: bool,
+
+    /// The number of lines to scroll by for half screen scrolls, if it has
+    /// been set by a repeat count.
+    half_screen_size: Option<usize>,
+
+    /// The number of lines to scroll by for window scrolls, if it has been
+    /// set by a repeat count.
+    window_size: Option<usize>,
+
+    /// How long rendering this screen has taken.
+    render_timings: RenderTimings,
+
+    /// The watch patterns for lines that arrive while following.
+    watch: Watch,
+
+    /// When the ruler started flashing because a watch pattern matched.
+    ruler_flash: Option<Instant>,
+
+    /// Whether to ring the terminal bell on the next render.
+    pending_bell: bool,
+
+    /// If this screen shows a snapshot, the file it is a snapshot of.
+    snapshot_of: Option<File>,
+
+    /// The escape sequence added to the start of error file lines to show
+    /// them in the error file style.
+    error_file_style: Vec<u8>,
+
+    /// Whether the error file is collapsed to its latest row.
+    error_file_collapsed: bool,
+
+    /// The last values entered in prompts, shared with the pager's other
+    /// screens.
+    last_values: LastValues,
 }
 
 impl Screen {
     /// Create a screen that displays a file.
-    pub(crate) fn new(file: File, config: Arc<Config>) -> Result<Screen, Error> {
-        Ok(Screen {
+    ///
+    /// Problems with the configuration that only affect part of it, such as
+    /// an invalid highlight rule, are shown as errors on the screen.
+    pub(crate) fn new(
+        file: File,
+        config: Arc<Config>,
+        last_values: LastValues,
+    ) -> Result<Screen, Error> {
+        let mut config_errors = Vec::new();
+        let decorations = load_decorations(&file, &config, &mut config_errors)?;
+        let code_blocks = CodeBlocks::new();
+        let wrapping_mode = config
+            .profile(&file.title())?
+            .and_then(|profile| profile.wrapping_mode)
+            .unwrap_or(config.wrapping_mode);
+        let backpressure = load_backpressure(&file, &config)?;
+        let keymap = load_keymap(&file, &config)?;
+        let watch = Watch::new(&config.watch_patterns, &mut config_errors);
+        let error_file_style = load_error_file_style(&config, &mut config_errors);
+        let gutter = Gutter::new(&config, &mut config_errors);
+        let mut screen = Screen {
             error_file: None,
             progress: None,
-            keymap: config.keymap.load()?,
+            keymap,
             width: 0,
             height: 0,
             left: 0,
             top_line: 0,
             top_line_portion: 0,
-            wrapping_mode: config.wrapping_mode,
+            wrapping_mode,
+            backpressure,
             rendered: RenderState::default(),
+            frame: None,
+            growing_line: None,
             line_numbers: false,
-            line_cache: LineCache::new(LINE_CACHE_SIZE),
-            search_line_cache: LineCache::new(LINE_CACHE_SIZE),
+            gutter,
+            line_cache: LineCache::new(
+                config.line_cache_bytes,
+                decorations.clone(),
+                code_blocks.clone(),
+            ),
+            search_line_cache: LineCache::new(
+                config.line_cache_bytes,
+                decorations,
+                code_blocks.clone(),
+            ),
+            code_blocks,
             error: None,
+            error_log: Vec::new(),
+            file_errors: 0,
+            messages: VecDeque::new(),
+            message_shown: None,
             prompt: None,
             search: None,
-            ruler: Ruler::new(file.clone()),
+            restoring_search: false,
+            ruler: Ruler::new(file.clone(), &config),
             show_ruler: config.show_ruler,
+            context_ruler: None,
+            search_visible_columns: config.search_visible_columns,
             following_end: false,
             pending_absolute_scroll: None,
             pending_relative_scroll: 0,
@@ -237,7 +428,46 @@ impl Screen {
             config,
             file,
             repeat_count: None,
-        })
+            half_screen_size: None,
+            window_size: None,
+            render_timings: RenderTimings::default(),
+            watch,
+            ruler_flash: None,
+            pending_bell: false,
+            snapshot_of: None,
+            error_file_style,
+            error_file_collapsed: false,
+            last_values,
+        };
+        for error in config_errors {
+            screen.set_error(error);
+        }
+        Ok(screen)
+    }
+
+    /// Replace the screen's configuration, e.g. because the config file has
+    /// been reloaded.  The keymap, line decorations and ruler are re-created
+    /// from the new configuration.
+    pub(crate) fn set_config(&mut self, config: Arc<Config>) -> Result<(), Error> {
+        let mut config_errors = Vec::new();
+        let decorations = load_decorations(&self.file, &config, &mut config_errors)?;
+        self.keymap = load_keymap(&self.file, &config)?;
+        self.backpressure = load_backpressure(&self.file, &config)?;
+        self.watch = Watch::new(&config.watch_patterns, &mut config_errors);
+        self.error_file_style = load_error_file_style(&config, &mut config_errors);
+        self.gutter = Gutter::new(&config, &mut config_errors);
+        self.ruler_flash = None;
+        self.line_cache.set_decorations(decorations.clone());
+        self.search_line_cache.set_decorations(decorations);
+        self.line_cache.set_budget(config.line_cache_bytes);
+        self.search_line_cache.set_budget(config.line_cache_bytes);
+        self.ruler = Ruler::new(self.file.clone(), &config);
+        self.config = config;
+        self.refresh();
+        for error in config_errors {
+            self.set_error(error);
+        }
+        Ok(())
     }
 
     /// Resize the screen
@@ -254,6 +484,49 @@ impl Screen {
         self.width
     }
 
+    /// Get the screen height
+    pub(crate) fn height(&self) -> usize {
+        self.height
+    }
+
+    /// Get the file line at the top of the screen, as of the last render.
+    pub(crate) fn top_line(&self) -> usize {
+        self.rendered.top_line
+    }
+
+    /// Get the line the user is looking at: the line of the current search
+    /// match if it is on the screen, otherwise the line at the top of the
+    /// screen.
+    fn current_line(&self) -> usize {
+        let current_match = self
+            .search
+            .as_ref()
+            .and_then(|search| search.current_match());
+        match current_match {
+            Some((line, _match_index))
+                if (self.rendered.top_line..self.rendered.bottom_line).contains(&line) =>
+            {
+                line
+            }
+            _ => self.rendered.top_line,
+        }
+    }
+
+    /// Get the file lines that were visible on the screen, as of the last
+    /// render.
+    pub(crate) fn visible_lines(&self) -> Vec<Vec<u8>> {
+        (self.rendered.top_line..self.rendered.bottom_line)
+            .filter_map(|index| self.file.with_line(index, |line| line.to_vec()))
+            .collect()
+    }
+
+    /// Get the state of the current search, if any.
+    pub(crate) fn search_state(&self) -> Option<(SearchState, SearchMatches)> {
+        self.search
+            .as_ref()
+            .map(|search| (search.state(), search.matches()))
+    }
+
     /// Get the current overlay height
     pub(crate) fn overlay_height(&self) -> usize {
         self.rendered.overlay_height
@@ -264,8 +537,14 @@ impl Screen {
         &self.keymap
     }
 
+    /// Get the last values entered in prompts.
+    pub(crate) fn last_values(&self) -> &LastValues {
+        &self.last_values
+    }
+
     /// Renders the part of the screen that has changed.
     pub(crate) fn render(&mut self, caps: &Capabilities) -> Vec<Change> {
+        self.check_watch_patterns();
         let mut changes = vec![
             // Hide the cursor while we render things.
             Change::CursorVisibility(CursorVisibility::Hidden),
@@ -282,13 +561,31 @@ impl Screen {
         if let Some(search) = self.search.as_ref() {
             render.searched_lines = search.searched_lines();
         }
-        let mut pending_refresh = self.pending_refresh.clone();
+        // The surface renderer draws every row, and works out what changed
+        // by comparing the result with the previous frame.
+        let compose = self.config.renderer == Renderer::Surface;
+        let redraw = self.pending_refresh == Refresh::All;
+        let mut pending_refresh = if compose {
+            Refresh::All
+        } else {
+            self.frame = None;
+            self.pending_refresh.clone()
+        };
         let file_loaded = self.file.loaded();
+        // The scrollbar takes the rightmost column.
+        let scrollbar_width = if self.config.show_scrollbar && render.width > 1 {
+            1
+        } else {
+            0
+        };
+        let lines_width = render.width - scrollbar_width;
         let file_width = if self.line_numbers {
-            render.width - number_width(render.file_lines) - 2
+            lines_width.saturating_sub(self.gutter.width(render.file_lines))
         } else {
-            render.width
+            lines_width
         };
+        render.file_width = file_width;
+        let wrap_width = self.wrap_width(file_width);
 
         #[derive(Copy, Clone, Debug)]
         enum RowContent {
@@ -299,10 +596,12 @@ impl Screen {
                 rows: usize,
             },
             Blank,
-            Error,
+            Error(usize),
+            Message,
             Prompt,
             Search,
             Ruler,
+            ContextRuler,
             ErrorFileLinePortion(usize, usize),
             ProgressLine(usize),
         }
@@ -310,6 +609,13 @@ impl Screen {
         let mut row_contents = vec![RowContent::Empty; render.height];
 
         // Assign the lines of the error file to rows (in reverse order).
+        let error_file_max_height = if self.error_file_collapsed {
+            1
+        } else if self.config.error_file_max_height == 0 {
+            render.height
+        } else {
+            self.config.error_file_max_height
+        };
         let error_file_line_portions: Vec<_> = (0..render.error_file_lines)
             .rev()
             .flat_map(|line_index| {
@@ -327,19 +633,24 @@ impl Screen {
                     Vec::new()
                 }
             })
-            .take(8)
+            .take(error_file_max_height)
             .collect();
 
         // Compute where the overlay will go
-        let ruler_height = self.show_ruler as usize;
+        let show_context_ruler = self.show_ruler && self.context_ruler.is_some();
+        let ruler_height = self.show_ruler as usize + show_context_ruler as usize;
         render.progress_height = self.progress.as_ref().map(|f| f.lines()).unwrap_or(0);
+        if self.config.progress_max_height != 0 {
+            render.progress_height = min(render.progress_height, self.config.progress_max_height);
+        }
         render.error_file_height = error_file_line_portions.len();
         render.overlay_height = render.progress_height
             + render.error_file_height
             + ruler_height
             + self.search.is_some() as usize
             + self.prompt.is_some() as usize
-            + self.error.is_some() as usize;
+            + self.error_height()
+            + !self.messages.is_empty() as usize;
 
         if render.overlay_height < render.height {
             let mut row = render.height - render.progress_height;
@@ -356,6 +667,11 @@ impl Screen {
                     error_file_line_portion.1,
                 );
             }
+            if show_context_ruler {
+                row -= 1;
+                row_contents[row] = RowContent::ContextRuler;
+                render.context_ruler_row = Some(row);
+            }
             if self.show_ruler {
                 row -= 1;
                 row_contents[row] = RowContent::Ruler;
@@ -372,10 +688,18 @@ impl Screen {
                 render.prompt_row = Some(row);
             }
             if self.error.is_some() {
-                row -= 1;
-                row_contents[row] = RowContent::Error;
+                render.error_height = self.error_height();
+                row -= render.error_height;
+                for error_line in 0..render.error_height {
+                    row_contents[row + error_line] = RowContent::Error(error_line);
+                }
                 render.error_row = Some(row);
             }
+            if !self.messages.is_empty() {
+                row -= 1;
+                row_contents[row] = RowContent::Message;
+                render.message_row = Some(row);
+            }
         } else {
             // The overlay doesn't fit.  Only show the prompt (if any).
             render.overlay_height = self.prompt.is_some() as usize;
@@ -398,7 +722,7 @@ impl Screen {
             while top_line > 0 && remaining > 0 {
                 top_line -= 1;
                 if let Some(line) = self.line_cache.get_or_create(&self.file, top_line, None) {
-                    let line_height = line.height(file_width, self.wrapping_mode);
+                    let line_height = line.height(wrap_width, self.wrapping_mode);
                     if line_height > remaining {
                         top_line_portion = line_height - remaining;
                         break;
@@ -420,7 +744,7 @@ impl Screen {
                 while scroll_line < end_top_line {
                     if let Some(line) = self.line_cache.get_or_create(&self.file, scroll_line, None)
                     {
-                        let line_height = line.height(file_width, self.wrapping_mode);
+                        let line_height = line.height(wrap_width, self.wrapping_mode);
                         scroll_by += line_height.saturating_sub(scroll_line_portion);
                         if scroll_by > file_view_height {
                             // We've scrolled an entire screen, just jump straight to the end.
@@ -445,9 +769,14 @@ impl Screen {
         }
 
         // Perform pending absolute scroll
-        if let Some(line) = self.pending_absolute_scroll.take() {
+        if let Some((line, portion)) = self.pending_absolute_scroll.take() {
+            let wrapping_mode = self.wrapping_mode;
+            let line_height = self
+                .line_cache
+                .get_or_create(&self.file, line, None)
+                .map_or(1, |line| line.height(wrap_width, wrapping_mode));
             self.top_line = line;
-            self.top_line_portion = 0;
+            self.top_line_portion = min(portion, line_height.saturating_sub(1));
             pending_refresh.add_range(0, file_view_height);
             // Scroll up so that the target line is in the center of the
             // file view.
@@ -478,7 +807,7 @@ impl Screen {
                 top_line -= 1;
                 top_line_portion = 0;
                 if let Some(line) = self.line_cache.get_or_create(&self.file, top_line, None) {
-                    let line_height = line.height(file_width, self.wrapping_mode);
+                    let line_height = line.height(wrap_width, self.wrapping_mode);
                     if line_height > scroll_up {
                         scroll_distance += scroll_up;
                         top_line_portion = line_height - scroll_up;
@@ -500,7 +829,7 @@ impl Screen {
                 let line_height = if let Some(line) =
                     self.line_cache.get_or_create(&self.file, last_line, None)
                 {
-                    line.height(file_width, self.wrapping_mode)
+                    line.height(wrap_width, self.wrapping_mode)
                 } else {
                     1
                 };
@@ -512,7 +841,7 @@ impl Screen {
                 && (top_line, top_line_portion) < (max_top_line, max_top_line_portion)
             {
                 if let Some(line) = self.line_cache.get_or_create(&self.file, top_line, None) {
-                    let line_height = line.height(file_width, self.wrapping_mode);
+                    let line_height = line.height(wrap_width, self.wrapping_mode);
                     let line_height_remaining = line_height.saturating_sub(top_line_portion);
                     if line_height_remaining > scroll_down {
                         scroll_distance += scroll_down;
@@ -533,6 +862,10 @@ impl Screen {
         render.left = self.left;
         self.pending_relative_scroll = 0;
 
+        // Rows of the scrollbar that need to be drawn again, even if they
+        // haven't changed, because the rows have been scrolled or redrawn.
+        let mut scrollbar_refresh = Refresh::None;
+
         // Scroll the region of the screen that had and still has file lines
         if pending_refresh != Refresh::All {
             let scroll_start = 0;
@@ -551,6 +884,7 @@ impl Screen {
                         region_size: scroll_end - scroll_start,
                         scroll_count: scroll_distance,
                     });
+                    scrollbar_refresh.add_range(scroll_start, scroll_end);
                     pending_refresh.rotate_range_down(
                         scroll_start,
                         scroll_end,
@@ -564,6 +898,7 @@ impl Screen {
                         region_size: scroll_end - scroll_start,
                         scroll_count: scroll_distance,
                     });
+                    scrollbar_refresh.add_range(scroll_start, scroll_end);
                     pending_refresh.rotate_range_up(
                         scroll_start,
                         scroll_end,
@@ -588,7 +923,10 @@ impl Screen {
             let mut top_portion = render.top_line_portion;
             for file_line in render.top_line..render.file_lines {
                 if let Some(line) = self.line_cache.get_or_create(&self.file, file_line, None) {
-                    let line_height = line.height(file_width, self.wrapping_mode);
+                    if self.wrapping_mode == WrappingMode::Unwrapped {
+                        render.max_line_width = max(render.max_line_width, line.width());
+                    }
+                    let line_height = line.height(wrap_width, self.wrapping_mode);
                     let visible_line_height = min(
                         line_height.saturating_sub(top_portion),
                         file_view_height - row,
@@ -632,7 +970,9 @@ impl Screen {
         // Work out what else needs to be refreshed
         if pending_refresh != Refresh::All {
             // What needs to be refreshed because more of the file was loaded?
-            if !file_loaded {
+            // If no lines were added, the last line may have grown, which is
+            // handled below.
+            if !file_loaded && render.file_lines != self.rendered.file_lines {
                 let last_line = self.rendered.file_lines.saturating_sub(1);
                 if let Some((start, end)) = render.file_line_rows(last_line) {
                     pending_refresh.add_range(start, end);
@@ -680,6 +1020,10 @@ impl Screen {
             {
                 pending_refresh.add_range(bottom_row - render.error_file_height, bottom_row);
             }
+            if self.rendered.progress_height != render.progress_height {
+                // The progress indicator has grown or shrunk.
+                pending_refresh.add_range(bottom_row, render.height);
+            }
 
             // Did the ruler move or does it need updating?
             if let Some(ruler_row) = render.ruler_row {
@@ -692,6 +1036,20 @@ impl Screen {
                 }
             }
 
+            // Did the ruler of the screen beneath move?
+            if let Some(context_ruler_row) = render.context_ruler_row {
+                if self.rendered.context_ruler_row != Some(context_ruler_row) {
+                    pending_refresh.add_range(context_ruler_row, context_ruler_row + 1);
+                }
+            }
+
+            // Did the search status move?
+            if let Some(search_row) = render.search_row {
+                if self.rendered.search_row != Some(search_row) {
+                    pending_refresh.add_range(search_row, search_row + 1);
+                }
+            }
+
             // Did the prompt move?
             if let Some(prompt_row) = render.prompt_row {
                 if self.rendered.prompt_row != Some(prompt_row) {
@@ -701,13 +1059,25 @@ impl Screen {
 
             // Did the error message move?
             if let Some(error_row) = render.error_row {
-                if self.rendered.error_row != Some(error_row) {
-                    pending_refresh.add_range(error_row, error_row + 1);
+                if self.rendered.error_row != Some(error_row)
+                    || self.rendered.error_height != render.error_height
+                {
+                    pending_refresh.add_range(error_row, error_row + render.error_height);
+                }
+            }
+
+            // Did the current message move?
+            if let Some(message_row) = render.message_row {
+                if self.rendered.message_row != Some(message_row) {
+                    pending_refresh.add_range(message_row, message_row + 1);
                 }
             }
         }
 
-        if self.wrapping_mode == WrappingMode::GraphemeBoundary && !self.line_numbers {
+        if self.wrapping_mode == WrappingMode::GraphemeBoundary
+            && !self.line_numbers
+            && scrollbar_width == 0
+        {
             // In wrapped mode with line numbers off, render full lines at once
             // so that the terminal can handle wrapped lines properly.
             let mut first_row: Option<(usize, &mut RowContent)> = None;
@@ -745,6 +1115,76 @@ impl Screen {
             }
         }
 
+        // In accessibility mode, any change repaints the whole screen.
+        if self.config.accessibility_mode && pending_refresh != Refresh::None {
+            pending_refresh = Refresh::All;
+        }
+
+        // While the file is loading, its last line may grow.  If nothing
+        // else about it has changed, only the part that was appended is
+        // drawn.
+        let growing_line = match render.file_lines.checked_sub(1) {
+            Some(line) if !file_loaded && !compose => render
+                .file_line_rows(line)
+                .filter(|(start, end)| start < end)
+                .map(|(start, end)| (line, start, end)),
+            _ => None,
+        };
+        if let Some((line, start, end)) = growing_line {
+            // An unwrapped line that doesn't fit is marked with an arrow that
+            // is drawn relative to the end of the row, which the frame can't
+            // reproduce, so it is always drawn in full.
+            let overflows = self.wrapping_mode == WrappingMode::Unwrapped
+                && match self.line_cache.get_or_create(&self.file, line, None) {
+                    Some(line) => line.width() >= render.left.saturating_add(render.file_width),
+                    None => true,
+                };
+            let mut line_changes = Vec::new();
+            for (offset, row_content) in row_contents[start..end].iter().enumerate() {
+                if let RowContent::FileLinePortions {
+                    line,
+                    first_portion,
+                    rows,
+                } = *row_content
+                {
+                    self.render_file_line(
+                        &mut line_changes,
+                        offset,
+                        line,
+                        first_portion,
+                        rows,
+                        render.left,
+                        lines_width,
+                    );
+                }
+            }
+            let mut frame = Surface::new(render.width, end - start);
+            frame.add_changes(line_changes);
+            match self.growing_line.take() {
+                Some(previous)
+                    if !overflows
+                        && previous.line == line
+                        && previous.row == start
+                        && previous.left == render.left
+                        && previous.frame.dimensions().0 == render.width
+                        && previous.frame.dimensions().1 <= end - start
+                        && !(start..end).any(|row| pending_refresh.contains(row)) =>
+                {
+                    diff_frames(&previous.frame, &frame, start, &mut changes);
+                    scrollbar_refresh.add_range(start, end);
+                }
+                _ => pending_refresh.add_range(start, end),
+            }
+            self.growing_line = Some(GrowingLine {
+                line,
+                row: start,
+                left: render.left,
+                frame,
+            });
+        } else {
+            self.growing_line = None;
+        }
+
         // Render pending rows
         for (row, row_content) in row_contents.into_iter().enumerate() {
             if pending_refresh.contains(row) {
@@ -762,14 +1202,17 @@ impl Screen {
                             first_portion,
                             rows,
                             render.left,
-                            render.width,
+                            lines_width,
                         );
                     }
                     RowContent::Blank => {
                         self.render_blank_line(&mut changes, row);
                     }
-                    RowContent::Error => {
-                        self.render_error(&mut changes, row, render.width);
+                    RowContent::Error(error_line) => {
+                        self.render_error(&mut changes, row, error_line, render.width);
+                    }
+                    RowContent::Message => {
+                        self.render_message(&mut changes, row, render.width);
                     }
                     RowContent::Prompt => {
                         self.prompt
@@ -778,13 +1221,18 @@ impl Screen {
                             .render(&mut changes, row, render.width);
                     }
                     RowContent::Search => {
-                        if let Some(search) = self.search.as_mut() {
+                        if let Some(search) = self.search.as_ref() {
                             search.render(&mut changes, row, render.width);
                         }
                     }
                     RowContent::Ruler => {
                         self.ruler.bar().render(&mut changes, row, render.width);
                     }
+                    RowContent::ContextRuler => {
+                        if let Some(context_ruler) = self.context_ruler.as_ref() {
+                            context_ruler.bar().render(&mut changes, row, render.width);
+                        }
+                    }
                     RowContent::ErrorFileLinePortion(line, portion) => {
                         self.render_error_file_line(&mut changes, row, line, portion, render.width);
                     }
@@ -795,6 +1243,12 @@ impl Screen {
             }
         }
 
+        if scrollbar_width > 0 {
+            self.render_scrollbar(&mut changes, &mut render, file_view_height, |row| {
+                pending_refresh.contains(row) || scrollbar_refresh.contains(row)
+            });
+        }
+
         // Set the cursor to the right position and shape.
         if let Some(prompt) = self.prompt.as_ref() {
             changes.push(Change::CursorPosition {
@@ -819,13 +1273,54 @@ impl Screen {
         // Restore attributes to default.
         changes.push(Change::AllAttributes(CellAttributes::default()));
 
+        if !self.config.use_color() {
+            remove_colors(&mut changes);
+        } else if self.config.color_depth != ColorDepth::Auto {
+            palette::downgrade_changes(&mut changes, self.config.color_depth);
+        }
+
         // Record what we've rendered.
         self.rendered = render;
         self.pending_refresh = Refresh::None;
 
+        let mut changes = if compose {
+            self.compose_frame(changes, redraw)
+        } else {
+            changes
+        };
+        if std::mem::take(&mut self.pending_bell) {
+            changes.push(Change::Text(String::from(BELL)));
+        }
         changes
     }
 
+    /// Composes the changes that draw the whole screen into a frame, and
+    /// returns the changes needed to update the previous frame to match
+    /// it.  If `redraw` is set, or there is no previous frame, the terminal
+    /// is cleared and the frame is drawn in full.
+    fn compose_frame(&mut self, changes: Vec<Change>, redraw: bool) -> Vec<Change> {
+        let mut frame = Surface::new(self.width, self.height);
+        frame.add_changes(changes);
+        let mut output = vec![Change::CursorVisibility(CursorVisibility::Hidden)];
+        let previous = match self.frame.take() {
+            Some(previous) if !redraw && previous.dimensions() == frame.dimensions() => previous,
+            _ => {
+                output.push(Change::ClearScreen(ColorAttribute::Default));
+                Surface::new(self.width, self.height)
+            }
+        };
+        diff_frames(&previous, &frame, 0, &mut output);
+        let (x, y) = frame.cursor_position();
+        output.push(Change::CursorPosition {
+            x: Position::Absolute(x),
+            y: Position::Absolute(y),
+        });
+        output.push(Change::CursorVisibility(frame.cursor_visibility()));
+        output.push(Change::AllAttributes(CellAttributes::default()));
+        self.frame = Some(frame);
+        output
+    }
+
     /// Renders a line of the file on the screen.
     fn render_file_line(
         &mut self,
@@ -837,10 +1332,11 @@ impl Screen {
         left: usize,
         width: usize,
     ) {
+        let wrap_width = self.wrap_width(width);
         let line = match self.search {
             Some(ref search) if search.line_matches(line_index) => self
                 .search_line_cache
-                .get_or_create(&self.file, line_index, Some(search.regex())),
+                .get_or_create(&self.file, line_index, Some(search)),
             _ => self.line_cache.get_or_create(&self.file, line_index, None),
         };
 
@@ -856,6 +1352,8 @@ impl Screen {
                 }
             });
 
+        let selected = self.file.cursor() == Some(line_index);
+
         if let Some(line) = line {
             changes.push(Change::CursorPosition {
                 x: Position::Absolute(0),
@@ -866,40 +1364,110 @@ impl Screen {
             let start = left;
             let mut end = left.saturating_add(width);
             if self.line_numbers {
-                let lw = number_width(self.file.lines());
-                if lw + 2 < width {
-                    changes.push(Change::AllAttributes(
-                        CellAttributes::default()
-                            .set_foreground(AnsiColor::Black)
-                            .set_background(AnsiColor::Silver)
-                            .clone(),
-                    ));
-                    if first_portion == 0 {
-                        changes.push(Change::Text(format!(" {:>1$} ", line_index + 1, lw)));
-                    } else {
-                        changes.push(Change::Text(" ".repeat(lw + 2)));
-                    };
-                    changes.push(Change::AllAttributes(CellAttributes::default()));
-                    end -= lw + 2;
+                let lines = self.file.lines();
+                let gutter_width = self.gutter.width(lines);
+                if gutter_width < width {
+                    self.gutter
+                        .render(changes, line_index, first_portion, lines);
+                    end -= gutter_width;
                 }
             }
             if self.wrapping_mode == WrappingMode::Unwrapped {
-                line.render(changes, start, end, match_index);
+                line.render(changes, start, end, match_index, selected);
             } else {
                 line.render_wrapped(
                     changes,
                     first_portion,
                     rows,
-                    end - start,
+                    min(end - start, wrap_width),
                     self.wrapping_mode,
                     match_index,
+                    selected,
                 );
             }
+            if self.wrapping_mode == WrappingMode::Column
+                && self.config.wrap_column > 0
+                && self.config.wrap_column < end - start
+            {
+                // Draw a faint margin after the wrap column.
+                changes.push(Change::ClearToEndOfLine(ColorAttribute::default()));
+                changes.push(Change::CursorPosition {
+                    x: Position::Absolute(width - (end - start) + self.config.wrap_column),
+                    y: Position::Absolute(row),
+                });
+                changes.push(Change::AllAttributes(
+                    CellAttributes::default()
+                        .set_intensity(Intensity::Half)
+                        .clone(),
+                ));
+                changes.push(Change::Text(MARGIN.into()));
+                changes.push(Change::AllAttributes(CellAttributes::default()));
+            }
         } else {
             self.render_blank_line(changes, row);
         }
     }
 
+    /// Returns the width to wrap lines at, given the width available for
+    /// them.
+    fn wrap_width(&self, width: usize) -> usize {
+        if self.wrapping_mode == WrappingMode::Column && self.config.wrap_column > 0 {
+            min(width, self.config.wrap_column)
+        } else {
+            width
+        }
+    }
+
+    /// Renders the scrollbar in the rightmost column of the rows showing the
+    /// file.  The rows showing the screen are highlighted, and rows where
+    /// the search has found matches are marked.
+    ///
+    /// Only rows that have changed since the last render, or where `redraw`
+    /// returns true, are drawn.
+    fn render_scrollbar(
+        &self,
+        changes: &mut Vec<Change>,
+        render: &mut RenderState,
+        height: usize,
+        redraw: impl Fn(usize) -> bool,
+    ) {
+        let lines = max(render.file_lines, 1);
+        let thumb_start = render.top_line * height / lines;
+        let thumb_end = max(
+            thumb_start + 1,
+            (render.bottom_line * height).div_ceil(lines),
+        );
+        let match_rows = match self.search.as_ref() {
+            Some(search) => search.matching_rows(lines, height),
+            None => vec![false; height],
+        };
+        let previous = &self.rendered.scrollbar;
+        let resized = previous.len() != height || self.rendered.width != render.width;
+        for (row, matched) in match_rows.into_iter().enumerate() {
+            let thumb = row >= thumb_start && row < thumb_end;
+            render.scrollbar.push((thumb, matched));
+            if !resized && !redraw(row) && previous[row] == (thumb, matched) {
+                continue;
+            }
+            changes.push(Change::CursorPosition {
+                x: Position::Absolute(render.width - 1),
+                y: Position::Absolute(row),
+            });
+            let mut attrs = CellAttributes::default();
+            if thumb {
+                attrs.set_background(AnsiColor::Grey);
+            }
+            if matched {
+                attrs
+                    .set_foreground(AnsiColor::Olive)
+                    .set_intensity(Intensity::Bold);
+            }
+            changes.push(Change::AllAttributes(attrs));
+            changes.push(Change::Text(if matched { "-" } else { " " }.into()));
+        }
+        changes.push(Change::AllAttributes(CellAttributes::default()));
+    }
+
     fn render_blank_line(&self, changes: &mut Vec<Change>, row: usize) {
         changes.push(Change::CursorPosition {
             x: Position::Absolute(0),
@@ -930,9 +1498,19 @@ impl Screen {
                 y: Position::Absolute(row),
             });
             changes.push(Change::AllAttributes(CellAttributes::default()));
-            if let Some(line) = error_file.with_line(line_index, |line| Line::new(line_index, line))
-            {
-                line.render_wrapped(changes, portion, 1, width, WrappingMode::WordBoundary, None);
+            let style = &self.error_file_style;
+            if let Some(line) = error_file.with_line(line_index, |line| {
+                Line::new(line_index, [style.as_slice(), &line].concat())
+            }) {
+                line.render_wrapped(
+                    changes,
+                    portion,
+                    1,
+                    width,
+                    WrappingMode::WordBoundary,
+                    None,
+                    false,
+                );
             } else {
                 changes.push(Change::ClearToEndOfLine(ColorAttribute::default()));
             }
@@ -953,16 +1531,26 @@ impl Screen {
             });
             changes.push(Change::AllAttributes(CellAttributes::default()));
             if let Some(line) = progress.with_line(line_index, |line| Line::new(line_index, line)) {
-                line.render(changes, 0, width, None);
+                line.render(changes, 0, width, None, false);
             } else {
                 changes.push(Change::ClearToEndOfLine(ColorAttribute::default()));
             }
         }
     }
 
-    /// Renders the error message at the bottom of the screen.
-    fn render_error(&mut self, changes: &mut Vec<Change>, row: usize, _width: usize) {
+    /// Renders a line of the error message at the bottom of the screen.
+    fn render_error(
+        &mut self,
+        changes: &mut Vec<Change>,
+        row: usize,
+        error_line: usize,
+        width: usize,
+    ) {
         if let Some(error) = self.error.as_ref() {
+            let mut text = format!("  {}  ", error.lines().nth(error_line).unwrap_or(""));
+            if error_line == 0 && self.error_log.len() > 1 {
+                text.push_str(&format!("({} errors)  ", self.error_log.len()));
+            }
             changes.push(Change::CursorPosition {
                 x: Position::Absolute(0),
                 y: Position::Absolute(row),
@@ -973,13 +1561,193 @@ impl Screen {
                     .set_background(AnsiColor::Maroon)
                     .clone(),
             ));
-            // TODO: truncate at width
-            changes.push(Change::Text(format!("  {}  ", error)));
+            changes.push(Change::Text(util::truncate_string(text, 0, width)));
+            changes.push(Change::AllAttributes(CellAttributes::default()));
+            changes.push(Change::ClearToEndOfLine(ColorAttribute::default()));
+        }
+    }
+
+    /// Returns the number of rows needed to show the error message.
+    fn error_height(&self) -> usize {
+        match self.error.as_ref() {
+            Some(error) => error.lines().count().clamp(1, MAX_ERROR_HEIGHT),
+            None => 0,
+        }
+    }
+
+    /// Show an error to the user, and add it to the error log.
+    pub(crate) fn set_error(&mut self, error: impl Into<String>) {
+        let error = error.into();
+        self.error_log.push(error.clone());
+        self.error = Some(error);
+        self.refresh_overlay();
+    }
+
+    /// Hide the current error.  It remains in the error log.
+    pub(crate) fn clear_error(&mut self) {
+        self.refresh_overlay();
+        self.error = None;
+    }
+
+    /// Returns the pages of the progress indicator, oldest first, or `None`
+    /// if there haven't been any.
+    pub(crate) fn progress_history(&self) -> Option<Vec<u8>> {
+        self.progress.as_ref()?.history()
+    }
+
+    /// Returns the error log as text, oldest first, or `None` if there have
+    /// been no errors.
+    pub(crate) fn error_log(&self) -> Option<String> {
+        if self.error_log.is_empty() {
+            return None;
+        }
+        let mut text = String::new();
+        for error in self.error_log.iter() {
+            text.push_str(error);
+            text.push('\n');
+        }
+        Some(text)
+    }
+
+    /// The error file shown below the file, if any.
+    pub(crate) fn error_file(&self) -> Option<&File> {
+        self.error_file.as_ref()
+    }
+
+    /// Record how long a render of the screen took, including sending it to
+    /// the terminal, and how many changes it sent.
+    pub(crate) fn record_render(&mut self, duration: Duration, changes: usize) {
+        let timings = &mut self.render_timings;
+        timings.count += 1;
+        timings.last = duration;
+        timings.last_changes = changes;
+        timings.total += duration;
+        timings.slowest = max(timings.slowest, duration);
+    }
+
+    /// Returns how long rendering this screen has taken.
+    pub(crate) fn render_timings(&self) -> RenderTimings {
+        self.render_timings
+    }
+
+    /// Returns statistics about the line caches as text.
+    pub(crate) fn cache_stats(&self) -> String {
+        let mut text = String::new();
+        for (name, cache) in [
+            ("Line cache", &self.line_cache),
+            ("Search line cache", &self.search_line_cache),
+        ] {
+            let stats = cache.stats();
+            let hit_rate = (stats.hits * 100)
+                .checked_div(stats.hits + stats.misses)
+                .unwrap_or(0);
+            text.push_str(&format!(
+                "{}:\n  lines:      {}\n  memory:     {} of {} bytes\n  hits:       {} ({}% hit rate)\n  misses:     {}\n  evictions:  {}\n  too large:  {}\n\n",
+                name,
+                cache.len(),
+                cache.used(),
+                cache.budget(),
+                stats.hits,
+                hit_rate,
+                stats.misses,
+                stats.evictions,
+                stats.too_large,
+            ));
+        }
+        text
+    }
+
+    /// Adds any new errors from loading the file to the error log, showing
+    /// the latest.
+    pub(crate) fn check_file_errors(&mut self) {
+        let errors = self.file.errors(self.file_errors);
+        self.file_errors += errors.len();
+        for error in errors {
+            self.set_error(error);
+        }
+    }
+
+    /// Checks the lines that have arrived since the last render against the
+    /// watch patterns, and alerts the user if one matched.
+    fn check_watch_patterns(&mut self) {
+        if let Some(watch_match) = self.watch.check(&self.file, self.following_end) {
+            match self.config.watch_alert {
+                WatchAlert::Bell => self.pending_bell = true,
+                WatchAlert::Flash => {
+                    self.ruler_flash = Some(Instant::now());
+                    self.ruler.set_flashing(true);
+                    self.refresh_ruler();
+                }
+                WatchAlert::Message => self.show_message(
+                    format!("Line {}: {}", watch_match.index + 1, watch_match.text),
+                    Severity::Warning,
+                ),
+            }
+        }
+    }
+
+    /// Renders the current message at the bottom of the screen.
+    fn render_message(&mut self, changes: &mut Vec<Change>, row: usize, width: usize) {
+        if let Some((message, severity)) = self.messages.front() {
+            // The message is shown for the timeout from when it first
+            // appears, not from when it was queued.
+            self.message_shown.get_or_insert_with(Instant::now);
+            let (foreground, background) = match severity {
+                Severity::Info => (AnsiColor::Black, AnsiColor::Teal),
+                Severity::Warning => (AnsiColor::Black, AnsiColor::Olive),
+                Severity::Error => (AnsiColor::Black, AnsiColor::Maroon),
+            };
+            let mut text = format!("  {}  ", message);
+            if self.messages.len() > 1 {
+                text.push_str(&format!("(+{} more)  ", self.messages.len() - 1));
+            }
+            changes.push(Change::CursorPosition {
+                x: Position::Absolute(0),
+                y: Position::Absolute(row),
+            });
+            changes.push(Change::AllAttributes(
+                CellAttributes::default()
+                    .set_foreground(foreground)
+                    .set_background(background)
+                    .clone(),
+            ));
+            changes.push(Change::Text(util::truncate_string(text, 0, width)));
             changes.push(Change::AllAttributes(CellAttributes::default()));
             changes.push(Change::ClearToEndOfLine(ColorAttribute::default()));
         }
     }
 
+    /// Queue a message to be shown at the bottom of the screen.
+    pub(crate) fn show_message(&mut self, message: impl Into<String>, severity: Severity) {
+        self.messages.push_back((message.into(), severity));
+        self.refresh_overlay();
+    }
+
+    /// Returns a copy of the ruler, which shares its indicators, for showing
+    /// beneath an overlay.
+    pub(crate) fn context_ruler(&self) -> Ruler {
+        self.ruler.clone()
+    }
+
+    /// Show `ruler` below this screen's ruler.  This is used for overlays,
+    /// with the ruler of the screen they are shown over.
+    pub(crate) fn set_context_ruler(&mut self, ruler: Ruler) {
+        self.context_ruler = Some(ruler);
+    }
+
+    /// Called when the file has changed on disk, so that the ruler shows its
+    /// new size and modification time.
+    pub(crate) fn file_changed(&mut self) {
+        self.ruler.refresh_disk_metadata();
+        self.refresh_ruler();
+    }
+
+    /// Shows or hides the mouse selection indicator in the ruler.
+    pub(crate) fn set_mouse_selection(&mut self, selecting: bool) {
+        self.ruler.set_mouse_selection(selecting);
+        self.refresh_ruler();
+    }
+
     /// Refreshes the ruler on the next render.
     pub(crate) fn refresh_ruler(&mut self) {
         if let Some(ruler_row) = self.rendered.ruler_row {
@@ -1021,6 +1789,14 @@ impl Screen {
         self.pending_refresh.add_range(start, end);
     }
 
+    /// Refreshes the parts of the screen that change when the file finishes
+    /// loading: the overlay, and the last line of the file, which may have
+    /// been incomplete.
+    pub(crate) fn refresh_loaded(&mut self) {
+        self.refresh_overlay();
+        self.refresh_file_line(self.rendered.file_lines.saturating_sub(1));
+    }
+
     /// Refresh a file line.
     pub(crate) fn refresh_file_line(&mut self, file_line_index: usize) {
         if let Some((start_row, end_row)) = self.rendered.file_line_rows(file_line_index) {
@@ -1056,23 +1832,67 @@ impl Screen {
 
     /// Scrolls to the given line number.
     pub(crate) fn scroll_to(&mut self, line: usize) {
-        self.pending_absolute_scroll = Some(line);
+        self.scroll_to_portion(line, 0);
+    }
+
+    /// Scrolls to the given portion of the given line number.
+    fn scroll_to_portion(&mut self, line: usize, portion: usize) {
+        self.pending_absolute_scroll = Some((line, portion));
         self.pending_relative_scroll = 0;
         self.following_end = false;
     }
 
+    /// Scrolls to the line `percent`% of the way through the file.
+    pub(crate) fn scroll_to_percent(&mut self, percent: usize) {
+        let last_line = self.file.lines().saturating_sub(1);
+        self.scroll_to(percent.min(100) * last_line / 100);
+    }
+
     /// Scroll the screen `step` characters up.
-    fn scroll_up(&mut self, step: usize) {
+    pub(crate) fn scroll_up(&mut self, step: usize) {
         self.pending_relative_scroll -= step as isize;
         self.following_end = false;
     }
 
     /// Scroll the screen `step` characters down.
-    fn scroll_down(&mut self, step: usize) {
+    pub(crate) fn scroll_down(&mut self, step: usize) {
         self.pending_relative_scroll += step as isize;
         self.following_end = false;
     }
 
+    /// Move the cursor from line `cursor` to line `line_index`, scrolling so
+    /// that the cursor stays on the screen.
+    fn move_cursor(&mut self, cursor: usize, line_index: usize) {
+        let line_index = min(line_index, self.file.lines().saturating_sub(1));
+        if line_index == cursor {
+            return;
+        }
+        self.file.move_cursor(line_index);
+        self.refresh_file_line(cursor);
+        self.refresh_file_line(line_index);
+        let (top_line, bottom_line) = (self.rendered.top_line, self.rendered.bottom_line);
+        if line_index < top_line {
+            let rows = self.rows(line_index..top_line) + self.rendered.top_line_portion;
+            self.scroll_up(rows);
+        } else if line_index >= bottom_line {
+            let rows = self.rows(bottom_line..line_index + 1);
+            self.scroll_down(rows);
+        }
+    }
+
+    /// Returns the number of rows the file lines in `lines` take up.
+    fn rows(&mut self, lines: Range<usize>) -> usize {
+        let wrap_width = self.wrap_width(self.rendered.file_width);
+        let wrapping_mode = self.wrapping_mode;
+        lines
+            .map(|index| {
+                self.line_cache
+                    .get_or_create(&self.file, index, None)
+                    .map_or(1, |line| line.height(wrap_width, wrapping_mode))
+            })
+            .sum()
+    }
+
     /// Scroll the screen `step` characters to the left.
     fn scroll_left(&mut self, step: usize) {
         if self.wrapping_mode == WrappingMode::Unwrapped && self.left > 0 && step > 0 {
@@ -1084,9 +1904,45 @@ impl Screen {
     /// Scroll the screen `step` characters to the right.
     fn scroll_right(&mut self, step: usize) {
         if self.wrapping_mode == WrappingMode::Unwrapped && step != 0 {
-            self.left = self.left.saturating_add(step);
-            self.refresh();
+            let mut left = self.left.saturating_add(step);
+            if self.config.snap_to_columns {
+                if let Some(column) = self.nearest_column_start(left) {
+                    left = column;
+                }
+            }
+            // Stop once the end of the longest line is visible.
+            let max_left = self
+                .rendered
+                .max_line_width
+                .saturating_sub(self.rendered.file_width);
+            left = min(left, max(max_left, self.left));
+            if left != self.left {
+                self.left = left;
+                self.refresh();
+            }
+        }
+    }
+
+    /// Returns the scroll position to the right of the current one that puts
+    /// the start of a field in the visible lines at the left of the screen,
+    /// and that is nearest to `left`.
+    fn nearest_column_start(&mut self, left: usize) -> Option<usize> {
+        let current_left = self.left;
+        let mut nearest: Option<usize> = None;
+        for line_index in self.rendered.top_line..self.rendered.bottom_line {
+            if let Some(line) = self.line_cache.get_or_create(&self.file, line_index, None) {
+                // The first column is taken by the left arrow.
+                for candidate in line.column_starts().into_iter().map(|start| start - 1) {
+                    let distance = |c: usize| max(c, left) - min(c, left);
+                    if candidate > current_left
+                        && !matches!(nearest, Some(n) if distance(n) <= distance(candidate))
+                    {
+                        nearest = Some(candidate);
+                    }
+                }
+            }
         }
+        nearest
     }
 
     /// Scroll up (screen / n) * repeat lines.
@@ -1105,6 +1961,37 @@ impl Screen {
         }
     }
 
+    /// The number of lines to scroll for half a screen.  A repeat count
+    /// changes this for subsequent half screen scrolls.  On a screen with a
+    /// single line, this is still one line.
+    fn half_screen_lines(&mut self) -> usize {
+        if let Some(n) = self.repeat_count {
+            self.half_screen_size = Some(n);
+        }
+        self.half_screen_size.unwrap_or_else(|| {
+            (self
+                .rendered
+                .height
+                .saturating_sub(self.rendered.overlay_height)
+                / 2)
+            .max(1)
+        })
+    }
+
+    /// The number of lines to scroll for one window.  A repeat count changes
+    /// this for subsequent window scrolls.
+    fn window_lines(&mut self) -> usize {
+        if let Some(n) = self.repeat_count {
+            self.window_size = Some(n);
+        }
+        self.window_size.unwrap_or_else(|| {
+            self.rendered
+                .height
+                .saturating_sub(self.rendered.overlay_height)
+                .max(1)
+        })
+    }
+
     /// Scroll left (screen / n) * repeat columns.
     fn scroll_left_screen_fraction(&mut self, n: usize, repeat: usize) {
         if n != 0 {
@@ -1130,16 +2017,42 @@ impl Screen {
         use Action::*;
         match action {
             Quit => return DisplayAction::Quit,
+            QuitAndPrintScreen => return DisplayAction::QuitAndPrintScreen,
+            QuitAll => return DisplayAction::Quit,
+            QuitCurrentFile => return DisplayAction::CloseFile,
+            QuitAndKillChild => return DisplayAction::QuitAndKillChild,
+            // How the command exits is shown once it has finished.
+            Interrupt if self.file.interrupt_command() => {}
+            Interrupt => return DisplayAction::Quit,
             Refresh => return DisplayAction::Refresh,
             Help => return DisplayAction::ShowHelp,
+            ShowErrorLog => return DisplayAction::ShowErrorLog,
+            ShowCacheStats => return DisplayAction::ShowCacheStats,
+            ShowProgressHistory => return DisplayAction::ShowProgressHistory,
+            ShowDiagnostics => return DisplayAction::ShowDiagnostics,
+            ReloadConfig => return DisplayAction::ReloadConfig,
+            ToggleMouseSelection => return DisplayAction::ToggleMouseSelection,
+            ToggleJsonView => return DisplayAction::ToggleJsonView,
+            SnapshotFile => return DisplayAction::SnapshotFile,
+            DiffSnapshot => return DisplayAction::DiffSnapshot,
             Cancel => {
                 if self.repeat_count.is_some() {
                     self.clear_repeat_count();
                 } else {
+                    // If there is a search or error to dismiss, only dismiss
+                    // that, so that searching within an overlay (e.g. the help
+                    // screen) doesn't close the overlay.
+                    let dismissed =
+                        self.search.is_some() || self.error.is_some() || !self.messages.is_empty();
                     self.error_file = None;
                     self.set_search(None);
                     self.error = None;
+                    self.messages.clear();
+                    self.message_shown = None;
                     self.refresh();
+                    if dismissed {
+                        return DisplayAction::Refresh;
+                    }
                     return DisplayAction::ClearOverlay;
                 }
             }
@@ -1148,14 +2061,46 @@ impl Screen {
             ToggleRuler => {
                 self.show_ruler = !self.show_ruler;
             }
+            ToggleErrorFile => {
+                if self.error_file.is_some() {
+                    self.error_file_collapsed = !self.error_file_collapsed;
+                } else {
+                    self.show_message("No error stream for this file", Severity::Info);
+                }
+            }
+            OpenErrorFile => return DisplayAction::OpenErrorFile,
             ScrollUpLines(n) => {
                 let n = self.apply_repeat_count(n);
-                self.scroll_up(n)
+                match self.file.cursor() {
+                    Some(cursor) => self.move_cursor(cursor, cursor.saturating_sub(n)),
+                    None => self.scroll_up(n),
+                }
             }
             ScrollDownLines(n) => {
                 let n = self.apply_repeat_count(n);
-                self.scroll_down(n)
+                match self.file.cursor() {
+                    Some(cursor) => self.move_cursor(cursor, cursor.saturating_add(n)),
+                    None => self.scroll_down(n),
+                }
+            }
+            OpenListedFile if self.file.select_cursor() => {
+                self.clear_repeat_count();
             }
+            OpenListedFile => match self.file.listed_path(self.current_line()) {
+                Some(path) => {
+                    self.clear_repeat_count();
+                    return DisplayAction::OpenFile(path);
+                }
+                None if self.file.toggle_section(self.current_line()) => {
+                    // The file is reloaded once the section has changed.
+                    self.clear_repeat_count();
+                    return DisplayAction::None;
+                }
+                None => {
+                    let n = self.apply_repeat_count(1);
+                    self.scroll_down(n)
+                }
+            },
             ScrollUpScreenFraction(n) => {
                 let repeat = self.apply_repeat_count(1);
                 self.scroll_up_screen_fraction(n, repeat)
@@ -1164,6 +2109,22 @@ impl Screen {
                 let repeat = self.apply_repeat_count(1);
                 self.scroll_down_screen_fraction(n, repeat)
             }
+            ScrollUpHalfScreen => {
+                let lines = self.half_screen_lines();
+                self.scroll_up(lines)
+            }
+            ScrollDownHalfScreen => {
+                let lines = self.half_screen_lines();
+                self.scroll_down(lines)
+            }
+            ScrollUpWindow => {
+                let lines = self.window_lines();
+                self.scroll_up(lines)
+            }
+            ScrollDownWindow => {
+                let lines = self.window_lines();
+                self.scroll_down(lines)
+            }
             ScrollToTop | ScrollToBottom if self.repeat_count.is_some() => {
                 if let Some(n) = self.repeat_count {
                     // Convert 1-based to 0-based line number.
@@ -1172,6 +2133,10 @@ impl Screen {
             }
             ScrollToTop => self.scroll_to(0),
             ScrollToBottom => self.following_end = true,
+            ScrollToPercent(n) => {
+                let percent = self.repeat_count.unwrap_or(n);
+                self.scroll_to_percent(percent);
+            }
             ScrollLeftColumns(n) => {
                 let n = self.apply_repeat_count(n);
                 self.scroll_left(n)
@@ -1196,7 +2161,47 @@ impl Screen {
                 self.wrapping_mode = self.wrapping_mode.next_mode();
                 return DisplayAction::Refresh;
             }
+            ToggleMarkdown => {
+                let mut decorations = LineDecorations::clone(self.line_cache.decorations());
+                decorations.set_markdown(!decorations.markdown());
+                let decorations = Arc::new(decorations);
+                self.line_cache.set_decorations(decorations.clone());
+                self.search_line_cache.set_decorations(decorations);
+                return DisplayAction::Refresh;
+            }
+            ToggleLineEndings => {
+                let mut decorations = LineDecorations::clone(self.line_cache.decorations());
+                decorations.set_show_line_endings(!decorations.show_line_endings());
+                let decorations = Arc::new(decorations);
+                self.line_cache.set_decorations(decorations.clone());
+                self.search_line_cache.set_decorations(decorations);
+                return DisplayAction::Refresh;
+            }
+            ToggleWhitespace => {
+                let mut decorations = LineDecorations::clone(self.line_cache.decorations());
+                decorations.set_show_whitespace(!decorations.show_whitespace());
+                let decorations = Arc::new(decorations);
+                self.line_cache.set_decorations(decorations.clone());
+                self.search_line_cache.set_decorations(decorations);
+                return DisplayAction::Refresh;
+            }
+            PauseLoading => {
+                self.file.set_loading_suspended(true);
+                self.refresh_ruler();
+            }
+            ResumeLoading => {
+                self.file.set_loading_suspended(false);
+                self.refresh_ruler();
+            }
+            ShowCommandPalette => {
+                self.prompt = Some(command::palette(&self.keymap, event_sender.clone()))
+            }
             PromptGoToLine => self.prompt = Some(command::goto()),
+            PromptShellCommand => self.prompt = Some(command::shell_command()),
+            RunCommand(command) => return DisplayAction::RunShellCommand(command),
+            RerunCommand(mode) => return DisplayAction::RerunCommand(mode),
+            ShowMessage(ref message, severity) => self.show_message(message.as_str(), severity),
+            Prompt(ref request) => self.prompt = Some(command::request(request)),
             PromptSearchFromStart => {
                 self.prompt = Some(command::search(SearchKind::First, event_sender.clone()))
             }
@@ -1226,6 +2231,45 @@ impl Screen {
             }
             FirstMatch => self.create_or_move_match(MatchMotion::First, event_sender.clone()),
             LastMatch => self.create_or_move_match(MatchMotion::Last, event_sender.clone()),
+            RepeatLastSearch => self.repeat_last_search(event_sender.clone()),
+            ToggleSearchVisibleColumns => {
+                self.search_visible_columns = !self.search_visible_columns;
+                self.show_message(
+                    if self.search_visible_columns {
+                        "Searching only the visible columns of unwrapped lines"
+                    } else {
+                        "Searching whole lines"
+                    },
+                    Severity::Info,
+                );
+                if let Some(pattern) = self
+                    .search
+                    .as_ref()
+                    .map(|search| search.pattern().to_string())
+                {
+                    self.refresh_matched_lines();
+                    let kind = SearchKind::FirstAfter(self.rendered.top_line);
+                    let columns = self.search_columns();
+                    self.set_search(
+                        crate::search::Search::new(
+                            &self.file,
+                            &pattern,
+                            kind,
+                            columns,
+                            &self.config,
+                            &self.keymap,
+                            event_sender.clone(),
+                        )
+                        .ok(),
+                    );
+                }
+                return DisplayAction::Render;
+            }
+            Search(ref pattern) => {
+                self.refresh_matched_lines();
+                let kind = SearchKind::FirstAfter(self.rendered.top_line);
+                self.search_for(pattern, kind, event_sender.clone());
+            }
             AppendDigitToRepeatCount(n) => self.append_digit_to_repeat_count(n),
         }
         if !matches!(action, AppendDigitToRepeatCount(_)) {
@@ -1242,6 +2286,18 @@ impl Screen {
     ) -> DisplayAction {
         if let Some(binding) = self.keymap.get(key.modifiers, key.key) {
             match binding {
+                Binding::Action(Action::Quit)
+                    if self.config.confirm_quit_while_loading && self.loading() =>
+                {
+                    self.prompt = Some(command::confirm_quit(self.file.running_command()));
+                }
+                Binding::Action(Action::Interrupt)
+                    if self.config.confirm_quit_while_loading
+                        && self.loading()
+                        && !self.file.running_command() =>
+                {
+                    self.prompt = Some(command::confirm_quit(false));
+                }
                 Binding::Action(action) => {
                     let action = action.clone();
                     return self.dispatch_action(action, event_sender);
@@ -1278,6 +2334,82 @@ impl Screen {
         self.repeat_count.unwrap_or(1).saturating_mul(n)
     }
 
+    /// Returns the state of the screen to save in a session, if its file was
+    /// opened by name.
+    pub(crate) fn session_file(&self) -> Option<SessionFile> {
+        let path = self.file.path()?;
+        Some(SessionFile {
+            path: std::fs::canonicalize(&path).unwrap_or(path),
+            top_line: self.top_line,
+            left: self.left,
+            search: self
+                .search
+                .as_ref()
+                .map(|search| search.pattern().to_string()),
+        })
+    }
+
+    /// Restores the state of the screen from a session.
+    pub(crate) fn restore(&mut self, file: &SessionFile, event_sender: EventSender) {
+        self.top_line = file.top_line;
+        self.top_line_portion = 0;
+        self.left = file.left;
+        self.following_end = false;
+        if let Some(pattern) = file.search.as_ref() {
+            let kind = SearchKind::FirstAfter(file.top_line);
+            self.search_for(pattern, kind, event_sender);
+            self.restoring_search = self.search.is_some();
+        }
+        self.refresh();
+    }
+
+    /// Carries over where `previous` was scrolled to and what it was
+    /// searching for, for a screen that replaces it, e.g. when its command
+    /// is run again.
+    pub(crate) fn continue_from(&mut self, previous: &Screen, event_sender: EventSender) {
+        self.top_line = previous.top_line;
+        self.top_line_portion = 0;
+        self.left = previous.left;
+        self.following_end = previous.following_end;
+        if let Some(search) = previous.search.as_ref() {
+            let kind = SearchKind::FirstAfter(previous.top_line);
+            self.search_for(search.pattern(), kind, event_sender);
+            self.restoring_search = self.search.is_some();
+        }
+        self.refresh();
+    }
+
+    /// Starts a search for a pattern, showing an error if it isn't valid.
+    pub(crate) fn search_for(
+        &mut self,
+        pattern: &str,
+        kind: SearchKind,
+        event_sender: EventSender,
+    ) {
+        self.restoring_search = false;
+        let columns = self.search_columns();
+        match Search::new(
+            &self.file,
+            pattern,
+            kind,
+            columns,
+            &self.config,
+            &self.keymap,
+            event_sender,
+        ) {
+            Ok(search) => self.set_search(Some(search)),
+            Err(e) => {
+                self.set_search(None);
+                // Show the regex error itself, which explains what is wrong
+                // with the pattern.
+                self.set_error(match e {
+                    Error::Regex(e) => e.to_string(),
+                    e => e.to_string(),
+                });
+            }
+        }
+    }
+
     /// Set the search for this file.
     pub(crate) fn set_search(&mut self, search: Option<Search>) {
         self.search = search;
@@ -1289,16 +2421,101 @@ impl Screen {
         self.error_file = error_file;
     }
 
+    /// Mark this screen as showing a snapshot of `file`.
+    pub(crate) fn set_snapshot_of(&mut self, file: File) {
+        self.snapshot_of = Some(file);
+    }
+
+    /// If this screen shows a snapshot, returns the file it is a snapshot
+    /// of.
+    pub(crate) fn snapshot_of(&self) -> Option<&File> {
+        self.snapshot_of.as_ref()
+    }
+
+    /// Returns the lines of the file that have been loaded so far, as they
+    /// are shown, for taking a snapshot.
+    pub(crate) fn snapshot(&self) -> Vec<u8> {
+        let mut data = Vec::new();
+        for index in 0..self.file.lines() {
+            self.file
+                .with_line(index, |line| data.extend_from_slice(&line));
+        }
+        data
+    }
+
     /// Set the progress indicator for this file.
     pub(crate) fn set_progress(&mut self, progress: Option<Progress>) {
         self.progress = progress;
     }
 
+    /// Returns true if the file or its error file is still loading.
+    pub(crate) fn loading(&self) -> bool {
+        !self.file.loaded()
+            || self
+                .error_file
+                .as_ref()
+                .map(|error_file| !error_file.loaded())
+                .unwrap_or(false)
+    }
+
+    /// Returns true while the file or its error file is being read, the
+    /// error file has lines that haven't been shown, the file is being
+    /// searched, or a message or ruler flash is due to be hidden, so what the
+    /// screen shows is about to change.
+    ///
+    /// Files that are paused or waiting for more input aren't being read.
+    pub(crate) fn busy(&self) -> bool {
+        let reading = |file: &File| {
+            !file.loaded()
+                && !file.paused()
+                && !file.loading_suspended()
+                && !file.waiting_for_input()
+        };
+        reading(&self.file)
+            || self
+                .error_file
+                .as_ref()
+                .map(|error_file| {
+                    reading(error_file) || error_file.lines() != self.rendered.error_file_lines
+                })
+                .unwrap_or(false)
+            || self
+                .search
+                .as_ref()
+                .map(|search| {
+                    // A search of a file that is still loading waits for
+                    // more lines once it has caught up.
+                    !search.finished()
+                        && (self.file.loaded()
+                            || search.searched_lines() < self.file.lines().saturating_sub(1))
+                })
+                .unwrap_or(false)
+            || self
+                .message_shown
+                .map(|shown| shown.elapsed() >= self.config.message_timeout())
+                .unwrap_or(false)
+            || self
+                .ruler_flash
+                .map(|flash| flash.elapsed() >= RULER_FLASH_DURATION)
+                .unwrap_or(false)
+    }
+
     /// Returns true if this screen is currently animating for any reason.
+    ///
+    /// Once nothing is changing, this is false, and the event loop waits for
+    /// the next event without waking up.
     pub(crate) fn animate(&self) -> bool {
-        self.error_file.is_some()
-            || (!self.file.loaded() && !self.file.paused())
-            || self.following_end
+        self.error_file
+            .as_ref()
+            .map(|error_file| {
+                !error_file.loaded() || error_file.lines() != self.rendered.error_file_lines
+            })
+            .unwrap_or(false)
+            || !self.messages.is_empty()
+            || self.ruler_flash.is_some()
+            || (self.config.animation() != Animation::Off
+                && !self.file.loaded()
+                && (self.following_end || (!self.file.paused() && !self.file.loading_suspended())))
             || self
                 .search
                 .as_ref()
@@ -1308,7 +2525,9 @@ impl Screen {
 
     /// Dispatch an animation timeout, updating for the next animation frame.
     pub(crate) fn dispatch_animation(&mut self) -> DisplayAction {
-        if !self.file.loaded() {
+        // If the loading indicator doesn't animate, the ruler is only
+        // refreshed when something else changes.
+        if !self.file.loaded() && self.config.animation() != Animation::Off {
             self.refresh_ruler();
         }
         if self
@@ -1324,6 +2543,20 @@ impl Screen {
                 self.refresh_overlay();
             }
         }
+        if let Some(flash) = self.ruler_flash {
+            if flash.elapsed() >= RULER_FLASH_DURATION {
+                self.ruler_flash = None;
+                self.ruler.set_flashing(false);
+                self.refresh_ruler();
+            }
+        }
+        if let Some(shown) = self.message_shown {
+            if shown.elapsed() >= self.config.message_timeout() {
+                self.refresh_overlay();
+                self.messages.pop_front();
+                self.message_shown = None;
+            }
+        }
         match &self.pending_refresh {
             Refresh::None => DisplayAction::None,
             _ => DisplayAction::Render,
@@ -1343,12 +2576,17 @@ impl Screen {
 
     /// Called when a search finds its first match in order to scroll to that match.
     pub(crate) fn search_first_match(&mut self) -> DisplayAction {
+        if std::mem::take(&mut self.restoring_search) {
+            self.refresh_matched_lines();
+            self.refresh_overlay();
+            return DisplayAction::Render;
+        }
         let current_match = self
             .search
             .as_ref()
             .and_then(|ref search| search.current_match());
-        if let Some((line_index, _match_index)) = current_match {
-            self.scroll_to(line_index);
+        if let Some((line_index, match_index)) = current_match {
+            self.scroll_to_match(line_index, match_index);
             self.refresh_matched_lines();
             self.refresh_overlay();
             return DisplayAction::Render;
@@ -1359,6 +2597,7 @@ impl Screen {
     /// Called when a search completes.
     #[allow(clippy::unnecessary_wraps)]
     pub(crate) fn search_finished(&mut self) -> DisplayAction {
+        self.restoring_search = false;
         self.refresh_matched_lines();
         self.refresh_overlay();
         DisplayAction::Render
@@ -1370,14 +2609,66 @@ impl Screen {
         if let Some(ref mut search) = self.search {
             let scope = self.rendered.top_line..=self.rendered.bottom_line;
             search.move_match(motion, scope);
-            if let Some((line_index, _match_index)) = search.current_match() {
-                self.scroll_to(line_index);
+            let current_match = search.current_match();
+            if let Some((line_index, match_index)) = current_match {
+                self.scroll_to_match(line_index, match_index);
             }
             self.refresh_matched_line();
             self.refresh_search_status();
         }
     }
 
+    /// Scroll to a search match so that the matched text is visible.  If
+    /// lines are wrapped, this is the portion of the line the match starts
+    /// on.  If they are not, scroll horizontally to the match.
+    fn scroll_to_match(&mut self, line_index: usize, match_index: usize) {
+        let search = match self.search {
+            Some(ref search) => search,
+            None => {
+                self.scroll_to(line_index);
+                return;
+            }
+        };
+        let wrapping_mode = self.wrapping_mode;
+        let wrap_width = self.wrap_width(self.rendered.file_width);
+        let line = self
+            .search_line_cache
+            .get_or_create(&self.file, line_index, Some(search));
+        let columns = line
+            .as_ref()
+            .and_then(|line| line.match_columns(match_index));
+        if wrapping_mode != WrappingMode::Unwrapped {
+            let portion = match (line, columns) {
+                (Some(line), Some((start, _))) => {
+                    line.row_at_column(start, wrap_width, wrapping_mode)
+                }
+                _ => 0,
+            };
+            self.scroll_to_portion(line_index, portion);
+            return;
+        }
+        self.scroll_to(line_index);
+        if let Some((start, end)) = columns {
+            let width = self.rendered.file_width;
+            // When scrolled, the first column is taken by the left arrow, and
+            // the last column by the right arrow if the line continues.
+            let left = if start < self.left + 1 && self.left > 0 {
+                start.saturating_sub(MATCH_MARGIN)
+            } else if end + 1 > self.left + width {
+                min(
+                    start.saturating_sub(MATCH_MARGIN),
+                    (end + MATCH_MARGIN).saturating_sub(width),
+                )
+            } else {
+                self.left
+            };
+            if left != self.left {
+                self.left = left;
+                self.refresh();
+            }
+        }
+    }
+
     /// Like `move_match`, but create a new search from history based on the
     /// last pattern on demand.
     pub(crate) fn create_or_move_match(&mut self, motion: MatchMotion, event_sender: EventSender) {
@@ -1385,7 +2676,7 @@ impl Screen {
             self.move_match(motion)
         } else {
             // Attempt to load search from history.
-            if let Some(pattern) = prompt_history::peek_last("search") {
+            if let Some(pattern) = self.last_values.peek("search") {
                 if !pattern.is_empty() {
                     let kind = match motion {
                         MatchMotion::First => SearchKind::First,
@@ -1399,7 +2690,16 @@ impl Screen {
                             SearchKind::FirstBefore(self.rendered.bottom_line)
                         }
                     };
-                    if let Ok(search) = Search::new(&self.file, &pattern, kind, event_sender) {
+                    let columns = self.search_columns();
+                    if let Ok(search) = Search::new(
+                        &self.file,
+                        &pattern,
+                        kind,
+                        columns,
+                        &self.config,
+                        &self.keymap,
+                        event_sender,
+                    ) {
                         self.search = Some(search);
                         self.move_match(motion)
                     }
@@ -1408,15 +2708,200 @@ impl Screen {
         }
     }
 
+    /// Returns the columns that new searches are restricted to, if searching
+    /// only the visible columns and lines are not wrapped.
+    pub(crate) fn search_columns(&self) -> Option<SearchColumns> {
+        if self.search_visible_columns && self.wrapping_mode == WrappingMode::Unwrapped {
+            Some(SearchColumns {
+                range: self.left..self.left + self.rendered.file_width,
+                tab_width: self.line_cache.decorations().tab_width(),
+            })
+        } else {
+            None
+        }
+    }
+
+    /// Search for the last pattern that was searched for, replacing any
+    /// current search.  The pattern comes from the search history, so it may
+    /// have been searched for in another file or a previous session.
+    pub(crate) fn repeat_last_search(&mut self, event_sender: EventSender) {
+        if let Some(pattern) = self.last_values.peek("search") {
+            if !pattern.is_empty() {
+                self.refresh_matched_lines();
+                let kind = SearchKind::FirstAfter(self.rendered.top_line);
+                let columns = self.search_columns();
+                self.set_search(
+                    Search::new(
+                        &self.file,
+                        &pattern,
+                        kind,
+                        columns,
+                        &self.config,
+                        &self.keymap,
+                        event_sender,
+                    )
+                    .ok(),
+                );
+            }
+        }
+    }
+
+    /// Returns the contents of the file with its JSON pretty-printed.
+    pub(crate) fn pretty_printed_json(&self) -> Result<Vec<u8>, String> {
+        if !self.file.loaded() {
+            return Err(String::from("The file is still loading"));
+        }
+        let mut data = Vec::new();
+        for index in 0..self.file.lines() {
+            self.file
+                .with_line(index, |line| data.extend_from_slice(&line));
+        }
+        json::pretty_print(&data, self.config.color_json_keys)
+            .ok_or_else(|| String::from("The file does not contain JSON"))
+    }
+
     pub(crate) fn flush_line_caches(&mut self) {
         self.line_cache.clear();
         self.search_line_cache.clear();
+        self.code_blocks.reset();
     }
 
     /// Load more lines from a stream.
     pub(crate) fn maybe_load_more(&mut self) {
-        // Fetch 1 screen + config.read_ahead_lines.
-        let needed_lines = self.rendered.bottom_line + self.height + self.config.read_ahead_lines;
+        // Fetch 1 screen + the configured read-ahead.
+        let needed_lines =
+            (self.rendered.bottom_line + self.height).saturating_add(self.config.read_ahead());
         self.file.set_needed_lines(needed_lines);
+        self.file
+            .set_backpressure(self.backpressure.applies(self.following_end));
+    }
+}
+
+/// Returns when loading a file stops once enough has been read ahead, from
+/// the file's profile if it sets it.
+fn load_backpressure(file: &File, config: &Config) -> Result<Backpressure, Error> {
+    Ok(config
+        .profile(&file.title())?
+        .and_then(|profile| profile.backpressure)
+        .unwrap_or(config.backpressure))
+}
+
+/// Returns the escape sequence that starts error file lines, or nothing if
+/// the error file style is invalid, in which case the problem is added to
+/// `errors`.
+fn load_error_file_style(config: &Config, errors: &mut Vec<String>) -> Vec<u8> {
+    line::text_style_prefix(&config.error_file_style).unwrap_or_else(|e| {
+        errors.push(format!("Invalid error_file_style: {}", e));
+        Vec::new()
+    })
+}
+
+/// Loads the keymap for a file, including any bindings specific to that file.
+fn load_keymap(file: &File, config: &Config) -> Result<Arc<Keymap>, Error> {
+    let mut keymap = config.keymap.load()?;
+    if file.opens_lines() {
+        let mut listing_keymap = Keymap::new();
+        listing_keymap.bind(
+            Modifiers::NONE,
+            KeyCode::Enter,
+            Binding::Action(Action::OpenListedFile),
+        );
+        keymap = Arc::new(keymap.merge(&listing_keymap));
+    }
+    match config.file_keymaps.get(&file.index()) {
+        Some(file_keymap) => Ok(Arc::new(keymap.merge(file_keymap))),
+        None => Ok(keymap),
+    }
+}
+
+/// Creates the decorations for the lines of a file.
+fn load_decorations(
+    file: &File,
+    config: &Config,
+    errors: &mut Vec<String>,
+) -> Result<Arc<LineDecorations>, Error> {
+    let title = file.title();
+    let profile = config.profile(&title)?;
+    let mut decorations = LineDecorations::new(config, profile, errors);
+    decorations.set_markdown(config.render_markdown_for(&title));
+    Ok(Arc::new(decorations))
+}
+
+/// Adds the changes that redraw the parts of `frame` that differ from
+/// `previous`, where both are drawn from `first_row` of the screen.
+///
+/// Each changed row is drawn from its first changed cell.  Surface lines
+/// don't keep trailing blank cells, so the rest of the row is cleared if the
+/// row is short.
+fn diff_frames(previous: &Surface, frame: &Surface, first_row: usize, changes: &mut Vec<Change>) {
+    let (width, _) = frame.dimensions();
+    let previous_lines = previous.screen_lines();
+    for (row, line) in frame.screen_lines().iter().enumerate() {
+        let cells: Vec<_> = line.visible_cells().collect();
+        let same = match previous_lines.get(row) {
+            Some(previous_line) => {
+                let previous_cells: Vec<_> = previous_line.visible_cells().collect();
+                let same = cells
+                    .iter()
+                    .zip(previous_cells.iter())
+                    .take_while(|(a, b)| a.cell_index() == b.cell_index() && a.same_contents(b))
+                    .count();
+                if same == cells.len() && same == previous_cells.len() {
+                    continue;
+                }
+                same
+            }
+            None => 0,
+        };
+        let x = match (cells.get(same), same.checked_sub(1)) {
+            (Some(cell), _) => cell.cell_index(),
+            (None, Some(last)) => cells[last].cell_index() + cells[last].width(),
+            (None, None) => 0,
+        };
+        changes.push(Change::CursorPosition {
+            x: Position::Absolute(x),
+            y: Position::Absolute(first_row + row),
+        });
+        let mut attrs = CellAttributes::default();
+        changes.push(Change::AllAttributes(attrs.clone()));
+        let mut end = x;
+        for cell in cells[same..].iter() {
+            if *cell.attrs() != attrs {
+                attrs = cell.attrs().clone();
+                changes.push(Change::AllAttributes(attrs.clone()));
+            }
+            match changes.last_mut() {
+                Some(Change::Text(text)) => text.push_str(cell.str()),
+                _ => changes.push(Change::Text(cell.str().to_string())),
+            }
+            end = cell.cell_index() + cell.width();
+        }
+        if end < width {
+            changes.push(Change::AllAttributes(CellAttributes::default()));
+            changes.push(Change::ClearToEndOfLine(ColorAttribute::Default));
+        }
+    }
+}
+
+/// Removes the colors from rendered changes, for when color is not used.
+/// The lines of the file are already rendered without color, so this only
+/// affects the interface, where anything drawn on a colored background is
+/// drawn in inverse video instead.
+fn remove_colors(changes: &mut [Change]) {
+    for change in changes.iter_mut() {
+        match change {
+            Change::AllAttributes(attrs) => {
+                if attrs.background() != ColorAttribute::Default {
+                    attrs.set_reverse(true);
+                }
+                attrs
+                    .set_foreground(ColorAttribute::Default)
+                    .set_background(ColorAttribute::Default);
+            }
+            Change::ClearToEndOfLine(color) | Change::ClearToEndOfScreen(color) => {
+                *color = ColorAttribute::Default;
+            }
+            _ => {}
+        }
     }
 }
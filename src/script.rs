@@ -0,0 +1,147 @@
+//! Startup commands.
+//!
+//! Commands are the names of bindings in kebab case, optionally followed by
+//! a parameter, e.g. `search ERROR` or `scroll-down-lines 5`.  Commands are
+//! separated by semicolons or newlines.  A semicolon can be included in a
+//! parameter by escaping it with a backslash.  In scripts, lines starting
+//! with `#` are comments.
+
+use std::path::PathBuf;
+
+use crate::action::Action;
+use crate::bindings::Binding;
+
+/// Commands that are more natural to write than the binding names.
+const ALIASES: &[(&str, &str)] = &[
+    ("scroll-to-start", "ScrollToTop"),
+    ("scroll-to-end", "ScrollToBottom"),
+    ("go-to-percent", "ScrollToPercent"),
+];
+
+/// Converts a command name to the name of the binding it runs.
+fn binding_name(command: &str) -> String {
+    for (alias, name) in ALIASES {
+        if command == *alias {
+            return String::from(*name);
+        }
+    }
+    let mut name = String::with_capacity(command.len());
+    for word in command.split('-') {
+        let mut chars = word.chars();
+        if let Some(first) = chars.next() {
+            name.extend(first.to_uppercase());
+            name.extend(chars);
+        }
+    }
+    name
+}
+
+/// Parses a single command into the action it performs.
+fn parse_command(command: &str) -> Result<Action, String> {
+    let (name, param) = match command.find(char::is_whitespace) {
+        Some(index) => (&command[..index], command[index..].trim()),
+        None => (command, ""),
+    };
+    let params = if param.is_empty() {
+        Vec::new()
+    } else {
+        vec![param.to_string()]
+    };
+    match Binding::parse(binding_name(name), params) {
+        Ok(Binding::Action(action)) => Ok(action),
+        Ok(_) => Err(format!("unknown command: {}", name)),
+        Err(e) => Err(format!("{}: {}", name, e)),
+    }
+}
+
+/// Splits a line into commands at unescaped semicolons.
+fn split_commands(line: &str) -> Vec<String> {
+    let mut commands = Vec::new();
+    let mut command = String::new();
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match chars.next() {
+                Some(';') => command.push(';'),
+                Some(c) => {
+                    command.push('\\');
+                    command.push(c);
+                }
+                None => command.push('\\'),
+            },
+            ';' => commands.push(std::mem::take(&mut command)),
+            c => command.push(c),
+        }
+    }
+    commands.push(command);
+    commands
+}
+
+/// Parses a script into the actions it performs.
+///
+/// If a command can't be parsed, returns the line it is on (starting from
+/// 1) and a description of the problem.
+pub(crate) fn parse(script: &str) -> Result<Vec<Action>, (usize, String)> {
+    let mut actions = Vec::new();
+    for (index, line) in script.lines().enumerate() {
+        if line.trim_start().starts_with('#') {
+            continue;
+        }
+        for command in split_commands(line) {
+            let command = command.trim();
+            if !command.is_empty() {
+                actions.push(parse_command(command).map_err(|e| (index + 1, e))?);
+            }
+        }
+    }
+    Ok(actions)
+}
+
+/// Reads the user's init script, if they have one.
+pub(crate) fn read_init_script() -> Option<(PathBuf, String)> {
+    let mut path = dirs::config_dir()?;
+    path.push("streampager");
+    path.push("init");
+    let script = std::fs::read_to_string(&path).ok()?;
+    Some((path, script))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(
+            parse("search ERROR; scroll-to-end"),
+            Ok(vec![
+                Action::Search(String::from("ERROR")),
+                Action::ScrollToBottom,
+            ])
+        );
+        assert_eq!(
+            parse("# Start near the middle\n  go-to-percent 50\nscroll-down-lines 3;\n"),
+            Ok(vec![
+                Action::ScrollToPercent(50),
+                Action::ScrollDownLines(3)
+            ])
+        );
+        assert_eq!(
+            parse("search a\\;b  ;toggle-line-numbers"),
+            Ok(vec![
+                Action::Search(String::from("a;b")),
+                Action::ToggleLineNumbers,
+            ])
+        );
+        assert_eq!(
+            parse("search \\d+"),
+            Ok(vec![Action::Search(String::from("\\d+"))])
+        );
+        assert_eq!(
+            parse("quit\nfrobnicate"),
+            Err((2, String::from("unknown command: frobnicate")))
+        );
+        assert!(parse("scroll-down-lines many").is_err());
+        assert!(parse("search").is_err());
+    }
+}
@@ -2,31 +2,107 @@
 
 use std::borrow::Cow;
 use std::cmp::min;
-use std::ops::RangeInclusive;
+use std::fmt;
+use std::ops::{Range, RangeInclusive};
 use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 use std::sync::{Arc, RwLock};
 use std::thread;
-use std::time;
+use std::time::{self, Duration, Instant};
 
 use bit_set::BitSet;
 use lazy_static::lazy_static;
-use regex::bytes::{NoExpand, Regex};
-use termwiz::cell::CellAttributes;
-use termwiz::color::AnsiColor;
+use regex::bytes::{Regex, RegexBuilder};
 use termwiz::surface::change::Change;
-use termwiz::surface::Position;
-use unicode_width::UnicodeWidthStr;
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
 
+use crate::action::Action;
+use crate::bar::{Bar, BarItem, BarPriority, BarString, BarStyle};
+use crate::bindings::{Binding, Keymap};
+use crate::config::{Config, SearchNormalization};
 use crate::error::Error;
 use crate::event::{Event, EventSender};
 use crate::file::{File, FileInfo};
+use crate::handle::{SearchMatch, SearchState};
+use crate::help;
+use crate::normalize;
 use crate::overstrike;
+use crate::trace::{trace_event, trace_span};
+use crate::util;
 
 const SEARCH_BATCH_SIZE: usize = 10000;
 
+/// The frames of the spinner shown in the search bar while searching.
+const SPINNER_FRAMES: [&str; 4] = ["|", "/", "-", "\\"];
+
 lazy_static! {
     /// Regex for detecting and removing escape sequences during search.
-    pub(crate) static ref ESCAPE_SEQUENCE: Regex = Regex::new("\x1B\\[[0123456789:;\\[?!\"'#%()*+ ]{0,32}m").unwrap();
+    static ref ESCAPE_SEQUENCE: Regex = Regex::new("\x1B\\[[0123456789:;\\[?!\"'#%()*+ ]{0,32}m").unwrap();
+}
+
+/// A table for translating offsets in a line with its escape sequences
+/// removed back to offsets in the original line.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub(crate) struct EscapeOffsets {
+    /// For each run of adjacent escape sequences, its offset in the line
+    /// without escape sequences, and the offset in the original line of the
+    /// start and end of the run.
+    escapes: Vec<(usize, usize, usize)>,
+}
+
+impl EscapeOffsets {
+    /// Removes escape sequences from `data`, returning the remaining data
+    /// and the table for translating offsets in it back to offsets in
+    /// `data`.
+    pub(crate) fn strip(data: &[u8]) -> (Cow<'_, [u8]>, EscapeOffsets) {
+        let mut escapes: Vec<(usize, usize, usize)> = Vec::new();
+        let mut removed = 0;
+        for m in ESCAPE_SEQUENCE.find_iter(data) {
+            match escapes.last_mut() {
+                Some(last) if last.2 == m.start() => last.2 = m.end(),
+                _ => escapes.push((m.start() - removed, m.start(), m.end())),
+            }
+            removed += m.end() - m.start();
+        }
+        if escapes.is_empty() {
+            return (Cow::Borrowed(data), EscapeOffsets { escapes });
+        }
+        let mut stripped = Vec::with_capacity(data.len() - removed);
+        let mut start = 0;
+        for &(_, escape_start, escape_end) in escapes.iter() {
+            stripped.extend_from_slice(&data[start..escape_start]);
+            start = escape_end;
+        }
+        stripped.extend_from_slice(&data[start..]);
+        (Cow::Owned(stripped), EscapeOffsets { escapes })
+    }
+
+    /// Translates `range`, a range of the line without escape sequences, to
+    /// the range of the original line.
+    ///
+    /// Escape sequences at the start of the range are skipped, so that the
+    /// range starts with the first matched character, and escape sequences
+    /// at the end of the range are excluded.  Escape sequences within the
+    /// range are included.
+    pub(crate) fn range(&self, range: Range<usize>) -> Range<usize> {
+        let translate = |offset: usize, escapes: usize| match escapes {
+            0 => offset,
+            escapes => {
+                let (stripped, _, escape_end) = self.escapes[escapes - 1];
+                escape_end + offset - stripped
+            }
+        };
+        let start = translate(
+            range.start,
+            self.escapes
+                .partition_point(|&(stripped, _, _)| stripped <= range.start),
+        );
+        let end = translate(
+            range.end,
+            self.escapes
+                .partition_point(|&(stripped, _, _)| stripped < range.end),
+        );
+        start..end.max(start)
+    }
 }
 
 /// What kind of search to perform.
@@ -37,6 +113,55 @@ pub(crate) enum SearchKind {
     FirstBefore(usize),
 }
 
+/// The columns of each line that a search is restricted to.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub(crate) struct SearchColumns {
+    /// The columns that are searched.
+    pub(crate) range: Range<usize>,
+
+    /// The number of columns between tab stops.
+    pub(crate) tab_width: usize,
+}
+
+impl SearchColumns {
+    /// Returns the range of bytes of `data`, a line with its escape
+    /// sequences removed, that is displayed within the columns.  Characters
+    /// that are partly within the columns are included.
+    pub(crate) fn byte_range(&self, data: &[u8]) -> Range<usize> {
+        let mut column = 0;
+        let mut offset = 0;
+        let mut start = None;
+        while offset < data.len() && column < self.range.end {
+            let (width, len) = match data[offset] {
+                b'\t' => (self.tab_width - column % self.tab_width, 1),
+                byte => {
+                    let len = match byte {
+                        0xC0..=0xDF => 2,
+                        0xE0..=0xEF => 3,
+                        0xF0..=0xF7 => 4,
+                        _ => 1,
+                    };
+                    // Control characters and invalid bytes are shown as `<XX>`.
+                    match data
+                        .get(offset..offset + len)
+                        .and_then(|bytes| std::str::from_utf8(bytes).ok())
+                        .and_then(|text| text.chars().next())
+                    {
+                        Some(c) => (c.width().unwrap_or(4), len),
+                        None => (4, 1),
+                    }
+                }
+            };
+            if start.is_none() && column + width > self.range.start {
+                start = Some(offset);
+            }
+            column += width;
+            offset += len;
+        }
+        start.unwrap_or(offset)..offset
+    }
+}
+
 /// Motion when changing search matches.
 #[derive(Copy, Clone, Debug, Eq, PartialEq)]
 pub(crate) enum MatchMotion {
@@ -57,98 +182,392 @@ struct SearchInner {
     pattern: String,
     kind: SearchKind,
     regex: Regex,
+    block_regex: Option<Regex>,
+    columns: Option<SearchColumns>,
+    normalization: SearchNormalization,
     matches: RwLock<Vec<(usize, usize)>>,
+    match_ranges: RwLock<Vec<Range<usize>>>,
     matching_lines: RwLock<BitSet>,
     current_match: RwLock<Option<usize>>,
+    /// A match found by searching ahead of the searched lines.  It is the
+    /// current match until the lines before it have been searched.
+    provisional_match: RwLock<Option<(usize, usize)>>,
     matching_line_count: AtomicUsize,
     search_line_count: AtomicUsize,
     finished: AtomicBool,
 }
 
+/// The matches found by a search, which can be shared with other threads.
+#[derive(Clone)]
+pub(crate) struct SearchMatches {
+    inner: Arc<SearchInner>,
+}
+
+impl SearchMatches {
+    /// Returns the matches found so far, starting with match number `start`.
+    pub(crate) fn get(&self, start: usize) -> Vec<SearchMatch> {
+        let matches = self.inner.matches.read().unwrap();
+        let match_ranges = self.inner.match_ranges.read().unwrap();
+        matches
+            .iter()
+            .zip(match_ranges.iter())
+            .skip(start)
+            .map(|(&(line, _), range)| SearchMatch {
+                line,
+                range: range.clone(),
+            })
+            .collect()
+    }
+}
+
+impl fmt::Debug for SearchMatches {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SearchMatches")
+            .field("pattern", &self.inner.pattern)
+            .finish()
+    }
+}
+
 /// A search for a pattern within a file.
 pub(crate) struct Search {
     inner: Arc<SearchInner>,
+    status: Bar,
+}
+
+/// Returns the ranges of the matches of `regex` in `data`, a line with its
+/// escape sequences removed.  Only matches within `columns` are returned,
+/// and the text is normalized first if `normalization` requires it.
+pub(crate) fn find_matches(
+    data: &[u8],
+    regex: &Regex,
+    columns: Option<&SearchColumns>,
+    normalization: SearchNormalization,
+) -> Vec<Range<usize>> {
+    let visible = match columns {
+        Some(columns) => columns.byte_range(data),
+        None => 0..data.len(),
+    };
+    let data = &data[visible.clone()];
+    match normalize::normalize(data, normalization) {
+        Some((normalized, offsets)) => regex
+            .find_iter(&normalized)
+            .map(|m| {
+                let range = offsets.range(m.start()..m.end());
+                range.start + visible.start..range.end + visible.start
+            })
+            .collect(),
+        None => regex
+            .find_iter(data)
+            .map(|m| m.start() + visible.start..m.end() + visible.start)
+            .collect(),
+    }
+}
+
+/// Returns the ranges of the matches of `regex` in `data`, a line of the
+/// file.
+fn match_line(
+    data: &[u8],
+    regex: &Regex,
+    columns: Option<&SearchColumns>,
+    normalization: SearchNormalization,
+) -> Vec<Range<usize>> {
+    // Strip trailing LF or CRLF if it is there.
+    let len = trim_trailing_newline(data);
+    let data = overstrike::convert_overstrike(&data[..len]);
+    let (data, _) = EscapeOffsets::strip(&data);
+    find_matches(&data, regex, columns, normalization)
+}
+
+/// Returns a regex for `pattern` that can be run over a block of lines, if
+/// the pattern matches the same lines in a block as it does line by line.
+///
+/// In multi-line mode, `^` and `$` match at the start and end of each line
+/// in the block.  Patterns that anchor to the start or end of the text, or
+/// that disable multi-line mode, could miss matches, so aren't run over
+/// blocks.
+fn block_regex(pattern: &str) -> Option<Regex> {
+    if pattern.contains("\\A") || pattern.contains("\\z") {
+        return None;
+    }
+    let disables_flags = pattern.match_indices("(?").any(|(index, _)| {
+        pattern[index + 2..]
+            .chars()
+            .take_while(|c| c.is_ascii_alphabetic() || *c == '-')
+            .any(|c| c == '-')
+    });
+    if disables_flags {
+        return None;
+    }
+    RegexBuilder::new(pattern).multi_line(true).build().ok()
+}
+
+/// Returns the lines in `data`, a block of lines starting with line `first`
+/// whose offsets are `offsets`, that match `regex`, with the ranges of
+/// their matches.
+///
+/// The block regex is run over the whole block to find the lines that may
+/// match, which are then matched individually.  Matches that span newlines
+/// are split at the newline, so the line they start on is matched, and the
+/// search resumes on the next line.  Lines containing escape sequences,
+/// overstrike or carriage returns differ from how they are searched, so are
+/// always matched individually.
+fn search_block(
+    data: &[u8],
+    offsets: &[usize],
+    first: usize,
+    regex: &Regex,
+    block_regex: &Regex,
+) -> Vec<(usize, Vec<Range<usize>>)> {
+    let line_count = offsets.len() - 1;
+    let line_at = |offset: usize| offsets.partition_point(|&start| start <= offset) - 1;
+    let mut special = data
+        .iter()
+        .enumerate()
+        .filter(|(_, byte)| matches!(**byte, b'\x1B' | b'\x08' | b'\r'))
+        .map(|(offset, _)| line_at(offset))
+        .peekable();
+    let mut found = Vec::new();
+    // Returns the next line from `line` that the block regex matches on, or
+    // `line_count` if there are no more matches in the block.
+    let find_from = |line: usize| {
+        block_regex
+            .find_at(data, offsets[line])
+            .map_or(line_count, |m| min(line_at(m.start()), line_count))
+    };
+    let mut next_match = find_from(0);
+    let mut line = 0;
+    while line < line_count {
+        if next_match < line {
+            next_match = find_from(line);
+        }
+        while special.next_if(|&special| special < line).is_some() {}
+        line = min(next_match, special.peek().copied().unwrap_or(line_count));
+        if line == line_count {
+            break;
+        }
+        let data = &data[offsets[line]..offsets[line + 1]];
+        if !data.is_empty() {
+            let ranges = match_line(data, regex, None, SearchNormalization::None);
+            if !ranges.is_empty() {
+                found.push((first + line, ranges));
+            }
+        }
+        line += 1;
+    }
+    found
+}
+
+/// Returns the lines in `lines` of `file` that match `regex`, with the
+/// ranges of their matches.
+///
+/// Where possible, the lines are searched as one block using `block_regex`,
+/// rather than fetching and searching each line separately.
+fn search_lines(
+    file: &File,
+    regex: &Regex,
+    block_regex: Option<&Regex>,
+    columns: Option<&SearchColumns>,
+    normalization: SearchNormalization,
+    lines: Range<usize>,
+) -> Vec<(usize, Vec<Range<usize>>)> {
+    if let (Some(block_regex), None) = (block_regex, columns) {
+        let found = file.with_lines(lines.clone(), |data, offsets| {
+            search_block(data, offsets, lines.start, regex, block_regex)
+        });
+        if let Some(found) = found {
+            return found;
+        }
+    }
+    lines
+        .filter_map(|line| {
+            let ranges = file
+                .with_line(line, |data| {
+                    match_line(&data, regex, columns, normalization)
+                })
+                .unwrap_or_default();
+            if ranges.is_empty() {
+                None
+            } else {
+                Some((line, ranges))
+            }
+        })
+        .collect()
 }
 
 impl SearchInner {
+    /// Returns the pattern, and for searches restricted to some columns,
+    /// which ones.
+    fn description(&self) -> String {
+        match self.columns {
+            Some(ref columns) => format!(
+                "{} (columns {}-{})",
+                self.pattern,
+                columns.range.start + 1,
+                columns.range.end
+            ),
+            None => self.pattern.clone(),
+        }
+    }
+
     /// Create a new SearchInner for a search.
+    ///
+    /// The file is searched in batches of lines, with up to `threads`
+    /// batches searched at once.  The matches of each batch are added in
+    /// order once the batches before it are complete.
+    ///
+    /// Normalized searches ignore case, and match the pattern against each
+    /// line in the normalization form, so can't search blocks of lines.
     fn new(
         file: &File,
         pattern: &str,
         kind: SearchKind,
+        columns: Option<SearchColumns>,
+        normalization: SearchNormalization,
+        threads: usize,
         event_sender: EventSender,
     ) -> Result<Arc<SearchInner>, Error> {
-        let regex = Regex::new(pattern)?;
+        let (regex, block_regex) = if normalization == SearchNormalization::None {
+            (Regex::new(pattern)?, block_regex(pattern))
+        } else {
+            let regex = RegexBuilder::new(&normalize::normalize_pattern(pattern, normalization))
+                .case_insensitive(true)
+                .build()?;
+            (regex, None)
+        };
+        let threads = threads.max(1);
         let search = Arc::new(SearchInner {
             pattern: pattern.to_string(),
             kind,
             regex: regex.clone(),
+            block_regex: block_regex.clone(),
+            columns: columns.clone(),
+            normalization,
             matches: RwLock::new(Vec::new()),
+            match_ranges: RwLock::new(Vec::new()),
             matching_lines: RwLock::new(BitSet::new()),
             current_match: RwLock::new(None),
+            provisional_match: RwLock::new(None),
             matching_line_count: AtomicUsize::new(0),
             search_line_count: AtomicUsize::new(0),
             finished: AtomicBool::new(false),
         });
+        if let SearchKind::FirstAfter(offset) = kind {
+            if offset > 0 && threads > 1 {
+                search.search_ahead(file, offset, event_sender.clone());
+            }
+        }
         thread::Builder::new()
             .name(String::from("sp-search"))
             .spawn({
                 let search = search.clone();
                 let file = file.clone();
                 move || {
+                    let _span =
+                        trace_span!("search", file = file.index(), pattern = %search.pattern);
                     let mut matched = false;
                     loop {
                         let loaded = file.loaded();
                         let lines = file.lines();
                         let search_line_count = search.search_line_count.load(Ordering::SeqCst);
                         let search_limit = min(
-                            search_line_count + SEARCH_BATCH_SIZE,
-                            if loaded { lines } else { lines - 1 },
+                            search_line_count + SEARCH_BATCH_SIZE * threads,
+                            if loaded {
+                                lines
+                            } else {
+                                lines.saturating_sub(1)
+                            },
                         );
-                        for line in search_line_count..search_limit {
-                            let count = file.with_line(line, |data| {
-                                // Strip trailing LF or CRLF if it is there.
-                                let len = trim_trailing_newline(&data[..]);
-                                let data = overstrike::convert_overstrike(&data[..len]);
-                                let data = ESCAPE_SEQUENCE.replace_all(&data[..], NoExpand(b""));
-                                regex.find_iter(&data[..]).count()
-                            });
-                            if count.unwrap_or(0) > 0 {
-                                let mut matching_lines = search.matching_lines.write().unwrap();
-                                matching_lines.insert(line);
-                                let mut matches = search.matches.write().unwrap();
-                                let first_match_index = matches.len();
-                                for i in 0..count.unwrap() {
-                                    matches.push((line, i));
-                                }
-                                search.matching_line_count.fetch_add(1, Ordering::SeqCst);
-                                if !matched {
-                                    if let Some(index) = match search.kind {
-                                        SearchKind::First => Some(first_match_index),
-                                        SearchKind::FirstAfter(offset) => {
-                                            if line >= offset {
-                                                Some(first_match_index)
-                                            } else {
-                                                None
-                                            }
+                        let batches = (search_line_count..search_limit)
+                            .step_by(SEARCH_BATCH_SIZE)
+                            .map(|start| start..min(start + SEARCH_BATCH_SIZE, search_limit))
+                            .collect::<Vec<_>>();
+                        let results = if batches.len() > 1 {
+                            thread::scope(|scope| {
+                                let workers = batches
+                                    .into_iter()
+                                    .map(|batch| {
+                                        let file = file.clone();
+                                        let regex = &regex;
+                                        let block_regex = block_regex.as_ref();
+                                        let columns = columns.as_ref();
+                                        scope.spawn(move || {
+                                            search_lines(
+                                                &file,
+                                                regex,
+                                                block_regex,
+                                                columns,
+                                                normalization,
+                                                batch,
+                                            )
+                                        })
+                                    })
+                                    .collect::<Vec<_>>();
+                                workers
+                                    .into_iter()
+                                    .map(|worker| worker.join().unwrap())
+                                    .collect::<Vec<_>>()
+                            })
+                        } else {
+                            batches
+                                .into_iter()
+                                .map(|batch| {
+                                    search_lines(
+                                        &file,
+                                        &regex,
+                                        block_regex.as_ref(),
+                                        columns.as_ref(),
+                                        normalization,
+                                        batch,
+                                    )
+                                })
+                                .collect()
+                        };
+                        for (line, ranges) in results.into_iter().flatten() {
+                            let mut matching_lines = search.matching_lines.write().unwrap();
+                            matching_lines.insert(line);
+                            let mut matches = search.matches.write().unwrap();
+                            let first_match_index = matches.len();
+                            for i in 0..ranges.len() {
+                                matches.push((line, i));
+                            }
+                            search.match_ranges.write().unwrap().extend(ranges);
+                            search.matching_line_count.fetch_add(1, Ordering::SeqCst);
+                            if !matched {
+                                if let Some(index) = match search.kind {
+                                    SearchKind::First => Some(first_match_index),
+                                    SearchKind::FirstAfter(offset) => {
+                                        if line >= offset {
+                                            Some(first_match_index)
+                                        } else {
+                                            None
                                         }
-                                        SearchKind::FirstBefore(offset) => {
-                                            if line >= offset
-                                                && first_match_index > 0
-                                                && matches[first_match_index - 1].0 < offset
-                                            {
-                                                Some(first_match_index - 1)
-                                            } else {
-                                                None
-                                            }
+                                    }
+                                    SearchKind::FirstBefore(offset) => {
+                                        if line >= offset
+                                            && first_match_index > 0
+                                            && matches[first_match_index - 1].0 < offset
+                                        {
+                                            Some(first_match_index - 1)
+                                        } else {
+                                            None
                                         }
-                                    } {
+                                    }
+                                } {
+                                    // If this match was already found by
+                                    // searching ahead, the screen has been
+                                    // told about it.
+                                    let announced = {
+                                        let mut provisional =
+                                            search.provisional_match.write().unwrap();
                                         *search.current_match.write().unwrap() = Some(index);
+                                        provisional.take()
+                                    };
+                                    if announced != Some(matches[index]) {
                                         event_sender
                                             .send(Event::SearchFirstMatch(file.index()))
                                             .unwrap();
-                                        matched = true;
                                     }
+                                    matched = true;
                                 }
                             }
                         }
@@ -159,7 +578,7 @@ impl SearchInner {
                             // Searched the whole file.
                             break;
                         }
-                        if !loaded && search_limit >= lines - 1 {
+                        if !loaded && search_limit >= lines.saturating_sub(1) {
                             // Searched the whole file so far.  Wait for more data.
                             thread::sleep(time::Duration::from_millis(100));
                         }
@@ -177,6 +596,10 @@ impl SearchInner {
                                 .unwrap();
                         }
                     }
+                    trace_event!(
+                        matches = search.matches.read().unwrap().len(),
+                        "search finished"
+                    );
                     search.finished.store(true, Ordering::SeqCst);
                     event_sender
                         .send(Event::SearchFinished(file.index()))
@@ -186,19 +609,106 @@ impl SearchInner {
             .unwrap();
         Ok(search)
     }
+
+    /// Start searching from line `offset` for the first match after it, so
+    /// that it can be shown before the lines before it have been searched.
+    /// Searching ahead stops once the main search has caught up.
+    fn search_ahead(self: &Arc<Self>, file: &File, offset: usize, event_sender: EventSender) {
+        thread::Builder::new()
+            .name(String::from("sp-search-ahead"))
+            .spawn({
+                let search = self.clone();
+                let file = file.clone();
+                move || {
+                    let mut line = offset;
+                    loop {
+                        if search.search_line_count.load(Ordering::SeqCst) >= line
+                            || search.current_match.read().unwrap().is_some()
+                        {
+                            return;
+                        }
+                        let loaded = file.loaded();
+                        let searchable = if loaded {
+                            file.lines()
+                        } else {
+                            file.lines().saturating_sub(1)
+                        };
+                        if line >= searchable {
+                            if loaded {
+                                return;
+                            }
+                            thread::sleep(time::Duration::from_millis(100));
+                            continue;
+                        }
+                        let end = min(line + SEARCH_BATCH_SIZE, searchable);
+                        let found = search_lines(
+                            &file,
+                            &search.regex,
+                            search.block_regex.as_ref(),
+                            search.columns.as_ref(),
+                            search.normalization,
+                            line..end,
+                        );
+                        if let Some(&(found_line, _)) = found.first() {
+                            {
+                                let mut provisional = search.provisional_match.write().unwrap();
+                                if search.current_match.read().unwrap().is_some() {
+                                    return;
+                                }
+                                *provisional = Some((found_line, 0));
+                            }
+                            search.matching_lines.write().unwrap().insert(found_line);
+                            let _ = event_sender.send(Event::SearchFirstMatch(file.index()));
+                            return;
+                        }
+                        line = end;
+                    }
+                }
+            })
+            .unwrap();
+    }
 }
 
 impl Search {
-    /// Create a new search for a pattern.
+    /// Create a new search for a pattern.  The search status bar shows the
+    /// keys from `keymap` for moving between matches and closing the search.
     pub(crate) fn new(
         file: &File,
         pattern: &str,
         kind: SearchKind,
+        columns: Option<SearchColumns>,
+        config: &Config,
+        keymap: &Keymap,
         event_sender: EventSender,
     ) -> Result<Search, Error> {
-        Ok(Search {
-            inner: SearchInner::new(file, pattern, kind, event_sender)?,
-        })
+        let inner = SearchInner::new(
+            file,
+            pattern,
+            kind,
+            columns,
+            config.search_normalization,
+            config.search_threads(),
+            event_sender,
+        )?;
+        let mut status = Bar::new(BarStyle::Normal);
+        status.add_left_item(Arc::new(BarString::new(inner.description())));
+        status.add_left_item(Arc::new(SearchProgressIndicator {
+            inner: inner.clone(),
+            file: file.clone(),
+            animation_start: Instant::now(),
+            frame_duration: config.animation().frame_duration(),
+        }));
+        if let Some(hints) = search_hints(keymap) {
+            status.add_center_item(Arc::new(
+                BarString::new(hints)
+                    .with_priority(BarPriority::Low)
+                    .whole(),
+            ));
+        }
+        status.add_right_item(Arc::new(MatchIndicator {
+            inner: inner.clone(),
+        }));
+        Ok(Search { inner, status })
     }
 
     /// Returns true if the search has finished searching the whole file.
@@ -206,64 +716,25 @@ impl Search {
         self.inner.finished.load(Ordering::SeqCst)
     }
 
-    /// Renders the search overlay line.
-    pub(crate) fn render(&mut self, changes: &mut Vec<Change>, line: usize, width: usize) {
-        let mut width = width;
-        changes.push(Change::CursorPosition {
-            x: Position::Absolute(0),
-            y: Position::Absolute(line),
-        });
-        changes.push(Change::AllAttributes(
-            CellAttributes::default()
-                .set_foreground(AnsiColor::Black)
-                .set_background(AnsiColor::Silver)
-                .clone(),
-        ));
-        if width < 8 {
-            // The screen is too small to write anything, just write a blank bar.
-            changes.push(Change::ClearToEndOfLine(AnsiColor::Silver.into()));
-            return;
-        }
-        changes.push(Change::Text("  ".into()));
-        width -= 2;
-
-        let matches = self.inner.matches.read().unwrap();
-        let match_info = match *self.inner.current_match.read().unwrap() {
-            Some(index) => Cow::Owned(format!(
-                "{} of {} matches on {} lines",
-                index + 1,
-                matches.len(),
-                self.inner.matching_line_count.load(Ordering::SeqCst),
-            )),
-            _ if self.inner.finished.load(Ordering::SeqCst) => Cow::Borrowed("No matches"),
-            _ => Cow::Owned(format!(
-                "Searched {} lines",
-                self.inner.search_line_count.load(Ordering::SeqCst),
-            )),
-        };
-
-        // The right-hand side is shown only if it can fit.
-        let right_width = match_info.width() + 2;
-        let mut left_width = width;
-        if width >= right_width {
-            left_width -= right_width;
-        }
+    /// Renders the search status bar.
+    pub(crate) fn render(&self, changes: &mut Vec<Change>, line: usize, width: usize) {
+        self.status.render(changes, line, width);
+    }
 
-        // Write the left-hand side if it fits.
-        match left_width {
-            0 => {}
-            1 => changes.push(Change::Text(" ".into())),
-            _ => changes.push(Change::Text(format!(
-                "{1:0$.0$} ",
-                left_width - 1,
-                self.inner.pattern
-            ))),
+    /// Returns the state of the search, for reporting to embedders.
+    pub(crate) fn state(&self) -> SearchState {
+        SearchState {
+            pattern: self.inner.pattern.clone(),
+            matches: self.inner.matches.read().unwrap().len(),
+            current_match: *self.inner.current_match.read().unwrap(),
+            finished: self.finished(),
         }
+    }
 
-        // Write the right-hand side if it fits.
-        if width >= right_width {
-            changes.push(Change::Text(match_info.into()));
-            changes.push(Change::ClearToEndOfLine(AnsiColor::Silver.into()));
+    /// Returns the matches found by the search, for sharing with embedders.
+    pub(crate) fn matches(&self) -> SearchMatches {
+        SearchMatches {
+            inner: self.inner.clone(),
         }
     }
 
@@ -271,7 +742,10 @@ impl Search {
     pub(crate) fn current_match(&self) -> Option<(usize, usize)> {
         let matches = self.inner.matches.read().unwrap();
         let current_match_index = self.inner.current_match.read().unwrap();
-        current_match_index.map(|index| matches[index])
+        match *current_match_index {
+            Some(index) => Some(matches[index]),
+            None => *self.inner.provisional_match.read().unwrap(),
+        }
     }
 
     /// Moves to another match if there is one.
@@ -368,6 +842,19 @@ impl Search {
         lines
     }
 
+    /// Returns, for each of `rows` rows that the `lines` lines of the file
+    /// are divided between, whether any of its lines match.
+    pub(crate) fn matching_rows(&self, lines: usize, rows: usize) -> Vec<bool> {
+        let mut matching_rows = vec![false; rows];
+        if rows > 0 {
+            let matching_lines = self.inner.matching_lines.read().unwrap();
+            for line in matching_lines.iter() {
+                matching_rows[min(line * rows / lines, rows - 1)] = true;
+            }
+        }
+        matching_rows
+    }
+
     /// Returns the number of searched lines.
     pub(crate) fn searched_lines(&self) -> usize {
         self.inner.search_line_count.load(Ordering::SeqCst)
@@ -378,6 +865,21 @@ impl Search {
         &self.inner.regex
     }
 
+    /// Returns the columns the search is restricted to, if it is.
+    pub(crate) fn columns(&self) -> Option<&SearchColumns> {
+        self.inner.columns.as_ref()
+    }
+
+    /// Returns the normalization applied to lines before they are searched.
+    pub(crate) fn normalization(&self) -> SearchNormalization {
+        self.inner.normalization
+    }
+
+    /// Returns the pattern being searched for.
+    pub(crate) fn pattern(&self) -> &str {
+        &self.inner.pattern
+    }
+
     /// Returns true if the line index matches the search
     pub(crate) fn line_matches(&self, line_index: usize) -> bool {
         self.inner
@@ -399,3 +901,205 @@ pub(crate) fn trim_trailing_newline(data: impl AsRef<[u8]>) -> usize {
     }
     len
 }
+
+/// Returns the hints for the keys that move between matches and close the
+/// search, or `None` if none of them are bound.
+fn search_hints(keymap: &Keymap) -> Option<String> {
+    let key = |action: Action| {
+        keymap
+            .iter_keys()
+            .find(|(binding, _)| **binding == Binding::Action(action.clone()))
+            .and_then(|(_, keys)| keys.first())
+            .map(|&(modifiers, keycode)| help::key_name(modifiers, keycode))
+    };
+    let mut hints = Vec::new();
+    match (
+        key(Action::NextMatchScreen),
+        key(Action::PreviousMatchScreen),
+    ) {
+        (Some(next), Some(previous)) => hints.push(format!("{}/{} next/prev", next, previous)),
+        (Some(next), None) => hints.push(format!("{} next", next)),
+        (None, Some(previous)) => hints.push(format!("{} prev", previous)),
+        (None, None) => {}
+    }
+    if let Some(cancel) = key(Action::Cancel) {
+        hints.push(format!("{} close", cancel));
+    }
+    (!hints.is_empty()).then(|| hints.join("  "))
+}
+
+/// The progress of a search through the file: a spinner and the percentage
+/// of the file searched so far.  Hidden once the search has finished.
+struct SearchProgressIndicator {
+    inner: Arc<SearchInner>,
+    file: File,
+    animation_start: Instant,
+    frame_duration: Option<Duration>,
+}
+
+impl SearchProgressIndicator {
+    fn content(&self) -> Option<String> {
+        if self.inner.finished.load(Ordering::SeqCst) {
+            return None;
+        }
+        let searched = self.inner.search_line_count.load(Ordering::SeqCst);
+        let percent = (searched * 100 / self.file.lines().max(1)).min(100);
+        match self.frame_duration {
+            Some(frame_duration) => {
+                let elapsed = self.animation_start.elapsed().as_millis();
+                let frame_index = (elapsed / frame_duration.as_millis()) as usize;
+                let frame = SPINNER_FRAMES[frame_index % SPINNER_FRAMES.len()];
+                Some(format!("{} {:>3}%", frame, percent))
+            }
+            None => Some(format!("[searching {}%]", percent)),
+        }
+    }
+}
+
+impl BarItem for SearchProgressIndicator {
+    fn width(&self) -> usize {
+        self.content().map_or(0, |content| content.width())
+    }
+
+    fn render(&self, changes: &mut Vec<Change>, width: usize) {
+        if let Some(content) = self.content() {
+            changes.push(Change::Text(util::truncate_string(content, 0, width)));
+        }
+    }
+}
+
+/// The current match and the number of matches found so far.
+struct MatchIndicator {
+    inner: Arc<SearchInner>,
+}
+
+impl MatchIndicator {
+    fn content(&self) -> Cow<'static, str> {
+        let matches = self.inner.matches.read().unwrap();
+        match *self.inner.current_match.read().unwrap() {
+            Some(index) => Cow::Owned(format!(
+                "{} of {} matches on {} lines",
+                index + 1,
+                matches.len(),
+                self.inner.matching_line_count.load(Ordering::SeqCst),
+            )),
+            _ if self.inner.finished.load(Ordering::SeqCst) => Cow::Borrowed("No matches"),
+            _ => Cow::Owned(format!(
+                "Searched {} lines",
+                self.inner.search_line_count.load(Ordering::SeqCst),
+            )),
+        }
+    }
+}
+
+impl BarItem for MatchIndicator {
+    fn width(&self) -> usize {
+        self.content().width()
+    }
+
+    fn render(&self, changes: &mut Vec<Change>, width: usize) {
+        changes.push(Change::Text(util::truncate_string(
+            self.content(),
+            0,
+            width,
+        )));
+    }
+
+    fn priority(&self) -> BarPriority {
+        BarPriority::High
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::thread;
+    use std::time::Duration;
+
+    use super::*;
+    use crate::event::EventStream;
+    use crate::file::LoadedFile;
+
+    #[test]
+    fn test_escape_offsets() {
+        let (stripped, offsets) = EscapeOffsets::strip(b"plain\ttext");
+        assert_eq!(stripped, Cow::Borrowed(b"plain\ttext"));
+        assert_eq!(offsets.range(2..7), 2..7);
+
+        let data = "\x1B[1;31mred\x1B[m\x1B[1m\tbold\x1B[0m \u{4E2D}\u{6587} wide".as_bytes();
+        let (stripped, offsets) = EscapeOffsets::strip(data);
+        assert_eq!(&stripped[..], "red\tbold \u{4E2D}\u{6587} wide".as_bytes());
+        // Escape sequences before the start of a match are skipped.
+        assert_eq!(&data[offsets.range(0..3)], b"red");
+        // Escape sequences within a match are included.
+        assert_eq!(&data[offsets.range(2..5)], b"d\x1B[m\x1B[1m\tb");
+        // Escape sequences after the end of a match are excluded.
+        assert_eq!(&data[offsets.range(4..8)], b"bold");
+        assert_eq!(&data[offsets.range(9..15)], "\u{4E2D}\u{6587}".as_bytes());
+        assert_eq!(&data[offsets.range(16..20)], b"wide");
+        // Empty matches are after the escape sequences at that point.
+        assert_eq!(offsets.range(3..3), 17..17);
+    }
+
+    #[test]
+    fn test_search_block() {
+        let events = EventStream::new(None);
+        let file: File = LoadedFile::new_static(
+            0,
+            "test",
+            &b"foo bar\r\nbar foo\n\nfo\x1B[1mo\x1B[m baz\nqux\nfoo\n   \nbaz\nb\x08baz foo"[..],
+            events.sender(),
+        )
+        .into();
+        while !file.loaded() {
+            thread::sleep(Duration::from_millis(1));
+        }
+        let lines = file.lines();
+        assert!(file.with_lines(0..lines, |_, _| ()).is_some());
+        let patterns = [
+            "foo",
+            "^bar",
+            "foo$",
+            "baz$",
+            "o\\s+b",
+            "(?s)o.*b",
+            "",
+            "^$",
+            "\\bqux\\b",
+            "[^x]+",
+            "(?i)FOO",
+        ];
+        for pattern in patterns.iter() {
+            let regex = Regex::new(pattern).unwrap();
+            let block_regex = block_regex(pattern);
+            assert!(block_regex.is_some(), "{}", pattern);
+            for start in 0..lines {
+                for end in start + 1..=lines {
+                    assert_eq!(
+                        search_lines(
+                            &file,
+                            &regex,
+                            block_regex.as_ref(),
+                            None,
+                            SearchNormalization::None,
+                            start..end
+                        ),
+                        search_lines(
+                            &file,
+                            &regex,
+                            None,
+                            None,
+                            SearchNormalization::None,
+                            start..end
+                        ),
+                        "{} in lines {}..{}",
+                        pattern,
+                        start,
+                        end,
+                    );
+                }
+            }
+        }
+        assert!(block_regex("\\Afoo").is_none());
+        assert!(block_regex("(?-m)foo$").is_none());
+    }
+}
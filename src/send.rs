@@ -0,0 +1,147 @@
+//! Sending streams to a running pager.
+//!
+//! A pager can listen on a per-user Unix socket for streams sent by other
+//! invocations, so that a single pager accumulates them as new files.  Each
+//! stream sent to the socket starts with its title on a line of its own.
+//!
+//! This is only public for `sp`, and is not part of the library's API.
+
+use std::fs::File;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::os::unix::fs::{DirBuilderExt, MetadataExt, OpenOptionsExt};
+use std::os::unix::io::AsRawFd;
+use std::os::unix::net::UnixStream;
+use std::path::{Path, PathBuf};
+
+use crate::error::{Error, Result};
+
+/// The longest title that is read from the start of a stream.
+const MAX_TITLE_LENGTH: u64 = 4096;
+
+/// Returns the path of the per-user socket, creating the directory it is in
+/// if necessary.
+///
+/// The socket is in the user's runtime directory if there is one.
+/// Otherwise it is in a directory in the temporary directory that only the
+/// user can access.
+pub fn socket_path() -> Result<PathBuf> {
+    let uid = unsafe { libc::getuid() };
+    let dir = match dirs::runtime_dir() {
+        Some(dir) => dir.join("streampager"),
+        None => std::env::temp_dir().join(format!("streampager-{}", uid)),
+    };
+    let with_dir = |err: std::io::Error| Error::from(err).with_file(dir.to_string_lossy());
+    match std::fs::DirBuilder::new().mode(0o700).create(&dir) {
+        Ok(()) => {}
+        Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => {}
+        Err(e) => return Err(with_dir(e)),
+    }
+    let metadata = std::fs::symlink_metadata(&dir).map_err(with_dir)?;
+    if !metadata.is_dir() || metadata.uid() != uid || metadata.mode() & 0o077 != 0 {
+        return Err(with_dir(std::io::Error::new(
+            std::io::ErrorKind::PermissionDenied,
+            "directory is not private to the user",
+        )));
+    }
+    Ok(dir.join("send.sock"))
+}
+
+/// Takes the lock that a pager holds for as long as it is listening on the
+/// per-user socket, returning `None` if another pager holds it.
+///
+/// The lock is released when the returned file is closed.
+pub(crate) fn lock(path: &Path) -> Result<Option<File>> {
+    let with_path = |err: std::io::Error| Error::from(err).with_file(path.to_string_lossy());
+    let file = std::fs::OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .mode(0o600)
+        .open(path)
+        .map_err(with_path)?;
+    if unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX | libc::LOCK_NB) } != 0 {
+        let err = std::io::Error::last_os_error();
+        if err.kind() == std::io::ErrorKind::WouldBlock {
+            return Ok(None);
+        }
+        return Err(with_path(err));
+    }
+    Ok(Some(file))
+}
+
+/// Connects to the socket of a running pager, returning `None` if there
+/// isn't one.
+pub fn connect() -> Result<Option<UnixStream>> {
+    let path = socket_path()?;
+    match UnixStream::connect(&path) {
+        Ok(stream) => Ok(Some(stream)),
+        Err(e)
+            if e.kind() == std::io::ErrorKind::NotFound
+                || e.kind() == std::io::ErrorKind::ConnectionRefused =>
+        {
+            Ok(None)
+        }
+        Err(e) => Err(Error::from(e).with_file(path.to_string_lossy())),
+    }
+}
+
+/// Sends `stream` to a running pager over `connection`, to be displayed as
+/// a new file with the given title.  Returns once the whole stream has been
+/// sent.
+pub fn send(mut connection: UnixStream, title: &str, mut stream: impl Read) -> Result<()> {
+    let title = title.replace(['\n', '\r'], " ");
+    writeln!(connection, "{}", title)?;
+    std::io::copy(&mut stream, &mut connection)?;
+    Ok(())
+}
+
+/// Reads the title from the start of a stream that has been sent, and
+/// returns it along with the rest of the stream.
+pub(crate) fn receive(
+    stream: impl Read + Send + 'static,
+) -> std::io::Result<(String, Box<dyn Read + Send>)> {
+    let mut reader = BufReader::new(stream);
+    let mut title = Vec::new();
+    (&mut reader)
+        .take(MAX_TITLE_LENGTH)
+        .read_until(b'\n', &mut title)?;
+    if title.last() == Some(&b'\n') {
+        title.pop();
+    }
+    Ok((
+        String::from_utf8_lossy(&title).into_owned(),
+        Box::new(reader),
+    ))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_receive() {
+        let (title, mut stream) = receive(std::io::Cursor::new("make\nline 1\nline 2\n")).unwrap();
+        assert_eq!(title, "make");
+        let mut data = String::new();
+        stream.read_to_string(&mut data).unwrap();
+        assert_eq!(data, "line 1\nline 2\n");
+
+        let (title, mut stream) = receive(std::io::Cursor::new("")).unwrap();
+        assert_eq!(title, "");
+        let mut data = String::new();
+        stream.read_to_string(&mut data).unwrap();
+        assert_eq!(data, "");
+    }
+
+    #[test]
+    fn test_lock() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("send.lock");
+        let held = lock(&path).unwrap();
+        assert!(held.is_some());
+        // Only one pager can hold the lock at a time.
+        assert!(lock(&path).unwrap().is_none());
+        drop(held);
+        assert!(lock(&path).unwrap().is_some());
+    }
+}
@@ -0,0 +1,125 @@
+//! Sessions.
+//!
+//! When the pager quits, the files that were opened by name are saved along
+//! with where each one was scrolled to, so that the session can be resumed
+//! later.
+
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+use tempfile::NamedTempFile;
+
+use crate::error::{Error, Result};
+use crate::file::FileIndex;
+
+/// The state of a file in a session.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub(crate) struct SessionFile {
+    /// The path of the file.
+    pub(crate) path: PathBuf,
+
+    /// The line at the top of the screen.
+    pub(crate) top_line: usize,
+
+    /// The left-most column shown, when lines aren't wrapped.
+    pub(crate) left: usize,
+
+    /// The pattern being searched for, if any.
+    pub(crate) search: Option<String>,
+}
+
+/// A saved session.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub(crate) struct Session {
+    /// The index in `files` of the file that was being shown.
+    pub(crate) current: usize,
+
+    /// The files that were open.
+    pub(crate) files: Vec<SessionFile>,
+}
+
+/// A session being resumed, with the file index each of its files was
+/// reopened as.
+pub(crate) struct Resume {
+    /// The file index of the file that was being shown.
+    pub(crate) current: Option<FileIndex>,
+
+    /// The files that were reopened.
+    pub(crate) files: Vec<(FileIndex, SessionFile)>,
+}
+
+/// Returns the path the session is saved to.
+fn session_path() -> Option<PathBuf> {
+    let mut path = dirs::data_dir()?;
+    path.push("streampager");
+    path.push("session.toml");
+    Some(path)
+}
+
+impl Session {
+    /// Loads the saved session, if there is one.
+    pub(crate) fn load() -> Result<Option<Session>> {
+        let path = match session_path() {
+            Some(path) => path,
+            None => return Ok(None),
+        };
+        match std::fs::read_to_string(&path) {
+            Ok(data) => toml::from_str(&data)
+                .map(Some)
+                .map_err(|e| Error::Session(format!("{}: {}", path.display(), e))),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(Error::from(e).with_file(path.to_string_lossy())),
+        }
+    }
+
+    /// Saves the session, replacing the previous one.
+    pub(crate) fn save(&self) -> Result<()> {
+        use std::io::Write;
+
+        if let Some(path) = session_path() {
+            let data = toml::to_string(self).map_err(|e| Error::Session(e.to_string()))?;
+            let dir = path.parent().expect("session path should have a parent");
+            std::fs::create_dir_all(dir)?;
+            let mut file = NamedTempFile::new_in(dir)?;
+            file.write_all(data.as_bytes())?;
+            file.persist(&path)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_session_format() {
+        let session = Session {
+            current: 1,
+            files: vec![
+                SessionFile {
+                    path: PathBuf::from("/var/log/one.log"),
+                    top_line: 120,
+                    left: 0,
+                    search: Some(String::from("ERROR")),
+                },
+                SessionFile {
+                    path: PathBuf::from("/var/log/two.log"),
+                    top_line: 5,
+                    left: 16,
+                    search: None,
+                },
+            ],
+        };
+        let data = toml::to_string(&session).unwrap();
+        assert_eq!(toml::from_str::<Session>(&data).unwrap(), session);
+
+        // Missing fields take their defaults.
+        let session: Session = toml::from_str("[[files]]\npath = \"/tmp/x\"\n").unwrap();
+        assert_eq!(session.current, 0);
+        assert_eq!(session.files[0].top_line, 0);
+        assert_eq!(session.files[0].search, None);
+    }
+}
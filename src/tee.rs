@@ -0,0 +1,88 @@
+//! Tees
+//!
+//! A tee writes the data of a file to an output file as it is loaded, so
+//! that the output file has everything that was read even if the pager is
+//! interrupted.
+
+use std::fs::File;
+use std::io::Write;
+use std::path::Path;
+
+use crate::error::{Error, Result};
+use crate::line::strip_escapes;
+
+/// Writes the data of a file to an output file.
+pub(crate) struct Tee {
+    /// The output file.
+    output: File,
+
+    /// Whether to remove escape sequences from the data written.
+    strip_escapes: bool,
+
+    /// The end of the data that hasn't been written yet, because escape
+    /// sequences are being stripped and it isn't a whole line.
+    partial: Vec<u8>,
+}
+
+impl Tee {
+    /// Create the output file at `path`.
+    pub(crate) fn create(path: &Path, strip_escapes: bool) -> Result<Tee> {
+        let output =
+            File::create(path).map_err(|e| Error::from(e).with_file(path.to_string_lossy()))?;
+        Ok(Tee {
+            output,
+            strip_escapes,
+            partial: Vec::new(),
+        })
+    }
+
+    /// Write data that has just been loaded.
+    ///
+    /// When stripping escape sequences, only whole lines are written, as an
+    /// escape sequence may be split between one block of data and the next.
+    pub(crate) fn write(&mut self, data: &[u8]) -> std::io::Result<()> {
+        if !self.strip_escapes {
+            return self.output.write_all(data);
+        }
+        match data.iter().rposition(|&b| b == b'\n') {
+            Some(end) => {
+                self.partial.extend_from_slice(&data[..=end]);
+                self.output.write_all(&strip_escapes(&self.partial))?;
+                self.partial.clear();
+                self.partial.extend_from_slice(&data[end + 1..]);
+            }
+            None => self.partial.extend_from_slice(data),
+        }
+        Ok(())
+    }
+
+    /// Write any partial last line.  Nothing more is written after this.
+    pub(crate) fn finish(&mut self) -> std::io::Result<()> {
+        if !self.partial.is_empty() {
+            self.output.write_all(&strip_escapes(&self.partial))?;
+            self.partial.clear();
+        }
+        self.output.flush()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_tee() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("output");
+        let mut tee = Tee::create(&path, true)?;
+        tee.write(b"\x1B[1mbold\x1B")?;
+        // Nothing is written until a whole line has been loaded.
+        assert_eq!(std::fs::read(&path)?, b"");
+        tee.write(b"[m line\nnext \x1B[3")?;
+        assert_eq!(std::fs::read(&path)?, b"bold line\n");
+        tee.write(b"2mpartial")?;
+        tee.finish()?;
+        assert_eq!(std::fs::read(&path)?, b"bold line\nnext partial");
+        Ok(())
+    }
+}
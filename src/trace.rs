@@ -0,0 +1,82 @@
+//! Tracing
+//!
+//! When built with the `trace` feature, file loading, searching and
+//! rendering are instrumented with `tracing`.  If the `SP_TRACE_FILE`
+//! environment variable is set, the trace is written to that file, so that
+//! users can produce traces of hangs or slow rendering.
+//!
+//! Without the feature, the macros here expand to nothing.
+
+/// Enter a span that lasts until the returned guard is dropped.
+#[cfg(feature = "trace")]
+macro_rules! trace_span {
+    ( $( $arg:tt )* ) => {
+        tracing::debug_span!( $( $arg )* ).entered()
+    };
+}
+
+/// Enter a span that lasts until the returned guard is dropped.
+#[cfg(not(feature = "trace"))]
+macro_rules! trace_span {
+    ( $( $arg:tt )* ) => {
+        $crate::trace::NoSpan
+    };
+}
+
+/// Record an event in the trace.
+#[cfg(feature = "trace")]
+macro_rules! trace_event {
+    ( $( $arg:tt )* ) => {
+        tracing::debug!( $( $arg )* )
+    };
+}
+
+/// Record an event in the trace.
+#[cfg(not(feature = "trace"))]
+macro_rules! trace_event {
+    ( $( $arg:tt )* ) => {{}};
+}
+
+pub(crate) use trace_event;
+pub(crate) use trace_span;
+
+/// A span guard that does nothing, used when tracing is not enabled.
+#[cfg(not(feature = "trace"))]
+pub(crate) struct NoSpan;
+
+/// Start writing the trace to the file named by `SP_TRACE_FILE`, if it is
+/// set.  Traces include when each span closes, so that slow renders and
+/// searches can be found.
+#[cfg(feature = "trace")]
+pub(crate) fn init() {
+    use tracing_subscriber::fmt::format::FmtSpan;
+
+    let path = match std::env::var_os("SP_TRACE_FILE") {
+        Some(path) => path,
+        None => return,
+    };
+    let file = match std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)
+    {
+        Ok(file) => file,
+        Err(e) => {
+            eprintln!("streampager: failed to open trace file {:?}: {}", path, e);
+            return;
+        }
+    };
+    // Another subscriber may already have been set, e.g. by the
+    // application.  That one is used instead.
+    let _ = tracing_subscriber::fmt()
+        .with_max_level(tracing::Level::DEBUG)
+        .with_span_events(FmtSpan::CLOSE)
+        .with_thread_names(true)
+        .with_ansi(false)
+        .with_writer(std::sync::Mutex::new(file))
+        .try_init();
+}
+
+/// Start writing the trace.  Tracing is not enabled, so this does nothing.
+#[cfg(not(feature = "trace"))]
+pub(crate) fn init() {}
@@ -0,0 +1,111 @@
+//! Line Transforms
+//!
+//! Line transforms rewrite the contents of lines before they are parsed,
+//! for example to redact secrets, demangle symbols, or show structured data
+//! as text.  Transforms registered on the pager are applied in the order
+//! they were added, after the line has been converted to UTF-8.  Lines are
+//! only transformed when they are needed, and the transformed lines are
+//! cached by the file.
+
+use std::fmt;
+use std::sync::Arc;
+
+/// A transformation of the contents of lines.
+pub trait LineTransform: Send + Sync {
+    /// Transforms `line`, the contents of a line including its line ending.
+    ///
+    /// Returns `None` if the line is unchanged.  Transformed lines should
+    /// keep the line ending, and should not contain other newlines.
+    fn transform(&self, line: &[u8]) -> Option<Vec<u8>>;
+}
+
+impl<F> LineTransform for F
+where
+    F: Fn(&[u8]) -> Option<Vec<u8>> + Send + Sync,
+{
+    fn transform(&self, line: &[u8]) -> Option<Vec<u8>> {
+        self(line)
+    }
+}
+
+/// The line transforms applied to the lines of files, in order.
+#[derive(Clone, Default)]
+pub struct LineTransforms {
+    transforms: Vec<Arc<dyn LineTransform>>,
+}
+
+impl LineTransforms {
+    /// Add a transform, which is applied after the existing transforms.
+    pub(crate) fn push(&mut self, transform: Arc<dyn LineTransform>) {
+        self.transforms.push(transform);
+    }
+
+    /// Returns true if there are no transforms.
+    pub(crate) fn is_empty(&self) -> bool {
+        self.transforms.is_empty()
+    }
+
+    /// Applies the transforms to `line`.  Returns `None` if none of the
+    /// transforms changed the line.
+    pub(crate) fn apply(&self, line: &[u8]) -> Option<Vec<u8>> {
+        let mut transformed: Option<Vec<u8>> = None;
+        for transform in self.transforms.iter() {
+            let line = transformed.as_deref().unwrap_or(line);
+            if let Some(line) = transform.transform(line) {
+                transformed = Some(line);
+            }
+        }
+        transformed
+    }
+}
+
+impl fmt::Debug for LineTransforms {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("LineTransforms")
+            .field("len", &self.transforms.len())
+            .finish()
+    }
+}
+
+impl PartialEq for LineTransforms {
+    fn eq(&self, other: &Self) -> bool {
+        self.transforms.len() == other.transforms.len()
+            && self
+                .transforms
+                .iter()
+                .zip(other.transforms.iter())
+                .all(|(a, b)| Arc::ptr_eq(a, b))
+    }
+}
+
+impl Eq for LineTransforms {}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_apply() {
+        let mut transforms = LineTransforms::default();
+        assert_eq!(transforms.apply(b"one two\n"), None);
+        transforms.push(Arc::new(|line: &[u8]| {
+            line.starts_with(b"one")
+                .then(|| [b"1", &line[3..]].concat())
+        }));
+        transforms.push(Arc::new(|line: &[u8]| {
+            line.ends_with(b"two\n")
+                .then(|| [&line[..line.len() - 4], b"2\n"].concat())
+        }));
+        assert_eq!(
+            transforms.apply(b"one two\n").as_deref(),
+            Some(&b"1 2\n"[..])
+        );
+        assert_eq!(
+            transforms.apply(b"one three\n").as_deref(),
+            Some(&b"1 three\n"[..])
+        );
+        assert_eq!(transforms.apply(b"three\n"), None);
+        assert_eq!(transforms.clone(), transforms);
+        assert_ne!(transforms, LineTransforms::default());
+    }
+}
@@ -1,6 +1,7 @@
 //! Utilities.
 
 use std::borrow::Cow;
+use std::time::Duration;
 
 use unicode_segmentation::UnicodeSegmentation;
 use unicode_width::UnicodeWidthStr;
@@ -57,3 +58,128 @@ pub(crate) fn truncate_string<'a>(
         text.into_owned()
     }
 }
+
+/// Fuzzy-matches a pattern against some text.
+///
+/// Returns `None` if the characters of the pattern (ignoring whitespace) do
+/// not all appear in the text in order, ignoring case.  Otherwise returns a
+/// score for the match, where higher scores are better.  Matches at the
+/// start of words and runs of consecutive matching characters score more
+/// highly.
+pub(crate) fn fuzzy_match(pattern: &str, text: &str) -> Option<usize> {
+    let pattern: Vec<char> = pattern.chars().filter(|c| !c.is_whitespace()).collect();
+    let text: Vec<char> = text.chars().collect();
+    let first = match pattern.first() {
+        Some(first) => first,
+        None => return Some(0),
+    };
+    // Try each place the match could start, and keep the best.
+    (0..text.len())
+        .filter(|&start| chars_match(text[start], *first))
+        .filter_map(|start| fuzzy_match_from(&pattern, &text, start))
+        .max()
+}
+
+/// Fuzzy-matches a pattern against text, starting at a particular character.
+fn fuzzy_match_from(pattern: &[char], text: &[char], start: usize) -> Option<usize> {
+    let mut pattern = pattern.iter().peekable();
+    let mut score = 0;
+    let mut consecutive = false;
+    for index in start..text.len() {
+        let c = text[index];
+        match pattern.peek() {
+            None => break,
+            Some(&&p) if chars_match(c, p) => {
+                score += 1;
+                if consecutive {
+                    score += 3;
+                }
+                if index == 0 || !text[index - 1].is_alphanumeric() {
+                    score += 3;
+                }
+                consecutive = true;
+                pattern.next();
+            }
+            Some(_) => consecutive = false,
+        }
+    }
+    if pattern.peek().is_none() {
+        Some(score)
+    } else {
+        None
+    }
+}
+
+/// Formats a size in bytes for people to read, e.g. `"1.5 KiB"`.
+pub(crate) fn format_size(size: u64) -> String {
+    const UNITS: &[&str] = &["KiB", "MiB", "GiB", "TiB"];
+    if size < 1024 {
+        return format!("{} B", size);
+    }
+    let mut scaled = size as f64 / 1024.0;
+    let mut unit = 0;
+    while scaled >= 1024.0 && unit + 1 < UNITS.len() {
+        scaled /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.1} {}", scaled, UNITS[unit])
+}
+
+/// Formats how long ago something happened, e.g. `"5m ago"`.
+pub(crate) fn format_age(age: Duration) -> String {
+    let seconds = age.as_secs();
+    match seconds {
+        0..=9 => String::from("just now"),
+        10..=59 => format!("{}s ago", seconds),
+        60..=3599 => format!("{}m ago", seconds / 60),
+        3600..=86399 => format!("{}h ago", seconds / 3600),
+        _ => format!("{}d ago", seconds / 86400),
+    }
+}
+
+/// Returns true if two characters are equal, ignoring case.
+fn chars_match(a: char, b: char) -> bool {
+    a.to_lowercase().eq(b.to_lowercase())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_format_size() {
+        assert_eq!(format_size(0), "0 B");
+        assert_eq!(format_size(1023), "1023 B");
+        assert_eq!(format_size(1536), "1.5 KiB");
+        assert_eq!(format_size(5 * 1024 * 1024 * 1024), "5.0 GiB");
+    }
+
+    #[test]
+    fn test_format_age() {
+        assert_eq!(format_age(Duration::from_secs(3)), "just now");
+        assert_eq!(format_age(Duration::from_secs(42)), "42s ago");
+        assert_eq!(format_age(Duration::from_secs(300)), "5m ago");
+        assert_eq!(format_age(Duration::from_secs(7200)), "2h ago");
+        assert_eq!(format_age(Duration::from_secs(3 * 86400)), "3d ago");
+    }
+
+    #[test]
+    fn test_fuzzy_match() {
+        assert!(fuzzy_match("", "Toggle the ruler").is_some());
+        assert!(fuzzy_match("ruler", "Toggle the ruler").is_some());
+        assert!(fuzzy_match("TTR", "Toggle the ruler").is_some());
+        assert!(fuzzy_match("tog rul", "Toggle the ruler").is_some());
+        assert!(fuzzy_match("rulers", "Toggle the ruler").is_none());
+        assert!(fuzzy_match("rt", "Toggle the ruler").is_none());
+
+        // Matches at word starts and consecutive matches score higher.
+        assert!(
+            fuzzy_match("sea", "Search forwards").unwrap()
+                > fuzzy_match("sea", "Close the area").unwrap()
+        );
+        assert!(
+            fuzzy_match("line", "Toggle line numbers").unwrap()
+                > fuzzy_match("line", "Toggle the ruler, i n e").unwrap()
+        );
+    }
+}
@@ -0,0 +1,83 @@
+//! Watch Patterns
+//!
+//! While the end of a file is being followed, each line that arrives is
+//! checked against the watch patterns, so that the user can be alerted when
+//! something interesting happens without having to keep an eye on the
+//! screen.
+
+use regex::bytes::Regex;
+
+use crate::file::{File, FileInfo};
+
+/// The watch patterns for a screen, and how far its file has been checked.
+pub(crate) struct Watch {
+    /// The compiled watch patterns.
+    regexes: Vec<Regex>,
+
+    /// The number of lines of the file that have been checked.
+    checked_lines: usize,
+}
+
+/// A line that matched a watch pattern.
+pub(crate) struct WatchMatch {
+    /// The index of the line.
+    pub(crate) index: usize,
+
+    /// The text of the line, without escape sequences or its line ending.
+    pub(crate) text: String,
+}
+
+impl Watch {
+    /// Create a watch for `patterns`.  Patterns that are not valid regular
+    /// expressions are skipped, and a description of each is added to
+    /// `errors`.
+    pub(crate) fn new(patterns: &[String], errors: &mut Vec<String>) -> Watch {
+        let mut regexes = Vec::new();
+        for pattern in patterns.iter() {
+            match Regex::new(pattern) {
+                Ok(regex) => regexes.push(regex),
+                Err(e) => errors.push(format!("Invalid watch pattern '{}': {}", pattern, e)),
+            }
+        }
+        Watch {
+            regexes,
+            checked_lines: 0,
+        }
+    }
+
+    /// Checks the lines of `file` that have arrived since the last check.
+    /// Lines are only checked while `following` is true; lines that arrive
+    /// at other times are skipped.  Returns the last line that matched.
+    ///
+    /// The last line of a file that is still loading may be incomplete, so
+    /// it isn't checked until it is complete.
+    pub(crate) fn check(&mut self, file: &File, following: bool) -> Option<WatchMatch> {
+        let lines = if file.loaded() {
+            file.lines()
+        } else {
+            file.lines().saturating_sub(1)
+        };
+        if lines < self.checked_lines {
+            // The file has been reloaded.
+            self.checked_lines = 0;
+        }
+        let start = self.checked_lines;
+        self.checked_lines = lines;
+        if self.regexes.is_empty() || !following {
+            return None;
+        }
+        (start..lines).rev().find_map(|index| {
+            file.with_line(index, |line| {
+                let line = crate::line::strip_escapes(&line);
+                self.regexes
+                    .iter()
+                    .any(|regex| regex.is_match(&line))
+                    .then(|| WatchMatch {
+                        index,
+                        text: String::from_utf8_lossy(&line).trim_end().to_string(),
+                    })
+            })
+            .flatten()
+        })
+    }
+}